@@ -0,0 +1,77 @@
+//! How long scanning an overlay, working out the visible entries and diffing
+//! modified files take, on overlays from `audit-box dev fixture`.
+//!
+//! Run with `cargo bench`; compare against a saved baseline with
+//! `cargo bench -- --save-baseline before` and `--baseline before`.
+
+use audit_box::app::App;
+use audit_box::config::Config;
+use audit_box::file_operations;
+use audit_box::fixture::{self, Fixture, FixtureSpec, SizeDistribution};
+use audit_box::types::{DiffOptions, FileStatus, FileTree, LowerView};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use std::hint::black_box;
+use std::sync::mpsc::channel;
+use tempfile::TempDir;
+
+fn fixture(files: usize, depth: usize, sizes: SizeDistribution) -> (TempDir, Fixture) {
+    let dir = TempDir::new().expect("temporary directory");
+    let spec = FixtureSpec { files, depth, sizes, modified_percent: 50, seed: 1 };
+    let fixture = fixture::generate(dir.path(), &spec).expect("fixture");
+    (dir, fixture)
+}
+
+fn scan(fixture: &Fixture) -> FileTree {
+    let lower = LowerView::new(fixture.base.clone(), Vec::new());
+    let mut tree = FileTree::new();
+    file_operations::scan_directory(&fixture.overlay, &fixture.overlay, &lower, 0, None, &mut tree).expect("scan");
+    tree
+}
+
+fn bench_scan(c: &mut Criterion) {
+    let mut group = c.benchmark_group("scan");
+    for files in [1_000, 10_000] {
+        let (_dir, fixture) = fixture(files, 3, SizeDistribution::Small);
+        group.bench_with_input(BenchmarkId::from_parameter(files), &fixture, |b, fixture| b.iter(|| scan(fixture)));
+    }
+    group.finish();
+}
+
+fn bench_visible(c: &mut Criterion) {
+    let mut group = c.benchmark_group("visible");
+    for files in [1_000, 10_000] {
+        let (_dir, fixture) = fixture(files, 3, SizeDistribution::Small);
+        let tree = scan(&fixture);
+        group.bench_with_input(BenchmarkId::new("tree", files), &tree, |b, tree| b.iter(|| tree.visible()));
+
+        let lower = LowerView::new(fixture.base.clone(), Vec::new());
+        let (_tx, rx) = channel();
+        let app = App::new(&fixture.overlay, lower, rx, &Config::default(), None).expect("app");
+        group.bench_with_input(BenchmarkId::new("app", files), &app, |b, app| b.iter(|| app.get_visible_files().len()));
+    }
+    group.finish();
+}
+
+fn bench_diff(c: &mut Criterion) {
+    let mut group = c.benchmark_group("diff");
+    // Large files take long enough to diff that a few make a good sample
+    group.sample_size(10);
+    for sizes in [SizeDistribution::Small, SizeDistribution::Mixed, SizeDistribution::Large] {
+        let (_dir, fixture) = fixture(10, 1, sizes);
+        let lower = LowerView::new(fixture.base.clone(), Vec::new());
+        let tree = scan(&fixture);
+        let modified: Vec<_> = tree.iter().map(|(_, e)| e).filter(|e| e.status == FileStatus::Modified).cloned().collect();
+        let options = DiffOptions::default();
+        group.bench_function(format!("{:?}", sizes).to_lowercase(), |b| {
+            b.iter(|| {
+                for entry in &modified {
+                    black_box(file_operations::generate_diff(entry, &lower, &options));
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_scan, bench_visible, bench_diff);
+criterion_main!(benches);
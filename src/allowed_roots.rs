@@ -0,0 +1,101 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The base subtrees applying may write to, from `apply.allowed_roots`.
+/// With none configured, apply may write anywhere in the base.
+#[derive(Debug, Default)]
+pub struct AllowedRoots {
+    roots: Vec<PathBuf>,
+}
+
+impl AllowedRoots {
+    /// Roots as configured, with a leading `~` standing for the home
+    /// directory. They are compared with symlinks resolved, so a link in the
+    /// base cannot lead apply out of them.
+    pub fn new(roots: &[PathBuf]) -> Self {
+        let home = std::env::var_os("HOME").map(PathBuf::from);
+        let roots = roots
+            .iter()
+            .map(|root| match (root.strip_prefix("~"), &home) {
+                (Ok(rest), Some(home)) => home.join(rest),
+                _ => root.clone(),
+            })
+            .map(|root| resolved(&root))
+            .collect();
+        AllowedRoots { roots }
+    }
+
+    pub fn is_restricted(&self) -> bool {
+        !self.roots.is_empty()
+    }
+
+    /// Whether apply may write to `path` in the base
+    pub fn allows(&self, path: &Path) -> bool {
+        if self.roots.is_empty() {
+            return true;
+        }
+        let path = resolved(path);
+        self.roots.iter().any(|root| path.starts_with(root))
+    }
+}
+
+/// An absolute path with the symlinks in its existing part resolved. What
+/// does not exist yet is kept as given, e.g. a file apply would create.
+fn resolved(path: &Path) -> PathBuf {
+    let path = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    for existing in path.ancestors() {
+        if let Ok(canonical) = fs::canonicalize(existing) {
+            let rest = path.strip_prefix(existing).unwrap_or(Path::new(""));
+            return canonical.join(rest);
+        }
+    }
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn no_roots_allow_anywhere() {
+        let roots = AllowedRoots::new(&[]);
+        assert!(!roots.is_restricted());
+        assert!(roots.allows(Path::new("/etc/shadow")));
+    }
+
+    #[test]
+    fn allows_only_paths_under_a_root() {
+        let base = tempfile::tempdir().unwrap();
+        fs::create_dir_all(base.path().join("project/src")).unwrap();
+        fs::create_dir_all(base.path().join("project2")).unwrap();
+        let roots = AllowedRoots::new(&[base.path().join("project")]);
+
+        assert!(roots.is_restricted());
+        assert!(roots.allows(&base.path().join("project/src/main.c")));
+        // Not there yet, as a file apply creates
+        assert!(roots.allows(&base.path().join("project/new/dir/file")));
+        assert!(!roots.allows(&base.path().join("project2/main.c")));
+        assert!(!roots.allows(&base.path().join(".ssh/authorized_keys")));
+    }
+
+    #[test]
+    fn a_symlink_under_a_root_cannot_lead_out_of_it() {
+        let base = tempfile::tempdir().unwrap();
+        fs::create_dir_all(base.path().join("project")).unwrap();
+        fs::create_dir_all(base.path().join(".ssh")).unwrap();
+        std::os::unix::fs::symlink(base.path().join(".ssh"), base.path().join("project/keys")).unwrap();
+        let roots = AllowedRoots::new(&[base.path().join("project")]);
+
+        assert!(!roots.allows(&base.path().join("project/keys/authorized_keys")));
+    }
+
+    #[test]
+    fn a_root_given_through_a_symlink_matches_the_real_path() {
+        let base = tempfile::tempdir().unwrap();
+        fs::create_dir_all(base.path().join("real")).unwrap();
+        std::os::unix::fs::symlink(base.path().join("real"), base.path().join("link")).unwrap();
+        let roots = AllowedRoots::new(&[base.path().join("link")]);
+
+        assert!(roots.allows(&base.path().join("real/file")));
+    }
+}
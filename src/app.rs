@@ -1,167 +1,965 @@
-use crate::file_operations;
-use crate::types::{ActivePane, DialogButton, FileEntry, FileStatus};
+use crate::allowed_roots::AllowedRoots;
+use crate::apply_hooks::{AppliedFile, ApplyHooks, ApplyReport};
+use crate::applied::{self, AppliedRecord};
+use crate::attribution::{self, WriteRecord};
+use crate::audit_log::{self, LogRecord};
+use crate::blame;
+use crate::changeset::{self, Changesets};
+use crate::checks::{CheckJob, CheckResult, Checker};
+use crate::desktop_notify::Notifier;
+use crate::classify::{self, Privileges, RiskHint};
+use crate::config::{Config, DialogDefaults, NormalizerConfig, ValidateConfig};
+use crate::dashboard::{self, Dashboard};
+use crate::dir_metadata;
+use crate::disk_usage::{self, DiskUsage};
+use crate::elf_summary;
+use crate::file_kind::FileKind;
+use crate::file_operations::{self, ApplyProgress};
+use crate::gitignore::GitIgnore;
+use crate::hash_lists::{HashLists, HashVerdict};
+use crate::identity::{self, Identity};
+use crate::keymap::Keymap;
+use crate::lock;
+use crate::minified;
+use crate::mounts;
+use crate::normalize;
+use crate::path_display::PathDisplay;
+use crate::plan::{self, Decision, Plan, Verdict};
+use crate::report;
+use crate::review_state::{self, Fingerprint, MarkChange, ReviewState};
+use crate::scan_cache::{self, CachedEntry, ScanCache};
+use crate::services::{self, ServiceAction, Services};
+use crate::session::{self, RunRecord, SessionMetadata};
+use crate::timeline::{self, PathActivity, Recorder, TimelineSort};
+use crate::transient::{self, TransientFiles};
+use crate::types::{
+    ActivePane, ApplyScope, ContentView, DialogButton, DiffOptions, FileEntry, FileStatus, FileTree, HiddenFiles, LowerView,
+    NodeId, QuitChoice, SelectionState, Toast,
+};
+use crate::ui::input::{self, Input};
+use crate::ui::icons::IconSet;
+use crate::ui::theme::Theme;
+use crate::validate;
+use crate::worker::Worker;
+use chrono::{DateTime, Local, TimeDelta};
 use notify::Event as NotifyEvent;
+use notify::event::{ModifyKind, RenameMode};
 use notify::EventKind;
-use ratatui::widgets::ListState;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::fs;
 use std::io;
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
 use std::path::{Path, PathBuf};
 use std::sync::mpsc::Receiver;
+use std::time::{Duration, Instant, SystemTime};
+
+/// How long a toast stays in the status bar
+const TOAST_DURATION: Duration = Duration::from_secs(5);
+
+/// Where the review's decisions are written unless another file is typed
+const DEFAULT_PLAN_FILE: &str = "decisions.json";
+
+/// The least an apply has to copy for its throughput to estimate the next
+const ESTIMATE_MIN_BYTES: u64 = 16 << 20;
+
+/// What a normalized diff was made from, so it is only reused while none
+/// of it has changed
+#[derive(Debug, Clone, PartialEq)]
+struct NormalizeKey {
+    overlay_file: PathBuf,
+    base_file: PathBuf,
+    options: DiffOptions,
+    overlay_fingerprint: Option<Fingerprint>,
+    base_fingerprint: Option<Fingerprint>,
+}
+
+impl NormalizeKey {
+    fn new(entry: &FileEntry, base_file: &Path, options: &DiffOptions) -> Self {
+        NormalizeKey {
+            overlay_file: entry.path.clone(),
+            base_file: base_file.to_path_buf(),
+            options: *options,
+            overlay_fingerprint: review_state::fingerprint(&entry.path),
+            base_fingerprint: review_state::fingerprint(base_file),
+        }
+    }
+}
+
+/// A diff to run through a normalizer in the background
+struct NormalizeJob {
+    key: NormalizeKey,
+    entry: FileEntry,
+    normalizer: NormalizerConfig,
+}
+
+/// A diff through a normalizer, or the raw diff with why it is not
+struct NormalizedDiff {
+    key: NormalizeKey,
+    lines: Vec<String>,
+}
+
+/// Git blame of a base file, as of its fingerprint when it was asked for,
+/// with no annotations if git has none
+struct BlamedFile {
+    base_file: PathBuf,
+    fingerprint: Option<Fingerprint>,
+    annotations: Option<Vec<String>>,
+}
+
+/// A file hashed in the background, as of its fingerprint when it was
+/// asked for, with no hash if it could not be read
+struct HashedFile {
+    path: PathBuf,
+    fingerprint: Fingerprint,
+    sha256: Option<String>,
+}
+
+/// The dialog taking keys, if any. Only one can be open at a time.
+pub enum Modal {
+    None,
+    Apply(ApplyState),
+    Discard(DiscardState),
+    Help,
+    Quit(QuitChoice),
+    /// Prompt for adding or removing a label
+    Label(Input),
+    /// Prompt for the changeset to group files into
+    Changeset(Input),
+    /// Prompt for the description of the changeset under the queue cursor
+    Describe(Input),
+    /// Prompt for a relative path to jump to
+    Path(Input),
+    /// Prompt for the file to write the review's decisions to
+    Plan(Input),
+    /// Whether to run the reloads applied files call for
+    Reload(DialogButton),
+}
+
+/// What the apply dialog applies, and what it says about it, worked out
+/// when it opened
+pub struct ApplyState {
+    pub scope: ApplyScope,
+    pub button: DialogButton,
+    /// Why the sandbox looks like it is still running
+    pub sandbox_warning: Option<String>,
+    /// Line counts and sizes of the files listed, by relative path
+    pub stats: HashMap<PathBuf, report::EntryStats>,
+    /// Files applied before whose base copy has changed since, so applying
+    /// them again overwrites someone else's change
+    pub conflicts: HashSet<PathBuf>,
+    /// Applying setuid, setgid or capability files was confirmed a second
+    /// time
+    pub privileged_confirmed: bool,
+}
+
+pub struct DiscardState {
+    /// Label whose files are discarded, instead of the cursor entry
+    pub label: Option<String>,
+    pub button: DialogButton,
+}
 
 pub struct App {
-    pub files: Vec<FileEntry>,
-    pub list_state: ListState,
-    pub base_path: PathBuf,
+    pub files: FileTree,
+    pub cursor: Option<NodeId>,
+    pub lower: LowerView,
     pub overlay_path: PathBuf,
     pub active_pane: ActivePane,
     pub file_content: Vec<String>,
+    /// Why the file under the cursor is risky to apply, if it is
+    pub cursor_risk: Option<RiskHint>,
     pub content_scroll: usize,
     pub is_diff_view: bool,
-    pub show_confirm_dialog: bool,
-    pub show_discard_dialog: bool,
-    pub show_help_dialog: bool,
-    pub dialog_button: DialogButton,
+    /// Whether modified files are shown as a diff, or as the overlay or
+    /// base version in full
+    pub content_view: ContentView,
+    /// Who last changed each removed line in the base's git history, by
+    /// line of file_content, when the base is a git repository
+    pub blame: Vec<Option<String>>,
+    /// Whether the blame gutter is shown
+    pub show_blame: bool,
+    /// Runs git blame on the base file under the cursor, so a large history
+    /// does not hold up the review
+    blaming: Worker<(PathBuf, Option<Fingerprint>), BlamedFile>,
+    /// The last base file blamed
+    blamed: Option<BlamedFile>,
+    pub diff_options: DiffOptions,
+    /// A large file whose diff is computed in full rather than showing only
+    /// the changes, by path relative to the overlay root
+    full_diff: Option<PathBuf>,
+    /// Columns between tab stops in the content pane
+    pub tab_width: usize,
+    pub theme: Theme,
+    /// Text markers instead of colour-only signals, for screen readers and
+    /// monochrome terminals
+    pub accessible: bool,
+    pub icons: IconSet,
+    pub keymap: Keymap,
+    pub modal: Modal,
+    /// Ask before quitting with pending work
+    pub confirm_quit: bool,
+    /// Session overview, built when it is opened
+    pub dashboard: Option<Dashboard>,
+    /// Per-file activity over the session, while the timeline view is open
+    pub timeline: Option<Vec<PathActivity>>,
+    pub timeline_sort: TimelineSort,
+    pub timeline_cursor: usize,
+    /// The changeset queue is shown in place of the panes
+    pub show_changeset_queue: bool,
+    pub queue_cursor: usize,
+    /// Where each dialog's highlighted button starts
+    dialog_defaults: DialogDefaults,
+    pub pending_key: Option<char>,
+    /// Files queued for apply, relative to the overlay root, in apply order
+    pub staged: Vec<PathBuf>,
+    pub show_staging_pane: bool,
+    pub staging_cursor: usize,
+    /// Labels of files, by path relative to the overlay root
+    pub labels: BTreeMap<PathBuf, BTreeSet<String>>,
+    /// Only show files with this label
+    pub label_filter: Option<String>,
+    /// Labels typed into the label prompt, oldest first
+    label_history: Vec<String>,
+    /// Files grouped into logical changes, by name
+    pub changesets: Changesets,
+    /// Only show files in this changeset
+    pub changeset_filter: Option<String>,
+    /// Changesets typed into the changeset prompt, oldest first
+    changeset_history: Vec<String>,
+    /// Longest time between writes of files suggested as one changeset
+    changeset_gap: TimeDelta,
+    /// How many changesets have been suggested, to step through them
+    suggestions_shown: usize,
+    /// Bookmarked entries, by path relative to the overlay root
+    pub bookmarks: BTreeSet<PathBuf>,
+    /// Paths typed into the jump-to-path prompt, oldest first
+    path_history: Vec<String>,
+    /// Files the review's decisions were written to, oldest first
+    plan_history: Vec<String>,
+    /// Full path, sizes, hashes and times of the entry under the cursor,
+    /// while the details popup is open
+    pub details: Option<Vec<(&'static str, String)>>,
+    /// Only show entries changed since the last review
+    pub changed_only: bool,
+    /// Entries changed since the last review, including their ancestors
+    changed_since_review: HashSet<NodeId>,
+    /// The base's .gitignore files, when ignored entries are hidden by default
+    gitignore: Option<GitIgnore>,
+    /// Entries the base's .gitignore files ignore
+    ignored: HashSet<NodeId>,
+    /// Show ignored entries anyway
+    pub show_ignored: bool,
+    /// Whether dotfiles and hidden directories are listed, and where
+    pub hidden_files: HiddenFiles,
+    /// List files grouped by kind instead of by directory
+    pub group_by_kind: bool,
+    /// Kind of each file, while grouping by kind
+    kinds: HashMap<NodeId, FileKind>,
+    /// Files that are setuid, setgid or have capabilities
+    privileged: HashMap<NodeId, Privileges>,
+    /// How directories the base already has differ from it in mode or
+    /// xattrs
+    dir_changes: HashMap<NodeId, Vec<String>>,
+    hash_lists: HashLists,
+    /// Files whose content is on the allowlist or the denylist
+    hash_verdicts: HashMap<NodeId, HashVerdict>,
+    /// SHA-256 of each file, as of its fingerprint then
+    hashes: HashMap<PathBuf, (Fingerprint, String)>,
+    /// Hashes files for the hash lists, so large ones do not hold up the
+    /// review
+    hashing: Worker<(PathBuf, Fingerprint), HashedFile>,
+    /// Files asked to be hashed that have not been yet
+    hashes_pending: HashSet<PathBuf>,
+    /// Runs the configured external checks, if there are any
+    checker: Option<Checker>,
+    /// Results of the external checks on each file, as of when it was last
+    /// modified, or none yet while they run
+    checks: HashMap<PathBuf, (SystemTime, Option<Vec<CheckResult>>)>,
+    /// How paths are shown, depending on the base
+    pub path_display: PathDisplay,
+    /// Whether the terminal has focus, as far as it reports focus changes
+    pub focused: bool,
+    /// Tells the desktop about overlay activity while unfocused, if enabled
+    notifier: Option<Notifier>,
+    /// Another review holds the session, so nothing may be applied or
+    /// discarded, and review state is left to that review
+    pub read_only: bool,
+    /// Refuse to apply while the sandbox is running, instead of warning
+    block_apply_while_running: bool,
+    /// Where in the base apply may write
+    allowed_roots: AllowedRoots,
+    /// Apply outside the allowed roots all the same, recording it in the
+    /// audit log
+    allow_outside_roots: bool,
+    /// Commands that must pass on a scratch copy with the changes applied
+    validations: Vec<ValidateConfig>,
+    /// Files copied at once when applying
+    apply_workers: usize,
+    /// Formatters both sides of a diff are run through, by file type
+    normalizers: Vec<NormalizerConfig>,
+    /// Runs the normalizer of the file under the cursor, so a slow one does
+    /// not hold up the review
+    normalizing: Worker<NormalizeJob, NormalizedDiff>,
+    /// The last diff through a normalizer to finish
+    normalized: Option<NormalizedDiff>,
+    /// Services applied files belong to, to offer reloading
+    services: Services,
+    /// Reloads applied files call for that have not been run or declined
+    /// yet, `systemctl daemon-reload` first
+    pub pending_reloads: Vec<ServiceAction>,
+    /// Bytes a second the last sizeable apply copied, to estimate the next
+    apply_throughput: Option<f64>,
+    /// Sends the record of each apply to a webhook or by mail, if configured
+    apply_hooks: Option<ApplyHooks>,
+    /// Who the audit log and apply reports say made the decisions
+    identity: Identity,
+    /// Snapshot taken when the last review ended
+    review_baseline: Option<BTreeMap<PathBuf, Fingerprint>>,
+    pub disk_usage: DiskUsage,
+    pub size_warning: Option<u64>,
+    size_warned: bool,
+    toast: Option<Toast>,
+    /// Something changed that the screen does not show yet
+    redraw: bool,
+    /// Most recent `run` in the session, if any
+    pub last_run: Option<RunRecord>,
+    /// Processes recorded writing each file by `run --attribute`, by path
+    /// relative to the overlay root
+    pub writers: BTreeMap<PathBuf, Vec<WriteRecord>>,
+    /// Files applied earlier in the session, to tell when the sandbox
+    /// writes one again
+    applied: BTreeMap<PathBuf, AppliedRecord>,
+    /// The session's audit log, for the history of each path
+    audit_records: Vec<LogRecord>,
+    /// Files copied in from the session template, as they were when seeded
+    seeded: BTreeMap<PathBuf, Fingerprint>,
+    session_dir: Option<PathBuf>,
+    review_state: ReviewState,
+    review_state_path: Option<PathBuf>,
+    /// The review state file as this review last read or wrote it, to
+    /// notice marks made by other reviews of the session
+    review_state_seen: Option<Fingerprint>,
+    timeline_recorder: Option<Recorder>,
     fs_events: Receiver<Result<NotifyEvent, notify::Error>>,
-    pending_updates: Vec<PathBuf>,
+    /// Overlay paths changed since the list was last updated, normalized so
+    /// each is updated once
+    pending_updates: HashSet<PathBuf>,
+    transient: TransientFiles,
+    /// Transient files held back from the list, by when they last changed
+    unsettled: HashMap<PathBuf, Instant>,
 }
 
 impl App {
     pub fn new(
         overlay_path: &Path,
-        base_path: PathBuf,
+        lower: LowerView,
         fs_events: Receiver<Result<NotifyEvent, notify::Error>>,
+        config: &Config,
+        session_dir: Option<PathBuf>,
     ) -> io::Result<Self> {
-        let mut files = Vec::new();
-        file_operations::scan_directory(overlay_path, overlay_path, &base_path, 0, &mut files)?;
+        // Review state and run history only exist for the saved session
+        let review_state_path = session_dir
+            .as_ref()
+            .map(|dir| dir.join(review_state::REVIEW_STATE_FILE));
+        let (review_state, review_state_seen) = match &review_state_path {
+            Some(path) => review_state::load_shared(path)?,
+            None => (ReviewState::default(), None),
+        };
+        let review_baseline = review_state.snapshot.clone();
+        let timeline_recorder = match &session_dir {
+            Some(dir) => Some(Recorder::open(dir, overlay_path)?),
+            None => None,
+        };
+        let metadata = match &session_dir {
+            Some(dir) => session::load_metadata(dir)?,
+            None => SessionMetadata::default(),
+        };
+        let last_run = metadata.runs.last().cloned();
+        let writers = match &session_dir {
+            Some(dir) => attribution::load_writers(dir)?,
+            None => BTreeMap::new(),
+        };
+        let applied = match &session_dir {
+            Some(dir) => applied::load(dir)?,
+            None => BTreeMap::new(),
+        };
+        let audit_records = match &session_dir {
+            Some(dir) => audit_log::load(dir)?,
+            None => Vec::new(),
+        };
 
-        let mut list_state = ListState::default();
-        if !files.is_empty() {
-            list_state.select(Some(0));
-        }
+        let gitignore = config.ui.gitignore.then(|| GitIgnore::new(&lower));
+        let transient = TransientFiles::new(&config.ui.transient_files)?;
+        let hash_lists = HashLists::load(&config.hashes)?;
+        validate::check(&config.apply.validate, &lower)?;
+        let checker = Checker::start(&config.checks)?;
+        let path_display = PathDisplay::new(&lower);
+
+        // Files unchanged since the last review keep the hashes it found
+        let scan_cache = session_dir.as_deref().map(scan_cache::load).unwrap_or_default();
+        let mut files = FileTree::new();
+        file_operations::scan_directory(overlay_path, overlay_path, &lower, 0, None, &mut files)?;
+        let hashes = scan_cache
+            .entries
+            .into_iter()
+            .filter_map(|(rel_path, cached)| Some((overlay_path.join(rel_path), (cached.fingerprint, cached.sha256?))))
+            .collect();
+        files.set_hidden_last(config.ui.hidden_files == HiddenFiles::Last);
+
+        let cursor = files.roots().first().copied();
 
         let mut app = App {
             files,
-            list_state,
-            base_path,
+            cursor,
+            lower,
             overlay_path: overlay_path.to_path_buf(),
             active_pane: ActivePane::FileList,
             file_content: Vec::new(),
+            cursor_risk: None,
             content_scroll: 0,
             is_diff_view: false,
-            show_confirm_dialog: false,
-            show_discard_dialog: false,
-            show_help_dialog: false,
-            dialog_button: DialogButton::Ok,
+            content_view: ContentView::default(),
+            blame: Vec::new(),
+            show_blame: true,
+            blaming: Worker::start(true, |(base_file, fingerprint): (PathBuf, Option<Fingerprint>)| BlamedFile {
+                annotations: blame::blame(&base_file),
+                base_file,
+                fingerprint,
+            }),
+            blamed: None,
+            diff_options: config.diff,
+            full_diff: None,
+            tab_width: config.ui.tab_width,
+            theme: config.ui.theme,
+            accessible: config.ui.accessible,
+            // Screen readers read icons out by name
+            icons: if config.ui.accessible { IconSet::Ascii } else { config.ui.icons },
+            keymap: config.ui.keymap,
+            modal: Modal::None,
+            confirm_quit: config.ui.confirm_quit,
+            dashboard: None,
+            timeline: None,
+            show_changeset_queue: false,
+            queue_cursor: 0,
+            timeline_sort: TimelineSort::default(),
+            timeline_cursor: 0,
+            dialog_defaults: config.ui.dialog_defaults,
+            pending_key: None,
+            staged: Vec::new(),
+            show_staging_pane: false,
+            staging_cursor: 0,
+            labels: BTreeMap::new(),
+            label_filter: None,
+            label_history: Vec::new(),
+            changesets: Changesets::new(),
+            changeset_filter: None,
+            changeset_history: Vec::new(),
+            changeset_gap: config.ui.changeset_gap()?,
+            suggestions_shown: 0,
+            bookmarks: BTreeSet::new(),
+            path_history: Vec::new(),
+            plan_history: Vec::new(),
+            details: None,
+            changed_only: false,
+            changed_since_review: HashSet::new(),
+            gitignore,
+            ignored: HashSet::new(),
+            show_ignored: false,
+            hidden_files: config.ui.hidden_files,
+            group_by_kind: false,
+            kinds: HashMap::new(),
+            privileged: HashMap::new(),
+            dir_changes: HashMap::new(),
+            hash_lists,
+            hash_verdicts: HashMap::new(),
+            hashes,
+            hashing: Worker::start(false, |(path, fingerprint): (PathBuf, Fingerprint)| HashedFile {
+                sha256: is_regular_file(&path).then(|| applied::hash_file(&path).ok()).flatten(),
+                path,
+                fingerprint,
+            }),
+            hashes_pending: HashSet::new(),
+            checker,
+            checks: HashMap::new(),
+            path_display,
+            focused: true,
+            notifier: config.ui.desktop_notifications.then(Notifier::default),
+            read_only: false,
+            block_apply_while_running: config.apply.block_while_running,
+            allowed_roots: AllowedRoots::new(&config.apply.allowed_roots),
+            allow_outside_roots: false,
+            validations: config.apply.validate.clone(),
+            apply_workers: config.apply.workers,
+            normalizers: config.normalizers.clone(),
+            normalizing: Worker::start(true, |job: NormalizeJob| NormalizedDiff {
+                lines: normalized_diff(&job.entry, &job.key.base_file, &job.key.options, &job.normalizer),
+                key: job.key,
+            }),
+            normalized: None,
+            services: Services::new(&config.apply.services)?,
+            pending_reloads: Vec::new(),
+            apply_throughput: None,
+            apply_hooks: ApplyHooks::new(&config.apply),
+            identity: config.identity.identity(),
+            review_baseline,
+            disk_usage: DiskUsage::scan(overlay_path),
+            size_warning: config.session.size_warning(),
+            size_warned: false,
+            toast: None,
+            redraw: true,
+            last_run,
+            writers,
+            applied,
+            audit_records,
+            seeded: metadata.seeded,
+            session_dir,
+            review_state,
+            review_state_path,
+            review_state_seen,
+            timeline_recorder,
             fs_events,
-            pending_updates: Vec::new(),
+            pending_updates: HashSet::new(),
+            transient,
+            unsettled: HashMap::new(),
         };
+        app.hold_back_unsettled();
 
+        let collapsed = app.review_state.collapsed.iter().cloned().collect();
+        app.restore_entry_state(&HashSet::new(), &collapsed);
+        app.staged = app.review_state.staged.clone();
+        app.prune_staged();
+        app.show_staging_pane = !app.staged.is_empty();
+        app.labels = app.review_state.labels.clone();
+        app.changesets = app.review_state.changesets.clone();
+        app.bookmarks = app.review_state.bookmarks.clone();
+
+        app.update_review_delta();
+        app.update_entry_info();
+        app.keep_cursor_visible();
         app.load_selected_file_content();
+        app.check_size_warning();
+        if app.review_baseline.is_some() {
+            let changed = app
+                .changed_since_review
+                .iter()
+                .filter(|&&id| app.files.get(id).is_some_and(|e| !e.is_dir))
+                .count();
+            app.show_toast(format!("{} files changed since the last review (c: show only those)", changed));
+        }
         Ok(app)
     }
 
     pub fn next(&mut self) {
-        let visible = self.get_visible_files();
+        let visible = self.visible_ids();
         if visible.is_empty() {
             return;
         }
 
-        let current_idx = self.list_state.selected();
-        let next_idx = if let Some(current) = current_idx {
+        let next_id = if let Some(current) = self.cursor {
             // Find current position in visible list
-            if let Some(pos) = visible.iter().position(|(idx, _)| *idx == current) {
+            if let Some(pos) = visible.iter().position(|&id| id == current) {
                 // Move to next visible item, or wrap to first
                 if pos >= visible.len() - 1 {
-                    visible[0].0
+                    visible[0]
                 } else {
-                    visible[pos + 1].0
+                    visible[pos + 1]
                 }
             } else {
                 // Current selection not visible, go to first
-                visible[0].0
+                visible[0]
             }
         } else {
-            visible[0].0
+            visible[0]
         };
 
-        self.list_state.select(Some(next_idx));
+        self.cursor = Some(next_id);
         self.load_selected_file_content();
     }
 
     pub fn previous(&mut self) {
-        let visible = self.get_visible_files();
+        let visible = self.visible_ids();
         if visible.is_empty() {
             return;
         }
 
-        let current_idx = self.list_state.selected();
-        let prev_idx = if let Some(current) = current_idx {
+        let prev_id = if let Some(current) = self.cursor {
             // Find current position in visible list
-            if let Some(pos) = visible.iter().position(|(idx, _)| *idx == current) {
+            if let Some(pos) = visible.iter().position(|&id| id == current) {
                 // Move to previous visible item, or wrap to last
                 if pos == 0 {
-                    visible[visible.len() - 1].0
+                    visible[visible.len() - 1]
                 } else {
-                    visible[pos - 1].0
+                    visible[pos - 1]
                 }
             } else {
                 // Current selection not visible, go to first
-                visible[0].0
+                visible[0]
             }
         } else {
-            visible[0].0
+            visible[0]
         };
 
-        self.list_state.select(Some(prev_idx));
+        self.cursor = Some(prev_id);
         self.load_selected_file_content();
     }
 
     pub fn jump_to_first(&mut self) {
-        let visible = self.get_visible_files();
-        if !visible.is_empty() {
-            self.list_state.select(Some(visible[0].0));
+        if let Some(&first) = self.visible_ids().first() {
+            self.cursor = Some(first);
             self.load_selected_file_content();
         }
     }
 
     pub fn jump_to_last(&mut self) {
-        let visible = self.get_visible_files();
-        if !visible.is_empty() {
-            self.list_state.select(Some(visible[visible.len() - 1].0));
+        if let Some(&last) = self.visible_ids().last() {
+            self.cursor = Some(last);
             self.load_selected_file_content();
         }
     }
 
+    /// The entry under the cursor in the file list
+    pub fn cursor_entry(&self) -> Option<&FileEntry> {
+        self.cursor.and_then(|id| self.files.get(id))
+    }
+
     pub fn load_selected_file_content(&mut self) {
         self.content_scroll = 0;
-        if let Some(selected) = self.list_state.selected() {
-            if let Some(entry) = self.files.get(selected).cloned() {
-                if !entry.is_dir {
-                    match entry.status {
-                        FileStatus::New => {
-                            // For new files, just show the content
-                            self.is_diff_view = false;
-                            if let Ok(content) = fs::read_to_string(&entry.path) {
-                                self.file_content = content.lines().map(|s| s.to_string()).collect();
-                            } else {
-                                self.file_content = vec!["<Unable to read file>".to_string()];
-                            }
-                        }
-                        FileStatus::Modified => {
+        self.blame.clear();
+        self.cursor_risk = self
+            .cursor_entry()
+            .and_then(|entry| classify::classify(&entry.path, &self.lower.base_path(&entry.rel_path)));
+        if let Some(entry) = self.cursor_entry().cloned() {
+            if !entry.is_dir {
+                match entry.status {
+                    FileStatus::New => {
+                        // For new files, just show the content, or what a binary is
+                        self.is_diff_view = false;
+                        self.file_content = self.viewed_lines(&entry.path);
+                    }
+                    FileStatus::Modified => match self.content_view {
+                        ContentView::Diff => {
                             // For modified files, generate and show a diff
                             self.is_diff_view = true;
-                            self.file_content = file_operations::generate_diff(
-                                &entry,
-                                &self.base_path,
-                            );
+                            let since_apply = self.applied_record(&entry).is_some();
+                            self.file_content = match self.applied_record(&entry) {
+                                Some(record) => self.diff_since_apply(&entry, record),
+                                None => self.generate_diff(&entry, &self.diff_options_for(&entry)),
+                            };
+                            // Pretty-printed or normalized lines are not the
+                            // base's lines, and a diff since the last apply
+                            // is not against the base
+                            if !since_apply && !self.is_pretty_printed(&entry) && self.normalizer_for(&entry).is_none() {
+                                self.blame = self.blame_for(&entry);
+                            }
+                        }
+                        ContentView::Overlay => {
+                            self.is_diff_view = false;
+                            self.file_content = self.viewed_lines(&entry.path);
+                        }
+                        ContentView::Base => {
+                            self.is_diff_view = false;
+                            self.file_content = match self.lower.resolve(&entry.rel_path) {
+                                Some(base_file) => self.viewed_lines(&base_file),
+                                None => vec!["<Not in the base>".to_string()],
+                            };
                         }
+                    },
+                    FileStatus::Deleted => {
+                        self.is_diff_view = false;
+                        self.file_content = self.deleted_content(&entry);
                     }
-                } else {
-                    self.is_diff_view = false;
-                    self.file_content = vec!["<Directory>".to_string()];
+                    FileStatus::TypeChanged => {
+                        self.is_diff_view = false;
+                        self.file_content = self.type_change_content(&entry);
+                    }
+                }
+                self.split_long_lines();
+            } else {
+                self.is_diff_view = false;
+                let empty = self.cursor.is_some_and(|id| self.files.children(id).is_empty());
+                let changes = self.dir_changes(&entry).map(<[String]>::to_vec);
+                self.file_content = match (empty, entry.status) {
+                    (_, FileStatus::Modified) if let Some(changes) = changes => {
+                        let mut lines = vec![
+                            if empty { "<Empty directory>" } else { "<Directory>" }.to_string(),
+                            "Its metadata differs from the base's; applying it sets the base's to match:".to_string(),
+                        ];
+                        lines.extend(changes.iter().map(|change| format!("  {}", change)));
+                        lines
+                    }
+                    (true, FileStatus::New) => vec![
+                        "<Empty directory>".to_string(),
+                        "Applying creates it in the base with the same mode".to_string(),
+                    ],
+                    (true, FileStatus::Modified) => vec![
+                        "<Empty directory>".to_string(),
+                        "The base already has it; applying removes it from the overlay".to_string(),
+                    ],
+                    _ => vec!["<Directory>".to_string()],
+                };
+            }
+        }
+    }
+
+    /// A file's lines for the content pane, pretty-printed if asked for and
+    /// it is JSON or JavaScript
+    fn viewed_lines(&self, path: &Path) -> Vec<String> {
+        if !self.diff_options.pretty_print || !minified::can_pretty_print(path) || elf_summary::summarize(path).is_some() {
+            return file_lines(path);
+        }
+        match fs::read_to_string(path) {
+            Ok(content) => minified::pretty_print(&content).lines().map(str::to_string).collect(),
+            Err(_) => vec!["<Unable to read file>".to_string()],
+        }
+    }
+
+    /// Whether an entry's content is shown pretty-printed
+    pub fn is_pretty_printed(&self, entry: &FileEntry) -> bool {
+        self.diff_options.pretty_print && !entry.is_dir && minified::can_pretty_print(&entry.path)
+    }
+
+    /// Split the very long lines of file_content, as in minified files, into
+    /// pieces that can be scrolled through. A piece after the first starts
+    /// with the line's diff sign, if any, then `↪`, and has no blame.
+    fn split_long_lines(&mut self) {
+        if !self.file_content.iter().any(|line| line.len() > minified::LONG_LINE_CHARS) {
+            return;
+        }
+        let mut lines = Vec::with_capacity(self.file_content.len());
+        let mut blame = Vec::with_capacity(self.blame.len());
+        for (i, line) in self.file_content.iter().enumerate() {
+            if i < self.blame.len() {
+                blame.push(self.blame[i].clone());
+            }
+            if line.chars().count() <= minified::LONG_LINE_CHARS {
+                lines.push(line.clone());
+                continue;
+            }
+            let (sign, rest) = match line.chars().next() {
+                Some(sign) if self.is_diff_view => line.split_at(sign.len_utf8()),
+                _ => ("", line.as_str()),
+            };
+            let mut pieces = minified::split_long_line(rest).into_iter();
+            lines.push(format!("{}{}", sign, pieces.next().unwrap_or_default()));
+            for piece in pieces {
+                lines.push(format!("{}↪ {}", sign, piece));
+                if i < self.blame.len() {
+                    blame.push(None);
                 }
             }
         }
+        self.file_content = lines;
+        self.blame = blame;
+    }
+
+    /// What a deleted entry takes away from the base: the file's content,
+    /// or the entries of a directory
+    fn deleted_content(&self, entry: &FileEntry) -> Vec<String> {
+        match self.lower.resolve(&entry.rel_path) {
+            Some(base_dir) if base_dir.is_dir() => {
+                let mut names: Vec<String> = fs::read_dir(&base_dir)
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|e| e.ok())
+                    .map(|e| e.file_name().to_string_lossy().into_owned())
+                    .collect();
+                names.sort();
+                let mut lines = vec!["<Directory>".to_string()];
+                lines.extend(names.into_iter().map(|name| format!("  {}", name)));
+                lines
+            }
+            Some(base_file) => file_lines(&base_file),
+            None => vec!["<Not in the base>".to_string()],
+        }
+    }
+
+    /// What an entry that is a different kind of entry than the base's is on
+    /// each side, then its content. Diffing through a symlink would compare
+    /// against whatever it points to rather than the link apply replaces.
+    fn type_change_content(&self, entry: &FileEntry) -> Vec<String> {
+        let base_file = self.lower.base_path(&entry.rel_path);
+        let mut lines = vec![
+            format!("Base: {}", file_operations::describe_file_type(&base_file)),
+            format!("Overlay: {}", file_operations::describe_file_type(&entry.path)),
+            "Applying replaces the base's entry rather than changing its content".to_string(),
+            String::new(),
+        ];
+        lines.extend(file_lines(&entry.path));
+        lines
+    }
+
+    /// Blame for the diff of an entry in file_content, once git blame of
+    /// its base file has finished. Until then there is none, and blame is
+    /// asked for in the background.
+    fn blame_for(&self, entry: &FileEntry) -> Vec<Option<String>> {
+        let Some(base_file) = self.lower.resolve(&entry.rel_path) else {
+            return Vec::new();
+        };
+        let fingerprint = review_state::fingerprint(&base_file);
+        match &self.blamed {
+            Some(blamed) if blamed.base_file == base_file && blamed.fingerprint == fingerprint => {
+                blamed.annotations.as_deref().map(|annotations| self.blame_removed_lines(annotations)).unwrap_or_default()
+            }
+            _ => {
+                self.blaming.request((base_file, fingerprint));
+                Vec::new()
+            }
+        }
+    }
+
+    /// Pick up git blame that has finished, showing it if it is for the
+    /// file under the cursor
+    pub fn receive_blame(&mut self) {
+        let Some(blamed) = self.blaming.finished().pop() else {
+            return;
+        };
+        let for_cursor = blamed.annotations.is_some()
+            && self.cursor_entry().and_then(|e| self.lower.resolve(&e.rel_path)).as_ref() == Some(&blamed.base_file);
+        self.blamed = Some(blamed);
+        if for_cursor {
+            let scroll = self.content_scroll;
+            self.load_selected_file_content();
+            self.content_scroll = scroll.min(self.file_content.len().saturating_sub(1));
+            self.redraw = true;
+        }
+    }
+
+    /// Blame annotations for the removed lines of the diff in file_content
+    fn blame_removed_lines(&self, annotations: &[String]) -> Vec<Option<String>> {
+        // The diff lists every base line in order after its header, which
+        // ends with an empty line
+        let body = self.file_content.iter().position(|line| line.is_empty()).map_or(0, |i| i + 1);
+        let mut base_line = 0;
+        let mut blame = vec![None; body];
+        for line in &self.file_content[body..] {
+            // The diff of a large file skips to where each hunk starts
+            if let Some(start) = line.strip_prefix("@@ -").and_then(|rest| rest.split(' ').next()?.parse::<usize>().ok()) {
+                base_line = start - 1;
+                blame.push(None);
+                continue;
+            }
+            if line.starts_with('-') {
+                blame.push(annotations.get(base_line).cloned());
+            } else {
+                blame.push(None);
+            }
+            if !line.starts_with('+') {
+                base_line += 1;
+            }
+        }
+        blame
+    }
+
+    /// Show or hide the blame gutter next to removed lines
+    pub fn toggle_blame(&mut self) {
+        if self.blame.iter().all(Option::is_none) {
+            self.show_toast("No git blame for this file (the base is not a git repository, or it has no removed lines)");
+            return;
+        }
+        self.show_blame = !self.show_blame;
+    }
+
+    /// Switch modified files between the diff, the overlay version and the
+    /// base version
+    pub fn cycle_content_view(&mut self) {
+        self.content_view = self.content_view.next();
+        if self.cursor_entry().is_some_and(|e| !e.is_dir && e.status == FileStatus::New) {
+            self.show_toast(format!("Showing {} for modified files; new files have no base version", self.content_view.label()));
+        }
+        self.load_selected_file_content();
+    }
+
+    /// When the file was last applied, if the sandbox has written it again since
+    pub fn applied_record(&self, entry: &FileEntry) -> Option<&AppliedRecord> {
+        if entry.is_dir {
+            return None;
+        }
+        self.applied.get(&entry.rel_path)
+    }
+
+    /// Diff a file written again after it was applied against the applied
+    /// version, which is in the base unless something else changed it since
+    fn diff_since_apply(&self, entry: &FileEntry, record: &AppliedRecord) -> Vec<String> {
+        let base_file = self.lower.base_path(&entry.rel_path);
+        let time = record.time.format("%Y-%m-%d %H:%M:%S");
+        let (mut lines, note) = if applied::hash_file(&base_file).is_ok_and(|hash| hash == record.sha256) {
+            (
+                self.diff_against(entry, &base_file, &self.diff_options_for(entry)),
+                format!("# Modified since apply: compared with the version applied at {}", time),
+            )
+        } else {
+            (
+                self.generate_diff(entry, &self.diff_options_for(entry)),
+                format!("# Modified since apply at {}, but the base has changed since too", time),
+            )
+        };
+        // Notes go after the ---/+++ header
+        lines.insert(2.min(lines.len()), note);
+        lines
+    }
+
+    /// Diff a modified file against its base version, through its
+    /// normalizer if it has one
+    fn generate_diff(&self, entry: &FileEntry, options: &DiffOptions) -> Vec<String> {
+        let base_file = self.lower.resolve(&entry.rel_path).unwrap_or_else(|| self.lower.base_path(&entry.rel_path));
+        self.diff_against(entry, &base_file, options)
+    }
+
+    /// Diff an overlay file against a given file, through its normalizer if
+    /// it has one. The normalizer runs in the background, the raw diff being
+    /// shown until it finishes.
+    fn diff_against(&self, entry: &FileEntry, base_file: &Path, options: &DiffOptions) -> Vec<String> {
+        let Some(normalizer) = self.normalizer_for(entry) else {
+            return file_operations::diff_against(entry, base_file, options);
+        };
+        let key = NormalizeKey::new(entry, base_file, options);
+        if let Some(normalized) = &self.normalized
+            && normalized.key == key
+        {
+            return normalized.lines.clone();
+        }
+        self.normalizing.request(NormalizeJob {
+            key,
+            entry: entry.clone(),
+            normalizer: normalizer.clone(),
+        });
+        let mut lines = file_operations::diff_against(entry, base_file, options);
+        lines.insert(2.min(lines.len()), format!("# Normalizing through `{}`…", normalizer.command.join(" ")));
+        lines
+    }
+
+    /// Pick up diffs through normalizers that have finished, showing the
+    /// one for the file under the cursor
+    pub fn receive_normalized_diffs(&mut self) {
+        let finished = self.normalizing.finished();
+        let cursor_file = self.cursor_entry().map(|e| e.path.clone());
+        let Some(normalized) = finished
+            .into_iter()
+            .max_by_key(|n| Some(&n.key.overlay_file) == cursor_file.as_ref())
+        else {
+            return;
+        };
+        let for_cursor = Some(&normalized.key.overlay_file) == cursor_file.as_ref();
+        self.normalized = Some(normalized);
+        if for_cursor {
+            let scroll = self.content_scroll;
+            self.load_selected_file_content();
+            self.content_scroll = scroll.min(self.file_content.len().saturating_sub(1));
+            self.redraw = true;
+        }
+    }
+
+    /// The normalizer an entry is diffed through, unless normalizing is
+    /// turned off
+    fn normalizer_for(&self, entry: &FileEntry) -> Option<&NormalizerConfig> {
+        if !self.diff_options.normalize || entry.is_dir {
+            return None;
+        }
+        normalize::for_path(&self.normalizers, &entry.path)
+    }
+
+    /// Whether a normalizer is configured for an entry's file type
+    pub fn has_normalizer(&self, entry: &FileEntry) -> bool {
+        !entry.is_dir && normalize::for_path(&self.normalizers, &entry.path).is_some()
     }
 
     pub fn scroll_content_down(&mut self) {
@@ -176,251 +974,2658 @@ impl App {
         }
     }
 
+    pub fn toggle_ignore_whitespace(&mut self) {
+        self.diff_options.ignore_whitespace = !self.diff_options.ignore_whitespace;
+        self.load_selected_file_content();
+    }
+
+    /// The diff options for an entry: a large file is diffed in full once
+    /// asked for
+    fn diff_options_for(&self, entry: &FileEntry) -> DiffOptions {
+        let mut options = self.diff_options;
+        if self.full_diff.as_ref() == Some(&entry.rel_path) {
+            options.large_file_kb = 0;
+        }
+        options
+    }
+
+    /// Whether the content pane shows only the changes of a large file
+    pub fn diff_is_partial(&self) -> bool {
+        self.is_diff_view && self.file_content.iter().take(8).any(|line| line.starts_with(file_operations::LARGE_DIFF_NOTE))
+    }
+
+    /// Diff the large file under the cursor in full, however long it takes
+    pub fn compute_full_diff(&mut self) {
+        if !self.diff_is_partial() {
+            self.show_toast("The whole diff is already shown");
+            return;
+        }
+        self.full_diff = self.cursor_entry().map(|entry| entry.rel_path.clone());
+        self.load_selected_file_content();
+    }
+
+    /// Show JSON and JavaScript pretty-printed, or as they are
+    pub fn toggle_pretty_print(&mut self) {
+        self.diff_options.pretty_print = !self.diff_options.pretty_print;
+        self.load_selected_file_content();
+    }
+
+    /// Diff files through their normalizers, or raw
+    pub fn toggle_normalize(&mut self) {
+        self.diff_options.normalize = !self.diff_options.normalize;
+        self.load_selected_file_content();
+    }
+
+    pub fn toggle_ignore_eol(&mut self) {
+        self.diff_options.ignore_eol = !self.diff_options.ignore_eol;
+        self.load_selected_file_content();
+    }
+
     pub fn toggle_pane(&mut self) {
         self.active_pane = match self.active_pane {
             ActivePane::FileList => ActivePane::FileContent,
-            ActivePane::FileContent => ActivePane::FileList,
+            ActivePane::FileContent if self.show_staging_pane => ActivePane::Staging,
+            ActivePane::FileContent | ActivePane::Staging => ActivePane::FileList,
         };
     }
 
-    pub fn toggle_selection(&mut self) {
-        if let Some(selected) = self.list_state.selected() {
-            if let Some(entry) = self.files.get(selected).cloned() {
-                if entry.is_dir {
-                    // For directories, toggle the directory itself
-                    let new_state = !entry.selected;
-                    self.files[selected].selected = new_state;
-
-                    let dir_path = entry.path.clone();
-                    let dir_depth = entry.depth;
-
-                    // Apply to all children (both files and directories)
-                    for i in (selected + 1)..self.files.len() {
-                        let child = &self.files[i];
-                        if child.depth <= dir_depth || !child.path.starts_with(&dir_path) {
-                            break;
-                        }
-                        self.files[i].selected = new_state;
-                    }
-                } else {
-                    // For files, toggle and handle parent deselection if needed
-                    let new_state = !entry.selected;
-                    self.files[selected].selected = new_state;
-
-                    // If deselecting a file, deselect all parent directories
-                    if !new_state {
-                        let file_path = entry.path.clone();
-                        for i in 0..selected {
-                            if self.files[i].is_dir && file_path.starts_with(&self.files[i].path) {
-                                self.files[i].selected = false;
-                            }
-                        }
-                    }
-                }
+    /// Open the session overview, or close it if it is open
+    pub fn toggle_dashboard(&mut self) {
+        self.dashboard = match self.dashboard {
+            Some(_) => None,
+            None => {
+                let report = report::build_report(&self.files, &self.overlay_path, &self.lower, true);
+                Some(dashboard::build(&report))
             }
+        };
+    }
+
+    /// Open the timeline view, or close it if it is open
+    pub fn toggle_timeline(&mut self) {
+        if self.timeline.take().is_some() {
+            return;
         }
+
+        let mut entries = self.path_activity();
+        self.timeline_sort.sort(&mut entries);
+        self.timeline = Some(entries);
+        self.timeline_cursor = 0;
     }
 
-    pub fn collapse_directory(&mut self) {
-        if let Some(selected) = self.list_state.selected() {
-            if let Some(entry) = self.files.get(selected).cloned() {
-                if entry.is_dir && !entry.collapsed {
-                    // Collapse the directory
-                    self.files[selected].collapsed = true;
-                } else if !entry.is_dir || entry.collapsed {
-                    // If it's a file or already collapsed, move to parent directory
-                    self.move_to_parent();
-                }
-            }
+    /// What happened to each file over the session, going by the recorded
+    /// timeline, or by modification time where nothing was recorded
+    fn path_activity(&mut self) -> Vec<PathActivity> {
+        let events = match &self.session_dir {
+            Some(dir) => timeline::load_events(dir).unwrap_or_else(|e| {
+                self.show_toast(format!("Failed to read the timeline: {}", e));
+                Vec::new()
+            }),
+            None => Vec::new(),
+        };
+        let current: Vec<(PathBuf, _)> = self
+            .files
+            .iter()
+            .filter(|(_, e)| !e.is_dir)
+            .map(|(_, e)| (e.rel_path.clone(), timeline::mtime(&e.path)))
+            .collect();
+
+        let mut entries = timeline::summarize(&events, &current);
+        entries.retain(|e| !self.overlay_path.join(&e.path).is_dir());
+        entries
+    }
+
+    pub fn cycle_timeline_sort(&mut self) {
+        self.timeline_sort = self.timeline_sort.next();
+        if let Some(entries) = &mut self.timeline {
+            self.timeline_sort.sort(entries);
         }
+        self.timeline_cursor = 0;
     }
 
-    pub fn expand_directory(&mut self) {
-        if let Some(selected) = self.list_state.selected() {
-            if let Some(entry) = self.files.get(selected).cloned() {
-                if entry.is_dir && entry.collapsed {
-                    // Expand the directory
-                    self.files[selected].collapsed = false;
-                }
+    pub fn timeline_next(&mut self) {
+        let len = self.timeline.as_ref().map_or(0, |e| e.len());
+        if self.timeline_cursor + 1 < len {
+            self.timeline_cursor += 1;
+        }
+    }
+
+    pub fn timeline_previous(&mut self) {
+        self.timeline_cursor = self.timeline_cursor.saturating_sub(1);
+    }
+
+    /// Close the timeline and move the file list cursor to the selected file
+    pub fn jump_to_timeline_entry(&mut self) {
+        let Some(rel_path) = self
+            .timeline
+            .as_ref()
+            .and_then(|entries| entries.get(self.timeline_cursor))
+            .map(|e| e.path.clone())
+        else {
+            return;
+        };
+        let Some(id) = self.files.find(&self.overlay_path.join(&rel_path)) else {
+            self.show_toast(format!("{} is no longer in the overlay", rel_path.display()));
+            return;
+        };
+
+        self.timeline = None;
+        self.reveal(id, &rel_path);
+    }
+
+    /// Move the cursor to an entry, expanding its ancestors
+    fn reveal(&mut self, id: NodeId, rel_path: &Path) {
+        for ancestor in self.files.ancestors(id).collect::<Vec<_>>() {
+            if let Some(entry) = self.files.get_mut(ancestor) {
+                entry.collapsed = false;
             }
         }
+        if !self.visible_ids().contains(&id) {
+            self.show_toast(format!("{} is hidden by the current filter", rel_path.display()));
+            return;
+        }
+        self.cursor = Some(id);
+        self.load_selected_file_content();
     }
 
-    fn move_to_parent(&mut self) {
-        if let Some(selected) = self.list_state.selected() {
-            if let Some(entry) = self.files.get(selected) {
-                let current_path = &entry.path;
-                let current_depth = entry.depth;
-
-                // Find the parent directory by looking backwards for a directory with depth-1
-                for i in (0..selected).rev() {
-                    if self.files[i].is_dir
-                        && self.files[i].depth == current_depth.saturating_sub(1)
-                        && current_path.starts_with(&self.files[i].path) {
-                        self.list_state.select(Some(i));
-                        self.load_selected_file_content();
-                        break;
+    /// Open the details popup for the entry under the cursor
+    pub fn open_details(&mut self) {
+        let Some(entry) = self.cursor_entry() else {
+            return;
+        };
+        let rel_path = entry.rel_path.clone();
+        let base_file = self.lower.resolve(&rel_path);
+        let overlay = fs::symlink_metadata(&entry.path).ok();
+        let base = base_file.as_deref().and_then(|path| fs::symlink_metadata(path).ok());
+
+        let status = match entry.status {
+            _ if self.is_seeded(entry) => "seeded from the template",
+            FileStatus::New => "new",
+            FileStatus::Modified => "modified",
+            FileStatus::Deleted => "deleted",
+            FileStatus::TypeChanged => "type changed",
+        };
+        let mut details = vec![
+            ("Path", self.path_display.show(&rel_path)),
+            ("Status", format!("{}{}", status, if entry.is_dir { " directory" } else { "" })),
+            ("Overlay", entry.path.display().to_string()),
+            (
+                "Base",
+                match &base_file {
+                    Some(path) => path.display().to_string(),
+                    None => format!("{} (not there yet)", self.lower.base_path(&rel_path).display()),
+                },
+            ),
+        ];
+        // What applying it would do to the base
+        match file_operations::apply_destination(entry, &self.lower) {
+            Ok(Some(dest)) => {
+                let action = match (dest.exists, entry.is_dir, dest.missing_parents) {
+                    (true, true, _) => "updates the directory".to_string(),
+                    (true, false, _) => "overwrites it".to_string(),
+                    (false, _, 0) => "creates it".to_string(),
+                    (false, _, parents) => {
+                        format!("creates it and {} parent director{}", parents, if parents == 1 { "y" } else { "ies" })
                     }
-                }
+                };
+                details.push(("Applies to", format!("{} ({})", dest.path.display(), action)));
+                let user = identity::user_name(dest.uid).unwrap_or_else(|| dest.uid.to_string());
+                let group = identity::group_name(dest.gid).unwrap_or_else(|| dest.gid.to_string());
+                let now = dest.replaced_mode.map(|mode| format!(" (now {:o})", mode)).unwrap_or_default();
+                details.push(("Lands as", format!("owner {}:{}, mode {:o}{}", user, group, dest.mode, now)));
+            }
+            Ok(None) => details.push(("Applies to", "nothing: deletions are not applied".to_string())),
+            Err(e) => details.push(("Applies to", format!("<{}>", e))),
+        }
+        let both = |describe: &dyn Fn(&fs::Metadata) -> String| match (&overlay, &base) {
+            (Some(overlay), Some(base)) => format!("{} (base: {})", describe(overlay), describe(base)),
+            (Some(overlay), None) => describe(overlay),
+            (None, _) => "-".to_string(),
+        };
+        details.push(("Mode", both(&|m| format!("{:o}", m.permissions().mode() & 0o7777))));
+        details.push(("Modified", both(&|m| match m.modified() {
+            Ok(time) => DateTime::<Local>::from(time).format("%Y-%m-%d %H:%M:%S").to_string(),
+            Err(_) => "?".to_string(),
+        })));
+        if !entry.is_dir {
+            details.push(("Size", both(&|m| format!("{} byte{}", m.len(), if m.len() == 1 { "" } else { "s" }))));
+            // Holes take no space on disk, so a sparse file is smaller than its length
+            if overlay.iter().chain(&base).any(file_operations::is_sparse) {
+                details.push(("Allocated", both(&|m| {
+                    let sparse = if file_operations::is_sparse(m) { ", sparse" } else { "" };
+                    format!("{}{}", disk_usage::format_size(m.blocks() * 512), sparse)
+                })));
+            }
+            let hash = |path: &Path| applied::hash_file(path).unwrap_or_else(|e| format!("<{}>", e));
+            details.push(("SHA-256", hash(&entry.path)));
+            if let Some(base_file) = &base_file {
+                details.push(("Base SHA-256", hash(base_file)));
             }
         }
+        self.details = Some(details);
     }
 
-    pub fn get_selected_files(&self) -> Vec<FileEntry> {
-        self.files
-            .iter()
-            .filter(|e| e.selected && !e.is_dir)
-            .cloned()
-            .collect()
+    pub fn open_path_input(&mut self) {
+        self.modal = Modal::Path(Input::new(&self.path_history));
     }
 
-    pub fn get_visible_files(&self) -> Vec<(usize, &FileEntry)> {
-        let mut visible = Vec::new();
-        let mut collapsed_dirs: Vec<(PathBuf, usize)> = Vec::new();
+    /// Open the prompt for the file to write the review's decisions to
+    pub fn open_plan_input(&mut self) {
+        let mut prompt = Input::new(&self.plan_history);
+        prompt.set_text(self.plan_history.last().map_or(DEFAULT_PLAN_FILE, String::as_str));
+        self.modal = Modal::Plan(prompt);
+    }
 
-        for (idx, entry) in self.files.iter().enumerate() {
-            // Remove collapsed dirs from stack if we've moved past their depth
-            collapsed_dirs.retain(|(_, depth)| entry.depth > *depth);
+    /// Write the review's decisions to the typed file, relative to the
+    /// directory the review was started in
+    pub fn submit_plan_input(&mut self, input: &str) {
+        self.modal = Modal::None;
+        let input = input.trim();
+        if input.is_empty() {
+            return;
+        }
+        input::remember(&mut self.plan_history, input);
+        let plan = self.decisions();
+        let applied = plan.decisions.iter().filter(|d| d.verdict == Verdict::Apply).count();
+        match plan::save(Path::new(input), &plan) {
+            Ok(()) => self.show_toast(format!(
+                "Wrote {} to apply and {} to skip to {}",
+                describe_count(applied),
+                plan.decisions.len() - applied,
+                input
+            )),
+            Err(e) => self.show_toast(format!("Failed to write {}: {}", input, e)),
+        }
+    }
 
-            // Check if this entry is inside a collapsed directory
-            let is_hidden = collapsed_dirs.iter().any(|(dir_path, _)| {
-                entry.path.starts_with(dir_path) && entry.path != *dir_path
-            });
+    /// The review's decisions as a plan: the selected and staged entries are
+    /// applied, with the SHA-256 of each file as reviewed, and the other
+    /// changes skipped
+    pub fn decisions(&self) -> Plan {
+        let mut chosen: Vec<PathBuf> = self.get_selected_files().into_iter().map(|e| e.rel_path).collect();
+        for rel_path in &self.staged {
+            if !chosen.contains(rel_path) {
+                chosen.push(rel_path.clone());
+            }
+        }
 
-            if !is_hidden {
-                visible.push((idx, entry));
+        let mut decisions: Vec<Decision> = chosen
+            .into_iter()
+            .map(|rel_path| {
+                let path = self.overlay_path.join(&rel_path);
+                let is_file = fs::symlink_metadata(&path).is_ok_and(|m| m.is_file());
+                let sha256 = is_file.then(|| applied::hash_file(&path).ok()).flatten();
+                Decision { path: rel_path, verdict: Verdict::Apply, sha256 }
+            })
+            .collect();
+        let skipped: Vec<Decision> = self
+            .change_entries()
+            .into_iter()
+            .filter(|e| !decisions.iter().any(|d| d.path == e.rel_path))
+            .map(|e| Decision { path: e.rel_path, verdict: Verdict::Skip, sha256: None })
+            .collect();
+        decisions.extend(skipped);
 
-                // If this is a collapsed directory, add it to the stack
-                if entry.is_dir && entry.collapsed {
-                    collapsed_dirs.push((entry.path.clone(), entry.depth));
-                }
-            }
+        Plan {
+            created: Some(Local::now()),
+            reviewer: Some(self.identity.clone()),
+            decisions,
         }
+    }
 
-        visible
+    /// Entries completing the typed path, one path component at a time like
+    /// a shell, with directories ending in '/'
+    pub fn path_completions(&self) -> Vec<String> {
+        let Modal::Path(prompt) = &self.modal else {
+            return Vec::new();
+        };
+        let input = prompt.text();
+        let mut completions: Vec<String> = self
+            .files
+            .iter()
+            .map(|(_, e)| {
+                let rel_path = e.rel_path.to_string_lossy().into_owned();
+                if e.is_dir { rel_path + "/" } else { rel_path }
+            })
+            .filter(|rel_path| {
+                rel_path
+                    .strip_prefix(input)
+                    .is_some_and(|rest| !rest.trim_end_matches('/').contains('/'))
+            })
+            .collect();
+        completions.sort();
+        completions
     }
 
-    pub fn apply_changes(&self) -> io::Result<()> {
-        let selected = self.get_selected_files();
-        file_operations::apply_changes(&selected, &self.overlay_path, &self.base_path)
+    /// Extend the typed path as far as the completions agree
+    pub fn complete_path_input(&mut self) {
+        let completions = self.path_completions();
+        let Some(first) = completions.first() else {
+            return;
+        };
+        let common = completions.iter().fold(first.as_str(), |common, c| {
+            let len = common
+                .char_indices()
+                .zip(c.chars())
+                .find(|((_, a), b)| a != b)
+                .map_or(common.len().min(c.len()), |((i, _), _)| i);
+            &common[..len]
+        });
+        let common = common.to_string();
+        if let Modal::Path(prompt) = &mut self.modal {
+            prompt.set_text(common);
+        }
     }
 
-    pub fn discard_selected_file(&mut self) -> io::Result<()> {
-        if let Some(selected) = self.list_state.selected() {
-            if let Some(entry) = self.files.get(selected) {
-                let path = entry.path.clone();
-                file_operations::discard_file(&path)?;
+    /// Jump to the typed path, or to the only entry completing it
+    pub fn submit_path_input(&mut self, input: &str) {
+        let completions = self.path_completions();
+        self.modal = Modal::None;
+        input::remember(&mut self.path_history, input);
+
+        let typed = PathBuf::from(input.trim().trim_end_matches('/'));
+        let rel_path = match self.files.find(&self.overlay_path.join(&typed)) {
+            Some(_) => typed,
+            None if completions.len() == 1 => PathBuf::from(completions[0].trim_end_matches('/')),
+            None => {
+                self.show_toast(format!("No entry {}", typed.display()));
+                return;
             }
+        };
+        if let Some(id) = self.files.find(&self.overlay_path.join(&rel_path)) {
+            self.reveal(id, &rel_path);
         }
-        Ok(())
     }
 
-    pub fn refresh_file_list(&mut self) -> io::Result<()> {
-        let current_selection = self.list_state.selected();
-        let selected_path = current_selection
-            .and_then(|i| self.files.get(i))
-            .map(|e| e.path.clone());
-
-        // Rescan the overlay directory
-        let mut files = Vec::new();
-        file_operations::scan_directory(
-            &self.overlay_path,
-            &self.overlay_path,
-            &self.base_path,
-            0,
-            &mut files,
-        )?;
+    pub fn toggle_staging_pane(&mut self) {
+        self.show_staging_pane = !self.show_staging_pane;
+        if !self.show_staging_pane && self.active_pane == ActivePane::Staging {
+            self.active_pane = ActivePane::FileList;
+        }
+    }
 
-        // Try to restore selection to the same file
-        let new_selection = if let Some(ref path) = selected_path {
-            files.iter().position(|e| e.path == *path)
-        } else {
-            None
+    /// Stage or unstage the file under the cursor. For a directory, stage
+    /// every file under it, or unstage them all if they are already staged.
+    pub fn toggle_staged(&mut self) {
+        let Some(cursor) = self.cursor else {
+            return;
         };
+        let files: Vec<PathBuf> = std::iter::once(cursor)
+            .chain(self.files.descendants(cursor))
+            .filter_map(|id| self.files.get(id))
+            .filter(|e| !e.is_dir)
+            .map(|e| e.rel_path.clone())
+            .collect();
+        if files.is_empty() {
+            return;
+        }
 
-        self.files = files;
-        if let Some(idx) = new_selection {
-            self.list_state.select(Some(idx));
-        } else if !self.files.is_empty() {
-            self.list_state.select(Some(0));
+        if files.iter().all(|f| self.staged.contains(f)) {
+            self.staged.retain(|p| !files.contains(p));
+            self.show_toast(format!("Unstaged {}", describe_count(files.len())));
+        } else {
+            let added: Vec<PathBuf> = files.into_iter().filter(|f| !self.staged.contains(f)).collect();
+            self.show_toast(format!("Staged {} ({} in queue)", describe_count(added.len()), self.staged.len() + added.len()));
+            self.staged.extend(added);
+            self.show_staging_pane = true;
         }
+        self.clamp_staging_cursor();
+    }
 
-        self.load_selected_file_content();
-        Ok(())
+    pub fn is_staged(&self, entry: &FileEntry) -> bool {
+        self.staged.iter().any(|p| p == &entry.rel_path)
     }
 
-    pub fn check_fs_events(&mut self) {
-        // Check for filesystem events without blocking
-        while let Ok(event) = self.fs_events.try_recv() {
-            if let Ok(event) = event {
-                match event.kind {
-                    EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_) => {
-                        // Collect all affected paths
-                        for path in event.paths {
-                            if !self.pending_updates.contains(&path) {
-                                self.pending_updates.push(path);
-                            }
-                        }
-                    }
-                    _ => {}
-                }
-            }
+    /// Whether a file was copied in from the session template and is
+    /// unchanged since
+    pub fn is_seeded(&self, entry: &FileEntry) -> bool {
+        self.seeded
+            .get(&entry.rel_path)
+            .is_some_and(|seeded| review_state::fingerprint(&entry.path).as_ref() == Some(seeded))
+    }
+
+    pub fn unstage_at_cursor(&mut self) {
+        if self.staging_cursor < self.staged.len() {
+            self.staged.remove(self.staging_cursor);
+            self.clamp_staging_cursor();
         }
     }
 
-    pub fn process_pending_updates(&mut self) -> io::Result<()> {
-        if self.pending_updates.is_empty() {
-            return Ok(());
+    pub fn staging_next(&mut self) {
+        if self.staging_cursor + 1 < self.staged.len() {
+            self.staging_cursor += 1;
         }
+    }
 
-        let current_selection = self.list_state.selected();
-        let selected_path = current_selection
-            .and_then(|i| self.files.get(i))
-            .map(|e| e.path.clone());
+    pub fn staging_previous(&mut self) {
+        self.staging_cursor = self.staging_cursor.saturating_sub(1);
+    }
 
-        // Collect paths to process
-        let paths: Vec<PathBuf> = self.pending_updates.drain(..).collect();
+    /// Move the file under the staging cursor one place earlier in the queue
+    pub fn move_staged_up(&mut self) {
+        if self.staging_cursor > 0 && self.staging_cursor < self.staged.len() {
+            self.staged.swap(self.staging_cursor, self.staging_cursor - 1);
+            self.staging_cursor -= 1;
+        }
+    }
+
+    /// Move the file under the staging cursor one place later in the queue
+    pub fn move_staged_down(&mut self) {
+        if self.staging_cursor + 1 < self.staged.len() {
+            self.staged.swap(self.staging_cursor, self.staging_cursor + 1);
+            self.staging_cursor += 1;
+        }
+    }
+
+    /// Entries for the staged files, in queue order
+    pub fn staged_entries(&self) -> Vec<FileEntry> {
+        self.staged
+            .iter()
+            .filter_map(|rel_path| self.files.find(&self.overlay_path.join(rel_path)))
+            .filter_map(|id| self.files.get(id))
+            .cloned()
+            .collect()
+    }
+
+    /// Drop staged files that are no longer in the overlay
+    fn prune_staged(&mut self) {
+        let present: Vec<bool> = self
+            .staged
+            .iter()
+            .map(|rel_path| {
+                self.files
+                    .find(&self.overlay_path.join(rel_path))
+                    .and_then(|id| self.files.get(id))
+                    .is_some_and(|e| !e.is_dir)
+            })
+            .collect();
+        let mut present = present.into_iter();
+        self.staged.retain(|_| present.next().unwrap_or(false));
+        self.clamp_staging_cursor();
+    }
+
+    fn clamp_staging_cursor(&mut self) {
+        self.staging_cursor = self.staging_cursor.min(self.staged.len().saturating_sub(1));
+    }
+
+    pub fn toggle_selection(&mut self) {
+        let Some(id) = self.cursor else {
+            return;
+        };
+
+        // Partially selected directories become fully selected
+        let new_state = self.files.selection_state(id) != SelectionState::Selected;
+        for node in std::iter::once(id).chain(self.files.descendants(id)) {
+            self.files.set_selected(node, new_state);
+        }
+    }
+
+    pub fn collapse_directory(&mut self) {
+        // Grouped by kind, there are no directories to collapse or move up to
+        if self.group_by_kind {
+            return;
+        }
+        if let Some(entry) = self.cursor_entry() {
+            if entry.is_dir && !entry.collapsed {
+                // Collapse the directory
+                if let Some(e) = self.cursor.and_then(|id| self.files.get_mut(id)) {
+                    e.collapsed = true;
+                }
+            } else {
+                // If it's a file or already collapsed, move to parent directory
+                self.move_to_parent();
+            }
+        }
+    }
+
+    pub fn expand_directory(&mut self) {
+        if let Some(entry) = self.cursor.and_then(|id| self.files.get_mut(id))
+            && entry.is_dir
+            && entry.collapsed
+        {
+            // Expand the directory
+            entry.collapsed = false;
+        }
+    }
+
+    /// Expand directories above `level` and collapse the rest, so that entries
+    /// up to `level` deep are shown. Level 0 collapses every directory.
+    pub fn expand_to_level(&mut self, level: usize) {
+        for id in self.files.ids() {
+            if let Some(entry) = self.files.get_mut(id)
+                && entry.is_dir
+            {
+                entry.collapsed = entry.depth >= level;
+            }
+        }
+        self.reveal_cursor();
+    }
+
+    pub fn collapse_all(&mut self) {
+        self.expand_to_level(0);
+    }
+
+    pub fn expand_all(&mut self) {
+        self.expand_to_level(usize::MAX);
+    }
+
+    /// If the cursor ended up inside a collapsed directory, move it to the
+    /// outermost collapsed ancestor so it stays visible.
+    fn reveal_cursor(&mut self) {
+        let Some(id) = self.cursor else {
+            return;
+        };
+        if self.group_by_kind {
+            return;
+        }
+        let hidden_in = self
+            .files
+            .ancestors(id)
+            .filter(|&a| self.files.get(a).is_some_and(|e| e.collapsed))
+            .last();
+        if let Some(dir) = hidden_in {
+            self.cursor = Some(dir);
+            self.load_selected_file_content();
+        }
+    }
+
+    fn move_to_parent(&mut self) {
+        if let Some(parent) = self.cursor.and_then(|id| self.files.parent(id)) {
+            self.cursor = Some(parent);
+            self.load_selected_file_content();
+        }
+    }
+
+    pub fn get_selected_files(&self) -> Vec<FileEntry> {
+        self.files
+            .iter()
+            .filter(|(id, e)| e.selected && self.is_applied_as_unit(*id))
+            .map(|(_, e)| e.clone())
+            .collect()
+    }
+
+    /// Files are applied one by one, and so are directories with nothing
+    /// under them or whose own mode or xattrs changed; other directories are
+    /// applied through their contents
+    fn is_applied_as_unit(&self, id: NodeId) -> bool {
+        self.files
+            .get(id)
+            .is_some_and(|e| !e.is_dir || self.files.children(id).is_empty() || self.dir_changes.contains_key(&id))
+    }
+
+    /// Visible entries in display order, honouring collapsed directories and
+    /// the changed-since-last-review filter. Grouped by kind, every file is
+    /// listed under its kind instead, by path.
+    fn visible_ids(&self) -> Vec<NodeId> {
+        let labelled = self.label_filter.as_ref().map(|label| {
+            let mut ids = HashSet::new();
+            for entry in self.labelled_entries(label) {
+                if let Some(id) = self.files.find(&entry.path) {
+                    ids.insert(id);
+                    ids.extend(self.files.ancestors(id));
+                }
+            }
+            ids
+        });
+        let grouped = self.changeset_filter.as_ref().map(|name| {
+            let mut ids = HashSet::new();
+            for entry in self.changeset_entries(name) {
+                if let Some(id) = self.files.find(&entry.path) {
+                    ids.insert(id);
+                    ids.extend(self.files.ancestors(id));
+                }
+            }
+            ids
+        });
+
+        let ids = if self.group_by_kind {
+            let mut ids: Vec<NodeId> = self.files.ids().into_iter().filter(|id| self.is_applied_as_unit(*id)).collect();
+            ids.sort_by_cached_key(|id| (self.kind_of(*id), self.files.get(*id).map(|e| e.path.clone())));
+            ids
+        } else {
+            self.files.visible()
+        };
+        ids.into_iter()
+            .filter(|id| !self.changed_only || self.changed_since_review.contains(id))
+            .filter(|id| self.show_ignored || !self.ignored.contains(id))
+            .filter(|id| self.hidden_files != HiddenFiles::Hide || !self.files.is_hidden(*id))
+            .filter(|id| labelled.as_ref().is_none_or(|ids| ids.contains(id)))
+            .filter(|id| grouped.as_ref().is_none_or(|ids| ids.contains(id)))
+            .collect()
+    }
+
+    /// Kind of a listed file, while grouping by kind
+    pub fn kind_of(&self, id: NodeId) -> FileKind {
+        self.kinds.get(&id).copied().unwrap_or(FileKind::Other)
+    }
+
+    /// List files grouped by kind, or by directory again
+    pub fn toggle_group_by_kind(&mut self) {
+        self.group_by_kind = !self.group_by_kind;
+        self.update_kinds();
+        self.keep_cursor_visible();
+    }
+
+    /// Work out what is shown about each entry besides its status, after
+    /// the list changed
+    fn update_entry_info(&mut self) {
+        self.update_ignored();
+        self.update_kinds();
+        self.update_privileged();
+        self.update_dir_changes();
+        self.update_hash_verdicts();
+        self.request_checks();
+    }
+
+    /// Have the external checks run on files that are new or changed since
+    /// they were last checked
+    fn request_checks(&mut self) {
+        let Some(checker) = &self.checker else {
+            return;
+        };
+        for (_, entry) in self.files.iter().filter(|(_, e)| !e.is_dir) {
+            let Ok(modified) = fs::symlink_metadata(&entry.path).and_then(|m| m.modified()) else {
+                continue;
+            };
+            if self.checks.get(&entry.path).is_some_and(|(checked, _)| *checked == modified) {
+                continue;
+            }
+            let rel_path = entry.rel_path.clone();
+            checker.request(CheckJob {
+                base_file: self.lower.base_path(&rel_path),
+                rel_path,
+                overlay_file: entry.path.clone(),
+                modified,
+            });
+            self.checks.insert(entry.path.clone(), (modified, None));
+        }
+    }
+
+    /// Pick up the results of checks that have finished
+    pub fn receive_check_results(&mut self) {
+        let Some(checker) = &self.checker else {
+            return;
+        };
+        for report in checker.finished() {
+            self.redraw = true;
+            // Results for an older version of the file wait for the next run
+            if let Some((modified, results)) = self.checks.get_mut(&report.overlay_file)
+                && *modified == report.modified
+            {
+                *results = Some(report.results);
+            }
+        }
+    }
+
+    /// Results of the external checks on an entry: none while they are
+    /// running or if no checks apply
+    pub fn check_results(&self, entry: &FileEntry) -> Option<&[CheckResult]> {
+        match self.checks.get(&entry.path) {
+            Some((_, Some(results))) if !results.is_empty() => Some(results),
+            _ => None,
+        }
+    }
+
+    /// Whether the external checks are still running on an entry
+    pub fn is_being_checked(&self, entry: &FileEntry) -> bool {
+        matches!(self.checks.get(&entry.path), Some((_, None)))
+    }
+
+    /// Look the content of each file up in the hash lists. Hashes are kept
+    /// until a file changes; those that are not are worked out in the
+    /// background and looked up as they arrive.
+    fn update_hash_verdicts(&mut self) {
+        self.hash_verdicts.clear();
+        if self.hash_lists.is_empty() {
+            return;
+        }
+        let files: Vec<(NodeId, PathBuf)> =
+            self.files.iter().filter(|(_, e)| !e.is_dir).map(|(id, e)| (id, e.path.clone())).collect();
+        for (id, path) in files {
+            // Only regular files: a symlink is not followed, and a FIFO
+            // would never finish reading
+            if !is_regular_file(&path) {
+                continue;
+            }
+            let Some(fingerprint) = review_state::fingerprint(&path) else {
+                continue;
+            };
+            match self.hashes.get(&path) {
+                Some((when, hash)) if *when == fingerprint => {
+                    if let Some(verdict) = self.hash_lists.verdict(hash) {
+                        self.hash_verdicts.insert(id, verdict);
+                    }
+                }
+                _ => {
+                    if self.hashes_pending.insert(path.clone()) {
+                        self.hashing.request((path, fingerprint));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Pick up files hashed in the background and look them up in the hash
+    /// lists, unless they changed again since
+    pub fn receive_hashes(&mut self) {
+        for hashed in self.hashing.finished() {
+            self.hashes_pending.remove(&hashed.path);
+            let Some(sha256) = hashed.sha256 else {
+                continue;
+            };
+            if review_state::fingerprint(&hashed.path).as_ref() == Some(&hashed.fingerprint)
+                && let Some(id) = self.files.find(&hashed.path)
+                && let Some(verdict) = self.hash_lists.verdict(&sha256)
+            {
+                self.hash_verdicts.insert(id, verdict);
+                self.redraw = true;
+            }
+            self.hashes.insert(hashed.path, (hashed.fingerprint, sha256));
+        }
+    }
+
+    /// Whether an entry's content is on the allowlist or the denylist
+    pub fn hash_verdict(&self, entry: &FileEntry) -> Option<HashVerdict> {
+        self.files.find(&entry.path).and_then(|id| self.hash_verdicts.get(&id)).copied()
+    }
+
+    /// Whether an entry's content is on the denylist, hashing it now if
+    /// the background hash of it is missing or out of date
+    fn is_denylisted(&self, entry: &FileEntry) -> bool {
+        if self.hash_lists.is_empty() || !is_regular_file(&entry.path) {
+            return false;
+        }
+        let fresh = self
+            .hashes
+            .get(&entry.path)
+            .filter(|(when, _)| review_state::fingerprint(&entry.path).as_ref() == Some(when))
+            .map(|(_, hash)| hash.clone());
+        let Some(hash) = fresh.or_else(|| applied::hash_file(&entry.path).ok()) else {
+            return false;
+        };
+        self.hash_lists.verdict(&hash) == Some(HashVerdict::Denied)
+    }
+
+    /// Find the files that are setuid, setgid or have capabilities
+    fn update_privileged(&mut self) {
+        self.privileged = self
+            .files
+            .iter()
+            .filter(|(_, e)| !e.is_dir)
+            .filter_map(|(id, e)| Privileges::of(&e.path).map(|p| (id, p)))
+            .collect();
+    }
+
+    /// Find the directories whose mode or xattrs differ from the base's. The
+    /// top-level directories of a session with several bases are where
+    /// those are mounted, so they are left out.
+    fn update_dir_changes(&mut self) {
+        self.dir_changes = self
+            .files
+            .iter()
+            .filter(|(_, e)| e.is_dir && e.status == FileStatus::Modified && e.depth >= self.lower.root_depth())
+            .filter_map(|(id, e)| {
+                let base_dir = self.lower.resolve(&e.rel_path)?;
+                let changes = dir_metadata::changes(&e.path, &base_dir);
+                (!changes.is_empty()).then_some((id, changes))
+            })
+            .collect();
+    }
+
+    /// How a directory's mode or xattrs differ from the base's, if they do
+    pub fn dir_changes(&self, entry: &FileEntry) -> Option<&[String]> {
+        self.files.find(&entry.path).and_then(|id| self.dir_changes.get(&id)).map(Vec::as_slice)
+    }
+
+    /// Privileges an entry would carry over to the base
+    pub fn privileges(&self, entry: &FileEntry) -> Option<&Privileges> {
+        self.files.find(&entry.path).and_then(|id| self.privileged.get(&id))
+    }
+
+    fn is_privileged(&self, entry: &FileEntry) -> bool {
+        self.privileges(entry).is_some()
+    }
+
+    /// Files the apply dialog would apply that are setuid, setgid or have
+    /// capabilities
+    pub fn privileged_targets(&self) -> Vec<FileEntry> {
+        self.apply_targets().into_iter().filter(|e| self.is_privileged(e)).collect()
+    }
+
+    /// Whether applying needs a second confirmation first
+    pub fn privileged_unconfirmed(&self) -> bool {
+        matches!(&self.modal, Modal::Apply(state) if !state.privileged_confirmed) && !self.privileged_targets().is_empty()
+    }
+
+    /// Work out the kind of every file, while grouping by kind
+    fn update_kinds(&mut self) {
+        self.kinds.clear();
+        if !self.group_by_kind {
+            return;
+        }
+        for (id, entry) in self.files.iter() {
+            if !entry.is_dir {
+                self.kinds.insert(id, FileKind::of(&entry.path));
+            }
+        }
+    }
+
+    pub fn is_changed_since_review(&self, id: NodeId) -> bool {
+        self.changed_since_review.contains(&id)
+    }
+
+    pub fn toggle_changed_only(&mut self) {
+        if self.review_baseline.is_none() {
+            self.show_toast("No earlier review of this session to compare against");
+            return;
+        }
+
+        self.changed_only = !self.changed_only;
+        self.keep_cursor_visible();
+    }
+
+    pub fn is_ignored(&self, id: NodeId) -> bool {
+        self.ignored.contains(&id)
+    }
+
+    /// Show or hide the entries the base's .gitignore files ignore
+    pub fn toggle_show_ignored(&mut self) {
+        if self.gitignore.is_none() {
+            self.show_toast("Ignoring paths from .gitignore is off (review --gitignore)");
+            return;
+        }
+
+        self.show_ignored = !self.show_ignored;
+        let count = self.ignored.len();
+        if self.show_ignored {
+            self.show_toast(format!("Showing {} ignored entries", count));
+        } else {
+            self.show_toast(format!("Hiding {} ignored entries", count));
+        }
+        self.keep_cursor_visible();
+    }
+
+    /// Switch between showing, hiding and grouping hidden entries last
+    pub fn cycle_hidden_files(&mut self) {
+        self.hidden_files = self.hidden_files.next();
+        self.files.set_hidden_last(self.hidden_files == HiddenFiles::Last);
+        self.show_toast(format!("Hidden files {}", self.hidden_files.describe()));
+        self.keep_cursor_visible();
+    }
+
+    /// Work out which entries the base's .gitignore files ignore. Everything
+    /// under an ignored directory is ignored too.
+    fn update_ignored(&mut self) {
+        self.ignored.clear();
+        let Some(gitignore) = self.gitignore.as_mut() else {
+            return;
+        };
+
+        for (id, entry) in self.files.iter() {
+            let ignored = self.files.parent(id).is_some_and(|parent| self.ignored.contains(&parent))
+                || gitignore.is_ignored(&entry.rel_path, entry.is_dir);
+            if ignored {
+                self.ignored.insert(id);
+            }
+        }
+    }
+
+    /// Move the cursor to the first shown entry if a filter hid it
+    fn keep_cursor_visible(&mut self) {
+        if !self.cursor.is_some_and(|id| self.visible_ids().contains(&id)) {
+            self.cursor = self.visible_ids().first().copied();
+            self.load_selected_file_content();
+        }
+    }
+
+    /// Work out which entries differ from the snapshot of the last review
+    fn update_review_delta(&mut self) {
+        self.changed_since_review.clear();
+        let Some(baseline) = &self.review_baseline else {
+            return;
+        };
+
+        let changed: Vec<NodeId> = self
+            .files
+            .iter()
+            .filter(|(_, e)| !e.is_dir)
+            .filter(|(_, e)| {
+                let rel_path = &e.rel_path;
+                baseline.get(rel_path) != review_state::fingerprint(&e.path).as_ref()
+            })
+            .map(|(id, _)| id)
+            .collect();
+
+        for id in changed {
+            self.changed_since_review.insert(id);
+            self.changed_since_review.extend(self.files.ancestors(id));
+        }
+    }
+
+    pub fn get_visible_files(&self) -> Vec<(NodeId, &FileEntry)> {
+        self.visible_ids()
+            .into_iter()
+            .filter_map(|id| self.files.get(id).map(|e| (id, e)))
+            .collect()
+    }
+
+    /// Review without changing the overlay or the base, while another
+    /// review holds the session
+    pub fn set_read_only(&mut self) {
+        self.read_only = true;
+        // The other review records the session's activity
+        self.timeline_recorder = None;
+    }
+
+    /// Let apply write outside `apply.allowed_roots`, as with
+    /// --allow-outside-roots
+    pub fn set_allow_outside_roots(&mut self) {
+        self.allow_outside_roots = true;
+    }
+
+    /// Whether applying an entry would write outside `apply.allowed_roots`
+    pub fn is_outside_allowed_roots(&self, entry: &FileEntry) -> bool {
+        !self.allowed_roots.allows(&self.lower.base_path(&entry.rel_path))
+    }
+
+    /// Whether applying is refused for entries outside the allowed roots,
+    /// rather than overridden
+    pub fn blocks_outside_roots(&self) -> bool {
+        self.allowed_roots.is_restricted() && !self.allow_outside_roots
+    }
+
+    /// Whether applying and discarding are allowed, with a toast if not
+    fn check_writable(&mut self) -> bool {
+        if self.read_only {
+            self.show_toast("Read-only review: applying and discarding are disabled");
+        }
+        !self.read_only
+    }
+
+    /// What shows the sandbox is still running: the last `run` of the
+    /// session not having finished, or its overlay still being mounted.
+    /// Files it is writing could be applied half-written.
+    pub fn sandbox_activity(&self) -> Option<String> {
+        if let Some(dir) = &self.session_dir
+            && let Ok(metadata) = session::load_metadata(dir)
+            && let Some(run) = metadata.runs.last()
+            && run.finished.is_none()
+            && let Some(pid) = run.pid
+            && lock::is_alive(pid)
+        {
+            return Some(format!("'{}' is still running in the sandbox (pid {})", run.command_line(), pid));
+        }
+        mounts::overlay_mounted(&self.overlay_path)
+            .map(|pid| format!("the overlay is still mounted by a sandbox (pid {})", pid))
+    }
+
+    /// Open the apply dialog for the selected files
+    pub fn open_apply_dialog(&mut self) {
+        if self.check_writable() {
+            self.show_apply_dialog(ApplyScope::Selected);
+        }
+    }
+
+    /// Open the discard dialog for the cursor entry
+    pub fn open_discard_dialog(&mut self) {
+        if self.check_writable() {
+            self.modal = Modal::Discard(DiscardState { label: None, button: self.dialog_defaults.discard });
+        }
+    }
+
+    /// Open the apply dialog for everything under the cursor's directory
+    pub fn open_apply_subtree_dialog(&mut self) {
+        if !self.check_writable() {
+            return;
+        }
+        let Some(cursor) = self.cursor else {
+            return;
+        };
+        let is_dir = self.files.get(cursor).is_some_and(|e| e.is_dir);
+        // A top-level file has no directory, so it is its own subtree
+        let root = if is_dir { cursor } else { self.files.parent(cursor).unwrap_or(cursor) };
+
+        self.show_apply_dialog(ApplyScope::Subtree(root));
+    }
+
+    /// Open the apply dialog for the staging queue
+    pub fn open_apply_staged_dialog(&mut self) {
+        if !self.check_writable() {
+            return;
+        }
+        if self.staged.is_empty() {
+            self.show_toast("Nothing staged (s: stage the file under the cursor)");
+            return;
+        }
+        self.show_apply_dialog(ApplyScope::Staged);
+    }
+
+    /// Open the apply dialog for every file with the filtered label
+    pub fn open_apply_label_dialog(&mut self) {
+        if !self.check_writable() {
+            return;
+        }
+        let Some(label) = self.label_filter.clone() else {
+            self.show_toast("No label filter (tf: filter by label)");
+            return;
+        };
+        self.show_apply_dialog(ApplyScope::Label(label));
+    }
+
+    /// Open the apply dialog for the changeset shown by the filter
+    pub fn open_apply_changeset_dialog(&mut self) {
+        if !self.check_writable() {
+            return;
+        }
+        let Some(name) = self.changeset_filter.clone() else {
+            self.show_toast("No changeset filter (tc: filter by changeset)");
+            return;
+        };
+        self.show_apply_dialog(ApplyScope::Changeset(name));
+    }
+
+    /// Show the apply dialog, with what it says about each file worked out
+    /// once rather than on every redraw. A running sandbox is warned about,
+    /// or stops it opening if so configured.
+    fn show_apply_dialog(&mut self, scope: ApplyScope) {
+        let sandbox_warning = self.sandbox_activity();
+        if let Some(activity) = &sandbox_warning
+            && self.block_apply_while_running
+        {
+            self.show_toast(format!("Not applying: {}", activity));
+            return;
+        }
+        let targets = self.targets(&scope);
+        let stats = targets
+            .iter()
+            .filter(|e| !e.is_dir)
+            .map(|e| {
+                let base_file = self.lower.resolve(&e.rel_path);
+                let (added, removed) = file_operations::diff_stats(&e.path, base_file.as_deref());
+                let size = fs::symlink_metadata(&e.path).map(|m| m.len()).unwrap_or(0);
+                (e.rel_path.clone(), report::EntryStats { size, added, removed })
+            })
+            .collect();
+        let conflicts = targets
+            .iter()
+            .filter(|e| {
+                self.applied_record(e).is_some_and(|record| {
+                    applied::hash_file(&self.lower.base_path(&e.rel_path)).is_ok_and(|hash| hash != record.sha256)
+                })
+            })
+            .map(|e| e.rel_path.clone())
+            .collect();
+        self.modal = Modal::Apply(ApplyState {
+            scope,
+            button: self.dialog_defaults.apply,
+            sandbox_warning,
+            stats,
+            conflicts,
+            privileged_confirmed: false,
+        });
+    }
+
+    /// Whether the apply dialog should warn about a file: its content is
+    /// denylisted, it lands outside the allowed roots, it carries
+    /// privileges, a check failed on it, or its base copy changed since it
+    /// was last applied
+    pub fn is_flagged(&self, entry: &FileEntry) -> bool {
+        self.hash_verdict(entry) == Some(HashVerdict::Denied)
+            || self.is_outside_allowed_roots(entry)
+            || self.privileges(entry).is_some()
+            || self.check_results(entry).is_some_and(|results| results.iter().any(|r| !r.passed))
+            || matches!(&self.modal, Modal::Apply(state) if state.conflicts.contains(&entry.rel_path))
+    }
+
+    /// Work that quitting now would leave behind, one line each
+    pub fn pending_work(&self) -> Vec<String> {
+        let mut pending = Vec::new();
+        let selected = self.get_selected_files().len();
+        if selected > 0 {
+            pending.push(format!("{} selected and not applied", describe_count(selected)));
+        }
+        if !self.staged.is_empty() {
+            pending.push(format!("{} in the staging queue", describe_count(self.staged.len())));
+        }
+        let unreviewed = self
+            .changed_since_review
+            .iter()
+            .filter(|id| self.files.get(**id).is_some_and(|e| !e.is_dir))
+            .count();
+        if unreviewed > 0 {
+            pending.push(format!("{} changed since the last review", describe_count(unreviewed)));
+        }
+        pending
+    }
+
+    /// Quit, or open the quit dialog first if there is pending work.
+    /// Returns whether to quit now.
+    pub fn request_quit(&mut self) -> bool {
+        if !self.confirm_quit || self.pending_work().is_empty() {
+            return true;
+        }
+        self.modal = Modal::Quit(match self.dialog_defaults.quit {
+            DialogButton::Ok => QuitChoice::Quit,
+            DialogButton::Cancel => QuitChoice::Cancel,
+        });
+        false
+    }
+
+    /// Whether the quit dialog offers to apply before quitting
+    pub fn can_apply_before_quit(&self) -> bool {
+        !self.read_only && (!self.staged.is_empty() || !self.get_selected_files().is_empty())
+    }
+
+    pub fn next_quit_choice(&mut self) {
+        let can_apply = self.can_apply_before_quit();
+        if let Modal::Quit(choice) = &mut self.modal {
+            *choice = match choice {
+                QuitChoice::Quit if can_apply => QuitChoice::ApplyAndQuit,
+                QuitChoice::Quit | QuitChoice::ApplyAndQuit => QuitChoice::Cancel,
+                QuitChoice::Cancel => QuitChoice::Quit,
+            };
+        }
+    }
+
+    pub fn previous_quit_choice(&mut self) {
+        let can_apply = self.can_apply_before_quit();
+        if let Modal::Quit(choice) = &mut self.modal {
+            *choice = match choice {
+                QuitChoice::Quit => QuitChoice::Cancel,
+                QuitChoice::ApplyAndQuit => QuitChoice::Quit,
+                QuitChoice::Cancel if can_apply => QuitChoice::ApplyAndQuit,
+                QuitChoice::Cancel => QuitChoice::Quit,
+            };
+        }
+    }
+
+    /// Apply the selected files and then the staging queue, as the quit
+    /// dialog's "Apply & quit" does
+    pub fn apply_before_quit(&mut self) -> io::Result<()> {
+        if self.block_apply_while_running
+            && let Some(activity) = self.sandbox_activity()
+        {
+            return Err(io::Error::other(activity));
+        }
+        // Those need confirming on their own, in the apply dialog
+        if self.get_selected_files().iter().chain(&self.staged_entries()).any(|e| self.is_privileged(e)) {
+            return Err(io::Error::other("setuid, setgid or capability files need applying from the apply dialog"));
+        }
+        if !self.get_selected_files().is_empty() {
+            self.apply_changes(&ApplyScope::Selected, &mut |_| {})?;
+            // Drops applied files from the queue
+            self.refresh_file_list()?;
+        }
+        if !self.staged.is_empty() {
+            self.apply_changes(&ApplyScope::Staged, &mut |_| {})?;
+        }
+        Ok(())
+    }
+
+    /// Entries by path relative to the overlay root, e.g. as a control
+    /// socket client names them
+    fn find_entries(&self, rel_paths: &[PathBuf]) -> io::Result<Vec<NodeId>> {
+        rel_paths
+            .iter()
+            .map(|rel_path| {
+                self.files.find(&self.overlay_path.join(rel_path)).ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::NotFound, format!("No entry {}", rel_path.display()))
+                })
+            })
+            .collect()
+    }
+
+    fn ensure_writable(&self) -> io::Result<()> {
+        if self.read_only {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "Read-only review: applying and discarding are disabled",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Apply entries, and everything under them, without the apply dialog.
+    /// Returns the paths applied, relative to the overlay root.
+    pub fn apply_paths(&mut self, rel_paths: &[PathBuf]) -> io::Result<Vec<PathBuf>> {
+        let roots = self.find_entries(rel_paths)?;
+        self.apply_scope_checked(ApplyScope::Paths(roots), false)
+    }
+
+    /// Apply exactly the entries given and nothing under them, as a
+    /// decisions file lists them. A directory is refused unless it is applied
+    /// as a unit, i.e. for its own creation, mode or xattrs.
+    pub fn apply_entries(&mut self, rel_paths: &[PathBuf]) -> io::Result<Vec<PathBuf>> {
+        self.apply_exactly(rel_paths, false)
+    }
+
+    /// Apply entries like `apply_entries`, setuid, setgid and capability
+    /// files included, once the reviewer has confirmed those on their own
+    pub fn apply_confirmed_entries(&mut self, rel_paths: &[PathBuf]) -> io::Result<Vec<PathBuf>> {
+        self.apply_exactly(rel_paths, true)
+    }
+
+    fn apply_exactly(&mut self, rel_paths: &[PathBuf], privileged_confirmed: bool) -> io::Result<Vec<PathBuf>> {
+        let ids = self.find_entries(rel_paths)?;
+        if let Some(&dir) = ids.iter().find(|&&id| !self.is_applied_as_unit(id))
+            && let Some(entry) = self.files.get(dir)
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} has changes under it; list those to apply instead", self.display_path(&entry.rel_path)),
+            ));
+        }
+        self.apply_scope_checked(ApplyScope::Entries(ids), privileged_confirmed)
+    }
+
+    fn apply_scope_checked(&mut self, scope: ApplyScope, privileged_confirmed: bool) -> io::Result<Vec<PathBuf>> {
+        self.ensure_writable()?;
+        if self.block_apply_while_running
+            && let Some(activity) = self.sandbox_activity()
+        {
+            return Err(io::Error::other(activity));
+        }
+        let targets = self.targets(&scope);
+        // Those need confirming on their own, in the apply dialog
+        if !privileged_confirmed && let Some(privileged) = targets.iter().find(|e| self.is_privileged(e)) {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!("{} is setuid, setgid or has capabilities; apply it from the apply dialog", self.display_path(&privileged.rel_path)),
+            ));
+        }
+        self.apply_changes(&scope, &mut |_| {})?;
+        // Deletions are skipped
+        Ok(targets.iter().filter(|e| e.status != FileStatus::Deleted).map(|e| e.rel_path.clone()).collect())
+    }
+
+    /// Discard entries, and everything under them, without the discard
+    /// dialog
+    pub fn discard_paths(&mut self, rel_paths: &[PathBuf]) -> io::Result<()> {
+        self.ensure_writable()?;
+        let entries: Vec<FileEntry> = self
+            .find_entries(rel_paths)?
+            .into_iter()
+            .filter_map(|id| self.files.get(id).cloned())
+            .collect();
+        for entry in entries {
+            file_operations::discard_file(&entry.path, &self.overlay_path, &self.lower)?;
+            self.audit(&format!("discarded {}", entry.rel_path.display()))?;
+        }
+        self.refresh_file_list()
+    }
+
+    /// What the content pane shows for an entry, by path relative to the
+    /// overlay root: a diff for a modified file, the content of a new one
+    pub fn content_of(&self, rel_path: &Path) -> io::Result<Vec<String>> {
+        let id = self.find_entries(&[rel_path.to_path_buf()])?[0];
+        let Some(entry) = self.files.get(id) else {
+            return Err(io::Error::new(io::ErrorKind::NotFound, format!("No entry {}", rel_path.display())));
+        };
+        if entry.is_dir {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("{} is a directory", rel_path.display())));
+        }
+        Ok(match entry.status {
+            FileStatus::New => file_lines(&entry.path),
+            FileStatus::Modified => {
+                let base_file = self.lower.resolve(&entry.rel_path).unwrap_or_else(|| self.lower.base_path(&entry.rel_path));
+                match self.normalizer_for(entry) {
+                    Some(normalizer) => normalized_diff(entry, &base_file, &self.diff_options, normalizer),
+                    None => file_operations::diff_against(entry, &base_file, &self.diff_options),
+                }
+            }
+            FileStatus::Deleted => self.deleted_content(entry),
+            FileStatus::TypeChanged => self.type_change_content(entry),
+        })
+    }
+
+    pub fn close_dialog(&mut self) {
+        self.modal = Modal::None;
+    }
+
+    /// Apply what the apply dialog lists, and close it, reporting the
+    /// copying to `on_progress` as it goes. Files granting privileges take a
+    /// second confirmation first, with the dialog left open.
+    pub fn confirm_apply(&mut self, on_progress: &mut dyn FnMut(&ApplyProgress)) -> io::Result<()> {
+        if self.privileged_unconfirmed() {
+            if let Modal::Apply(state) = &mut self.modal {
+                state.privileged_confirmed = true;
+            }
+            return Ok(());
+        }
+        let Modal::Apply(state) = std::mem::replace(&mut self.modal, Modal::None) else {
+            return Ok(());
+        };
+        self.apply_changes(&state.scope, on_progress)
+    }
+
+    /// How long copying `bytes` would take at the last sizeable apply's
+    /// throughput, if there has been one
+    pub fn apply_estimate(&self, bytes: u64) -> Option<Duration> {
+        self.apply_throughput.map(|throughput| Duration::from_secs_f64(bytes as f64 / throughput))
+    }
+
+    /// Files the apply dialog will apply, if it is open
+    pub fn apply_targets(&self) -> Vec<FileEntry> {
+        match &self.modal {
+            Modal::Apply(state) => self.targets(&state.scope),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Files applying a scope applies
+    fn targets(&self, scope: &ApplyScope) -> Vec<FileEntry> {
+        match scope {
+            ApplyScope::Selected => self.get_selected_files(),
+            ApplyScope::Subtree(root) => self.subtree_targets(&[*root]),
+            ApplyScope::Paths(roots) => self.subtree_targets(roots),
+            ApplyScope::Entries(ids) => ids.iter().filter_map(|&id| self.files.get(id)).cloned().collect(),
+            ApplyScope::Staged => self.staged_entries(),
+            ApplyScope::Label(label) => self.labelled_entries(label),
+            ApplyScope::Changeset(name) => self.changeset_entries(name),
+            ApplyScope::Queue => self.queued().iter().flat_map(|name| self.changeset_entries(name)).collect(),
+        }
+    }
+
+    /// The changes that are applied or discarded one by one, in list order:
+    /// files, and directories applied as a unit
+    pub fn change_entries(&self) -> Vec<FileEntry> {
+        self.files
+            .iter()
+            .filter(|(id, _)| self.is_applied_as_unit(*id))
+            .map(|(_, e)| e.clone())
+            .collect()
+    }
+
+    /// Entries applied as a unit at or under the given ones
+    fn subtree_targets(&self, roots: &[NodeId]) -> Vec<FileEntry> {
+        let mut ids = Vec::new();
+        for &root in roots {
+            for id in std::iter::once(root).chain(self.files.descendants(root)) {
+                if self.is_applied_as_unit(id) && !ids.contains(&id) {
+                    ids.push(id);
+                }
+            }
+        }
+        ids.into_iter().filter_map(|id| self.files.get(id)).cloned().collect()
+    }
+
+    pub fn apply_changes(&mut self, scope: &ApplyScope, on_progress: &mut dyn FnMut(&ApplyProgress)) -> io::Result<()> {
+        if *scope == ApplyScope::Queue {
+            return self.apply_queue(on_progress);
+        }
+        // Deletions are not carried over to the base; the rest are applied
+        // all the same
+        let (deletions, selected): (Vec<FileEntry>, Vec<FileEntry>) =
+            self.targets(scope).into_iter().partition(|e| e.status == FileStatus::Deleted);
+        if let Some(denied) = selected.iter().find(|e| self.is_denylisted(e)) {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!("{} has denylisted content", self.display_path(&denied.rel_path)),
+            ));
+        }
+        // Refused, or let through on the reviewer's say-so, on the record
+        // either way
+        let outside: Vec<PathBuf> = selected
+            .iter()
+            .filter(|e| self.is_outside_allowed_roots(e))
+            .map(|e| self.lower.base_path(&e.rel_path))
+            .collect();
+        if let Some(first) = outside.first()
+            && !self.allow_outside_roots
+        {
+            self.audit(&format!("apply blocked: {} is outside apply.allowed_roots", first.display()))?;
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!(
+                    "{} is outside apply.allowed_roots; use --allow-outside-roots to apply it anyway",
+                    first.display()
+                ),
+            ));
+        }
+        for path in &outside {
+            self.audit(&format!("allowed outside apply.allowed_roots: {}", path.display()))?;
+        }
+        // Tried on a scratch copy first, and applied only if that passes
+        for result in validate::run(&self.validations, &selected, &self.lower)? {
+            if !result.passed {
+                self.audit(&format!("apply blocked: validation {} {}", result.name, result.outcome))?;
+                let last_line = result.output.iter().rev().find(|line| !line.trim().is_empty());
+                return Err(io::Error::other(format!(
+                    "Validation '{}' failed ({}), nothing applied{}",
+                    result.name,
+                    result.outcome,
+                    last_line.map(|line| format!(": {}", line.trim())).unwrap_or_default()
+                )));
+            }
+            self.audit(&format!("validation {} passed", result.name))?;
+        }
+        let mut last_progress = ApplyProgress::default();
+        let mut report = |progress: &ApplyProgress| {
+            last_progress = progress.clone();
+            on_progress(progress);
+        };
+        let result = if matches!(scope, ApplyScope::Staged | ApplyScope::Changeset(_)) {
+            file_operations::apply_transaction(&selected, &self.overlay_path, &self.lower, self.apply_workers, &mut report)
+        } else {
+            file_operations::apply_changes(&selected, &self.overlay_path, &self.lower, self.apply_workers, &mut report)
+        };
+        // A few small files say more about the overhead than the throughput
+        if last_progress.bytes >= ESTIMATE_MIN_BYTES
+            && let Some(throughput) = last_progress.throughput()
+        {
+            self.apply_throughput = Some(throughput);
+        }
+        self.remove_emptied_dirs(&selected);
+        self.update_dir_changes();
+        // What landed before a failure is on the record all the same
+        if let Err(failure) = result {
+            self.journal_applied(&failure.applied)?;
+            self.audit(&format!("apply failed: {}", failure.error))?;
+            return Err(failure.error);
+        }
+
+        if *scope == ApplyScope::Staged {
+            self.staged.clear();
+            self.staging_cursor = 0;
+        }
+        // An applied changeset's work is done
+        if let ApplyScope::Changeset(name) = scope
+            && let Some(applied) = self.changesets.remove(name)
+        {
+            self.share_marks(&[MarkChange::Ungroup { files: applied.files.into_iter().collect() }]);
+            self.clamp_queue_cursor();
+            match applied.description.as_str() {
+                "" => self.audit(&format!("{}{}", audit_log::CHANGESET_PREFIX, name))?,
+                description => self.audit(&format!("{}{}: {}", audit_log::CHANGESET_PREFIX, name, description))?,
+            }
+        }
+        self.journal_applied(&selected)?;
+        if !deletions.is_empty() {
+            for entry in &deletions {
+                self.audit(&format!("skipped deletion of {}", entry.rel_path.display()))?;
+            }
+            self.show_toast(format!(
+                "Skipped {} deleted in the overlay: applying deletions is not supported",
+                describe_count(deletions.len())
+            ));
+        }
+        self.send_apply_report(&selected)
+    }
+
+    /// Put entries that landed in the base on the record, in order: the
+    /// audit log, the applied history and the services to reload
+    fn journal_applied(&mut self, applied: &[FileEntry]) -> io::Result<()> {
+        if applied.is_empty() {
+            return Ok(());
+        }
+        for entry in applied {
+            self.audit(&format!("applied {}", entry.rel_path.display()))?;
+        }
+        self.record_applied(applied)?;
+        let actions = self.services.actions(applied, &self.lower);
+        services::merge(&mut self.pending_reloads, actions);
+        Ok(())
+    }
+
+    /// Apply the changesets not on hold in queue order, each as a
+    /// transaction of its own with its own audit log entries, stopping at the
+    /// first to fail. Those before it stay applied.
+    fn apply_queue(&mut self, on_progress: &mut dyn FnMut(&ApplyProgress)) -> io::Result<()> {
+        let queued = self.queued();
+        if queued.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "Every changeset is on hold"));
+        }
+        for (i, name) in queued.iter().enumerate() {
+            if let Err(e) = self.apply_changes(&ApplyScope::Changeset(name.clone()), on_progress) {
+                let applied = match i {
+                    0 => String::new(),
+                    1 => format!("; {} was applied before it", queued[0]),
+                    _ => format!("; the {} before it were applied", i),
+                };
+                return Err(io::Error::new(e.kind(), format!("Changeset {}: {}{}", name, e, applied)));
+            }
+        }
+        Ok(())
+    }
+
+    /// Send the record of an apply to the configured webhook and mail
+    /// recipients
+    fn send_apply_report(&mut self, applied: &[FileEntry]) -> io::Result<()> {
+        if self.apply_hooks.is_none() {
+            return Ok(());
+        }
+        let files = applied
+            .iter()
+            .map(|entry| {
+                let path = self.lower.base_path(&entry.rel_path);
+                AppliedFile {
+                    sha256: (!entry.is_dir).then(|| applied::hash_file(&path).ok()).flatten(),
+                    path,
+                    status: entry.status.clone(),
+                    is_dir: entry.is_dir,
+                }
+            })
+            .collect();
+        let report = ApplyReport {
+            time: Local::now(),
+            session: self.session_dir.clone(),
+            overlay: self.overlay_path.clone(),
+            base: self.lower.base.clone(),
+            run: self.last_run.clone(),
+            reviewer: self.identity.clone(),
+            files,
+        };
+        match &mut self.apply_hooks {
+            Some(hooks) => hooks.send(&report),
+            None => Ok(()),
+        }
+    }
+
+    /// Toast about apply reports that could not be delivered
+    pub fn check_apply_hooks(&mut self) {
+        let Some(hooks) = &mut self.apply_hooks else {
+            return;
+        };
+        let failures = hooks.failures();
+        if !failures.is_empty() {
+            self.show_toast(failures.join("; "));
+        }
+    }
+
+    /// Ask whether to run the reloads applied files call for, once nothing
+    /// else is open
+    pub fn offer_reloads(&mut self) {
+        if !self.pending_reloads.is_empty() && matches!(self.modal, Modal::None) {
+            self.modal = Modal::Reload(DialogButton::Ok);
+        }
+    }
+
+    /// Run the reloads the reload dialog lists, and close it
+    pub fn confirm_reload(&mut self) -> io::Result<()> {
+        self.modal = Modal::None;
+        let results = self.run_reloads()?;
+        match results.iter().find(|result| !result.passed) {
+            Some(failed) => {
+                let last_line = failed.output.iter().rev().find(|line| !line.trim().is_empty());
+                self.show_toast(format!(
+                    "{} failed ({}){}",
+                    failed.name,
+                    failed.outcome,
+                    last_line.map(|line| format!(": {}", line.trim())).unwrap_or_default()
+                ));
+            }
+            None => {
+                let names: Vec<&str> = results.iter().map(|result| result.name.as_str()).collect();
+                self.show_toast(format!("Ran {}", names.join(", ")));
+            }
+        }
+        Ok(())
+    }
+
+    /// Close the reload dialog without running them
+    pub fn decline_reload(&mut self) -> io::Result<()> {
+        self.modal = Modal::None;
+        self.skip_reloads()
+    }
+
+    /// Run the pending reloads in order, recording each in the audit log
+    pub fn run_reloads(&mut self) -> io::Result<Vec<CheckResult>> {
+        let mut results = Vec::new();
+        for action in std::mem::take(&mut self.pending_reloads) {
+            let result = services::run(&action);
+            self.audit(&format!(
+                "ran {} for {}: {}",
+                result.name,
+                action.because.display(),
+                result.outcome
+            ))?;
+            results.push(result);
+        }
+        Ok(results)
+    }
+
+    /// Forget the pending reloads, recording in the audit log that they
+    /// were not run
+    pub fn skip_reloads(&mut self) -> io::Result<()> {
+        for action in std::mem::take(&mut self.pending_reloads) {
+            self.audit(&format!("did not run {} for {}", action.command_line(), action.because.display()))?;
+        }
+        Ok(())
+    }
+
+    /// Wait for apply reports still being delivered, returning why any failed
+    pub fn wait_for_apply_hooks(&mut self) -> Vec<String> {
+        self.apply_hooks.as_mut().map(ApplyHooks::wait).unwrap_or_default()
+    }
+
+    /// Remove the overlay directories left empty by applied entries, and
+    /// prune both from the file list without waiting for the watcher
+    fn remove_emptied_dirs(&mut self, applied: &[FileEntry]) {
+        for entry in applied.iter().filter(|e| !e.path.exists()) {
+            file_operations::remove_file_from_list(&mut self.files, &entry.path);
+            for dir in file_operations::remove_empty_parents(&entry.path, &self.overlay_path, &self.lower) {
+                file_operations::remove_file_from_list(&mut self.files, &dir);
+            }
+        }
+        // The cursor may have been on one of them
+        if self.cursor.is_some_and(|id| self.files.get(id).is_none()) {
+            self.cursor = self.files.roots().first().copied();
+            self.load_selected_file_content();
+        }
+    }
+
+    /// Remember what each applied file looked like, so the sandbox writing
+    /// it again can be diffed against what was applied
+    fn record_applied(&mut self, files: &[FileEntry]) -> io::Result<()> {
+        let Some(dir) = self.session_dir.clone() else {
+            return Ok(());
+        };
+        for entry in files {
+            let rel_path = entry.rel_path.clone();
+            if let Ok(sha256) = applied::hash_file(&self.lower.base_path(&rel_path)) {
+                self.applied.insert(rel_path, AppliedRecord { sha256, time: Local::now() });
+            }
+        }
+        applied::save(&dir, &self.applied)
+    }
+
+    pub fn discard_selected_file(&mut self) -> io::Result<()> {
+        if let Some(entry) = self.cursor_entry() {
+            let (path, rel_path) = (entry.path.clone(), entry.rel_path.clone());
+            file_operations::discard_file(&path, &self.overlay_path, &self.lower)?;
+            self.audit(&format!("discarded {}", rel_path.display()))?;
+        }
+        Ok(())
+    }
+
+    /// Open the discard dialog for every file with the filtered label
+    pub fn open_discard_label_dialog(&mut self) {
+        if !self.check_writable() {
+            return;
+        }
+        let Some(label) = self.label_filter.clone() else {
+            self.show_toast("No label filter (tf: filter by label)");
+            return;
+        };
+        self.modal = Modal::Discard(DiscardState { label: Some(label), button: self.dialog_defaults.discard });
+    }
+
+    /// Discard what the discard dialog is for, and close it
+    pub fn confirm_discard(&mut self) -> io::Result<()> {
+        let Modal::Discard(state) = std::mem::replace(&mut self.modal, Modal::None) else {
+            return Ok(());
+        };
+        match state.label {
+            Some(label) => self.discard_labelled_files(&label),
+            None => self.discard_selected_file(),
+        }
+    }
+
+    pub fn discard_labelled_files(&mut self, label: &str) -> io::Result<()> {
+        for entry in self.labelled_entries(label) {
+            file_operations::discard_file(&entry.path, &self.overlay_path, &self.lower)?;
+            self.audit(&format!("discarded {}", entry.rel_path.display()))?;
+        }
+        Ok(())
+    }
+
+    /// Open the prompt for adding or removing a label
+    pub fn open_label_input(&mut self) {
+        if self.cursor.is_some() {
+            self.modal = Modal::Label(Input::new(&self.label_history).with_validator(validate_label));
+        }
+    }
+
+    /// Add the typed label to the file under the cursor, or to every file
+    /// under a directory. A leading '-' removes the label instead.
+    pub fn submit_label_input(&mut self, input: &str) {
+        self.modal = Modal::None;
+        input::remember(&mut self.label_history, input);
+        let (remove, label) = match input.trim().strip_prefix('-') {
+            Some(label) => (true, label.trim().to_string()),
+            None => (false, input.trim().to_string()),
+        };
+        let Some(cursor) = self.cursor else {
+            return;
+        };
+        if label.is_empty() {
+            return;
+        }
+
+        let files: Vec<PathBuf> = std::iter::once(cursor)
+            .chain(self.files.descendants(cursor))
+            .filter_map(|id| self.files.get(id))
+            .filter(|e| !e.is_dir)
+            .map(|e| e.rel_path.clone())
+            .collect();
+
+        let mut changes = Vec::new();
+        for rel_path in &files {
+            changes.push(MarkChange::Label { rel_path: rel_path.clone(), label: label.clone(), added: !remove });
+            if remove {
+                if let Some(labels) = self.labels.get_mut(rel_path) {
+                    labels.remove(&label);
+                    if labels.is_empty() {
+                        self.labels.remove(rel_path);
+                    }
+                }
+            } else {
+                self.labels.entry(rel_path.clone()).or_default().insert(label.clone());
+            }
+        }
+
+        let verb = if remove { "Removed label" } else { "Labelled" };
+        self.show_toast(format!("{} '{}': {}", verb, label, describe_count(files.len())));
+        self.share_marks(&changes);
+        if self.label_filter.is_some() {
+            self.keep_cursor_visible();
+        }
+    }
+
+    /// Open the prompt for grouping files into a changeset
+    pub fn open_changeset_input(&mut self) {
+        if self.cursor.is_some() {
+            let mut input = Input::new(&self.changeset_history);
+            // Regrouping starts from the changeset the file is in
+            if let Some(name) = self.cursor_entry().and_then(|e| changeset::of(&self.changesets, &e.rel_path)) {
+                input.set_text(name);
+            }
+            self.modal = Modal::Changeset(input);
+        }
+    }
+
+    /// Suggest the next group of files written close together, not yet in a
+    /// changeset, as one: they are selected, and the changeset prompt opened
+    /// with a name and description to accept or edit. Escaping the prompt
+    /// leaves them selected, to add or take away files before grouping them.
+    pub fn suggest_changeset(&mut self) {
+        let mut entries = self.path_activity();
+        entries.retain(|e| changeset::of(&self.changesets, &e.path).is_none());
+        let clusters = timeline::cluster(&entries, self.changeset_gap);
+        if clusters.is_empty() {
+            self.show_toast("No files written close together outside the changesets");
+            return;
+        }
+        let index = self.suggestions_shown % clusters.len();
+        self.suggestions_shown += 1;
+        let cluster = &clusters[index];
+
+        for id in self.files.ids() {
+            let selected = self.files.get(id).is_some_and(|entry| cluster.files.contains(&entry.rel_path));
+            self.files.set_selected(id, selected);
+        }
+        // Names stand apart from the description by the first ':'
+        let base_name = match cluster.common_dir() {
+            Some(dir) => format!("{} changes", dir.display()).replace(':', "-"),
+            None => "written together".to_string(),
+        };
+        let name = std::iter::once(base_name.clone())
+            .chain((2..).map(|n| format!("{} {}", base_name, n)))
+            .find(|name| !self.changesets.contains_key(name))
+            .unwrap_or(base_name);
+        let (first, last) = (cluster.first.format("%H:%M:%S").to_string(), cluster.last.format("%H:%M:%S").to_string());
+        let when = if first == last { format!("at {}", first) } else { format!("between {} and {}", first, last) };
+        let description = format!("{} written {}", describe_count(cluster.files.len()), when);
+        if let Some(rel_path) = cluster.files.first().cloned()
+            && let Some(id) = self.files.find(&self.overlay_path.join(&rel_path))
+        {
+            self.reveal(id, &rel_path);
+        }
+
+        let mut input = Input::new(&self.changeset_history);
+        input.set_text(format!("{}: {}", name, description));
+        self.modal = Modal::Changeset(input);
+        self.show_toast(format!(
+            "Suggestion {} of {} (ts: next, Esc: change the selection first)",
+            index + 1,
+            clusters.len()
+        ));
+    }
+
+    /// Files the changeset prompt groups: the selected ones, or else those
+    /// at or under the cursor
+    pub fn changeset_targets(&self) -> Vec<FileEntry> {
+        let selected = self.get_selected_files();
+        match self.cursor {
+            Some(cursor) if selected.is_empty() => self.subtree_targets(&[cursor]),
+            _ => selected,
+        }
+    }
+
+    /// Group the files into the typed changeset, as "name: description". A
+    /// lone '-' takes them out of their changesets instead.
+    pub fn submit_changeset_input(&mut self, input: &str) {
+        self.modal = Modal::None;
+        input::remember(&mut self.changeset_history, input);
+        let files: Vec<PathBuf> = self.changeset_targets().into_iter().map(|e| e.rel_path).collect();
+        if files.is_empty() {
+            return;
+        }
+
+        let change = if input.trim() == "-" {
+            changeset::ungroup(&mut self.changesets, &files);
+            self.show_toast(format!("Took {} out of their changesets", describe_count(files.len())));
+            MarkChange::Ungroup { files }
+        } else {
+            let (name, description) = changeset::parse(input);
+            if name.is_empty() {
+                return;
+            }
+            changeset::group(&mut self.changesets, name, description, &files);
+            // The files grouped are no longer suggested, so the next
+            // suggestion is the first of those left
+            self.suggestions_shown = 0;
+            self.show_toast(format!("Grouped {} into changeset '{}'", describe_count(files.len()), name));
+            MarkChange::Group { name: name.to_string(), description: description.to_string(), files }
+        };
+        self.share_marks(&[change]);
+        if self.changeset_filter.as_ref().is_some_and(|name| !self.changesets.contains_key(name)) {
+            self.changeset_filter = None;
+        }
+        if self.changeset_filter.is_some() {
+            self.keep_cursor_visible();
+        }
+    }
+
+    pub fn is_bookmarked(&self, entry: &FileEntry) -> bool {
+        self.bookmarks.contains(&entry.rel_path)
+    }
+
+    /// Bookmark the entry under the cursor, or remove its bookmark
+    pub fn toggle_bookmark(&mut self) {
+        let Some(rel_path) = self.selected_relative_path() else {
+            return;
+        };
+        let added = !self.bookmarks.remove(&rel_path);
+        if added {
+            self.show_toast(format!("Bookmarked {} (': next bookmark)", self.path_display.show(&rel_path)));
+            self.bookmarks.insert(rel_path.clone());
+        } else {
+            self.show_toast(format!("Removed bookmark {}", self.path_display.show(&rel_path)));
+        }
+        self.share_marks(&[MarkChange::Bookmark { rel_path, added }]);
+    }
+
+    /// Record label, bookmark and changeset changes in the session's review state
+    /// straight away, for the other reviews of the session to see, and pick
+    /// up theirs at the same time. Read-only reviews share marks too.
+    fn share_marks(&mut self, changes: &[MarkChange]) {
+        let Some(path) = &self.review_state_path else {
+            return;
+        };
+        match review_state::update_review_state(path, |state| changes.iter().for_each(|change| change.apply(state))) {
+            Ok((state, seen)) => {
+                self.labels = state.labels;
+                self.bookmarks = state.bookmarks;
+                self.changesets = state.changesets;
+                self.review_state_seen = seen;
+                self.clamp_queue_cursor();
+            }
+            Err(e) => self.show_toast(format!("Failed to share labels and bookmarks: {}", e)),
+        }
+    }
+
+    /// Pick up labels, bookmarks and changesets another review of the
+    /// session made
+    pub fn sync_marks(&mut self) {
+        let Some(path) = &self.review_state_path else {
+            return;
+        };
+        if review_state::fingerprint(path) == self.review_state_seen {
+            return;
+        }
+        let Ok((state, seen)) = review_state::load_shared(path) else {
+            return;
+        };
+        self.review_state_seen = seen;
+        if state.labels == self.labels && state.bookmarks == self.bookmarks && state.changesets == self.changesets {
+            return;
+        }
+        self.labels = state.labels;
+        self.bookmarks = state.bookmarks;
+        self.changesets = state.changesets;
+        self.clamp_queue_cursor();
+        self.show_toast("Labels, bookmarks or changesets changed in another review of the session");
+        if self.changeset_filter.as_ref().is_some_and(|name| !self.changesets.contains_key(name)) {
+            self.changeset_filter = None;
+        }
+        if self.label_filter.is_some() || self.changeset_filter.is_some() {
+            self.keep_cursor_visible();
+        }
+        self.request_redraw();
+    }
+
+    /// Move to the next bookmarked entry in file list order, wrapping around
+    pub fn next_bookmark(&mut self) {
+        let bookmarked: Vec<NodeId> = self
+            .files
+            .ids()
+            .into_iter()
+            .filter(|&id| self.files.get(id).is_some_and(|e| self.is_bookmarked(e)))
+            .collect();
+        if bookmarked.is_empty() {
+            self.show_toast("No bookmarks (m: bookmark the current entry)");
+            return;
+        }
+
+        let order = self.files.ids();
+        let position = |id: NodeId| order.iter().position(|&o| o == id);
+        let cursor = self.cursor.and_then(position);
+        let next = bookmarked
+            .iter()
+            .copied()
+            .find(|&id| position(id) > cursor)
+            .unwrap_or(bookmarked[0]);
+        if let Some(entry) = self.files.get(next) {
+            let rel_path = entry.rel_path.clone();
+            self.reveal(next, &rel_path);
+        }
+    }
+
+    pub fn labels_for(&self, entry: &FileEntry) -> Option<&BTreeSet<String>> {
+        self.labels.get(&entry.rel_path)
+    }
+
+    /// Every label in use, in sorted order
+    pub fn all_labels(&self) -> BTreeSet<&String> {
+        self.labels.values().flatten().collect()
+    }
+
+    /// Files with a label, in file list order
+    pub fn labelled_entries(&self, label: &str) -> Vec<FileEntry> {
+        self.files
+            .iter()
+            .filter(|(_, e)| !e.is_dir)
+            .filter(|(_, e)| self.labels_for(e).is_some_and(|labels| labels.contains(label)))
+            .map(|(_, e)| e.clone())
+            .collect()
+    }
+
+    /// Step the label filter through the labels in use, then back to none
+    pub fn cycle_label_filter(&mut self) {
+        let labels = self.all_labels();
+        if labels.is_empty() {
+            self.label_filter = None;
+            self.show_toast("No labels yet (tt: label the current entry)");
+            return;
+        }
+
+        let next = match &self.label_filter {
+            Some(current) => labels.into_iter().find(|l| *l > current).cloned(),
+            None => labels.into_iter().next().cloned(),
+        };
+        self.label_filter = next;
+        self.keep_cursor_visible();
+    }
+
+    /// Show the changeset queue in place of the panes, or close it
+    pub fn toggle_changeset_queue(&mut self) {
+        if !self.show_changeset_queue && self.changesets.is_empty() {
+            self.show_toast("No changesets yet (tg: group files into one)");
+            return;
+        }
+        self.show_changeset_queue = !self.show_changeset_queue;
+        self.clamp_queue_cursor();
+    }
+
+    fn clamp_queue_cursor(&mut self) {
+        self.queue_cursor = self.queue_cursor.min(self.changesets.len().saturating_sub(1));
+    }
+
+    /// Name of the changeset under the queue cursor
+    pub fn queue_name(&self) -> Option<String> {
+        changeset::queue(&self.changesets).get(self.queue_cursor).map(|name| name.to_string())
+    }
+
+    /// Changesets the queue applies: those not on hold, in order
+    pub fn queued(&self) -> Vec<String> {
+        changeset::queue(&self.changesets)
+            .into_iter()
+            .filter(|name| self.changesets.get(*name).is_some_and(|c| !c.hold))
+            .map(str::to_string)
+            .collect()
+    }
+
+    pub fn queue_next(&mut self) {
+        if self.queue_cursor + 1 < self.changesets.len() {
+            self.queue_cursor += 1;
+        }
+    }
+
+    pub fn queue_previous(&mut self) {
+        self.queue_cursor = self.queue_cursor.saturating_sub(1);
+    }
+
+    /// Move the changeset under the queue cursor one place earlier, or later
+    pub fn move_queued(&mut self, earlier: bool) {
+        let mut names: Vec<String> = changeset::queue(&self.changesets).into_iter().map(str::to_string).collect();
+        let to = if earlier { self.queue_cursor.checked_sub(1) } else { Some(self.queue_cursor + 1) };
+        let Some(to) = to.filter(|&to| to < names.len()) else {
+            return;
+        };
+        names.swap(self.queue_cursor, to);
+        changeset::reorder(&mut self.changesets, &names);
+        self.queue_cursor = to;
+        self.share_marks(&[MarkChange::Reorder { names }]);
+    }
+
+    /// Fold the changeset under the queue cursor into the one before it
+    pub fn squash_queued(&mut self) {
+        let Some(name) = self.queue_name() else {
+            return;
+        };
+        if self.queue_cursor == 0 {
+            self.show_toast("The first changeset has none before it to squash into");
+            return;
+        }
+        changeset::squash(&mut self.changesets, &name);
+        self.queue_cursor -= 1;
+        if self.changeset_filter.as_ref() == Some(&name) {
+            self.changeset_filter = None;
+        }
+        self.show_toast(format!("Squashed {} into {}", name, self.queue_name().unwrap_or_default()));
+        self.share_marks(&[MarkChange::Squash { name }]);
+    }
+
+    /// Hold the changeset under the queue cursor back from applying, or
+    /// release it
+    pub fn toggle_hold(&mut self) {
+        let Some(name) = self.queue_name() else {
+            return;
+        };
+        let Some(changeset) = self.changesets.get_mut(&name) else {
+            return;
+        };
+        changeset.hold = !changeset.hold;
+        let hold = changeset.hold;
+        self.share_marks(&[MarkChange::Hold { name, hold }]);
+    }
+
+    /// Open the prompt for the description of the changeset under the queue
+    /// cursor
+    pub fn open_describe_input(&mut self) {
+        let Some(changeset) = self.queue_name().and_then(|name| self.changesets.get(&name)) else {
+            return;
+        };
+        let mut input = Input::new(&[]);
+        input.set_text(changeset.description.clone());
+        self.modal = Modal::Describe(input);
+    }
+
+    pub fn submit_describe_input(&mut self, input: &str) {
+        self.modal = Modal::None;
+        let Some(name) = self.queue_name() else {
+            return;
+        };
+        let description = input.trim().to_string();
+        if let Some(changeset) = self.changesets.get_mut(&name) {
+            changeset.description = description.clone();
+        }
+        self.share_marks(&[MarkChange::Describe { name, description }]);
+    }
+
+    /// Close the queue and show only the files of the changeset under its
+    /// cursor
+    pub fn show_queued_files(&mut self) {
+        let Some(name) = self.queue_name() else {
+            return;
+        };
+        self.show_changeset_queue = false;
+        self.changeset_filter = Some(name);
+        self.keep_cursor_visible();
+    }
+
+    /// Open the apply dialog for the changesets not on hold, in queue order
+    pub fn open_apply_queue_dialog(&mut self) {
+        if !self.check_writable() {
+            return;
+        }
+        if self.queued().is_empty() {
+            self.show_toast("Nothing to apply: every changeset is on hold (Space: release)");
+            return;
+        }
+        self.show_apply_dialog(ApplyScope::Queue);
+    }
+
+    /// Name of the changeset an entry is in, if any
+    pub fn changeset_of(&self, entry: &FileEntry) -> Option<&str> {
+        changeset::of(&self.changesets, &entry.rel_path)
+    }
+
+    /// Files in a changeset, in file list order
+    pub fn changeset_entries(&self, name: &str) -> Vec<FileEntry> {
+        let Some(changeset) = self.changesets.get(name) else {
+            return Vec::new();
+        };
+        self.files
+            .iter()
+            .filter(|(id, e)| self.is_applied_as_unit(*id) && changeset.files.contains(&e.rel_path))
+            .map(|(_, e)| e.clone())
+            .collect()
+    }
+
+    /// Step the changeset filter through the changesets, then back to none
+    pub fn cycle_changeset_filter(&mut self) {
+        if self.changesets.is_empty() {
+            self.changeset_filter = None;
+            self.show_toast("No changesets yet (tg: group files into one)");
+            return;
+        }
+
+        let next = match &self.changeset_filter {
+            Some(current) => self.changesets.keys().find(|name| *name > current).cloned(),
+            None => self.changesets.keys().next().cloned(),
+        };
+        self.changeset_filter = next;
+        self.keep_cursor_visible();
+    }
+
+    /// Record an action in the session's audit log, if there is a session
+    fn audit(&mut self, message: &str) -> io::Result<()> {
+        let Some(dir) = &self.session_dir else {
+            return Ok(());
+        };
+        audit_log::append(dir, &self.identity, message)?;
+        self.audit_records.push(LogRecord {
+            time: Local::now().fixed_offset(),
+            user: Some(self.identity.to_string()),
+            message: message.to_string(),
+        });
+        Ok(())
+    }
+
+    /// What was done to the entry under the cursor earlier in the session
+    pub fn cursor_history(&self) -> Vec<String> {
+        match self.selected_relative_path() {
+            Some(rel_path) => audit_log::path_history(&self.audit_records, &rel_path),
+            None => Vec::new(),
+        }
+    }
+
+    pub fn show_toast(&mut self, message: impl Into<String>) {
+        self.toast = Some(Toast {
+            message: message.into(),
+            expires: Instant::now() + TOAST_DURATION,
+        });
+        self.redraw = true;
+    }
+
+    /// Have the screen drawn again, e.g. after a key press
+    pub fn request_redraw(&mut self) {
+        self.redraw = true;
+    }
+
+    /// Whether the screen needs drawing again: something changed since it
+    /// was last drawn, or a toast has expired
+    pub fn take_redraw(&mut self) -> bool {
+        if self.toast.as_ref().is_some_and(|t| t.expires <= Instant::now()) {
+            self.toast = None;
+            self.redraw = true;
+        }
+        std::mem::take(&mut self.redraw)
+    }
+
+    pub fn active_toast(&self) -> Option<&str> {
+        self.toast
+            .as_ref()
+            .filter(|t| t.expires > Instant::now())
+            .map(|t| t.message.as_str())
+    }
+
+    pub fn is_over_size_warning(&self) -> bool {
+        self.size_warning
+            .is_some_and(|limit| self.disk_usage.total() > limit)
+    }
+
+    /// Toast once when the overlay grows past the warning threshold
+    fn check_size_warning(&mut self) {
+        if !self.is_over_size_warning() {
+            self.size_warned = false;
+        } else if !self.size_warned {
+            self.size_warned = true;
+            self.show_toast(format!(
+                "Overlay has grown to {}, over the {} warning threshold",
+                disk_usage::format_size(self.disk_usage.total()),
+                disk_usage::format_size(self.size_warning.unwrap_or_default())
+            ));
+        }
+    }
+
+    /// An overlay path relative to the overlay root; one already relative is
+    /// returned as is
+    fn relative_path<'a>(&self, path: &'a Path) -> &'a Path {
+        path.strip_prefix(&self.overlay_path).unwrap_or(path)
+    }
+
+    /// An overlay path, or one relative to the overlay root, as it is shown
+    pub fn display_path(&self, path: &Path) -> String {
+        self.path_display.show(self.relative_path(path))
+    }
+
+    /// Number of changed files under each top-level area, e.g. `/etc` and
+    /// `~`, when the base is the root or home directory. Busiest first.
+    pub fn root_summary(&self) -> Vec<(String, usize)> {
+        let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+        for (_, entry) in self.files.iter().filter(|(_, e)| !e.is_dir) {
+            if let Some(root) = self.path_display.root_of(&entry.rel_path) {
+                *counts.entry(root).or_default() += 1;
+            }
+        }
+        let mut summary: Vec<(String, usize)> = counts.into_iter().collect();
+        summary.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+        summary
+    }
+
+    /// Directory of the saved session being reviewed, if any
+    pub fn session_dir(&self) -> Option<&Path> {
+        self.session_dir.as_deref()
+    }
+
+    /// The processes recorded writing the file under the cursor, if any
+    pub fn cursor_writers(&self) -> Option<String> {
+        let records = self.writers.get(&self.selected_relative_path()?)?;
+        let mut writers: Vec<String> = Vec::new();
+        for record in records {
+            let writer = record.describe();
+            if !writers.contains(&writer) {
+                writers.push(writer);
+            }
+        }
+        Some(writers.join(", "))
+    }
+
+    fn selected_relative_path(&self) -> Option<PathBuf> {
+        self.cursor_entry().map(|e| e.rel_path.clone())
+    }
+
+    /// Find the entry for a relative path, falling back to its closest
+    /// ancestor still present in the list.
+    fn find_by_relative_path(&self, rel_path: &Path) -> Option<NodeId> {
+        rel_path
+            .ancestors()
+            .filter(|p| !p.as_os_str().is_empty())
+            .find_map(|p| self.files.find(&self.overlay_path.join(p)))
+    }
+
+    /// Relative paths of all entries matching `predicate`
+    fn relative_paths_where(&self, predicate: impl Fn(&FileEntry) -> bool) -> HashSet<PathBuf> {
+        self.files
+            .iter()
+            .filter(|(_, e)| predicate(e))
+            .map(|(_, e)| e.rel_path.clone())
+            .collect()
+    }
+
+    /// Re-apply checkbox and collapse state recorded by relative path
+    fn restore_entry_state(&mut self, checked: &HashSet<PathBuf>, collapsed: &HashSet<PathBuf>) {
+        for id in self.files.ids() {
+            let is_checked = self.files.get(id).is_some_and(|entry| checked.contains(&entry.rel_path));
+            self.files.set_selected(id, is_checked);
+            if let Some(entry) = self.files.get_mut(id) {
+                entry.collapsed = entry.is_dir && collapsed.contains(&entry.rel_path);
+            }
+        }
+    }
+
+    /// Persist review state to the session, if there is one
+    pub fn save_review_state(&mut self) -> io::Result<()> {
+        self.write_review_state(true)
+    }
+
+    /// Persist review state without marking the overlay as reviewed, e.g.
+    /// from the daemon, so the next review still shows what changed
+    pub fn save_progress(&mut self) -> io::Result<()> {
+        self.write_review_state(false)
+    }
+
+    fn write_review_state(&mut self, reviewed: bool) -> io::Result<()> {
+        let Some(path) = &self.review_state_path else {
+            return Ok(());
+        };
+        if self.read_only {
+            return Ok(());
+        }
+
+        let collapsed = self.relative_paths_where(|e| e.collapsed).into_iter().collect();
+        // Snapshot the overlay so the next review can tell what changed since
+        let snapshot = reviewed.then(|| {
+            self.files
+                .iter()
+                .filter(|(_, e)| !e.is_dir)
+                .filter_map(|(_, e)| {
+                    let fingerprint = review_state::fingerprint(&e.path)?;
+                    Some((e.rel_path.clone(), fingerprint))
+                })
+                .collect()
+        });
+        let staged = self.staged.clone();
+        let overlay_path = &self.overlay_path;
+
+        // Labels and bookmarks were shared as they were made, so those in
+        // the file are the latest from every review of the session
+        let (state, seen) = review_state::update_review_state(path, |state| {
+            state.collapsed = collapsed;
+            if snapshot.is_some() {
+                state.snapshot = snapshot;
+            }
+            state.staged = staged;
+            // Labels of files that were applied or discarded go with them. A
+            // symlink is still there whether or not what it points to is.
+            let present = |rel_path: &PathBuf| fs::symlink_metadata(overlay_path.join(rel_path)).is_ok();
+            state.labels.retain(|rel_path, _| present(rel_path));
+            state.bookmarks.retain(present);
+            for changeset in state.changesets.values_mut() {
+                changeset.files.retain(present);
+            }
+            state.changesets.retain(|_, changeset| !changeset.files.is_empty());
+        })?;
+        self.review_state = state;
+        self.review_state_seen = seen;
+
+        self.save_scan_cache()
+    }
+
+    /// Cache what the next review would otherwise work out again: the hash
+    /// of files hashed this time
+    fn save_scan_cache(&self) -> io::Result<()> {
+        let Some(dir) = &self.session_dir else {
+            return Ok(());
+        };
+        let entries = self
+            .files
+            .iter()
+            .filter_map(|(_, e)| {
+                let fingerprint = review_state::fingerprint(&e.path)?;
+                let sha256 = self
+                    .hashes
+                    .get(&e.path)
+                    .filter(|(hashed, _)| *hashed == fingerprint)
+                    .map(|(_, hash)| hash.clone())?;
+                Some((e.rel_path.clone(), CachedEntry { fingerprint, sha256: Some(sha256) }))
+            })
+            .collect();
+        scan_cache::save(dir, &ScanCache { entries })
+    }
+
+    pub fn refresh_file_list(&mut self) -> io::Result<()> {
+        // Pick up writers and runs recorded since the review started
+        if let Some(dir) = &self.session_dir {
+            self.writers = attribution::load_writers(dir)?;
+            self.audit_records = audit_log::load(dir)?;
+        }
+        let selected_rel_path = self.selected_relative_path();
+        let checked = self.relative_paths_where(|e| e.selected);
+        let collapsed = self.relative_paths_where(|e| e.collapsed);
+
+        // Rescan the overlay directory
+        let mut files = FileTree::new();
+        file_operations::scan_directory(
+            &self.overlay_path,
+            &self.overlay_path,
+            &self.lower,
+            0,
+            None,
+            &mut files,
+        )?;
+
+        // Carry checkbox and collapse state over to the new entries
+        files.set_hidden_last(self.hidden_files == HiddenFiles::Last);
+        self.files = files;
+        self.hold_back_unsettled();
+        self.restore_entry_state(&checked, &collapsed);
+        self.update_review_delta();
+        self.update_entry_info();
+        self.prune_staged();
+
+        self.disk_usage = DiskUsage::scan(&self.overlay_path);
+        self.check_size_warning();
+
+        // Try to restore selection to the same file, or the nearest surviving ancestor
+        self.cursor = selected_rel_path
+            .as_deref()
+            .and_then(|rel_path| self.find_by_relative_path(rel_path))
+            .or_else(|| self.files.roots().first().copied());
+
+        self.load_selected_file_content();
+        Ok(())
+    }
+
+    /// Take transient files that changed too recently out of a freshly
+    /// scanned list, to be listed by process_pending_updates once they settle
+    fn hold_back_unsettled(&mut self) {
+        let unsettled: Vec<(NodeId, PathBuf)> = self
+            .files
+            .iter()
+            .filter(|(_, e)| !e.is_dir && self.transient.is_unsettled(&e.path))
+            .map(|(id, e)| (id, e.path.clone()))
+            .collect();
+        for (id, path) in unsettled {
+            self.files.remove(id);
+            self.unsettled.insert(path, Instant::now());
+        }
+        if self.cursor_entry().is_none() {
+            self.cursor = self.files.roots().first().copied();
+        }
+    }
+
+    pub fn check_fs_events(&mut self) {
+        // Check for filesystem events without blocking
+        while let Ok(event) = self.fs_events.try_recv() {
+            // E.g. the watcher falling back to polling
+            if let Err(e) = &event {
+                self.show_toast(format!("Watching the overlay: {}", e));
+            }
+            if let Ok(event) = event {
+                if let Some(recorder) = &mut self.timeline_recorder
+                    && let Err(e) = recorder.record(&event)
+                {
+                    self.show_toast(format!("Failed to record filesystem event: {}", e));
+                }
+                if !self.focused
+                    && let Some(notifier) = &mut self.notifier
+                {
+                    for path in &event.paths {
+                        match event.kind {
+                            EventKind::Create(_) => notifier.created(path.clone()),
+                            EventKind::Modify(_) => notifier.modified(path.clone()),
+                            EventKind::Remove(_) => notifier.removed(path.clone()),
+                            _ => {}
+                        }
+                    }
+                }
+                match event.kind {
+                    EventKind::Modify(ModifyKind::Name(RenameMode::Both)) if event.paths.len() == 2 => {
+                        let from = file_operations::normalize_event_path(&event.paths[0]);
+                        // A temporary file renamed over the one it replaces, as
+                        // editors save, is one update of the destination
+                        if self.files.find(&from).is_none() {
+                            self.unsettled.remove(&from);
+                            self.pending_updates.remove(&from);
+                        } else {
+                            self.queue_update(from);
+                        }
+                        self.queue_update(file_operations::normalize_event_path(&event.paths[1]));
+                    }
+                    EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_) => {
+                        for path in &event.paths {
+                            self.queue_update(file_operations::normalize_event_path(path));
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    /// Note an overlay path to update, holding transient files back until
+    /// they settle
+    fn queue_update(&mut self, path: PathBuf) {
+        // The overlay root has no entry, and nothing outside it belongs in the list
+        if file_operations::relative_path(&path, &self.overlay_path).is_err() {
+            return;
+        }
+        if self.transient.matches(&path) {
+            self.unsettled.insert(path, Instant::now());
+        } else {
+            self.pending_updates.insert(path);
+        }
+    }
+
+    /// Note the terminal gaining or losing focus. Activity seen while away
+    /// is only worth a notification until the review is looked at again.
+    pub fn set_focused(&mut self, focused: bool) {
+        self.focused = focused;
+        if focused && let Some(notifier) = &mut self.notifier {
+            notifier.clear();
+        }
+    }
+
+    /// Send a desktop notification of the overlay activity since the last
+    /// one, if the review is unfocused and it is time for another
+    pub fn send_notifications(&mut self) {
+        if self.focused {
+            return;
+        }
+        let session = match &self.session_dir {
+            Some(dir) => dir.display().to_string(),
+            None => self.overlay_path.display().to_string(),
+        };
+        if let Some(notifier) = &mut self.notifier {
+            notifier.flush(&session);
+        }
+    }
+
+    /// Bring the list in line with the overlay paths that changed. A path
+    /// that cannot be looked at is left as it was, with why in a toast.
+    pub fn process_pending_updates(&mut self) {
+        // Transient files left alone long enough are listed, or dropped if gone
+        let settled: Vec<PathBuf> = self
+            .unsettled
+            .iter()
+            .filter(|(_, changed)| changed.elapsed() >= transient::SETTLE_TIME)
+            .map(|(path, _)| path.clone())
+            .collect();
+        for path in settled {
+            self.unsettled.remove(&path);
+            self.pending_updates.insert(path);
+        }
+
+        if self.pending_updates.is_empty() {
+            return;
+        }
+        self.redraw = true;
 
+        let selected_rel_path = self.selected_relative_path();
+
+        // Collect paths to process
+        let paths: Vec<PathBuf> = self.pending_updates.drain().collect();
+
+        for path in &paths {
+            self.disk_usage.update(path);
+        }
+        self.check_size_warning();
+
+        let mut scanned_dir = false;
+        let mut failures = Vec::new();
         for path in paths {
-            if path.is_dir() {
-                // For directories, refresh the entire list (simpler for now)
-                self.refresh_file_list()?;
-                return Ok(());
-            } else if path.exists() {
-                // File exists - update or add it
+            // A symlink to a directory is listed as a file, as when scanning
+            let result = if fs::symlink_metadata(&path).is_ok_and(|m| m.is_dir()) {
+                // Scan just this directory, which may have filled up before it was watched
+                scanned_dir = true;
+                file_operations::update_or_add_directory(
+                    &mut self.files,
+                    &path,
+                    &self.overlay_path,
+                    &self.lower,
+                )
+            } else if fs::symlink_metadata(&path).is_ok() {
+                // File exists, or a symlink even if dangling - update or add it
                 file_operations::update_or_add_file(
                     &mut self.files,
                     &path,
                     &self.overlay_path,
-                    &self.base_path,
-                )?;
+                    &self.lower,
+                )
             } else {
                 // File was deleted - remove it
-                if let Some(_removed_idx) =
-                    file_operations::remove_file_from_list(&mut self.files, &path)
-                {
-                    // Adjust selection if needed
-                    if let Some(selected) = self.list_state.selected() {
-                        if selected >= self.files.len() && !self.files.is_empty() {
-                            self.list_state.select(Some(self.files.len() - 1));
-                        } else if self.files.is_empty() {
-                            self.list_state.select(None);
-                        }
-                    }
-                }
+                file_operations::remove_file_from_list(&mut self.files, &path);
+                Ok(())
+            };
+            // The review goes on with the rest; the next event for the path
+            // tries again
+            if let Err(e) = result {
+                failures.push(format!("Failed to update {}: {}", path.display(), e));
             }
         }
+        if !failures.is_empty() {
+            self.show_toast(failures.join("; "));
+        }
+        if scanned_dir {
+            self.hold_back_unsettled();
+        }
+
+        self.update_review_delta();
+        self.update_entry_info();
+        self.prune_staged();
 
         // Restore selection if possible
-        if let Some(ref path) = selected_path {
-            if let Some(idx) = self.files.iter().position(|e| e.path == *path) {
-                self.list_state.select(Some(idx));
-            }
+        if let Some(ref rel_path) = selected_rel_path
+            && let Some(id) = self.find_by_relative_path(rel_path)
+        {
+            self.cursor = Some(id);
+        } else if self.cursor_entry().is_none() {
+            self.cursor = self.files.roots().first().copied();
         }
 
         // Reload content if the selected file changed
         self.load_selected_file_content();
+    }
+}
 
-        Ok(())
+/// Diff an overlay file against a given file through a normalizer, or raw
+/// with why if the normalizer fails
+fn normalized_diff(entry: &FileEntry, base_file: &Path, options: &DiffOptions, normalizer: &NormalizerConfig) -> Vec<String> {
+    match file_operations::diff_normalized(entry, base_file, options, normalizer) {
+        Ok(lines) => lines,
+        Err(e) => {
+            let mut lines = file_operations::diff_against(entry, base_file, options);
+            lines.insert(2.min(lines.len()), format!("# Not normalized: {}", e));
+            lines
+        }
+    }
+}
+
+/// Whether a path is a regular file itself, not a symlink to one
+fn is_regular_file(path: &Path) -> bool {
+    fs::symlink_metadata(path).is_ok_and(|metadata| metadata.is_file())
+}
+
+/// Lines of a file to show in the content pane, or what a binary is
+fn file_lines(path: &Path) -> Vec<String> {
+    if let Some(summary) = elf_summary::summarize(path) {
+        summary
+    } else if let Ok(content) = fs::read_to_string(path) {
+        content.lines().map(|s| s.to_string()).collect()
+    } else {
+        vec!["<Unable to read file>".to_string()]
+    }
+}
+
+/// "1 file" or "N files"
+pub fn describe_count(count: usize) -> String {
+    format!("{} file{}", count, if count == 1 { "" } else { "s" })
+}
+
+/// "1 entry" or "N entries", for paths that may be files or directories
+pub fn describe_entries(count: usize) -> String {
+    format!("{} {}", count, if count == 1 { "entry" } else { "entries" })
+}
+
+/// Labels are single words, shown as `#name`
+fn validate_label(input: &str) -> Result<(), String> {
+    let label = input.trim();
+    let label = label.strip_prefix('-').unwrap_or(label).trim();
+    if label.contains(char::is_whitespace) {
+        return Err("Labels cannot contain spaces".to_string());
     }
+    Ok(())
 }
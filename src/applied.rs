@@ -0,0 +1,45 @@
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// File name of the record of applied files, stored in the session directory
+pub const APPLIED_FILE: &str = "applied.json";
+
+/// What a file looked like when it was applied to the base
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AppliedRecord {
+    pub sha256: String,
+    pub time: DateTime<Local>,
+}
+
+/// Applied files by path relative to the overlay root, latest apply only
+pub fn load(session_dir: &Path) -> io::Result<BTreeMap<PathBuf, AppliedRecord>> {
+    let path = session_dir.join(APPLIED_FILE);
+    if !path.exists() {
+        return Ok(BTreeMap::new());
+    }
+
+    let content = fs::read_to_string(&path)?;
+    serde_json::from_str(&content).map_err(|e| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Failed to parse '{}': {}", path.display(), e),
+        )
+    })
+}
+
+pub fn save(session_dir: &Path, applied: &BTreeMap<PathBuf, AppliedRecord>) -> io::Result<()> {
+    let content = serde_json::to_string_pretty(applied).map_err(io::Error::other)?;
+    fs::write(session_dir.join(APPLIED_FILE), content)
+}
+
+/// Hex SHA-256 of a file's contents
+pub fn hash_file(path: &Path) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect())
+}
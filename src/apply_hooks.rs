@@ -0,0 +1,158 @@
+use crate::config::ApplyConfig;
+use crate::identity::Identity;
+use crate::session::RunRecord;
+use crate::types::FileStatus;
+use chrono::{DateTime, Local};
+use serde::Serialize;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::Duration;
+
+/// How long a webhook may take to accept a report
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// What one apply promoted from the sandbox to the base, for change
+/// management to keep
+#[derive(Debug, Serialize)]
+pub struct ApplyReport {
+    pub time: DateTime<Local>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub session: Option<PathBuf>,
+    pub overlay: PathBuf,
+    pub base: PathBuf,
+    /// The last command run in the session, which produced the changes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub run: Option<RunRecord>,
+    /// Who applied the files
+    pub reviewer: Identity,
+    pub files: Vec<AppliedFile>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AppliedFile {
+    /// Where the file was applied to in the base
+    pub path: PathBuf,
+    pub status: FileStatus,
+    pub is_dir: bool,
+    /// SHA-256 of the applied content
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+}
+
+/// Sends the record of each apply to the configured webhook and mail
+/// recipients, in the background so the review carries on meanwhile
+pub struct ApplyHooks {
+    webhook: Option<String>,
+    mail_to: Vec<String>,
+    sendmail: PathBuf,
+    deliveries: Vec<thread::JoinHandle<Vec<String>>>,
+}
+
+impl ApplyHooks {
+    /// None unless a webhook or mail recipients are configured
+    pub fn new(config: &ApplyConfig) -> Option<ApplyHooks> {
+        if config.webhook.is_none() && config.mail_to.is_empty() {
+            return None;
+        }
+        Some(ApplyHooks {
+            webhook: config.webhook.clone(),
+            mail_to: config.mail_to.clone(),
+            sendmail: config.sendmail.clone().unwrap_or_else(|| PathBuf::from("sendmail")),
+            deliveries: Vec::new(),
+        })
+    }
+
+    pub fn send(&mut self, report: &ApplyReport) -> io::Result<()> {
+        let json = serde_json::to_string_pretty(report).map_err(io::Error::other)?;
+        let webhook = self.webhook.clone();
+        let mail = (!self.mail_to.is_empty()).then(|| (self.sendmail.clone(), message(report, &self.mail_to, &json)));
+        self.deliveries.push(thread::spawn(move || {
+            let mut failures = Vec::new();
+            if let Some(url) = webhook
+                && let Err(e) = post(&url, &json)
+            {
+                failures.push(format!("Failed to send apply report to {}: {}", url, e));
+            }
+            if let Some((sendmail, message)) = mail
+                && let Err(e) = sendmail_message(&sendmail, &message)
+            {
+                failures.push(format!("Failed to mail apply report: {}", e));
+            }
+            failures
+        }));
+        Ok(())
+    }
+
+    /// Why deliveries finished since the last call failed, without waiting
+    pub fn failures(&mut self) -> Vec<String> {
+        let (finished, pending) = self.deliveries.drain(..).partition(|delivery| delivery.is_finished());
+        self.deliveries = pending;
+        finished.into_iter().flat_map(|delivery: thread::JoinHandle<_>| delivery.join().unwrap_or_default()).collect()
+    }
+
+    /// Wait for the deliveries still going, e.g. before exiting, and say why
+    /// any failed
+    pub fn wait(&mut self) -> Vec<String> {
+        self.deliveries.drain(..).flat_map(|delivery| delivery.join().unwrap_or_default()).collect()
+    }
+}
+
+fn post(url: &str, json: &str) -> Result<(), ureq::Error> {
+    ureq::post(url)
+        .config()
+        .timeout_global(Some(WEBHOOK_TIMEOUT))
+        .build()
+        .content_type("application/json")
+        .send(json)?;
+    Ok(())
+}
+
+/// A mail listing the applied files, with the JSON record after them
+fn message(report: &ApplyReport, to: &[String], json: &str) -> String {
+    let mut message = format!(
+        "To: {}\nSubject: audit-box applied {} {} to {}\nContent-Type: text/plain; charset=utf-8\n\n",
+        to.join(", "),
+        report.files.len(),
+        if report.files.len() == 1 { "entry" } else { "entries" },
+        report.base.display()
+    );
+    message.push_str(&format!(
+        "Applied from {} by {} at {}:\n\n",
+        report.overlay.display(),
+        report.reviewer,
+        report.time.format("%Y-%m-%d %H:%M:%S %z")
+    ));
+    for file in &report.files {
+        message.push_str(&format!("  {} {}\n", file.status.letter(), file.path.display()));
+    }
+    if let Some(run) = &report.run {
+        message.push_str(&format!("\nProduced by: {} ({})\n", run.command_line(), run.outcome()));
+    }
+    message.push_str(&format!("\n{}\n", json));
+    message
+}
+
+/// Hand a message to sendmail, which takes the recipients from its headers
+fn sendmail_message(sendmail: &Path, message: &str) -> io::Result<()> {
+    let mut child = Command::new(sendmail)
+        .args(["-t", "-oi"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| io::Error::new(e.kind(), format!("failed to start {}: {}", sendmail.display(), e)))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(message.as_bytes())?;
+    }
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "{} failed: {}",
+            sendmail.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(())
+}
@@ -0,0 +1,135 @@
+use crate::daemon;
+use crate::lock::LOCK_FILE;
+use crate::scan_cache::SCAN_CACHE_FILE;
+use crate::session::{self, Session};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// File name of the manifest at the top of a session archive
+const MANIFEST_FILE: &str = "audit-box-session.json";
+
+/// What a session archive needs besides the session directory to be
+/// reviewed elsewhere: what the session file says about it
+#[derive(Debug, Serialize, Deserialize)]
+struct Manifest {
+    base: PathBuf,
+    #[serde(default)]
+    lower_layers: Vec<PathBuf>,
+    exported: DateTime<Local>,
+}
+
+/// Entries of the session directory left out of an archive: the overlayfs
+/// work directory, which is recreated empty, and what only means something
+/// on this machine. The scan cache goes by this machine's inodes.
+const LEFT_OUT: [&str; 4] = ["work", LOCK_FILE, daemon::SOCKET, SCAN_CACHE_FILE];
+
+/// Bundle a session into a gzipped tar archive: the overlay with its
+/// whiteouts and xattrs, the session metadata, review state, timeline and
+/// audit log
+pub fn export(session: &Session, file: &Path) -> io::Result<()> {
+    let manifest = Manifest {
+        base: session.base_path.clone(),
+        lower_layers: session.lower_layers.clone(),
+        exported: Local::now(),
+    };
+    let manifest_dir = tempfile::tempdir()?;
+    let content = serde_json::to_string_pretty(&manifest).map_err(io::Error::other)?;
+    fs::write(manifest_dir.path().join(MANIFEST_FILE), content)?;
+
+    let mut entries: Vec<_> = fs::read_dir(&session.tmpdir)?
+        .filter_map(|e| e.ok())
+        .map(|e| e.file_name())
+        .filter(|name| !LEFT_OUT.iter().any(|left_out| name == *left_out))
+        .collect();
+    entries.sort();
+
+    let mut command = tar();
+    command
+        .arg("--create")
+        .arg("--file")
+        .arg(file)
+        .arg("--directory")
+        .arg(manifest_dir.path())
+        .arg(MANIFEST_FILE)
+        .arg("--directory")
+        .arg(&session.tmpdir)
+        .arg("--")
+        .args(&entries);
+    run(command)
+}
+
+/// Unpack a session archive into a new session directory under `workdir`
+/// and make it the current session
+pub fn import(file: &Path, workdir: &Path) -> io::Result<Session> {
+    let tmpdir = session::create_session_dir(workdir)?;
+    let manifest = match unpack(file, &tmpdir) {
+        Ok(manifest) => manifest,
+        Err(e) => {
+            let _ = fs::remove_dir_all(&tmpdir);
+            return Err(e);
+        }
+    };
+    session::save_session(&tmpdir, &manifest.base, &manifest.lower_layers)?;
+    Ok(Session {
+        tmpdir,
+        base_path: manifest.base,
+        lower_layers: manifest.lower_layers,
+    })
+}
+
+fn unpack(file: &Path, tmpdir: &Path) -> io::Result<Manifest> {
+    let mut command = tar();
+    command.arg("--extract").arg("--file").arg(file).arg("--directory").arg(tmpdir);
+    run(command)?;
+
+    let manifest_path = tmpdir.join(MANIFEST_FILE);
+    let content = fs::read_to_string(&manifest_path).map_err(|e| match e.kind() {
+        io::ErrorKind::NotFound => io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{} is not an audit-box session archive", file.display()),
+        ),
+        _ => e,
+    })?;
+    let manifest: Manifest = serde_json::from_str(&content).map_err(|e| {
+        io::Error::new(io::ErrorKind::InvalidData, format!("Invalid session archive manifest: {}", e))
+    })?;
+    fs::remove_file(&manifest_path)?;
+
+    // A session with several bases has a work directory for each
+    let metadata = session::load_metadata(tmpdir)?;
+    for name in metadata.roots.keys() {
+        fs::create_dir_all(tmpdir.join("work").join(name))?;
+    }
+    Ok(manifest)
+}
+
+/// tar, keeping what overlayfs stores in the overlay: whiteouts, xattrs
+/// such as opaque directory markers, and timestamps to the nanosecond so
+/// the review can still tell what changed since it was last reviewed
+fn tar() -> Command {
+    let mut command = Command::new("tar");
+    command
+        .arg("--gzip")
+        .arg("--format=posix")
+        .arg("--xattrs")
+        .arg("--xattrs-include=*")
+        .arg("--numeric-owner");
+    command
+}
+
+fn run(mut command: Command) -> io::Result<()> {
+    let output = command
+        .output()
+        .map_err(|e| io::Error::new(e.kind(), format!("Failed to run tar: {}", e)))?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "tar failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(())
+}
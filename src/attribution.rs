@@ -0,0 +1,296 @@
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::thread::JoinHandle;
+
+/// File name of the process attribution log, stored in the session directory
+pub const WRITERS_FILE: &str = "writers.jsonl";
+
+#[cfg(target_os = "linux")]
+use std::collections::HashSet;
+#[cfg(target_os = "linux")]
+use std::ffi::CString;
+#[cfg(target_os = "linux")]
+use std::fs::{self, OpenOptions};
+#[cfg(target_os = "linux")]
+use std::io::{BufWriter, Write};
+#[cfg(target_os = "linux")]
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+#[cfg(target_os = "linux")]
+use std::os::unix::ffi::OsStrExt;
+
+/// How long the monitor waits for events before checking whether to stop
+#[cfg(target_os = "linux")]
+const POLL_TIMEOUT_MS: i32 = 100;
+
+/// Parents looked through for the sandbox before giving up on a process
+#[cfg(target_os = "linux")]
+const MAX_ANCESTORS: usize = 64;
+
+/// A process that wrote to an overlay file, one JSON object per line on disk
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WriteRecord {
+    pub time: DateTime<Local>,
+    /// Path relative to the overlay root
+    pub path: PathBuf,
+    pub pid: i32,
+    /// Command name from /proc, or "?" if the process had already exited
+    pub comm: String,
+}
+
+impl WriteRecord {
+    pub fn describe(&self) -> String {
+        format!("{} ({})", self.comm, self.pid)
+    }
+}
+
+/// Watches the filesystem holding the overlay with fanotify and records
+/// which processes write to overlay files. The mark covers the whole
+/// filesystem, so only writes by the sandbox's processes are recorded: those
+/// descended from the process given to `attribute_to`.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+pub struct Monitor {
+    stop: Arc<AtomicBool>,
+    sandbox_pid: Arc<AtomicU32>,
+    thread: JoinHandle<()>,
+}
+
+impl Monitor {
+    /// Start monitoring. Fails with PermissionDenied without CAP_SYS_ADMIN.
+    #[cfg(target_os = "linux")]
+    pub fn start(session_dir: &Path, overlay_path: &Path) -> io::Result<Self> {
+        // SAFETY: plain syscall, the returned descriptor is owned below
+        let fd = unsafe {
+            libc::fanotify_init(
+                libc::FAN_CLASS_NOTIF | libc::FAN_CLOEXEC | libc::FAN_NONBLOCK,
+                (libc::O_RDONLY | libc::O_LARGEFILE | libc::O_CLOEXEC) as u32,
+            )
+        };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: fanotify_init returned a new descriptor that nothing else owns
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+
+        // Writes through the sandbox's overlay mount land in the upper directory
+        // on overlayfs's private mount, which only a filesystem-wide mark sees
+        let overlay = CString::new(overlay_path.as_os_str().as_bytes()).map_err(io::Error::other)?;
+        // SAFETY: fd is a valid fanotify descriptor and overlay a NUL-terminated path
+        let marked = unsafe {
+            libc::fanotify_mark(
+                fd.as_raw_fd(),
+                libc::FAN_MARK_ADD | libc::FAN_MARK_FILESYSTEM,
+                libc::FAN_MODIFY | libc::FAN_CLOSE_WRITE,
+                libc::AT_FDCWD,
+                overlay.as_ptr(),
+            )
+        };
+        if marked < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(session_dir.join(WRITERS_FILE))?;
+        let sandbox_pid = Arc::new(AtomicU32::new(0));
+        let mut writer = Writer {
+            output: BufWriter::new(file),
+            overlay_path: overlay_path.to_path_buf(),
+            sandbox_pid: Arc::clone(&sandbox_pid),
+            sandboxed: HashSet::new(),
+            seen: HashSet::new(),
+        };
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let stopping = Arc::clone(&stop);
+        let thread = std::thread::spawn(move || {
+            if let Err(e) = writer.run(&fd, &stopping) {
+                eprintln!("Warning: process attribution stopped: {}", e);
+            }
+        });
+
+        Ok(Monitor { stop, sandbox_pid, thread })
+    }
+
+    /// fanotify is Linux-only
+    #[cfg(not(target_os = "linux"))]
+    pub fn start(_session_dir: &Path, _overlay_path: &Path) -> io::Result<Self> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "fanotify is only available on Linux"))
+    }
+
+    /// Record writes by `pid`, the sandbox, and its descendants from now on.
+    /// Until then nothing is recorded.
+    pub fn attribute_to(&self, pid: u32) {
+        self.sandbox_pid.store(pid, Ordering::Relaxed);
+    }
+
+    /// Record any events still queued, then stop
+    pub fn stop(self) {
+        self.stop.store(true, Ordering::Relaxed);
+        let _ = self.thread.join();
+    }
+}
+
+#[cfg(target_os = "linux")]
+struct Writer {
+    output: BufWriter<File>,
+    overlay_path: PathBuf,
+    /// The process the sandbox runs under, 0 until it has started
+    sandbox_pid: Arc<AtomicU32>,
+    /// Processes found to be in the sandbox
+    sandboxed: HashSet<i32>,
+    /// Each process is recorded once per file
+    seen: HashSet<(PathBuf, i32)>,
+}
+
+#[cfg(target_os = "linux")]
+impl Writer {
+    fn run(&mut self, fd: &OwnedFd, stop: &AtomicBool) -> io::Result<()> {
+        let mut buf = vec![0u8; 64 * 1024];
+        loop {
+            let mut pollfd = libc::pollfd { fd: fd.as_raw_fd(), events: libc::POLLIN, revents: 0 };
+            // SAFETY: pollfd points to one valid pollfd struct
+            let ready = unsafe { libc::poll(&mut pollfd, 1, POLL_TIMEOUT_MS) };
+            if ready < 0 {
+                let err = io::Error::last_os_error();
+                if err.kind() == io::ErrorKind::Interrupted {
+                    continue;
+                }
+                return Err(err);
+            }
+            if ready == 0 {
+                // Stop once the queue is empty, so writes made just before the
+                // command exited are still recorded
+                if stop.load(Ordering::Relaxed) {
+                    return Ok(());
+                }
+                continue;
+            }
+
+            // SAFETY: buf is valid for writes of buf.len() bytes
+            let len = unsafe { libc::read(fd.as_raw_fd(), buf.as_mut_ptr().cast(), buf.len()) };
+            if len < 0 {
+                let err = io::Error::last_os_error();
+                if matches!(err.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::Interrupted) {
+                    continue;
+                }
+                return Err(err);
+            }
+            self.handle_events(&buf[..len as usize])?;
+        }
+    }
+
+    fn handle_events(&mut self, mut buf: &[u8]) -> io::Result<()> {
+        let header = std::mem::size_of::<libc::fanotify_event_metadata>();
+        while buf.len() >= header {
+            // SAFETY: the kernel writes whole metadata structs; the buffer has no alignment guarantee
+            let event: libc::fanotify_event_metadata =
+                unsafe { std::ptr::read_unaligned(buf.as_ptr().cast()) };
+            let event_len = event.event_len as usize;
+            if event.vers != libc::FANOTIFY_METADATA_VERSION || event_len < header || event_len > buf.len() {
+                break;
+            }
+            buf = &buf[event_len..];
+
+            if event.fd < 0 {
+                continue;
+            }
+            // SAFETY: each event carries a new descriptor for us to close
+            let file = unsafe { OwnedFd::from_raw_fd(event.fd) };
+            let Ok(path) = fs::read_link(format!("/proc/self/fd/{}", file.as_raw_fd())) else {
+                continue;
+            };
+            drop(file);
+
+            // Processes outside the sandbox write to the same filesystem
+            if !self.is_sandboxed(event.pid) {
+                continue;
+            }
+            if let Some(rel_path) = self.relative_path(&path) {
+                self.record(rel_path, event.pid)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether a process is the sandbox or descended from it. One that has
+    /// already exited cannot be told apart from a host process, so is not.
+    fn is_sandboxed(&mut self, pid: i32) -> bool {
+        let sandbox_pid = self.sandbox_pid.load(Ordering::Relaxed) as i32;
+        if sandbox_pid == 0 {
+            return false;
+        }
+        if self.sandboxed.contains(&pid) {
+            return true;
+        }
+        let mut ancestor = pid;
+        for _ in 0..MAX_ANCESTORS {
+            if ancestor == sandbox_pid {
+                self.sandboxed.insert(pid);
+                return true;
+            }
+            match parent_pid(ancestor) {
+                Some(parent) if parent > 0 => ancestor = parent,
+                _ => return false,
+            }
+        }
+        false
+    }
+
+    /// Map an event's path to a path in the overlay
+    fn relative_path(&self, path: &Path) -> Option<PathBuf> {
+        if let Ok(rel_path) = path.strip_prefix(&self.overlay_path) {
+            return Some(rel_path.to_path_buf());
+        }
+        // Files written through the sandbox's overlay mount are reported
+        // relative to overlayfs's private mount of the upper directory, so
+        // "/etc/hosts" means "<overlay>/etc/hosts" if that exists
+        let rel_path = path.strip_prefix("/").ok()?;
+        self.overlay_path.join(rel_path).exists().then(|| rel_path.to_path_buf())
+    }
+
+    fn record(&mut self, path: PathBuf, pid: i32) -> io::Result<()> {
+        if !self.seen.insert((path.clone(), pid)) {
+            return Ok(());
+        }
+        let comm = fs::read_to_string(format!("/proc/{}/comm", pid))
+            .map(|comm| comm.trim_end().to_string())
+            .unwrap_or_else(|_| "?".to_string());
+        let record = WriteRecord { time: Local::now(), path, pid, comm };
+        let line = serde_json::to_string(&record).map_err(io::Error::other)?;
+        writeln!(self.output, "{}", line)?;
+        self.output.flush()
+    }
+}
+
+/// A process's parent, from /proc/<pid>/stat, whose second field (the
+/// command name in parentheses) may itself contain spaces and parentheses
+#[cfg(target_os = "linux")]
+fn parent_pid(pid: i32) -> Option<i32> {
+    let stat = fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+    let (_, rest) = stat.rsplit_once(')')?;
+    rest.split_whitespace().nth(1)?.parse().ok()
+}
+
+/// Load the recorded writers of each overlay file, in the order they first wrote
+pub fn load_writers(session_dir: &Path) -> io::Result<BTreeMap<PathBuf, Vec<WriteRecord>>> {
+    let path = session_dir.join(WRITERS_FILE);
+    if !path.exists() {
+        return Ok(BTreeMap::new());
+    }
+
+    let reader = BufReader::new(File::open(path)?);
+    let mut writers: BTreeMap<PathBuf, Vec<WriteRecord>> = BTreeMap::new();
+    for line in reader.lines() {
+        if let Ok(record) = serde_json::from_str::<WriteRecord>(&line?) {
+            writers.entry(record.path.clone()).or_default().push(record);
+        }
+    }
+    Ok(writers)
+}
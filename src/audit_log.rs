@@ -0,0 +1,102 @@
+use crate::identity::Identity;
+use chrono::{DateTime, FixedOffset, Local};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// File name of the audit log, stored in the session directory
+pub const AUDIT_LOG_FILE: &str = "audit.log";
+
+const TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S %z";
+
+/// How an applied changeset's line starts, before its name
+pub const CHANGESET_PREFIX: &str = "applied changeset ";
+
+/// One line of the audit log
+#[derive(Debug, Clone)]
+pub struct LogRecord {
+    pub time: DateTime<FixedOffset>,
+    /// Who made the change, as "name <email>"; logs written before
+    /// identities were recorded leave it out
+    pub user: Option<String>,
+    pub message: String,
+}
+
+impl LogRecord {
+    /// The name of who made the change, without the email
+    pub fn user_name(&self) -> Option<&str> {
+        let user = self.user.as_deref()?;
+        Some(user.split_once(" <").map_or(user, |(name, _)| name))
+    }
+}
+
+/// Append a timestamped line to the session's audit log, saying who did it
+pub fn append(session_dir: &Path, identity: &Identity, message: &str) -> io::Result<()> {
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(session_dir.join(AUDIT_LOG_FILE))?;
+    writeln!(file, "{}  [{}]  {}", Local::now().format(TIME_FORMAT), identity, message)
+}
+
+/// Read the session's audit log, skipping lines that do not parse
+pub fn load(session_dir: &Path) -> io::Result<Vec<LogRecord>> {
+    let path = session_dir.join(AUDIT_LOG_FILE);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    Ok(fs::read_to_string(path)?
+        .lines()
+        .filter_map(|line| {
+            let (time, rest) = line.split_once("  ")?;
+            let (user, message) = match rest.strip_prefix('[').and_then(|rest| rest.split_once("]  ")) {
+                Some((user, message)) => (Some(user.to_string()), message),
+                None => (None, rest),
+            };
+            Some(LogRecord {
+                time: DateTime::parse_from_str(time, TIME_FORMAT).ok()?,
+                user,
+                message: message.to_string(),
+            })
+        })
+        .collect())
+}
+
+/// What happened to a path earlier in the session, oldest first, e.g.
+/// "applied 10-16 14:02 by alice, run npm install". Applying or discarding a
+/// directory counts for everything under it. Runs are only listed once the
+/// path has been applied or discarded, as they explain why it is back.
+pub fn path_history(records: &[LogRecord], rel_path: &Path) -> Vec<String> {
+    let mut history = Vec::new();
+    for record in records {
+        let time = record.time.format("%m-%d %H:%M");
+        if let Some((action, path)) = decision(&record.message) {
+            if rel_path.starts_with(&path) {
+                match record.user_name() {
+                    Some(name) => history.push(format!("{} {} by {}", action, time, name)),
+                    None => history.push(format!("{} {}", action, time)),
+                }
+            }
+        } else if let Some(command) = record.message.strip_prefix("run started: ")
+            && !history.is_empty()
+        {
+            // Drop the " (profile ...)" suffix
+            let command = command.rsplit_once(" (profile ").map_or(command, |(command, _)| command);
+            history.push(format!("run {} {}", command, time));
+        }
+    }
+    history
+}
+
+/// A path applied or discarded. An applied changeset is not a path; its
+/// files are logged on lines of their own after it.
+fn decision(message: &str) -> Option<(&'static str, PathBuf)> {
+    if message.starts_with(CHANGESET_PREFIX) {
+        None
+    } else if let Some(path) = message.strip_prefix("applied ") {
+        Some(("applied", PathBuf::from(path)))
+    } else {
+        message.strip_prefix("discarded ").map(|path| ("discarded", PathBuf::from(path)))
+    }
+}
@@ -0,0 +1,66 @@
+use chrono::DateTime;
+use std::collections::HashMap;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// Width of a blame annotation, so the lines after it stay aligned
+pub const WIDTH: usize = 32;
+
+/// Who last changed each line of a base file tracked by git, as
+/// "commit author date" annotations indexed by line. None if the file is
+/// not in a git repository, is not tracked or git is not installed.
+pub fn blame(file: &Path) -> Option<Vec<String>> {
+    let dir = file.parent()?;
+    // Most bases are not repositories; spare them a git process per file
+    if !dir.ancestors().any(|ancestor| ancestor.join(".git").exists()) {
+        return None;
+    }
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["blame", "--porcelain", "--"])
+        .arg(file.file_name()?)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(parse_porcelain(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Porcelain output gives each commit's details the first time it appears,
+/// in headers between the line's "<commit> <orig> <final>" line and its
+/// tab-prefixed content
+fn parse_porcelain(output: &str) -> Vec<String> {
+    let mut commits: HashMap<&str, (String, String)> = HashMap::new();
+    let mut annotations = Vec::new();
+    let mut commit = "";
+    for line in output.lines() {
+        if line.starts_with('\t') {
+            let (author, date) = commits.get(commit).cloned().unwrap_or_default();
+            annotations.push(if commit.bytes().all(|b| b == b'0') {
+                format!("{:<width$}", "uncommitted", width = WIDTH)
+            } else {
+                format!("{:.8} {:<12.12} {:<10}", commit, author, date)
+            });
+        } else if let Some(author) = line.strip_prefix("author ") {
+            commits.entry(commit).or_default().0 = author.to_string();
+        } else if let Some(time) = line.strip_prefix("author-time ") {
+            let date = time
+                .parse()
+                .ok()
+                .and_then(|secs| DateTime::from_timestamp(secs, 0))
+                .map(|time| time.format("%Y-%m-%d").to_string())
+                .unwrap_or_default();
+            commits.entry(commit).or_default().1 = date;
+        } else if let Some((hash, _)) = line.split_once(' ')
+            && hash.len() == 40
+            && hash.bytes().all(|b| b.is_ascii_hexdigit())
+        {
+            commit = hash;
+        }
+    }
+    annotations
+}
@@ -0,0 +1,112 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+
+/// Changed files grouped into one logical change, e.g. "nginx config
+/// update", which are applied, exported and reported together
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Changeset {
+    /// What the change is and why, used as its patch's commit message
+    pub description: String,
+    /// Paths relative to the overlay root
+    pub files: BTreeSet<PathBuf>,
+    /// Place in the queue changesets are applied in, lowest first
+    pub order: u32,
+    /// Left out when the queue is applied, until released
+    pub hold: bool,
+}
+
+/// Changesets by name. A file is in one at most.
+pub type Changesets = BTreeMap<String, Changeset>;
+
+/// Move files into the named changeset, out of any other they were in,
+/// creating it if need be. A description replaces the one it had, unless
+/// empty.
+pub fn group(changesets: &mut Changesets, name: &str, description: &str, files: &[PathBuf]) {
+    for (_, changeset) in changesets.iter_mut().filter(|(other, _)| *other != name) {
+        for rel_path in files {
+            changeset.files.remove(rel_path);
+        }
+    }
+    changesets.retain(|other, changeset| other == name || !changeset.files.is_empty());
+
+    // New changesets join the end of the queue
+    let next = changesets.values().map(|c| c.order + 1).max().unwrap_or_default();
+    let changeset = changesets.entry(name.to_string()).or_insert_with(|| Changeset { order: next, ..Default::default() });
+    if !description.is_empty() {
+        changeset.description = description.to_string();
+    }
+    changeset.files.extend(files.iter().cloned());
+}
+
+/// Take files out of whichever changesets they are in, dropping those left
+/// empty
+pub fn ungroup(changesets: &mut Changesets, files: &[PathBuf]) {
+    for changeset in changesets.values_mut() {
+        for rel_path in files {
+            changeset.files.remove(rel_path);
+        }
+    }
+    changesets.retain(|_, changeset| !changeset.files.is_empty());
+}
+
+/// Names of the changesets in the order they are applied
+pub fn queue(changesets: &Changesets) -> Vec<&str> {
+    let mut names: Vec<(&u32, &str)> = changesets.iter().map(|(name, c)| (&c.order, name.as_str())).collect();
+    names.sort();
+    names.into_iter().map(|(_, name)| name).collect()
+}
+
+/// Put the changesets in the given order, those left out keeping theirs
+/// after them
+pub fn reorder(changesets: &mut Changesets, names: &[String]) {
+    let rest: Vec<String> = queue(changesets)
+        .into_iter()
+        .filter(|name| !names.iter().any(|n| n == name))
+        .map(str::to_string)
+        .collect();
+    for (order, name) in names.iter().chain(&rest).enumerate() {
+        if let Some(changeset) = changesets.get_mut(name) {
+            changeset.order = order as u32;
+        }
+    }
+}
+
+/// Fold a changeset into the one before it in the queue, as with a squash
+/// in `git rebase -i`: its files join that one's, and its description goes
+/// after that one's. The first in the queue has nothing to fold into.
+pub fn squash(changesets: &mut Changesets, name: &str) {
+    let queue = queue(changesets);
+    let Some(previous) = queue.iter().position(|n| *n == name).filter(|&i| i > 0).map(|i| queue[i - 1].to_string()) else {
+        return;
+    };
+    let Some(squashed) = changesets.remove(name) else {
+        return;
+    };
+    if let Some(into) = changesets.get_mut(&previous) {
+        into.files.extend(squashed.files);
+        into.description = match (into.description.as_str(), squashed.description.as_str()) {
+            (description, "") => description.to_string(),
+            ("", description) => description.to_string(),
+            (first, second) => format!("{}; {}", first, second),
+        };
+    }
+}
+
+/// Name of the changeset a file is in, if any
+pub fn of<'a>(changesets: &'a Changesets, rel_path: &Path) -> Option<&'a str> {
+    changesets
+        .iter()
+        .find(|(_, changeset)| changeset.files.contains(rel_path))
+        .map(|(name, _)| name.as_str())
+}
+
+/// Name and description from what was typed into the changeset prompt, as
+/// "name: description", the description being optional
+pub fn parse(input: &str) -> (&str, &str) {
+    match input.split_once(':') {
+        Some((name, description)) => (name.trim(), description.trim()),
+        None => (input.trim(), ""),
+    }
+}
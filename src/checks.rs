@@ -0,0 +1,187 @@
+use crate::config::CheckConfig;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+/// Lines of a check's output kept for the content pane
+const MAX_OUTPUT_LINES: usize = 20;
+
+/// How a configured check went on one file
+#[derive(Debug, Clone)]
+pub struct CheckResult {
+    pub name: String,
+    /// Exited with status 0
+    pub passed: bool,
+    /// e.g. "exit 1", "timed out after 30s" or "failed to start: ..."
+    pub outcome: String,
+    pub output: Vec<String>,
+}
+
+/// A file to check, as it was when last modified
+pub struct CheckJob {
+    /// Path relative to the overlay root, to match the checks' file patterns
+    pub rel_path: PathBuf,
+    pub overlay_file: PathBuf,
+    pub base_file: PathBuf,
+    pub modified: SystemTime,
+}
+
+/// Results for one file
+pub struct CheckReport {
+    pub overlay_file: PathBuf,
+    pub modified: SystemTime,
+    pub results: Vec<CheckResult>,
+}
+
+struct Check {
+    config: CheckConfig,
+    files: GlobSet,
+}
+
+/// Runs the configured checks on a background thread, one file at a time,
+/// so slow scanners do not hold up the review
+pub struct Checker {
+    jobs: Sender<CheckJob>,
+    reports: Receiver<CheckReport>,
+}
+
+impl Checker {
+    /// Start the checker thread, if any checks are configured
+    pub fn start(configs: &[CheckConfig]) -> io::Result<Option<Checker>> {
+        if configs.is_empty() {
+            return Ok(None);
+        }
+        let mut checks = Vec::new();
+        for config in configs {
+            if config.command.is_empty() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("Check '{}' has no command", config.name),
+                ));
+            }
+            let mut files = GlobSetBuilder::new();
+            for pattern in &config.files {
+                files.add(Glob::new(pattern).map_err(|e| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("Invalid file pattern '{}' for check '{}': {}", pattern, config.name, e),
+                    )
+                })?);
+            }
+            checks.push(Check {
+                config: config.clone(),
+                files: files.build().map_err(io::Error::other)?,
+            });
+        }
+
+        let (jobs, job_rx) = channel::<CheckJob>();
+        let (report_tx, reports) = channel();
+        thread::spawn(move || {
+            for job in job_rx {
+                let results = checks
+                    .iter()
+                    .filter(|check| check.config.files.is_empty() || check.files.is_match(&job.rel_path))
+                    .map(|check| run_check(&check.config, &job.overlay_file, &job.base_file))
+                    .collect();
+                let report = CheckReport {
+                    overlay_file: job.overlay_file,
+                    modified: job.modified,
+                    results,
+                };
+                if report_tx.send(report).is_err() {
+                    break;
+                }
+            }
+        });
+        Ok(Some(Checker { jobs, reports }))
+    }
+
+    pub fn request(&self, job: CheckJob) {
+        // The thread only stops once the review does
+        let _ = self.jobs.send(job);
+    }
+
+    /// Reports finished since the last call, without waiting
+    pub fn finished(&self) -> Vec<CheckReport> {
+        self.reports.try_iter().collect()
+    }
+}
+
+/// Run one check on a file. `{overlay}` and `{base}` in the command stand
+/// for the file in the overlay and where it would be applied; without
+/// either, the overlay file is passed last.
+fn run_check(config: &CheckConfig, overlay_file: &Path, base_file: &Path) -> CheckResult {
+    let overlay = overlay_file.to_string_lossy();
+    let base = base_file.to_string_lossy();
+    let mut args: Vec<String> = config
+        .command
+        .iter()
+        .map(|arg| arg.replace("{overlay}", &overlay).replace("{base}", &base))
+        .collect();
+    if !config.command.iter().any(|arg| arg.contains("{overlay}") || arg.contains("{base}")) {
+        args.push(overlay.to_string());
+    }
+    run_command(&config.name, &args, None, config.timeout_secs)
+}
+
+/// Run a check's command, in `dir` if given, stopping it after
+/// `timeout_secs`. It passes if it exits with status 0.
+pub fn run_command(name: &str, args: &[String], dir: Option<&Path>, timeout_secs: u64) -> CheckResult {
+    let result = |passed, outcome: String, output: String| CheckResult {
+        name: name.to_string(),
+        passed,
+        outcome,
+        output: output.lines().take(MAX_OUTPUT_LINES).map(str::to_string).collect(),
+    };
+    let mut command = Command::new(&args[0]);
+    command.args(&args[1..]).stdin(Stdio::null()).stdout(Stdio::piped()).stderr(Stdio::piped());
+    if let Some(dir) = dir {
+        command.current_dir(dir);
+    }
+    let mut child = match command.spawn() {
+        Ok(child) => child,
+        Err(e) => return result(false, format!("failed to start: {}", e), String::new()),
+    };
+
+    // Read the output as it comes, so a chatty check cannot fill a pipe and stall
+    let stdout = read_all(child.stdout.take());
+    let stderr = read_all(child.stderr.take());
+
+    let timeout = Duration::from_secs(timeout_secs);
+    let started = Instant::now();
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break Some(status),
+            Ok(None) if started.elapsed() < timeout => thread::sleep(Duration::from_millis(20)),
+            _ => {
+                let _ = child.kill();
+                let _ = child.wait();
+                break None;
+            }
+        }
+    };
+    let output = stdout.join().unwrap_or_default() + &stderr.join().unwrap_or_default();
+    match status {
+        Some(status) if status.success() => result(true, "passed".to_string(), output),
+        Some(status) => match status.code() {
+            Some(code) => result(false, format!("exit {}", code), output),
+            None => result(false, "killed by a signal".to_string(), output),
+        },
+        None => result(false, format!("timed out after {}s", timeout_secs), output),
+    }
+}
+
+/// Read a pipe to the end on a thread of its own
+pub fn read_all(pipe: Option<impl Read + Send + 'static>) -> thread::JoinHandle<String> {
+    thread::spawn(move || {
+        let mut output = String::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_string(&mut output);
+        }
+        output
+    })
+}
@@ -0,0 +1,228 @@
+use std::fs::{self, File};
+use std::io::Read;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+
+/// How closely a change deserves to be looked at
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum RiskLevel {
+    Medium,
+    High,
+}
+
+impl RiskLevel {
+    /// Badge shown with the hint
+    pub fn badge(self) -> &'static str {
+        match self {
+            RiskLevel::Medium => "RISK: MEDIUM",
+            RiskLevel::High => "RISK: HIGH",
+        }
+    }
+}
+
+/// Why a file is risky to apply, from the first rule it matches
+#[derive(Debug, Clone, PartialEq)]
+pub struct RiskHint {
+    pub level: RiskLevel,
+    /// What the file is, e.g. "systemd unit"
+    pub kind: &'static str,
+    pub explanation: &'static str,
+}
+
+/// What the rules look at: where the file would be applied, its mode and
+/// its first bytes
+pub struct Subject<'a> {
+    /// Path in the base, as the sandboxed program saw it
+    pub path: &'a Path,
+    pub mode: u32,
+    pub head: &'a [u8],
+}
+
+impl Subject<'_> {
+    fn name(&self) -> &str {
+        self.path.file_name().and_then(|n| n.to_str()).unwrap_or("")
+    }
+
+    /// Whether the file is somewhere below a directory ending in `dir`,
+    /// e.g. `systemd/user` for `~/.config/systemd/user`
+    fn below(&self, dir: &str) -> bool {
+        self.path.ancestors().skip(1).any(|ancestor| ancestor.ends_with(dir))
+    }
+
+    fn in_dir(&self, dir: &str) -> bool {
+        self.path.parent() == Some(Path::new(dir))
+    }
+
+    fn is_elf(&self) -> bool {
+        self.head.starts_with(b"\x7fELF")
+    }
+
+    fn is_setid(&self) -> bool {
+        self.mode & 0o6000 != 0
+    }
+}
+
+/// A kind of file worth a warning when it changes
+pub struct Rule {
+    pub level: RiskLevel,
+    pub kind: &'static str,
+    pub explanation: &'static str,
+    pub matches: fn(&Subject) -> bool,
+}
+
+/// Checked in order; the first that matches gives the hint. Add a rule here
+/// to recognise another kind of file.
+pub const RULES: &[Rule] = &[
+    Rule {
+        level: RiskLevel::High,
+        kind: "setuid script",
+        explanation: "runs with its owner's or group's privileges, whoever starts it",
+        matches: |s| s.is_setid() && s.head.starts_with(b"#!"),
+    },
+    Rule {
+        level: RiskLevel::High,
+        kind: "setuid binary",
+        explanation: "runs with its owner's or group's privileges, whoever starts it",
+        matches: |s| s.is_setid() && s.is_elf(),
+    },
+    Rule {
+        level: RiskLevel::High,
+        kind: "systemd unit",
+        explanation: "can start a service automatically, at boot or on login",
+        matches: |s| {
+            (s.below("systemd/system") || s.below("systemd/user"))
+                && [".service", ".timer", ".socket", ".path", ".mount", ".target"]
+                    .iter()
+                    .any(|ext| s.name().ends_with(ext))
+        },
+    },
+    Rule {
+        level: RiskLevel::High,
+        kind: "cron entry",
+        explanation: "runs commands on a schedule",
+        matches: |s| {
+            s.name() == "crontab"
+                || s.path.starts_with("/var/spool/cron")
+                || ["/etc/cron.d", "/etc/cron.hourly", "/etc/cron.daily", "/etc/cron.weekly", "/etc/cron.monthly"]
+                    .iter()
+                    .any(|dir| s.in_dir(dir))
+        },
+    },
+    Rule {
+        level: RiskLevel::High,
+        kind: "sudoers file",
+        explanation: "grants users root privileges",
+        matches: |s| s.path == Path::new("/etc/sudoers") || s.in_dir("/etc/sudoers.d"),
+    },
+    Rule {
+        level: RiskLevel::High,
+        kind: "SSH authorized keys",
+        explanation: "lets the holders of these keys log in",
+        matches: |s| s.name() == "authorized_keys" || s.name() == "authorized_keys2",
+    },
+    Rule {
+        level: RiskLevel::Medium,
+        kind: "shell profile",
+        explanation: "runs in every new shell or login",
+        matches: |s| {
+            [
+                ".bashrc", ".bash_profile", ".bash_login", ".bash_logout", ".profile", ".zshrc", ".zshenv", ".zprofile",
+                ".zlogin", ".cshrc", ".tcshrc",
+            ]
+            .contains(&s.name())
+                || [Path::new("/etc/profile"), Path::new("/etc/bash.bashrc"), Path::new("/etc/zshrc")].contains(&s.path)
+                || s.in_dir("/etc/profile.d")
+        },
+    },
+    Rule {
+        level: RiskLevel::Medium,
+        kind: "ELF binary",
+        explanation: "native code that cannot be reviewed as text; check it is the one expected",
+        matches: |s| s.is_elf(),
+    },
+];
+
+/// How much of a file the rules look at
+const HEAD_LEN: u64 = 512;
+
+/// Risk hint for an overlay file that would be applied to `base_path`
+pub fn classify(overlay_file: &Path, base_path: &Path) -> Option<RiskHint> {
+    let metadata = fs::symlink_metadata(overlay_file).ok()?;
+    if !metadata.is_file() {
+        return None;
+    }
+    let mut head = Vec::new();
+    if let Ok(file) = File::open(overlay_file) {
+        let _ = file.take(HEAD_LEN).read_to_end(&mut head);
+    }
+    let subject = Subject {
+        path: base_path,
+        mode: metadata.permissions().mode(),
+        head: &head,
+    };
+    RULES.iter().find(|rule| (rule.matches)(&subject)).map(|rule| RiskHint {
+        level: rule.level,
+        kind: rule.kind,
+        explanation: rule.explanation,
+    })
+}
+
+/// Privileges a file grants whoever runs it, which applying carries over
+/// to the base
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Privileges {
+    pub setuid: bool,
+    pub setgid: bool,
+    /// File capabilities, in the security.capability xattr
+    pub capabilities: bool,
+}
+
+impl Privileges {
+    /// The privileges of a regular file, if it has any
+    pub fn of(path: &Path) -> Option<Privileges> {
+        let metadata = fs::symlink_metadata(path).ok()?;
+        if !metadata.is_file() {
+            return None;
+        }
+        let mode = metadata.permissions().mode();
+        let privileges = Privileges {
+            setuid: mode & 0o4000 != 0,
+            setgid: mode & 0o2000 != 0,
+            capabilities: has_capabilities(path),
+        };
+        (privileges.setuid || privileges.setgid || privileges.capabilities).then_some(privileges)
+    }
+
+    /// e.g. "setuid, capabilities"
+    pub fn describe(&self) -> String {
+        let names: Vec<&str> = [
+            (self.setuid, "setuid"),
+            (self.setgid, "setgid"),
+            (self.capabilities, "capabilities"),
+        ]
+        .into_iter()
+        .filter_map(|(set, name)| set.then_some(name))
+        .collect();
+        names.join(", ")
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn has_capabilities(path: &Path) -> bool {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let Ok(path) = CString::new(path.as_os_str().as_bytes()) else {
+        return false;
+    };
+    let name = CString::new("security.capability").unwrap();
+    // SAFETY: both strings are NUL-terminated; a null buffer of size 0 only asks for the length
+    let len = unsafe { libc::lgetxattr(path.as_ptr(), name.as_ptr(), std::ptr::null_mut(), 0) };
+    len > 0
+}
+
+/// File capabilities are a Linux feature
+#[cfg(not(target_os = "linux"))]
+fn has_capabilities(_path: &Path) -> bool {
+    false
+}
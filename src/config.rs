@@ -0,0 +1,326 @@
+use crate::identity::Identity;
+use crate::keymap::Keymap;
+use crate::sandbox::SandboxProfile;
+use crate::types::{DialogButton, DiffOptions, HiddenFiles};
+use crate::ui::icons::IconSet;
+use crate::ui::theme::Theme;
+use chrono::TimeDelta;
+use serde::Deserialize;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+const CONFIG_FILE: &str = ".config/audit-box/config.toml";
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub diff: DiffOptions,
+    pub ui: UiConfig,
+    pub run: RunConfig,
+    pub session: SessionConfig,
+    pub apply: ApplyConfig,
+    pub hashes: HashesConfig,
+    pub watch: WatchConfig,
+    pub identity: IdentityConfig,
+    /// External commands run on each changed file, as `[[checks]]` tables
+    pub checks: Vec<CheckConfig>,
+    /// Formatters both sides of a diff are run through first, by file
+    /// extension, as `[[normalizers]]` tables
+    pub normalizers: Vec<NormalizerConfig>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct UiConfig {
+    pub theme: Theme,
+    /// Symbols before file list entries: "ascii", "unicode" or "nerd-font"
+    pub icons: IconSet,
+    pub keymap: Keymap,
+    /// Ask before quitting the review with selected, staged or unreviewed files
+    pub confirm_quit: bool,
+    /// Hide overlay paths the base's .gitignore files ignore, until shown with I
+    pub gitignore: bool,
+    /// Whether dotfiles and hidden directories are shown, hidden or listed last
+    pub hidden_files: HiddenFiles,
+    /// File name patterns of temporary files, listed only once they settle
+    pub transient_files: Vec<String>,
+    /// Sum up new overlay activity in a desktop notification while the
+    /// review's terminal is not focused
+    pub desktop_notifications: bool,
+    /// Columns between tab stops in the content pane
+    pub tab_width: usize,
+    /// The button each dialog highlights when it opens
+    pub dialog_defaults: DialogDefaults,
+    /// Most times a second the screen is drawn, however fast things change
+    pub max_fps: u32,
+    /// Text markers in place of colour-only signals and symbols, no emoji,
+    /// and the terminal cursor kept on the highlighted row for screen readers
+    pub accessible: bool,
+    /// Files written no more than this many seconds apart are suggested as
+    /// one changeset
+    pub changeset_gap_secs: u64,
+}
+
+impl Default for UiConfig {
+    fn default() -> Self {
+        UiConfig {
+            theme: Theme::default(),
+            icons: IconSet::default(),
+            keymap: Keymap::default(),
+            confirm_quit: true,
+            gitignore: false,
+            hidden_files: HiddenFiles::default(),
+            transient_files: ["*~", ".#*", "*.swp", ".tmp*"].map(String::from).to_vec(),
+            desktop_notifications: false,
+            tab_width: 8,
+            dialog_defaults: DialogDefaults::default(),
+            max_fps: 30,
+            accessible: false,
+            changeset_gap_secs: 3,
+        }
+    }
+}
+
+impl UiConfig {
+    /// How far apart writes may be to be suggested as one changeset
+    pub fn changeset_gap(&self) -> io::Result<TimeDelta> {
+        i64::try_from(self.changeset_gap_secs).ok().and_then(TimeDelta::try_seconds).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("ui.changeset_gap_secs = {} is too large", self.changeset_gap_secs),
+            )
+        })
+    }
+}
+
+/// The button Enter picks in each dialog until another is chosen, "cancel"
+/// or "ok"; "ok" in the quit dialog is Quit
+#[derive(Debug, Default, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct DialogDefaults {
+    pub apply: DialogButton,
+    pub discard: DialogButton,
+    pub quit: DialogButton,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct RunConfig {
+    /// Sandbox profile used by `run` unless overridden with --profile
+    pub profile: SandboxProfile,
+    /// Record which processes write each overlay file, as with --attribute
+    pub attribution: bool,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct ApplyConfig {
+    /// Refuse to apply while the sandbox is still running, rather than warn
+    pub block_while_running: bool,
+    /// URL the JSON record of each apply is POSTed to
+    pub webhook: Option<String>,
+    /// Addresses the record of each apply is mailed to with sendmail
+    pub mail_to: Vec<String>,
+    /// sendmail program to mail with, instead of the one on the PATH
+    pub sendmail: Option<PathBuf>,
+    /// Base subtrees apply may write to; anywhere in the base if empty
+    pub allowed_roots: Vec<PathBuf>,
+    /// Commands that must pass on a scratch copy of the base with the
+    /// changes applied before they are applied for real, as
+    /// `[[apply.validate]]` tables
+    pub validate: Vec<ValidateConfig>,
+    /// Files copied at once when applying; one at a time if 0 or 1
+    pub workers: usize,
+    /// Services whose files, once applied, they are offered a reload for,
+    /// as `[[apply.services]]` tables
+    pub services: Vec<ServiceConfig>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ValidateConfig {
+    pub name: String,
+    /// The base subtree copied to the scratch directory, relative to the
+    /// overlay root like the paths in the review; never a whole base. The
+    /// command only runs when an apply touches it.
+    pub root: PathBuf,
+    /// Program and arguments, run in the scratch copy; `{scratch}` stands
+    /// for its path
+    pub command: Vec<String>,
+    /// Seconds before a command that has not finished is stopped, failing
+    #[serde(default = "default_validate_timeout")]
+    pub timeout_secs: u64,
+}
+
+fn default_validate_timeout() -> u64 {
+    120
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ServiceConfig {
+    /// The systemd unit, e.g. "nginx"
+    pub name: String,
+    /// Patterns of the paths belonging to it, relative to the overlay root
+    pub files: Vec<String>,
+    /// Program and arguments that make it pick its files up;
+    /// `systemctl reload NAME` if empty
+    #[serde(default)]
+    pub command: Vec<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct HashesConfig {
+    /// Files listing the SHA-256 of content known to be fine
+    pub allowlist: Vec<PathBuf>,
+    /// Files listing the SHA-256 of content that must never be applied
+    pub denylist: Vec<PathBuf>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct CheckConfig {
+    pub name: String,
+    /// Program and arguments; `{overlay}` and `{base}` stand for the file in
+    /// the overlay and where it would be applied
+    pub command: Vec<String>,
+    /// Patterns of the paths to check, relative to the overlay root; all
+    /// files if empty
+    #[serde(default)]
+    pub files: Vec<String>,
+    /// Seconds before a check that has not finished is stopped
+    #[serde(default = "default_check_timeout")]
+    pub timeout_secs: u64,
+}
+
+fn default_check_timeout() -> u64 {
+    30
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct NormalizerConfig {
+    /// File name extensions it applies to, without the dot, e.g. "json"
+    pub extensions: Vec<String>,
+    /// Program and arguments, given the file on standard input and writing
+    /// the normalized text to standard output; `{file}` stands for the path
+    /// of a copy of the file, with the same extension, in a directory of
+    /// its own
+    pub command: Vec<String>,
+    /// Seconds before a normalizer that has not finished is stopped, and the
+    /// raw diff shown instead
+    #[serde(default = "default_normalizer_timeout")]
+    pub timeout_secs: u64,
+}
+
+impl NormalizerConfig {
+    /// Whether it applies to the file at `path`
+    pub fn matches(&self, path: &Path) -> bool {
+        path.extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|extension| self.extensions.iter().any(|e| e.eq_ignore_ascii_case(extension)))
+    }
+}
+
+fn default_normalizer_timeout() -> u64 {
+    10
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct SessionConfig {
+    /// Where `new` creates session directories unless overridden with --workdir
+    pub workdir: Option<PathBuf>,
+    /// Warn once the overlay uses more than this many MiB; 0 disables the warning
+    pub size_warning_mb: u64,
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        SessionConfig {
+            workdir: None,
+            size_warning_mb: 1024,
+        }
+    }
+}
+
+impl SessionConfig {
+    /// Overlay size warning threshold in bytes
+    pub fn size_warning(&self) -> Option<u64> {
+        (self.size_warning_mb > 0).then(|| self.size_warning_mb.saturating_mul(1024 * 1024))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct WatchConfig {
+    /// Seconds between scans of the overlay when inotify's limits leave it
+    /// to be polled
+    pub poll_interval_secs: u64,
+}
+
+impl Default for WatchConfig {
+    fn default() -> Self {
+        WatchConfig { poll_interval_secs: 2 }
+    }
+}
+
+impl WatchConfig {
+    pub fn poll_interval(&self) -> Duration {
+        Duration::from_secs(self.poll_interval_secs.max(1))
+    }
+}
+
+/// Who decisions are recorded as made by, when the login name and host
+/// do not say
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct IdentityConfig {
+    pub name: Option<String>,
+    pub email: Option<String>,
+}
+
+impl IdentityConfig {
+    pub fn identity(&self) -> Identity {
+        Identity::new(self.name.as_deref(), self.email.as_deref())
+    }
+}
+
+pub fn get_config_file_path() -> io::Result<PathBuf> {
+    let home = dirs::home_dir()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "Home directory not found"))?;
+    Ok(home.join(CONFIG_FILE))
+}
+
+pub fn load_config() -> io::Result<Config> {
+    let config_path = get_config_file_path()?;
+
+    // A missing config file just means defaults
+    if !config_path.exists() {
+        return Ok(Config::default());
+    }
+
+    let content = fs::read_to_string(&config_path)?;
+    let config: Config = toml::from_str(&content).map_err(|e| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Failed to parse config file '{}': {}", config_path.display(), e),
+        )
+    })?;
+    config.ui.changeset_gap().map_err(|e| {
+        io::Error::new(e.kind(), format!("Invalid config file '{}': {}", config_path.display(), e))
+    })?;
+    Ok(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn changeset_gap_rejects_values_too_large() {
+        let ui = UiConfig { changeset_gap_secs: u64::MAX, ..UiConfig::default() };
+        assert!(ui.changeset_gap().is_err());
+        let ui = UiConfig { changeset_gap_secs: 3, ..UiConfig::default() };
+        assert_eq!(ui.changeset_gap().unwrap(), TimeDelta::seconds(3));
+    }
+}
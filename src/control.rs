@@ -0,0 +1,234 @@
+use crate::app::{describe_entries, App};
+use crate::report;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread;
+use std::time::Duration;
+
+// JSON-RPC 2.0 error codes
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+/// The request was understood, but applying, discarding or reading failed
+const FAILED: i64 = -32000;
+
+/// A call from a client, waiting for the review to answer it
+pub struct Call {
+    method: String,
+    params: Value,
+    reply: Sender<Result<Value, (i64, String)>>,
+}
+
+/// Lets editors and other tools drive the review over a Unix domain socket,
+/// with one JSON-RPC 2.0 request per line. Calls are answered by the review
+/// between key presses, so they see and change the same session.
+pub struct ControlSocket {
+    path: PathBuf,
+    calls: Receiver<Call>,
+}
+
+impl ControlSocket {
+    /// Listen on a socket only the current user can connect to. A socket
+    /// left behind by a review that crashed is replaced; anything else at
+    /// `path` is left alone.
+    pub fn listen(path: &Path) -> io::Result<ControlSocket> {
+        use std::os::unix::fs::FileTypeExt;
+
+        if let Ok(metadata) = fs::symlink_metadata(path) {
+            if !metadata.file_type().is_socket() {
+                return Err(io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    format!("'{}' exists and is not a socket", path.display()),
+                ));
+            }
+            if UnixStream::connect(path).is_ok() {
+                return Err(io::Error::new(
+                    io::ErrorKind::AddrInUse,
+                    format!("Another audit-box is listening on '{}'", path.display()),
+                ));
+            }
+            fs::remove_file(path)?;
+        }
+        // Created without access for anyone else, rather than made private
+        // after it is already accepting connections
+        // SAFETY: umask cannot fail
+        let umask = unsafe { libc::umask(0o177) };
+        let bound = UnixListener::bind(path);
+        // SAFETY: as above
+        unsafe { libc::umask(umask) };
+        let listener =
+            bound.map_err(|e| io::Error::new(e.kind(), format!("Failed to listen on '{}': {}", path.display(), e)))?;
+        fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+
+        let (calls_tx, calls) = channel();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let calls = calls_tx.clone();
+                thread::spawn(move || serve(stream, calls));
+            }
+        });
+        Ok(ControlSocket {
+            path: path.to_path_buf(),
+            calls,
+        })
+    }
+
+    /// Answer the calls that arrived since the last time, without waiting
+    pub fn answer(&self, app: &mut App) {
+        for call in self.calls.try_iter() {
+            call.answer(app);
+            app.request_redraw();
+        }
+    }
+
+    /// Wait up to `timeout` for the next call
+    pub fn next_call(&self, timeout: Duration) -> Option<Call> {
+        self.calls.recv_timeout(timeout).ok()
+    }
+}
+
+impl Call {
+    pub fn method(&self) -> &str {
+        &self.method
+    }
+
+    /// Answer with what the review makes of the call
+    pub fn answer(self, app: &mut App) {
+        let result = dispatch(app, &self.method, self.params);
+        // The client may have gone away meanwhile
+        let _ = self.reply.send(result);
+    }
+
+    pub fn reply(self, result: Value) {
+        let _ = self.reply.send(Ok(result));
+    }
+
+    pub fn fail(self, message: impl Into<String>) {
+        let _ = self.reply.send(Err((FAILED, message.into())));
+    }
+}
+
+/// Make one call to a socket and wait for the result
+pub fn request(socket: &Path, method: &str) -> io::Result<Value> {
+    let mut stream = UnixStream::connect(socket)?;
+    writeln!(stream, "{}", json!({"jsonrpc": "2.0", "id": 1, "method": method}))?;
+    let mut line = String::new();
+    BufReader::new(stream).read_line(&mut line)?;
+    let mut response: Value = serde_json::from_str(&line)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("Invalid response from '{}': {}", socket.display(), e)))?;
+    if let Some(message) = response.pointer("/error/message").and_then(Value::as_str) {
+        return Err(io::Error::other(message.to_string()));
+    }
+    Ok(response["result"].take())
+}
+
+impl Drop for ControlSocket {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Read requests from one client and write back the answers, in order
+fn serve(stream: UnixStream, calls: Sender<Call>) {
+    let Ok(mut writer) = stream.try_clone() else {
+        return;
+    };
+    for line in BufReader::new(stream).lines() {
+        let Ok(line) = line else {
+            return;
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = match serde_json::from_str::<Value>(&line) {
+            Ok(request) => call(request, &calls),
+            Err(e) => Some(error(Value::Null, PARSE_ERROR, format!("Parse error: {}", e))),
+        };
+        // Notifications, without an id, get no answer
+        if let Some(response) = response
+            && writeln!(writer, "{}", response).is_err()
+        {
+            return;
+        }
+    }
+}
+
+/// Pass one request on to the review and wait for its answer
+fn call(request: Value, calls: &Sender<Call>) -> Option<Value> {
+    let id = request.get("id").cloned();
+    let Some(method) = request.get("method").and_then(Value::as_str) else {
+        return Some(error(id.unwrap_or(Value::Null), INVALID_REQUEST, "Invalid request: no method".to_string()));
+    };
+    let (reply, answer) = channel();
+    let sent = calls.send(Call {
+        method: method.to_string(),
+        params: request.get("params").cloned().unwrap_or(Value::Null),
+        reply,
+    });
+    let result = match sent.ok().and_then(|_| answer.recv().ok()) {
+        Some(result) => result,
+        None => Err((FAILED, "The review has ended".to_string())),
+    };
+    let id = id?;
+    Some(match result {
+        Ok(result) => json!({"jsonrpc": "2.0", "id": id, "result": result}),
+        Err((code, message)) => error(id, code, message),
+    })
+}
+
+fn error(id: Value, code: i64, message: String) -> Value {
+    json!({"jsonrpc": "2.0", "id": id, "error": {"code": code, "message": message}})
+}
+
+#[derive(Deserialize)]
+struct PathParams {
+    path: PathBuf,
+}
+
+#[derive(Deserialize)]
+struct PathsParams {
+    paths: Vec<PathBuf>,
+}
+
+fn params<T: for<'de> Deserialize<'de>>(params: Value) -> Result<T, (i64, String)> {
+    serde_json::from_value(params).map_err(|e| (INVALID_PARAMS, format!("Invalid params: {}", e)))
+}
+
+/// Answer one call. Paths are relative to the overlay root, as `list`
+/// gives them.
+fn dispatch(app: &mut App, method: &str, params_value: Value) -> Result<Value, (i64, String)> {
+    let failed = |e: io::Error| (FAILED, e.to_string());
+    match method {
+        // The changes, as `audit-box list --format json` gives them
+        "list" => {
+            let report = report::build_report(&app.files, &app.overlay_path, &app.lower, false);
+            serde_json::to_value(report).map_err(|e| (FAILED, e.to_string()))
+        }
+        // The diff of a modified file, or the content of a new one
+        "diff" => {
+            let PathParams { path } = params(params_value)?;
+            let lines = app.content_of(&path).map_err(failed)?;
+            Ok(json!({"path": path, "lines": lines}))
+        }
+        "apply" => {
+            let PathsParams { paths } = params(params_value)?;
+            let applied = app.apply_paths(&paths).map_err(failed)?;
+            app.show_toast(format!("Applied {} for a control socket client", describe_entries(applied.len())));
+            Ok(json!({"applied": applied}))
+        }
+        "discard" => {
+            let PathsParams { paths } = params(params_value)?;
+            app.discard_paths(&paths).map_err(failed)?;
+            app.show_toast(format!("Discarded {} for a control socket client", describe_entries(paths.len())));
+            Ok(json!({"discarded": paths}))
+        }
+        _ => Err((METHOD_NOT_FOUND, format!("Method not found: {}", method))),
+    }
+}
@@ -0,0 +1,233 @@
+use crate::app::App;
+use crate::config::{self, Config};
+use crate::control::{self, ControlSocket};
+use crate::lock::SessionLock;
+use crate::types::LowerView;
+use crate::watch::OverlayWatcher;
+use serde_json::json;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::channel;
+use std::time::{Duration, Instant};
+
+/// File name of the daemon's control socket, in the session directory
+pub const SOCKET: &str = "daemon.sock";
+
+/// How often the review state is written back while nothing else happens
+const SAVE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Set by SIGINT, SIGTERM and SIGHUP, so the daemon can save and clean up
+static STOP_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn request_stop(_signal: libc::c_int) {
+    STOP_REQUESTED.store(true, Ordering::Relaxed);
+}
+
+/// The session as the daemon holds it while no review is attached
+struct Held {
+    app: App,
+    _watcher: OverlayWatcher,
+    _lock: SessionLock,
+}
+
+/// Supervise a session: run the sandbox, keep watching the overlay and
+/// serve the control socket, until stopped with a signal. A `review` of
+/// the session attaches to the daemon, which hands the session over until
+/// the review ends. Errors along the way are logged and the daemon goes
+/// on; one it cannot go on from still stops the sandbox and saves the
+/// review state before it is returned.
+pub fn run_daemon(
+    overlay_path: &Path,
+    lower: LowerView,
+    session_dir: &Path,
+    run_args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let config = config::load_config()?;
+    let mut held = Some(hold(overlay_path, &lower, session_dir, &config)?);
+    let socket_path = session_dir.join(SOCKET);
+    let socket = ControlSocket::listen(&socket_path).map_err(|e| e.to_string())?;
+
+    // SAFETY: the handler only stores to an atomic, which is async-signal-safe
+    unsafe {
+        for signal in [libc::SIGINT, libc::SIGTERM, libc::SIGHUP] {
+            libc::signal(signal, request_stop as *const () as libc::sighandler_t);
+        }
+    }
+
+    // The sandbox is started through `run`, so it is recorded like any other
+    let mut sandbox = if run_args.is_empty() {
+        None
+    } else {
+        Some(Command::new(std::env::current_exe()?).arg("run").args(&run_args).spawn()?)
+    };
+    let mut sandbox_status = None;
+
+    eprintln!("Listening on {}", socket_path.display());
+    let mut last_save = Instant::now();
+    let mut fatal = None;
+    while !STOP_REQUESTED.load(Ordering::Relaxed) {
+        if let Some(held) = &mut held {
+            held.app.check_fs_events();
+            held.app.process_pending_updates();
+            held.app.receive_check_results();
+            held.app.receive_hashes();
+            held.app.check_apply_hooks();
+            // Nobody to ask whether to run them
+            for action in &held.app.pending_reloads {
+                eprintln!("Not run: {} (for {})", action.command_line(), action.because.display());
+            }
+            if let Err(e) = held.app.skip_reloads() {
+                eprintln!("Warning: {}", e);
+            }
+            if last_save.elapsed() >= SAVE_INTERVAL {
+                if let Err(e) = held.app.save_progress() {
+                    eprintln!("Warning: failed to save the review state: {}", e);
+                }
+                last_save = Instant::now();
+            }
+        }
+
+        if let Some(child) = &mut sandbox {
+            match child.try_wait() {
+                Ok(Some(status)) => {
+                    eprintln!("Sandbox exited: {}", status);
+                    sandbox_status = Some(status);
+                    sandbox = None;
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    fatal = Some(e);
+                    break;
+                }
+            }
+        }
+
+        let Some(call) = socket.next_call(Duration::from_millis(100)) else {
+            continue;
+        };
+        match call.method() {
+            // A review takes the session over, with everything saved for it
+            "attach" => {
+                // Kept until it is saved, so the review does not start from
+                // an older state
+                if let Some(session) = &mut held
+                    && let Err(e) = session.app.save_progress()
+                {
+                    call.fail(format!("Failed to save the review state: {}", e));
+                    continue;
+                }
+                if let Some(mut held) = held.take() {
+                    for failure in held.app.wait_for_apply_hooks() {
+                        eprintln!("Warning: {}", failure);
+                    }
+                }
+                call.reply(json!({"session": session_dir}));
+            }
+            // The review ended and saved its state; take the session back
+            "detach" => {
+                if held.is_none() {
+                    match hold(overlay_path, &lower, session_dir, &config) {
+                        Ok(session) => held = Some(session),
+                        Err(e) => {
+                            call.fail(e.to_string());
+                            continue;
+                        }
+                    }
+                }
+                call.reply(json!({"session": session_dir}));
+            }
+            "status" => {
+                let sandbox = match (&sandbox, sandbox_status) {
+                    (Some(child), _) => json!({"running": true, "pid": child.id()}),
+                    (None, Some(status)) => json!({"running": false, "exit_code": status.code()}),
+                    (None, None) => json!(null),
+                };
+                call.reply(json!({"session": session_dir, "attached": held.is_none(), "sandbox": sandbox}));
+            }
+            _ => match &mut held {
+                Some(held) => call.answer(&mut held.app),
+                None => call.fail("A review is attached to the session; use its own --listen socket"),
+            },
+        }
+    }
+
+    // However the loop ended, the sandbox is stopped and the state saved
+    let stopped = sandbox.as_mut().map_or(Ok(()), stop);
+    let saved = match held {
+        Some(mut held) => {
+            let saved = held.app.save_progress();
+            for failure in held.app.wait_for_apply_hooks() {
+                eprintln!("Warning: {}", failure);
+            }
+            saved
+        }
+        None => Ok(()),
+    };
+    fatal.map_or(Ok(()), Err).and(stopped).and(saved)?;
+    Ok(())
+}
+
+/// Lock the session and start watching it
+fn hold(overlay_path: &Path, lower: &LowerView, session_dir: &Path, config: &Config) -> io::Result<Held> {
+    let lock = match SessionLock::acquire(session_dir)? {
+        Ok(lock) => lock,
+        Err(pid) => {
+            return Err(io::Error::new(
+                io::ErrorKind::WouldBlock,
+                format!("Session is already being reviewed (pid {})", pid),
+            ));
+        }
+    };
+    let (tx, rx) = channel();
+    let (watcher, warning) = OverlayWatcher::watch(overlay_path, tx, config.watch.poll_interval())?;
+    if let Some(warning) = warning {
+        eprintln!("Warning: {}", warning);
+    }
+    let app = App::new(overlay_path, lower.clone(), rx, config, Some(session_dir.to_path_buf()))?;
+    Ok(Held {
+        app,
+        _watcher: watcher,
+        _lock: lock,
+    })
+}
+
+/// Ask the sandbox to finish and wait for it. `run` passes SIGTERM on to
+/// bwrap, waits for it and records how the run ended before it exits.
+fn stop(child: &mut Child) -> io::Result<()> {
+    if let Ok(pid) = libc::pid_t::try_from(child.id()) {
+        // SAFETY: the pid is our own child, which has not been reaped yet
+        unsafe {
+            libc::kill(pid, libc::SIGTERM);
+        }
+    }
+    let status = child.wait()?;
+    eprintln!("Sandbox exited: {}", status);
+    Ok(())
+}
+
+/// A review's hold on a session the daemon supervises. The session goes
+/// back to the daemon when dropped.
+pub struct Attachment {
+    socket: PathBuf,
+}
+
+/// Take the session over from its daemon, if one is running
+pub fn attach(session_dir: &Path) -> io::Result<Option<Attachment>> {
+    let socket = session_dir.join(SOCKET);
+    match control::request(&socket, "attach") {
+        Ok(_) => Ok(Some(Attachment { socket })),
+        // No daemon, or one that crashed and left its socket behind
+        Err(e) if matches!(e.kind(), io::ErrorKind::NotFound | io::ErrorKind::ConnectionRefused) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+impl Drop for Attachment {
+    fn drop(&mut self) {
+        if let Err(e) = control::request(&self.socket, "detach") {
+            eprintln!("Warning: failed to hand the session back to the daemon: {}", e);
+        }
+    }
+}
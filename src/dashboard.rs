@@ -0,0 +1,104 @@
+use crate::report::Report;
+use crate::types::FileStatus;
+use chrono::{DateTime, Local};
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Component, PathBuf};
+
+/// How many files the largest and most-changed lists show
+const TOP_FILES: usize = 10;
+
+/// Overview of a session's changes, built from a report with stats
+#[derive(Debug, Default)]
+pub struct Dashboard {
+    pub new_files: usize,
+    pub modified_files: usize,
+    pub deleted_files: usize,
+    pub directories: usize,
+    pub total_size: u64,
+    /// Files and bytes per top-level directory, largest first
+    pub by_dir: Vec<(String, usize, u64)>,
+    pub largest: Vec<(PathBuf, u64)>,
+    /// Files with the most lines added and removed
+    pub most_changed: Vec<(PathBuf, usize, usize)>,
+    /// Modification times of the changed files
+    pub mtimes: Vec<DateTime<Local>>,
+}
+
+pub fn build(report: &Report) -> Dashboard {
+    let mut dashboard = Dashboard::default();
+    let mut by_dir: BTreeMap<String, (usize, u64)> = BTreeMap::new();
+
+    for entry in &report.entries {
+        if entry.is_dir {
+            dashboard.directories += 1;
+            continue;
+        }
+        match entry.status {
+            FileStatus::New => dashboard.new_files += 1,
+            FileStatus::Modified | FileStatus::TypeChanged => dashboard.modified_files += 1,
+            FileStatus::Deleted => dashboard.deleted_files += 1,
+        }
+
+        let size = entry.stats.as_ref().map_or(0, |s| s.size);
+        dashboard.total_size += size;
+
+        let dir = match entry.path.components().next() {
+            Some(Component::Normal(name)) if entry.depth > 0 => name.to_string_lossy().into_owned(),
+            _ => "(top level)".to_string(),
+        };
+        let totals = by_dir.entry(dir).or_default();
+        totals.0 += 1;
+        totals.1 += size;
+
+        if let Some(stats) = &entry.stats {
+            dashboard.largest.push((entry.path.clone(), stats.size));
+            dashboard.most_changed.push((entry.path.clone(), stats.added, stats.removed));
+        }
+        if let Ok(modified) = fs::symlink_metadata(report.overlay.join(&entry.path)).and_then(|m| m.modified()) {
+            dashboard.mtimes.push(DateTime::<Local>::from(modified));
+        }
+    }
+
+    dashboard.by_dir = by_dir.into_iter().map(|(dir, (count, size))| (dir, count, size)).collect();
+    dashboard.by_dir.sort_by_key(|(_, _, size)| Reverse(*size));
+
+    dashboard.largest.sort_by_key(|(_, size)| Reverse(*size));
+    dashboard.largest.truncate(TOP_FILES);
+
+    dashboard.most_changed.retain(|(_, added, removed)| added + removed > 0);
+    dashboard.most_changed.sort_by_key(|(_, added, removed)| Reverse(added + removed));
+    dashboard.most_changed.truncate(TOP_FILES);
+
+    dashboard
+}
+
+impl Dashboard {
+    /// Count the modification times into `buckets` equal slices of the span
+    /// they cover, returning the start of each slice and its count
+    pub fn timeline(&self, buckets: usize) -> Vec<(DateTime<Local>, usize)> {
+        let (Some(first), Some(last)) = (self.mtimes.iter().min(), self.mtimes.iter().max()) else {
+            return Vec::new();
+        };
+
+        let span = (*last - *first).num_milliseconds();
+        if span == 0 || buckets < 2 {
+            return vec![(*first, self.mtimes.len())];
+        }
+
+        let step = span as f64 / buckets as f64;
+        let mut counts = vec![0; buckets];
+        for mtime in &self.mtimes {
+            let offset = (*mtime - *first).num_milliseconds() as f64;
+            let bucket = ((offset / step) as usize).min(buckets - 1);
+            counts[bucket] += 1;
+        }
+
+        counts
+            .into_iter()
+            .enumerate()
+            .map(|(i, count)| (*first + chrono::Duration::milliseconds((step * i as f64) as i64), count))
+            .collect()
+    }
+}
@@ -0,0 +1,99 @@
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+/// Notifications are sent at most this often, so a busy sandbox does not
+/// flood the desktop
+const MIN_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Gathers the overlay activity seen while the review is not being looked
+/// at and sums it up in a desktop notification now and then
+#[derive(Default)]
+pub struct Notifier {
+    created: HashSet<PathBuf>,
+    modified: HashSet<PathBuf>,
+    removed: HashSet<PathBuf>,
+    last_sent: Option<Instant>,
+}
+
+impl Notifier {
+    /// Whether notifications can be shown at all, i.e. audit-box was built
+    /// with the desktop-notifications feature
+    pub fn is_supported() -> bool {
+        cfg!(feature = "desktop-notifications")
+    }
+
+    pub fn created(&mut self, path: PathBuf) {
+        self.removed.remove(&path);
+        self.created.insert(path);
+    }
+
+    pub fn modified(&mut self, path: PathBuf) {
+        if !self.created.contains(&path) {
+            self.modified.insert(path);
+        }
+    }
+
+    pub fn removed(&mut self, path: PathBuf) {
+        // A file created and removed again in between is not worth mentioning
+        if !self.created.remove(&path) {
+            self.modified.remove(&path);
+            self.removed.insert(path);
+        }
+    }
+
+    /// Forget the activity gathered so far, e.g. once the review is looked at again
+    pub fn clear(&mut self) {
+        self.created.clear();
+        self.modified.clear();
+        self.removed.clear();
+    }
+
+    /// Send the summary of the activity gathered so far, unless there is
+    /// none or the last notification was too recent
+    pub fn flush(&mut self, session: &str) {
+        if self.created.is_empty() && self.modified.is_empty() && self.removed.is_empty() {
+            return;
+        }
+        if self.last_sent.is_some_and(|sent| sent.elapsed() < MIN_INTERVAL) {
+            return;
+        }
+        let summary = self.summary();
+        self.clear();
+        self.last_sent = Some(Instant::now());
+        show(&format!("audit-box: {}", session), &summary);
+    }
+
+    /// e.g. "3 new and 1 changed file in the overlay"
+    fn summary(&self) -> String {
+        let counts: Vec<String> = [
+            (self.created.len(), "new"),
+            (self.modified.len(), "changed"),
+            (self.removed.len(), "removed"),
+        ]
+        .into_iter()
+        .filter(|(count, _)| *count > 0)
+        .map(|(count, what)| format!("{} {}", count, what))
+        .collect();
+        let total = self.created.len() + self.modified.len() + self.removed.len();
+        let counts = match counts.split_last() {
+            Some((last, [])) => last.clone(),
+            Some((last, rest)) => format!("{} and {}", rest.join(", "), last),
+            None => String::new(),
+        };
+        format!("{} file{} in the overlay", counts, if total == 1 { "" } else { "s" })
+    }
+}
+
+#[cfg(feature = "desktop-notifications")]
+fn show(summary: &str, body: &str) {
+    // A desktop without a notification daemon is not worth interrupting the review for
+    let _ = notify_rust::Notification::new()
+        .appname("audit-box")
+        .summary(summary)
+        .body(body)
+        .show();
+}
+
+#[cfg(not(feature = "desktop-notifications"))]
+fn show(_summary: &str, _body: &str) {}
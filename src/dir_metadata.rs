@@ -0,0 +1,185 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+
+/// The only namespace of extended attributes compared and applied. Others
+/// belong to the system, e.g. security.selinux labels, which the sandbox's
+/// copy must not replace or strip on the base's directories.
+#[cfg(target_os = "linux")]
+const USER_XATTR_PREFIX: &str = "user.";
+
+/// Extended attributes overlayfs keeps on its own directories, which say
+/// nothing about the directory itself
+#[cfg(target_os = "linux")]
+const OVERLAY_XATTR_PREFIX: &str = "user.overlay.";
+
+/// Where file capabilities are kept
+const CAPABILITY_XATTR: &str = "security.capability";
+
+/// How a directory copied up into the overlay differs from the base's, e.g.
+/// "mode 755 → 700" or "xattr user.comment changed". Ownership is left out,
+/// as copy-up in the sandbox's user namespace changes it anyway, and so are
+/// xattrs outside the user namespace.
+pub fn changes(overlay_dir: &Path, base_dir: &Path) -> Vec<String> {
+    let (Ok(overlay), Ok(base)) = (fs::metadata(overlay_dir), fs::metadata(base_dir)) else {
+        return Vec::new();
+    };
+    let mut changes = Vec::new();
+    let (overlay_mode, base_mode) = (overlay.permissions().mode() & 0o7777, base.permissions().mode() & 0o7777);
+    if overlay_mode != base_mode {
+        changes.push(format!("mode {:o} → {:o}", base_mode, overlay_mode));
+    }
+
+    let overlay_xattrs = xattrs(overlay_dir);
+    let base_xattrs = xattrs(base_dir);
+    for (name, value) in &overlay_xattrs {
+        match base_xattrs.get(name) {
+            None => changes.push(format!("xattr {} added", name)),
+            Some(base_value) if base_value != value => changes.push(format!("xattr {} changed", name)),
+            Some(_) => {}
+        }
+    }
+    for name in base_xattrs.keys().filter(|name| !overlay_xattrs.contains_key(*name)) {
+        changes.push(format!("xattr {} removed", name));
+    }
+    changes
+}
+
+/// Give the base directory the overlay directory's mode and user extended
+/// attributes, leaving its contents and other xattrs alone
+pub fn apply(overlay_dir: &Path, base_dir: &Path) -> io::Result<()> {
+    let overlay_xattrs = xattrs(overlay_dir);
+    let base_xattrs = xattrs(base_dir);
+    for (name, value) in &overlay_xattrs {
+        if base_xattrs.get(name) != Some(value) {
+            set_xattr(base_dir, name, value)?;
+        }
+    }
+    for name in base_xattrs.keys().filter(|name| !overlay_xattrs.contains_key(*name)) {
+        remove_xattr(base_dir, name)?;
+    }
+    // Last, in case the new mode takes away our own write access
+    fs::set_permissions(base_dir, fs::metadata(overlay_dir)?.permissions())
+}
+
+/// Give a copied file the source's user extended attributes and file
+/// capabilities, so it keeps the privileges it was reviewed with. Setting
+/// capabilities takes CAP_SETFCAP, and failing to is an error rather than
+/// a file quietly applied without them.
+pub fn copy_file_xattrs(source: &Path, dest_path: &Path) -> io::Result<()> {
+    for (name, value) in xattrs_matching(source, |name| name == CAPABILITY_XATTR || is_user_xattr(name)) {
+        set_xattr(dest_path, &name, &value)?;
+    }
+    Ok(())
+}
+
+/// A directory's user extended attributes, other than overlayfs's own
+fn xattrs(path: &Path) -> BTreeMap<String, Vec<u8>> {
+    xattrs_matching(path, is_user_xattr)
+}
+
+#[cfg(target_os = "linux")]
+fn is_user_xattr(name: &str) -> bool {
+    name.starts_with(USER_XATTR_PREFIX) && !name.starts_with(OVERLAY_XATTR_PREFIX)
+}
+
+#[cfg(target_os = "linux")]
+fn xattrs_matching(path: &Path, keep: impl Fn(&str) -> bool) -> BTreeMap<String, Vec<u8>> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let mut xattrs = BTreeMap::new();
+    let Ok(c_path) = CString::new(path.as_os_str().as_bytes()) else {
+        return xattrs;
+    };
+    // SAFETY: c_path is NUL-terminated; a null buffer of size 0 only asks for the length
+    let len = unsafe { libc::llistxattr(c_path.as_ptr(), std::ptr::null_mut(), 0) };
+    if len <= 0 {
+        return xattrs;
+    }
+    let mut names = vec![0u8; len as usize];
+    // SAFETY: names is valid for writes of names.len() bytes
+    let len = unsafe { libc::llistxattr(c_path.as_ptr(), names.as_mut_ptr().cast(), names.len()) };
+    if len <= 0 {
+        return xattrs;
+    }
+    names.truncate(len as usize);
+
+    for name in names.split(|b| *b == 0).filter(|name| !name.is_empty()) {
+        let Ok(name_str) = std::str::from_utf8(name) else {
+            continue;
+        };
+        if !keep(name_str) {
+            continue;
+        }
+        let c_name = CString::new(name).unwrap();
+        // SAFETY: as above, asking for the value's length
+        let len = unsafe { libc::lgetxattr(c_path.as_ptr(), c_name.as_ptr(), std::ptr::null_mut(), 0) };
+        if len < 0 {
+            continue;
+        }
+        let mut value = vec![0u8; len as usize];
+        // SAFETY: value is valid for writes of value.len() bytes
+        let len = unsafe { libc::lgetxattr(c_path.as_ptr(), c_name.as_ptr(), value.as_mut_ptr().cast(), value.len()) };
+        if len < 0 {
+            continue;
+        }
+        value.truncate(len as usize);
+        xattrs.insert(name_str.to_string(), value);
+    }
+    xattrs
+}
+
+#[cfg(target_os = "linux")]
+fn set_xattr(path: &Path, name: &str, value: &[u8]) -> io::Result<()> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let c_path = CString::new(path.as_os_str().as_bytes()).map_err(io::Error::other)?;
+    let c_name = CString::new(name).map_err(io::Error::other)?;
+    // SAFETY: both strings are NUL-terminated and value is valid for value.len() bytes
+    let result = unsafe { libc::lsetxattr(c_path.as_ptr(), c_name.as_ptr(), value.as_ptr().cast(), value.len(), 0) };
+    if result < 0 {
+        let e = io::Error::last_os_error();
+        return Err(io::Error::new(e.kind(), format!("Failed to set {} on {}: {}", name, path.display(), e)));
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+fn remove_xattr(path: &Path, name: &str) -> io::Result<()> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let c_path = CString::new(path.as_os_str().as_bytes()).map_err(io::Error::other)?;
+    let c_name = CString::new(name).map_err(io::Error::other)?;
+    // SAFETY: both strings are NUL-terminated
+    if unsafe { libc::lremovexattr(c_path.as_ptr(), c_name.as_ptr()) } < 0 {
+        let e = io::Error::last_os_error();
+        return Err(io::Error::new(e.kind(), format!("Failed to remove {} from {}: {}", name, path.display(), e)));
+    }
+    Ok(())
+}
+
+/// Extended attributes are only compared on Linux
+#[cfg(not(target_os = "linux"))]
+fn xattrs_matching(_path: &Path, _keep: impl Fn(&str) -> bool) -> BTreeMap<String, Vec<u8>> {
+    BTreeMap::new()
+}
+
+#[cfg(not(target_os = "linux"))]
+fn is_user_xattr(_name: &str) -> bool {
+    false
+}
+
+#[cfg(not(target_os = "linux"))]
+fn set_xattr(_path: &Path, _name: &str, _value: &[u8]) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn remove_xattr(_path: &Path, _name: &str) -> io::Result<()> {
+    Ok(())
+}
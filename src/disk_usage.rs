@@ -0,0 +1,77 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+
+/// Disk space used by the files in an overlay. Sizes are allocated blocks
+/// rather than file lengths, since that is what fills up the filesystem.
+#[derive(Debug, Default)]
+pub struct DiskUsage {
+    sizes: BTreeMap<PathBuf, u64>,
+    total: u64,
+}
+
+impl DiskUsage {
+    pub fn scan(root: &Path) -> Self {
+        let mut usage = DiskUsage::default();
+        usage.add_tree(root);
+        usage
+    }
+
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    /// Re-measure a path after a filesystem event
+    pub fn update(&mut self, path: &Path) {
+        self.remove_tree(path);
+        self.add_tree(path);
+    }
+
+    fn add_tree(&mut self, path: &Path) {
+        let Ok(metadata) = fs::symlink_metadata(path) else {
+            return;
+        };
+
+        if metadata.is_dir() {
+            if let Ok(entries) = fs::read_dir(path) {
+                for entry in entries.flatten() {
+                    self.add_tree(&entry.path());
+                }
+            }
+        } else {
+            let size = metadata.blocks().saturating_mul(512);
+            self.total = self.total.saturating_add(size);
+            self.sizes.insert(path.to_path_buf(), size);
+        }
+    }
+
+    fn remove_tree(&mut self, path: &Path) {
+        let mut removed: u64 = 0;
+        self.sizes.retain(|p, size| {
+            let keep = !p.starts_with(path);
+            if !keep {
+                removed = removed.saturating_add(*size);
+            }
+            keep
+        });
+        self.total = self.total.saturating_sub(removed);
+    }
+}
+
+/// Human-readable size using binary units, e.g. "3.4 MiB"
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
@@ -0,0 +1,272 @@
+use goblin::container::{Ctx, Endian};
+use goblin::elf::dynamic::{Dynamic, DT_NEEDED};
+use goblin::elf::header::{machine_to_str, Header, ET_CORE, ET_DYN, ET_EXEC, ET_REL};
+use goblin::elf::note::NT_GNU_BUILD_ID;
+use goblin::elf::program_header::{ProgramHeader, PT_DYNAMIC, PT_INTERP, PT_NOTE};
+use goblin::elf::section_header::{SectionHeader, SHT_SYMTAB};
+use goblin::elf::Elf;
+use std::collections::BTreeSet;
+use std::fs::File;
+use std::io;
+use std::os::unix::fs::FileExt;
+use std::path::Path;
+
+/// Printable runs shorter than this are not looked at for URLs and addresses
+const MIN_STRING_LEN: usize = 7;
+
+/// URLs and addresses listed at most, in the order they appear
+const MAX_FINDINGS: usize = 40;
+
+/// The most of a binary searched for URLs and addresses, from its start
+const MAX_SCANNED_BYTES: u64 = 16 << 20;
+
+/// The largest table or segment read to summarize a binary; anything larger
+/// is taken to be corrupt
+const MAX_RANGE_BYTES: u64 = 16 << 20;
+
+/// What a binary is, what it loads and where it might connect to, for
+/// judging a new executable that cannot be reviewed as text. None if the
+/// file is not an ELF binary. Only the headers, and the tables and segments
+/// they point to, are read, besides the start of the file for its strings.
+pub fn summarize(path: &Path) -> Option<Vec<String>> {
+    let file = File::open(path).ok()?;
+    let size = file.metadata().ok()?.len();
+    let mut header_bytes = vec![0; 64];
+    let read = file.read_at(&mut header_bytes, 0).ok()?;
+    header_bytes.truncate(read);
+    if !header_bytes.starts_with(b"\x7fELF") {
+        return None;
+    }
+    match Binary::read(&file, &header_bytes, size) {
+        Ok(binary) => Some(binary.describe(&file, size)),
+        Err(e) => Some(vec![format!("<ELF binary that could not be parsed: {}>", e)]),
+    }
+}
+
+/// What the headers of a binary say about it
+struct Binary {
+    header: Header,
+    interpreter: Option<String>,
+    libraries: Vec<String>,
+    build_id: Option<String>,
+    has_symbols: bool,
+}
+
+impl Binary {
+    fn read(file: &File, header_bytes: &[u8], size: u64) -> Result<Binary, Box<dyn std::error::Error>> {
+        let header = Elf::parse_header(header_bytes)?;
+        let ctx = Ctx::new(header.container()?, header.endianness()?);
+        let phdr_bytes = read_range(file, size, header.e_phoff, u64::from(header.e_phnum) * u64::from(header.e_phentsize))?;
+        let phdrs = ProgramHeader::parse(&phdr_bytes, 0, usize::from(header.e_phnum), ctx)?;
+
+        let interpreter = match phdrs.iter().find(|phdr| phdr.p_type == PT_INTERP) {
+            Some(phdr) => {
+                let bytes = read_range(file, size, phdr.p_offset, phdr.p_filesz)?;
+                let end = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+                Some(String::from_utf8_lossy(&bytes[..end]).into_owned())
+            }
+            None => None,
+        };
+        let libraries = libraries(file, size, &phdrs, ctx)?;
+        let mut build_id = None;
+        for phdr in phdrs.iter().filter(|phdr| phdr.p_type == PT_NOTE) {
+            let notes = read_range(file, size, phdr.p_offset, phdr.p_filesz)?;
+            build_id = gnu_build_id(&notes, phdr.p_align, ctx.le);
+            if build_id.is_some() {
+                break;
+            }
+        }
+        let has_symbols = if header.e_shoff == 0 || header.e_shnum == 0 {
+            false
+        } else {
+            let shdr_bytes = read_range(file, size, header.e_shoff, u64::from(header.e_shnum) * u64::from(header.e_shentsize))?;
+            SectionHeader::parse_from(&shdr_bytes, 0, usize::from(header.e_shnum), ctx)?
+                .iter()
+                .any(|shdr| shdr.sh_type == SHT_SYMTAB)
+        };
+        Ok(Binary { header, interpreter, libraries, build_id, has_symbols })
+    }
+
+    fn describe(&self, file: &File, size: u64) -> Vec<String> {
+        let kind = match self.header.e_type {
+            ET_EXEC => "executable",
+            ET_DYN if self.interpreter.is_some() => "position-independent executable",
+            ET_DYN => "shared object",
+            ET_REL => "relocatable object",
+            ET_CORE => "core dump",
+            _ => "file",
+        };
+        let (is_64, little_endian) = (
+            self.header.container().is_ok_and(|container| container.is_big()),
+            self.header.endianness().is_ok_and(|endian| endian.is_little()),
+        );
+        let mut lines = vec![
+            "<ELF binary: summary instead of contents>".to_string(),
+            String::new(),
+            format!(
+                "Type:          {} ({}, {}-bit, {} endian)",
+                kind,
+                machine_to_str(self.header.e_machine),
+                if is_64 { 64 } else { 32 },
+                if little_endian { "little" } else { "big" }
+            ),
+            format!("Size:          {} bytes", size),
+            format!(
+                "Linking:       {}",
+                match &self.interpreter {
+                    Some(interpreter) => format!("dynamic, loaded by {}", interpreter),
+                    None if self.libraries.is_empty() => "static".to_string(),
+                    None => "dynamic".to_string(),
+                }
+            ),
+            format!("Build ID:      {}", self.build_id.as_deref().unwrap_or("none")),
+            format!("Stripped:      {}", if self.has_symbols { "no" } else { "yes, no symbol table" }),
+        ];
+
+        lines.push(String::new());
+        if self.libraries.is_empty() {
+            lines.push("Linked libraries: none".to_string());
+        } else {
+            lines.push("Linked libraries:".to_string());
+            lines.extend(self.libraries.iter().map(|library| format!("  {}", library)));
+        }
+
+        let mut start = vec![0; size.min(MAX_SCANNED_BYTES) as usize];
+        let findings = match file.read_exact_at(&mut start, 0) {
+            Ok(()) => network_strings(&start),
+            Err(_) => Vec::new(),
+        };
+        let scanned = if size > MAX_SCANNED_BYTES {
+            format!(" (first {} MiB)", MAX_SCANNED_BYTES >> 20)
+        } else {
+            String::new()
+        };
+        lines.push(String::new());
+        if findings.is_empty() {
+            lines.push(format!("URLs and IP addresses in its strings{}: none", scanned));
+        } else {
+            lines.push(format!("URLs and IP addresses in its strings{}:", scanned));
+            lines.extend(findings.iter().map(|finding| format!("  {}", finding)));
+        }
+        lines
+    }
+}
+
+/// Read `len` bytes at `offset`, refusing ranges past the end of the file
+/// or too large to be real
+fn read_range(file: &File, size: u64, offset: u64, len: u64) -> io::Result<Vec<u8>> {
+    if len > MAX_RANGE_BYTES || offset.checked_add(len).is_none_or(|end| end > size) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{} bytes at offset {} are past the end of the file or too large", len, offset),
+        ));
+    }
+    let mut bytes = vec![0; len as usize];
+    file.read_exact_at(&mut bytes, offset)?;
+    Ok(bytes)
+}
+
+/// The libraries the dynamic segment says to load, read from the segment
+/// and the string table it points to
+fn libraries(file: &File, size: u64, phdrs: &[ProgramHeader], ctx: Ctx) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let Some(dynamic) = phdrs.iter().find(|phdr| phdr.p_type == PT_DYNAMIC) else {
+        return Ok(Vec::new());
+    };
+    let bytes = read_range(file, size, dynamic.p_offset, dynamic.p_filesz)?;
+    // Parsed from the segment alone, as if it started the file; the other
+    // headers still map addresses to where they are in the file
+    let phdrs: Vec<ProgramHeader> = phdrs
+        .iter()
+        .map(|phdr| match phdr.p_type {
+            PT_DYNAMIC => ProgramHeader { p_offset: 0, ..phdr.clone() },
+            _ => phdr.clone(),
+        })
+        .collect();
+    let Some(dynamic) = Dynamic::parse(&bytes, &phdrs, ctx)? else {
+        return Ok(Vec::new());
+    };
+    if dynamic.info.needed_count == 0 {
+        return Ok(Vec::new());
+    }
+    let strtab = read_range(file, size, dynamic.info.strtab as u64, dynamic.info.strsz as u64)?;
+    Ok(dynamic
+        .dyns
+        .iter()
+        .filter(|entry| entry.d_tag == DT_NEEDED)
+        .filter_map(|entry| {
+            let name = strtab.get(usize::try_from(entry.d_val).ok()?..)?;
+            let end = name.iter().position(|b| *b == 0)?;
+            Some(String::from_utf8_lossy(&name[..end]).into_owned())
+        })
+        .collect())
+}
+
+/// The GNU build ID as hex, from a note segment
+fn gnu_build_id(notes: &[u8], align: u64, endian: Endian) -> Option<String> {
+    let align = if align == 8 { 8 } else { 4 };
+    let padded = |len: usize| len.checked_add(align - 1).map(|len| len / align * align);
+    let word = |at: usize| -> Option<usize> {
+        let bytes: [u8; 4] = notes.get(at..at + 4)?.try_into().ok()?;
+        Some(if endian.is_little() { u32::from_le_bytes(bytes) } else { u32::from_be_bytes(bytes) } as usize)
+    };
+    let mut at = 0;
+    while at + 12 <= notes.len() {
+        let (name_len, desc_len, note_type) = (word(at)?, word(at + 4)?, word(at + 8)?);
+        let name_start = at + 12;
+        let desc_start = name_start.checked_add(padded(name_len)?)?;
+        let desc = notes.get(desc_start..desc_start.checked_add(desc_len)?)?;
+        if note_type == NT_GNU_BUILD_ID as usize && notes.get(name_start..name_start + name_len)? == b"GNU\0" {
+            return Some(desc.iter().map(|b| format!("{:02x}", b)).collect());
+        }
+        at = desc_start.checked_add(padded(desc_len)?)?;
+    }
+    None
+}
+
+/// URLs and IPv4 addresses in the printable strings of a binary, without
+/// duplicates
+fn network_strings(bytes: &[u8]) -> Vec<String> {
+    let mut seen = BTreeSet::new();
+    let mut findings = Vec::new();
+    let strings = bytes
+        .split(|b| !(b.is_ascii_graphic() || *b == b' '))
+        .filter(|run| run.len() >= MIN_STRING_LEN)
+        .filter_map(|run| std::str::from_utf8(run).ok());
+    for string in strings {
+        for word in string.split(|c: char| c.is_whitespace() || "\"'<>()[]{},;".contains(c)) {
+            let found = if let Some(start) = word.find("://") {
+                let scheme_start = word[..start].rfind(|c: char| !c.is_ascii_alphanumeric()).map_or(0, |i| i + 1);
+                let url = &word[scheme_start..];
+                let url = url
+                    .find(|c: char| !(c.is_ascii_alphanumeric() || "-._~:/?#@!$&*+=%".contains(c)))
+                    .map_or(url, |end| &url[..end]);
+                (start > scheme_start && url.len() > start - scheme_start + 3).then_some(url)
+            } else {
+                ipv4_in(word)
+            };
+            if let Some(found) = found
+                && seen.insert(found.to_string())
+            {
+                findings.push(found.to_string());
+                if findings.len() == MAX_FINDINGS {
+                    return findings;
+                }
+            }
+        }
+    }
+    findings
+}
+
+/// A dotted IPv4 address in a word, optionally with a port
+fn ipv4_in(word: &str) -> Option<&str> {
+    let address = word.trim_matches(|c: char| !c.is_ascii_digit());
+    let host = address.split(':').next()?;
+    let octets: Vec<&str> = host.split('.').collect();
+    let valid = octets.len() == 4
+        && octets
+            .iter()
+            .all(|octet| !octet.is_empty() && octet.len() <= 3 && octet.parse::<u8>().is_ok());
+    // Version numbers such as 1.2.3.4 are far more common in binaries than
+    // addresses of that shape
+    (valid && host != "0.0.0.0" && !octets.iter().all(|octet| octet.len() == 1)).then_some(address)
+}
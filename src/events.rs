@@ -0,0 +1,118 @@
+use crossterm::event::{self, Event};
+use std::collections::VecDeque;
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// How long the terminal reader waits for input before checking whether it
+/// should stop
+const READ_TIMEOUT: Duration = Duration::from_millis(50);
+
+/// What woke the review's event loop
+pub enum Wake {
+    /// A key press, resize or focus change
+    Terminal(Event),
+    /// Something changed in the overlay
+    Overlay,
+    /// Time to look at work done in the background: debounced updates,
+    /// checks, apply reports and control socket calls
+    Tick,
+}
+
+/// Sends wake-ups to the event loop, e.g. from the overlay watcher
+pub type Waker = Sender<io::Result<Wake>>;
+
+/// Everything the review's event loop waits on, so it sleeps until there
+/// is something to do instead of polling the terminal
+pub struct Events {
+    tx: Waker,
+    rx: Receiver<io::Result<Wake>>,
+    /// Terminal events that arrived while overlay wake-ups were coalesced
+    pending: VecDeque<Event>,
+    tick: Duration,
+    next_tick: Instant,
+    stop: Arc<AtomicBool>,
+    reader: Option<JoinHandle<()>>,
+}
+
+impl Events {
+    pub fn new(tick: Duration) -> Self {
+        let (tx, rx) = channel();
+        Events {
+            tx,
+            rx,
+            pending: VecDeque::new(),
+            tick,
+            next_tick: Instant::now() + tick,
+            stop: Arc::new(AtomicBool::new(false)),
+            reader: None,
+        }
+    }
+
+    pub fn waker(&self) -> Waker {
+        self.tx.clone()
+    }
+
+    /// Read terminal input on a thread of its own, once the terminal is in
+    /// raw mode
+    pub fn read_terminal(&mut self) {
+        let tx = self.tx.clone();
+        let stop = Arc::clone(&self.stop);
+        self.reader = Some(thread::spawn(move || {
+            while !stop.load(Ordering::Relaxed) {
+                let result = match event::poll(READ_TIMEOUT) {
+                    Ok(false) => continue,
+                    Ok(true) => event::read().map(Wake::Terminal),
+                    Err(e) => Err(e),
+                };
+                let failed = result.is_err();
+                if tx.send(result).is_err() || failed {
+                    break;
+                }
+            }
+        }));
+    }
+
+    /// Wait for the next thing to happen, or for the next tick, or until
+    /// `frame_due` if a frame is being held back. A burst of overlay changes
+    /// wakes the loop once.
+    pub fn next(&mut self, frame_due: Option<Instant>) -> io::Result<Wake> {
+        if let Some(event) = self.pending.pop_front() {
+            return Ok(Wake::Terminal(event));
+        }
+        let until = frame_due.map_or(self.next_tick, |due| due.min(self.next_tick));
+        let wake = match self.rx.recv_timeout(until.saturating_duration_since(Instant::now())) {
+            Ok(wake) => wake?,
+            // This holds a sender, so the channel is never disconnected
+            Err(RecvTimeoutError::Timeout | RecvTimeoutError::Disconnected) => {
+                let now = Instant::now();
+                if now >= self.next_tick {
+                    self.next_tick = now + self.tick;
+                }
+                return Ok(Wake::Tick);
+            }
+        };
+        if let Wake::Overlay = wake {
+            while let Ok(next) = self.rx.try_recv() {
+                if let Wake::Terminal(event) = next? {
+                    self.pending.push_back(event);
+                }
+            }
+        }
+        Ok(wake)
+    }
+}
+
+impl Drop for Events {
+    /// Stop reading the terminal, so input typed once the review has ended
+    /// goes to the shell
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(reader) = self.reader.take() {
+            let _ = reader.join();
+        }
+    }
+}
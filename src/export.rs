@@ -0,0 +1,247 @@
+use crate::changeset;
+use crate::identity::Identity;
+use crate::review_state::ReviewState;
+use crate::types::{is_whiteout, FileTree, LowerView};
+use chrono::Local;
+use similar::TextDiff;
+use std::fmt::Write;
+use std::fs;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Component, Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum PatchGrouping {
+    /// One patch with every change
+    Single,
+    /// One patch per top-level directory
+    Dir,
+    /// One patch per label assigned during review
+    Label,
+    /// One patch per changeset grouped during review, with its description
+    /// as the commit message
+    Changeset,
+}
+
+/// One patch of a series: a commit message subject and the files it changes
+#[derive(Debug)]
+pub struct Patch {
+    pub subject: String,
+    /// Commit message body, if there is more to say than the subject
+    pub description: String,
+    /// Short name used for the patch file, e.g. "etc"
+    pub slug: String,
+    /// Paths relative to the overlay root
+    pub files: Vec<PathBuf>,
+}
+
+/// Split the changed files under `only` (everything if empty), and with
+/// `label` or in `changeset` if given, into a series. Labels and changesets
+/// are those of the session's review state.
+pub fn build_series(
+    files: &FileTree,
+    only: &[PathBuf],
+    review: &ReviewState,
+    label: Option<&str>,
+    changeset: Option<&str>,
+    grouping: PatchGrouping,
+) -> Vec<Patch> {
+    let labels = &review.labels;
+    let changed = files
+        .iter()
+        .filter(|(_, e)| !e.is_dir)
+        .map(|(_, e)| e.rel_path.clone())
+        .filter(|rel_path| only.is_empty() || only.iter().any(|p| rel_path.starts_with(p)))
+        .filter(|rel_path| label.is_none_or(|l| labels.get(rel_path).is_some_and(|ls| ls.contains(l))))
+        .filter(|rel_path| changeset.is_none_or(|c| changeset::of(&review.changesets, rel_path) == Some(c)));
+
+    // A changeset's patch is named after it and described by its description
+    let for_changeset = |name: &str| {
+        let description = review.changesets.get(name).map(|c| c.description.clone()).unwrap_or_default();
+        (name.to_string(), slugify(name), description)
+    };
+    let mut series: Vec<Patch> = Vec::new();
+    for rel_path in changed {
+        let (subject, slug, description) = match grouping {
+            PatchGrouping::Single if let Some(name) = changeset => for_changeset(name),
+            PatchGrouping::Single => ("Apply overlay changes".to_string(), "changes".to_string(), String::new()),
+            PatchGrouping::Dir => match top_level_dir(&rel_path) {
+                Some(dir) => (format!("Update {}", dir), slugify(&dir), String::new()),
+                None => ("Update top-level files".to_string(), "top-level".to_string(), String::new()),
+            },
+            // A file with several labels goes in the patch for the first one
+            PatchGrouping::Label => match label.or_else(|| labels.get(&rel_path)?.first().map(String::as_str)) {
+                Some(label) => (format!("Update files labelled {}", label), slugify(label), String::new()),
+                None => ("Update unlabelled files".to_string(), "unlabelled".to_string(), String::new()),
+            },
+            PatchGrouping::Changeset => match changeset::of(&review.changesets, &rel_path) {
+                Some(name) => for_changeset(name),
+                None => ("Update ungrouped files".to_string(), "ungrouped".to_string(), String::new()),
+            },
+        };
+
+        match series.iter_mut().find(|p| p.slug == slug) {
+            Some(patch) => patch.files.push(rel_path),
+            None => series.push(Patch { subject, description, slug, files: vec![rel_path] }),
+        }
+    }
+    // Changesets go in the order they are queued to apply, ungrouped files last
+    if grouping == PatchGrouping::Changeset {
+        series.sort_by_key(|patch| review.changesets.get(&patch.subject).map_or(u32::MAX, |c| c.order));
+    }
+    series
+}
+
+/// First component of a path that is inside a directory
+fn top_level_dir(rel_path: &Path) -> Option<String> {
+    let mut components = rel_path.components();
+    let first = components.next()?;
+    components.next()?;
+    match first {
+        Component::Normal(name) => Some(name.to_string_lossy().into_owned()),
+        _ => None,
+    }
+}
+
+fn slugify(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '.' { c } else { '-' })
+        .collect::<String>()
+        .trim_matches(['-', '.'])
+        .to_string()
+}
+
+/// A patch rendered in `git format-patch` style, so a series can be applied
+/// with `git am` as well as `git apply`
+pub struct RenderedPatch {
+    pub content: String,
+    /// Binary files left out, since git cannot apply them without blob hashes
+    pub skipped: Vec<PathBuf>,
+}
+
+pub fn render_patch(
+    patch: &Patch,
+    number: usize,
+    count: usize,
+    overlay_path: &Path,
+    lower: &LowerView,
+    author: &Identity,
+) -> io::Result<RenderedPatch> {
+    let mut diffs = String::new();
+    let mut skipped = Vec::new();
+    for rel_path in &patch.files {
+        match file_diff(rel_path, &overlay_path.join(rel_path), lower.resolve(rel_path).as_deref())? {
+            Some(diff) => diffs.push_str(&diff),
+            None => skipped.push(rel_path.clone()),
+        }
+    }
+
+    let mut out = String::new();
+    let _ = writeln!(out, "From 0000000000000000000000000000000000000000 Mon Sep 17 00:00:00 2001");
+    let _ = writeln!(out, "From: {}", author);
+    let _ = writeln!(out, "Date: {}", Local::now().to_rfc2822());
+    if count == 1 {
+        let _ = writeln!(out, "Subject: [PATCH] {}", patch.subject);
+    } else {
+        let _ = writeln!(out, "Subject: [PATCH {}/{}] {}", number, count, patch.subject);
+    }
+    out.push('\n');
+    if !patch.description.is_empty() {
+        let _ = writeln!(out, "{}", patch.description);
+        out.push('\n');
+    }
+    let _ = writeln!(out, "Exported from the overlay {}", overlay_path.display());
+    if !skipped.is_empty() {
+        out.push('\n');
+        let _ = writeln!(out, "Binary files not included:");
+        for rel_path in &skipped {
+            let _ = writeln!(out, "  {}", rel_path.display());
+        }
+    }
+    let _ = writeln!(out, "---");
+    out.push_str(&diffs);
+    let _ = writeln!(out, "-- ");
+    let _ = writeln!(out, "audit-box");
+    out.push('\n');
+    Ok(RenderedPatch { content: out, skipped })
+}
+
+/// Git-style diff of one overlay file against the file it shadows, if any.
+/// A whiteout in the overlay diffs as the base file's deletion, and a
+/// change of type, e.g. a regular file in place of a symlink, as a deletion
+/// and a creation, like git does. Returns None for binary files, and for a
+/// deletion of a directory or of nothing.
+fn file_diff(rel_path: &Path, overlay_file: &Path, base_file: Option<&Path>) -> io::Result<Option<String>> {
+    let deleted = fs::symlink_metadata(overlay_file).is_ok_and(|metadata| is_whiteout(&metadata));
+    if deleted && base_file.is_none_or(Path::is_dir) {
+        return Ok(None);
+    }
+    let new = if deleted { None } else { Some(read_for_diff(overlay_file)?) };
+    let old = base_file.map(read_for_diff).transpose()?;
+
+    let name = rel_path.display().to_string();
+    let file_type = |(mode, _): &(u32, Vec<u8>)| mode & 0o170000;
+    if old.as_ref().zip(new.as_ref()).is_some_and(|(old, new)| file_type(old) != file_type(new)) {
+        let removal = diff_section(&name, old.as_ref(), None);
+        let addition = diff_section(&name, None, new.as_ref());
+        return Ok(removal.zip(addition).map(|(removal, addition)| removal + &addition));
+    }
+    Ok(diff_section(&name, old.as_ref(), new.as_ref()))
+}
+
+/// One `diff --git` section between the old and new mode and content, None
+/// standing for no file. None if either is binary.
+fn diff_section(name: &str, old: Option<&(u32, Vec<u8>)>, new: Option<&(u32, Vec<u8>)>) -> Option<String> {
+    let mut out = String::new();
+    let _ = writeln!(out, "diff --git a/{} b/{}", name, name);
+
+    let no_content = Vec::new();
+    let (old_content, new_content) = match (old, new) {
+        (None, Some((new_mode, new_content))) => {
+            let _ = writeln!(out, "new file mode {:o}", new_mode);
+            (&no_content, new_content)
+        }
+        (Some((old_mode, old_content)), None) => {
+            let _ = writeln!(out, "deleted file mode {:o}", old_mode);
+            (old_content, &no_content)
+        }
+        (Some((old_mode, old_content)), Some((new_mode, new_content))) => {
+            if old_mode != new_mode {
+                let _ = writeln!(out, "old mode {:o}", old_mode);
+                let _ = writeln!(out, "new mode {:o}", new_mode);
+            }
+            (old_content, new_content)
+        }
+        (None, None) => return None,
+    };
+    let old_name = if old.is_some() { format!("a/{}", name) } else { "/dev/null".to_string() };
+    let new_name = if new.is_some() { format!("b/{}", name) } else { "/dev/null".to_string() };
+
+    match (std::str::from_utf8(old_content), std::str::from_utf8(new_content)) {
+        (Ok(old_text), Ok(new_text)) if !old_text.contains('\0') && !new_text.contains('\0') => {
+            let diff = TextDiff::from_lines(old_text, new_text);
+            let hunks = diff.unified_diff().context_radius(3).header(&old_name, &new_name).to_string();
+            out.push_str(&hunks);
+        }
+        _ if old_content != new_content => return None,
+        _ => {}
+    }
+    Some(out)
+}
+
+/// Git file mode and content. Symlinks are diffed as their target path,
+/// like git does.
+fn read_for_diff(path: &Path) -> io::Result<(u32, Vec<u8>)> {
+    let metadata = fs::symlink_metadata(path)?;
+    if metadata.is_symlink() {
+        return Ok((0o120000, fs::read_link(path)?.as_os_str().as_bytes().to_vec()));
+    }
+    let mode = if metadata.permissions().mode() & 0o111 != 0 { 0o100755 } else { 0o100644 };
+    Ok((mode, fs::read(path)?))
+}
+
+/// File name for a patch in a series, like `git format-patch` uses
+pub fn patch_file_name(patch: &Patch, number: usize) -> String {
+    format!("{:04}-{}.patch", number, patch.slug)
+}
@@ -0,0 +1,78 @@
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+/// What a file is for, to review the files of one kind together, e.g. every
+/// script an installer dropped. Listed in this order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum FileKind {
+    Script,
+    Config,
+    Binary,
+    Data,
+    Other,
+}
+
+const SCRIPT_EXTENSIONS: &[&str] = &[
+    "sh", "bash", "zsh", "fish", "ksh", "csh", "py", "pl", "rb", "php", "js", "mjs", "cjs", "ts", "lua", "tcl", "ps1",
+    "bat", "cmd", "awk",
+];
+const CONFIG_EXTENSIONS: &[&str] = &[
+    "conf", "cfg", "cnf", "ini", "toml", "yaml", "yml", "json", "xml", "plist", "properties", "env", "rc", "service",
+    "socket", "timer", "target", "mount", "path", "desktop", "rules", "list", "repo", "pc", "gitconfig",
+];
+const BINARY_EXTENSIONS: &[&str] = &["so", "o", "a", "ko", "exe", "dll", "dylib", "bin", "elf", "pyc", "class", "wasm"];
+const DATA_EXTENSIONS: &[&str] = &[
+    "csv", "tsv", "db", "sqlite", "sqlite3", "dat", "log", "parquet", "tar", "gz", "tgz", "bz2", "xz", "zst", "zip",
+    "jar", "whl", "deb", "rpm", "png", "jpg", "jpeg", "gif", "svg", "ico", "pdf",
+];
+
+/// How much of a file is looked at to tell scripts and binaries from text
+const SNIFF_LEN: u64 = 512;
+
+impl FileKind {
+    /// Heading of the kind's group in the file list
+    pub fn label(self) -> &'static str {
+        match self {
+            FileKind::Script => "Scripts",
+            FileKind::Config => "Configs",
+            FileKind::Binary => "Binaries",
+            FileKind::Data => "Data",
+            FileKind::Other => "Other",
+        }
+    }
+
+    /// Classify a file by its extension, or else by its first bytes: a `#!`
+    /// line makes it a script, an ELF header or NUL bytes a binary
+    pub fn of(path: &Path) -> FileKind {
+        let extension = path.extension().and_then(|e| e.to_str()).map(str::to_ascii_lowercase);
+        if let Some(extension) = extension.as_deref() {
+            for (extensions, kind) in [
+                (SCRIPT_EXTENSIONS, FileKind::Script),
+                (CONFIG_EXTENSIONS, FileKind::Config),
+                (BINARY_EXTENSIONS, FileKind::Binary),
+                (DATA_EXTENSIONS, FileKind::Data),
+            ] {
+                if extensions.contains(&extension) {
+                    return kind;
+                }
+            }
+        }
+        // Shared libraries are versioned after the extension, e.g. libfoo.so.1
+        if path.file_name().and_then(|n| n.to_str()).is_some_and(|name| name.contains(".so.")) {
+            return FileKind::Binary;
+        }
+
+        let mut head = Vec::new();
+        if File::open(path).and_then(|f| f.take(SNIFF_LEN).read_to_end(&mut head)).is_err() {
+            return FileKind::Other;
+        }
+        if head.starts_with(b"#!") {
+            FileKind::Script
+        } else if head.starts_with(b"\x7fELF") || head.contains(&0) {
+            FileKind::Binary
+        } else {
+            FileKind::Other
+        }
+    }
+}
@@ -1,15 +1,26 @@
-use crate::types::{FileEntry, FileStatus};
-use similar::{ChangeTag, TextDiff};
+use crate::applied;
+use crate::config::NormalizerConfig;
+use crate::dir_metadata;
+use crate::minified;
+use crate::normalize;
+use crate::types::{is_opaque, is_whiteout, DiffOptions, FileEntry, FileStatus, FileTree, LowerView, NodeId};
+use similar::{capture_diff_slices_deadline, Algorithm, ChangeTag, DiffTag, TextDiff};
+use std::collections::{HashSet, VecDeque};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::fmt;
 use std::fs;
-use std::io;
-use std::path::Path;
+use std::io::{self, BufRead};
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::AtomicU64;
+use std::time::{Duration, Instant};
 
 pub fn scan_directory(
     overlay_root: &Path,
     dir: &Path,
-    base_root: &Path,
+    lower: &LowerView,
     depth: usize,
-    entries: &mut Vec<FileEntry>,
+    parent: Option<NodeId>,
+    tree: &mut FileTree,
 ) -> io::Result<()> {
     let mut items: Vec<_> = fs::read_dir(dir)?
         .filter_map(|e| e.ok())
@@ -20,163 +31,1656 @@ pub fn scan_directory(
     for entry in items {
         let path = entry.path();
         let name = entry.file_name().to_string_lossy().to_string();
-        let is_dir = path.is_dir();
+        // A symlink is listed as an entry of its own and never followed, so
+        // a link out of the overlay cannot bring other files into the review
+        let is_dir = entry.file_type().is_ok_and(|t| t.is_dir());
 
         // Calculate relative path from overlay root
-        let rel_path = path.strip_prefix(overlay_root).unwrap();
-        let base_path = base_root.join(rel_path);
+        let rel_path = relative_path(&path, overlay_root)?;
+        let status = status_for(&path, lower, rel_path);
 
-        // Determine status: New if doesn't exist in base, Modified if it exists
-        let status = if base_path.exists() {
-            FileStatus::Modified
-        } else {
-            FileStatus::New
-        };
-
-        entries.push(FileEntry {
-            path: path.clone(),
-            name,
-            is_dir,
-            depth,
-            status,
-            selected: false,
-            collapsed: false,
-        });
+        let id = tree.insert(
+            parent,
+            FileEntry {
+                path: path.clone(),
+                rel_path: rel_path.to_path_buf(),
+                name,
+                is_dir,
+                depth,
+                status,
+                selected: false,
+                collapsed: false,
+            },
+        );
 
         if is_dir {
-            scan_directory(overlay_root, &path, base_root, depth + 1, entries)?;
+            scan_subdirectory(overlay_root, &path, lower, depth + 1, id, tree)?;
         }
     }
 
     Ok(())
 }
 
-pub fn generate_diff(entry: &FileEntry, base_path: &Path) -> Vec<String> {
-    // Calculate the path in the base filesystem
-    let overlay_root = entry.path.ancestors().nth(entry.depth + 1).unwrap_or(&entry.path);
-    let rel_path = entry.path.strip_prefix(overlay_root).unwrap_or(&entry.path);
-    let base_file = base_path.join(rel_path);
+/// Scan a directory found in the overlay, dropping it from the tree if it
+/// was removed again before it could be read
+fn scan_subdirectory(
+    overlay_root: &Path,
+    dir: &Path,
+    lower: &LowerView,
+    depth: usize,
+    id: NodeId,
+    tree: &mut FileTree,
+) -> io::Result<()> {
+    match scan_directory(overlay_root, dir, lower, depth, Some(id), tree) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            tree.remove(id);
+            Ok(())
+        }
+        result => result,
+    }
+}
+
+/// `path` relative to the overlay root, or an error for a path that is not
+/// inside the overlay, e.g. an event for a directory watched before a rename
+pub fn relative_path<'a>(path: &'a Path, overlay_path: &Path) -> io::Result<&'a Path> {
+    match path.strip_prefix(overlay_path) {
+        Ok(rel_path) if !rel_path.as_os_str().is_empty() => Ok(rel_path),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} is not inside the overlay {}", path.display(), overlay_path.display()),
+        )),
+    }
+}
+
+/// An event path with `.` and `..` resolved and its directory canonicalized,
+/// so the same file reached two ways is updated once. The file name itself
+/// is kept, so a symlink is not resolved to what it points to, and a path
+/// whose directory is gone is only tidied lexically.
+pub fn normalize_event_path(path: &Path) -> PathBuf {
+    let mut lexical = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                lexical.pop();
+            }
+            other => lexical.push(other),
+        }
+    }
+    match (lexical.parent(), lexical.file_name()) {
+        (Some(dir), Some(name)) => fs::canonicalize(dir).map_or_else(|_| lexical.clone(), |dir| dir.join(name)),
+        _ => lexical,
+    }
+}
+
+/// Determine status: Deleted for a whiteout, otherwise New if it doesn't
+/// exist in the lower view, Modified if it exists as the same kind of
+/// entry, and TypeChanged if not, e.g. a file replacing a symlink
+fn status_for(path: &Path, lower: &LowerView, rel_path: &Path) -> FileStatus {
+    let Ok(metadata) = fs::symlink_metadata(path) else {
+        return FileStatus::New;
+    };
+    if is_whiteout(&metadata) {
+        return FileStatus::Deleted;
+    }
+    match lower.resolve(rel_path).and_then(|base_file| fs::symlink_metadata(base_file).ok()) {
+        Some(base) if base.file_type() != metadata.file_type() => FileStatus::TypeChanged,
+        Some(_) => FileStatus::Modified,
+        None => FileStatus::New,
+    }
+}
+
+/// What kind of entry is at `path`, e.g. "a regular file" or "a symlink to
+/// /etc/alternatives/editor", without following a symlink
+pub fn describe_file_type(path: &Path) -> String {
+    use std::os::unix::fs::FileTypeExt;
 
-    // Read both files
-    let base_content = fs::read_to_string(&base_file).unwrap_or_default();
-    let overlay_content = fs::read_to_string(&entry.path).unwrap_or_default();
+    let Ok(metadata) = fs::symlink_metadata(path) else {
+        return "nothing".to_string();
+    };
+    let file_type = metadata.file_type();
+    if file_type.is_symlink() {
+        match fs::read_link(path) {
+            Ok(target) => format!("a symlink to {}", target.display()),
+            Err(_) => "a symlink".to_string(),
+        }
+    } else if file_type.is_dir() {
+        "a directory".to_string()
+    } else if file_type.is_fifo() {
+        "a named pipe".to_string()
+    } else if file_type.is_socket() {
+        "a socket".to_string()
+    } else if file_type.is_block_device() || file_type.is_char_device() {
+        "a device".to_string()
+    } else {
+        "a regular file".to_string()
+    }
+}
 
-    // Generate diff
-    let diff = TextDiff::from_lines(&base_content, &overlay_content);
+pub fn generate_diff(entry: &FileEntry, lower: &LowerView, options: &DiffOptions) -> Vec<String> {
+    let base_file = lower.resolve(&entry.rel_path).unwrap_or_else(|| lower.base_path(&entry.rel_path));
+    diff_against(entry, &base_file, options)
+}
+
+/// Diff an overlay file against a given file rather than the lower view.
+/// Files over the `large_file_kb` threshold show only their changes, as
+/// they are, even when pretty-printing is on, which would take them whole.
+pub fn diff_against(entry: &FileEntry, base_file: &Path, options: &DiffOptions) -> Vec<String> {
+    let pretty = options.pretty_print && minified::can_pretty_print(&entry.path);
+    let limit = options.large_file_kb * 1024;
+    let large = limit > 0 && [base_file, &entry.path].iter().any(|path| fs::metadata(path).is_ok_and(|m| m.len() > limit));
+    if large && let Some(mut diff) = diff_streamed(entry, base_file, options) {
+        if pretty {
+            diff.insert(2.min(diff.len()), "# Not pretty-printed, being a large file; press 'F' to pretty-print it in full".to_string());
+        }
+        return diff;
+    }
+
+    // Read and decode both files
+    let (mut base_content, base_encoding) = read_text(base_file);
+    let (mut overlay_content, overlay_encoding) = read_text(&entry.path);
+    if pretty {
+        base_content = minified::pretty_print(&base_content);
+        overlay_content = minified::pretty_print(&overlay_content);
+    }
+    diff_texts(entry, base_file, (&base_content, base_encoding), (&overlay_content, overlay_encoding), options)
+}
+
+/// Diff an overlay file against a given file with both run through a
+/// normalizer first, e.g. a formatter, so only the changes it does not iron
+/// out show. A note below the header says so.
+pub fn diff_normalized(
+    entry: &FileEntry,
+    base_file: &Path,
+    options: &DiffOptions,
+    normalizer: &NormalizerConfig,
+) -> io::Result<Vec<String>> {
+    let base_content = if base_file.exists() { normalize::run(normalizer, base_file)? } else { String::new() };
+    let overlay_content = normalize::run(normalizer, &entry.path)?;
+    let (base, overlay) = ((base_content.as_str(), TextEncoding::Utf8), (overlay_content.as_str(), TextEncoding::Utf8));
+    let mut result = diff_texts(entry, base_file, base, overlay, options);
+    result.insert(
+        2.min(result.len()),
+        format!("# Normalized: both sides run through `{}`; press 'N' for the raw diff", normalizer.command.join(" ")),
+    );
+    Ok(result)
+}
+
+/// Diff two files' decoded contents, showing their paths in the header
+fn diff_texts(
+    entry: &FileEntry,
+    base_file: &Path,
+    (base_content, base_encoding): (&str, TextEncoding),
+    (overlay_content, overlay_encoding): (&str, TextEncoding),
+    options: &DiffOptions,
+) -> Vec<String> {
+    let old_lines: Vec<&str> = base_content.split_inclusive('\n').collect();
+    let new_lines: Vec<&str> = overlay_content.split_inclusive('\n').collect();
+
+    // Diff on normalized lines so ignored differences compare equal,
+    // but always display the original text
+    let old_keys: Vec<String> = old_lines.iter().map(|l| normalize_line(l, options)).collect();
+    let new_keys: Vec<String> = new_lines.iter().map(|l| normalize_line(l, options)).collect();
+    // A few megabyte-long lines, as in minified files, can take Myers a long
+    // time to compare; past the deadline it settles for a rougher diff
+    let deadline = Instant::now() + LARGE_DIFF_DEADLINE;
+    let ops = capture_diff_slices_deadline(Algorithm::Myers, &old_keys, &new_keys, Some(deadline));
 
     let mut result = Vec::new();
     result.push(format!("--- {}", base_file.display()));
     result.push(format!("+++ {}", entry.path.display()));
+
+    // Summarize file-wide changes that would otherwise show up as every line changing
+    if base_encoding != overlay_encoding {
+        result.push(format!("# Encoding changed: {} → {}", base_encoding, overlay_encoding));
+    }
+    let base_eol = detect_line_ending(base_content);
+    let overlay_eol = detect_line_ending(overlay_content);
+    if base_eol != overlay_eol && base_eol != LineEnding::None && overlay_eol != LineEnding::None {
+        let hint = if options.ignore_eol || options.ignore_whitespace {
+            "ignored"
+        } else {
+            "press 'e' to ignore"
+        };
+        result.push(format!("# Line endings changed: {} → {} ({})", base_eol, overlay_eol, hint));
+    }
     result.push(String::new());
 
+    for op in ops {
+        let (tag, old_range, new_range) = op.as_tag_tuple();
+        match tag {
+            DiffTag::Equal => {
+                for (old_idx, new_idx) in old_range.zip(new_range) {
+                    let old_line = old_lines[old_idx];
+                    let new_line = new_lines[new_idx];
+                    // Lines that only compare equal after normalization are marked with '~'
+                    let sign = if old_line == new_line { " " } else { "~" };
+                    result.push(format!("{}{}", sign, new_line.trim_end()));
+                }
+            }
+            DiffTag::Delete | DiffTag::Insert | DiffTag::Replace => {
+                for old_idx in old_range {
+                    result.push(format!("-{}", old_lines[old_idx].trim_end()));
+                }
+                for new_idx in new_range {
+                    result.push(format!("+{}", new_lines[new_idx].trim_end()));
+                }
+            }
+        }
+    }
+
+    result
+}
+
+/// Diff two large files without holding either in memory: lines are read
+/// a chunk at a time and diffed by their hashes, then read again to show
+/// the changes with a few lines around each. Returns None for UTF-16 files,
+/// which are diffed in full.
+fn diff_streamed(entry: &FileEntry, base_file: &Path, options: &DiffOptions) -> Option<Vec<String>> {
+    let (old_hashes, old_stats) = LineReader::open(base_file)?.hashes(options);
+    let (new_hashes, new_stats) = LineReader::open(&entry.path)?.hashes(options);
+    let deadline = Instant::now() + LARGE_DIFF_DEADLINE;
+    let ops = capture_diff_slices_deadline(Algorithm::Myers, &old_hashes, &new_hashes, Some(deadline));
+
+    let mut old = LineReader::open(base_file)?;
+    let mut new = LineReader::open(&entry.path)?;
+    let mut hunks = Hunks::default();
+    // Checked line by line, as one op can span the whole of a new or
+    // rewritten file
+    let mut complete = true;
+    'ops: for op in ops {
+        let (tag, old_range, new_range) = op.as_tag_tuple();
+        match tag {
+            DiffTag::Equal => {
+                for (old_no, new_no) in old_range.zip(new_range) {
+                    if hunks.lines.len() >= LARGE_DIFF_MAX_LINES {
+                        complete = false;
+                        break 'ops;
+                    }
+                    let (old_line, new_line) = (old.next_line().unwrap_or_default(), new.next_line().unwrap_or_default());
+                    let sign = if old_line == new_line { ' ' } else { '~' };
+                    hunks.unchanged(old_no, new_no, sign, &new_line);
+                }
+            }
+            DiffTag::Delete | DiffTag::Insert | DiffTag::Replace => {
+                hunks.changed(old_range.start, new_range.start);
+                for _ in old_range {
+                    if hunks.lines.len() >= LARGE_DIFF_MAX_LINES {
+                        complete = false;
+                        break 'ops;
+                    }
+                    hunks.lines.push(format!("-{}", old.next_line().unwrap_or_default().trim_end()));
+                }
+                for _ in new_range {
+                    if hunks.lines.len() >= LARGE_DIFF_MAX_LINES {
+                        complete = false;
+                        break 'ops;
+                    }
+                    hunks.lines.push(format!("+{}", new.next_line().unwrap_or_default().trim_end()));
+                }
+            }
+        }
+    }
+
+    let mut result = vec![
+        format!("--- {}", base_file.display()),
+        format!("+++ {}", entry.path.display()),
+    ];
+    if old_stats.encoding != new_stats.encoding {
+        result.push(format!("# Encoding changed: {} → {}", old_stats.encoding, new_stats.encoding));
+    }
+    let (base_eol, overlay_eol) = (old_stats.line_ending(), new_stats.line_ending());
+    if base_eol != overlay_eol && base_eol != LineEnding::None && overlay_eol != LineEnding::None {
+        let hint = if options.ignore_eol || options.ignore_whitespace { "ignored" } else { "press 'e' to ignore" };
+        result.push(format!("# Line endings changed: {} → {} ({})", base_eol, overlay_eol, hint));
+    }
+    result.push(format!("{} showing only the changes; press 'F' for the full diff", LARGE_DIFF_NOTE));
+    result.push(String::new());
+    result.append(&mut hunks.lines);
+    if !complete {
+        result.push(format!("# Stopped after {} lines; press 'F' for the full diff", LARGE_DIFF_MAX_LINES));
+    }
+    Some(result)
+}
+
+/// Unchanged lines shown before and after each change of a large file
+const LARGE_DIFF_CONTEXT: usize = 3;
+/// Lines of a large file's diff shown before it stops
+const LARGE_DIFF_MAX_LINES: usize = 10_000;
+/// How long to look for the smallest diff of a large file before settling
+/// for a larger one
+const LARGE_DIFF_DEADLINE: Duration = Duration::from_secs(2);
+/// Starts the note on a diff that shows only the changes of a large file
+pub const LARGE_DIFF_NOTE: &str = "# Large file:";
+
+/// What reading a whole file line by line found out about it
+struct TextStats {
+    encoding: TextEncoding,
+    crlf: usize,
+    lf: usize,
+}
+
+impl TextStats {
+    fn line_ending(&self) -> LineEnding {
+        match (self.crlf, self.lf) {
+            (0, 0) => LineEnding::None,
+            (0, _) => LineEnding::Lf,
+            (_, 0) => LineEnding::Crlf,
+            _ => LineEnding::Mixed,
+        }
+    }
+}
+
+/// Reads a file for diff_streamed a line at a time, through a buffer
+struct LineReader {
+    reader: Option<io::BufReader<fs::File>>,
+    stats: TextStats,
+}
+
+impl LineReader {
+    /// A reader of `path`, empty if it is missing or unreadable, or None for
+    /// a UTF-16 file
+    fn open(path: &Path) -> Option<Self> {
+        let mut reader = fs::File::open(path).ok().map(io::BufReader::new);
+        let mut encoding = TextEncoding::Utf8;
+        if let Some(reader) = &mut reader {
+            let start = reader.fill_buf().unwrap_or_default();
+            if start.starts_with(&[0xFF, 0xFE]) || start.starts_with(&[0xFE, 0xFF]) {
+                return None;
+            }
+            if start.starts_with(&[0xEF, 0xBB, 0xBF]) {
+                encoding = TextEncoding::Utf8Bom;
+                reader.consume(3);
+            }
+        }
+        Some(LineReader { reader, stats: TextStats { encoding, crlf: 0, lf: 0 } })
+    }
+
+    fn next_line(&mut self) -> Option<String> {
+        let mut bytes = Vec::new();
+        match self.reader.as_mut()?.read_until(b'\n', &mut bytes) {
+            Ok(0) | Err(_) => return None,
+            Ok(_) => {}
+        }
+        if bytes.ends_with(b"\r\n") {
+            self.stats.crlf += 1;
+        } else if bytes.ends_with(b"\n") {
+            self.stats.lf += 1;
+        }
+        Some(String::from_utf8(bytes).unwrap_or_else(|e| {
+            self.stats.encoding = TextEncoding::NonUtf8;
+            String::from_utf8_lossy(e.as_bytes()).into_owned()
+        }))
+    }
+
+    /// A hash of every line as the diff compares it, and what reading them
+    /// found
+    fn hashes(mut self, options: &DiffOptions) -> (Vec<u64>, TextStats) {
+        let mut hashes = Vec::new();
+        while let Some(line) = self.next_line() {
+            let mut hasher = DefaultHasher::new();
+            normalize_line(&line, options).hash(&mut hasher);
+            hashes.push(hasher.finish());
+        }
+        (hashes, self.stats)
+    }
+}
+
+/// The changes of a diff with a few unchanged lines around each, under
+/// `@@ -old +new @@` headers giving where each hunk starts
+#[derive(Default)]
+struct Hunks {
+    lines: Vec<String>,
+    /// Unchanged lines since the last change, with their line numbers, kept
+    /// to lead into the next one
+    before: VecDeque<(usize, usize, String)>,
+    /// Unchanged lines still to show after the last change
+    after: usize,
+    /// Whether lines were left out since the last line shown
+    gap: bool,
+}
+
+impl Hunks {
+    fn unchanged(&mut self, old_no: usize, new_no: usize, sign: char, line: &str) {
+        let shown = format!("{}{}", sign, line.trim_end());
+        if self.after > 0 {
+            self.lines.push(shown);
+            self.after -= 1;
+            return;
+        }
+        self.before.push_back((old_no, new_no, shown));
+        if self.before.len() > LARGE_DIFF_CONTEXT {
+            self.before.pop_front();
+            self.gap = true;
+        }
+    }
+
+    /// Start a change at the given line numbers, after the lines leading
+    /// into it
+    fn changed(&mut self, old_no: usize, new_no: usize) {
+        if self.gap || self.lines.is_empty() {
+            let (old_no, new_no) = self.before.front().map_or((old_no, new_no), |&(o, n, _)| (o, n));
+            self.lines.push(format!("@@ -{} +{} @@", old_no + 1, new_no + 1));
+            self.gap = false;
+        }
+        self.lines.extend(self.before.drain(..).map(|(_, _, line)| line));
+        self.after = LARGE_DIFF_CONTEXT;
+    }
+}
+
+/// Count lines added and removed between a base file and its overlay version.
+/// A missing base file counts every overlay line as added, as does one of a
+/// different type, e.g. a symlink the overlay's regular file replaces.
+pub fn diff_stats(overlay_file: &Path, base_file: Option<&Path>) -> (usize, usize) {
+    let file_type = |path: &Path| fs::symlink_metadata(path).map(|m| m.file_type()).ok();
+    let base_content = base_file
+        .filter(|f| file_type(f) == file_type(overlay_file))
+        .map(|f| read_text(f).0)
+        .unwrap_or_default();
+    let (overlay_content, _) = read_text(overlay_file);
+
+    let (mut added, mut removed) = (0, 0);
+    let diff = TextDiff::configure().timeout(LARGE_DIFF_DEADLINE).diff_lines(&base_content, &overlay_content);
     for change in diff.iter_all_changes() {
-        let sign = match change.tag() {
-            ChangeTag::Delete => "-",
-            ChangeTag::Insert => "+",
-            ChangeTag::Equal => " ",
+        match change.tag() {
+            ChangeTag::Insert => added += 1,
+            ChangeTag::Delete => removed += 1,
+            ChangeTag::Equal => {}
+        }
+    }
+    (added, removed)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TextEncoding {
+    Utf8,
+    Utf8Bom,
+    Utf16Le,
+    Utf16Be,
+    NonUtf8,
+}
+
+impl fmt::Display for TextEncoding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            TextEncoding::Utf8 => "UTF-8",
+            TextEncoding::Utf8Bom => "UTF-8 with BOM",
+            TextEncoding::Utf16Le => "UTF-16LE",
+            TextEncoding::Utf16Be => "UTF-16BE",
+            TextEncoding::NonUtf8 => "non-UTF-8",
         };
-        let line = format!("{}{}", sign, change.value().trim_end());
-        result.push(line);
+        write!(f, "{}", name)
     }
+}
 
-    result
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum LineEnding {
+    None,
+    Lf,
+    Crlf,
+    Mixed,
+}
+
+impl fmt::Display for LineEnding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            LineEnding::None => "none",
+            LineEnding::Lf => "LF",
+            LineEnding::Crlf => "CRLF",
+            LineEnding::Mixed => "mixed",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Read a file as text, decoding according to its byte order mark.
+/// Missing or unreadable files are treated as empty UTF-8.
+fn read_text(path: &Path) -> (String, TextEncoding) {
+    let bytes = fs::read(path).unwrap_or_default();
+
+    if let Some(rest) = bytes.strip_prefix(&[0xEF, 0xBB, 0xBF]) {
+        (String::from_utf8_lossy(rest).into_owned(), TextEncoding::Utf8Bom)
+    } else if let Some(rest) = bytes.strip_prefix(&[0xFF, 0xFE]) {
+        let units: Vec<u16> = rest.chunks_exact(2).map(|c| u16::from_le_bytes([c[0], c[1]])).collect();
+        (String::from_utf16_lossy(&units), TextEncoding::Utf16Le)
+    } else if let Some(rest) = bytes.strip_prefix(&[0xFE, 0xFF]) {
+        let units: Vec<u16> = rest.chunks_exact(2).map(|c| u16::from_be_bytes([c[0], c[1]])).collect();
+        (String::from_utf16_lossy(&units), TextEncoding::Utf16Be)
+    } else {
+        match String::from_utf8(bytes) {
+            Ok(text) => (text, TextEncoding::Utf8),
+            Err(e) => (
+                String::from_utf8_lossy(e.as_bytes()).into_owned(),
+                TextEncoding::NonUtf8,
+            ),
+        }
+    }
+}
+
+fn detect_line_ending(text: &str) -> LineEnding {
+    let crlf = text.matches("\r\n").count();
+    let lf = text.matches('\n').count() - crlf;
+
+    match (crlf, lf) {
+        (0, 0) => LineEnding::None,
+        (0, _) => LineEnding::Lf,
+        (_, 0) => LineEnding::Crlf,
+        _ => LineEnding::Mixed,
+    }
+}
+
+fn normalize_line(line: &str, options: &DiffOptions) -> String {
+    if options.ignore_whitespace {
+        line.chars().filter(|c| !c.is_whitespace()).collect()
+    } else if options.ignore_eol {
+        line.trim_end_matches(['\r', '\n']).to_string()
+    } else {
+        line.to_string()
+    }
+}
+
+/// How far an apply has got copying files, reported as it goes
+#[derive(Debug, Clone, Default)]
+pub struct ApplyProgress {
+    pub files: usize,
+    pub total_files: usize,
+    pub bytes: u64,
+    pub total_bytes: u64,
+    pub elapsed: Duration,
+}
+
+impl ApplyProgress {
+    /// Bytes copied a second so far
+    pub fn throughput(&self) -> Option<f64> {
+        let secs = self.elapsed.as_secs_f64();
+        (secs > 0.0 && self.bytes > 0).then(|| self.bytes as f64 / secs)
+    }
+
+    /// How long copying the rest would take at the throughput so far
+    pub fn remaining(&self) -> Option<Duration> {
+        let left = self.total_bytes.saturating_sub(self.bytes) as f64;
+        self.throughput().map(|throughput| Duration::from_secs_f64(left / throughput))
+    }
+}
+
+/// How often an apply reports its progress
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Bytes cloned or copied in the kernel at a time, so progress shows during a
+/// large file. A multiple of any filesystem's block size, as cloning needs.
+#[cfg(target_os = "linux")]
+const COPY_CHUNK: u64 = 1 << 26;
+
+/// An apply that stopped partway, with the entries that had already landed
+/// in the base, in the order they were given, so they can still be recorded
+#[derive(Debug)]
+pub struct ApplyFailure {
+    pub applied: Vec<FileEntry>,
+    pub error: io::Error,
+}
+
+impl ApplyFailure {
+    /// The entries among `entries` that landed, keeping their order
+    fn landed<'a>(entries: &[FileEntry], landed: impl IntoIterator<Item = &'a FileEntry>, error: io::Error) -> Self {
+        let landed: std::collections::HashSet<&Path> = landed.into_iter().map(|e| e.path.as_path()).collect();
+        ApplyFailure {
+            applied: entries.iter().filter(|e| landed.contains(e.path.as_path())).cloned().collect(),
+            error,
+        }
+    }
 }
 
+impl From<io::Error> for ApplyFailure {
+    fn from(error: io::Error) -> Self {
+        ApplyFailure { applied: Vec::new(), error }
+    }
+}
+
+/// Apply entries one by one, copying files with up to `workers` at a time
+/// and reporting how far it has got to `on_progress`. On failure the files
+/// copied so far stay applied, and are returned with the error.
 pub fn apply_changes(
     selected_files: &[FileEntry],
     overlay_path: &Path,
-    base_path: &Path,
-) -> io::Result<()> {
-    for entry in selected_files {
-        let rel_path = entry.path.strip_prefix(overlay_path).unwrap();
-        let dest_path = base_path.join(rel_path);
+    lower: &LowerView,
+    workers: usize,
+    on_progress: &mut dyn FnMut(&ApplyProgress),
+) -> Result<(), ApplyFailure> {
+    refuse_into_overlay(selected_files, overlay_path, lower)?;
+    refuse_outside_base(selected_files, overlay_path, lower)?;
+    refuse_deletions(selected_files)?;
 
-        // Create parent directories if needed
-        if let Some(parent) = dest_path.parent() {
-            fs::create_dir_all(parent)?;
+    let (dirs, files): (Vec<&FileEntry>, Vec<&FileEntry>) = selected_files.iter().partition(|e| e.is_dir);
+    // Parent directories are created up front, so workers do not race to
+    // create the same ones
+    for entry in &files {
+        create_parents(&entry.rel_path, overlay_path, lower, &mut Vec::new())?;
+    }
+    let total_bytes = total_size(files.iter().copied());
+    let copy = |entry: &&FileEntry, copied: &AtomicU64| {
+        // Copy the file, then it is safe to delete the source
+        copy_verified(&entry.path, &lower.base_path(&entry.rel_path), copied)?;
+        fs::remove_file(&entry.path)
+    };
+    if let Err((copied, e)) = copy_all(&files, workers, copy, total_bytes, on_progress) {
+        return Err(ApplyFailure::landed(selected_files, copied.into_iter().map(|i| files[i]), e));
+    }
+    // Directories after the files in them, deepest first, so a mode that
+    // takes away write access is set last
+    for (i, entry) in dirs.iter().enumerate().rev() {
+        if let Err(e) = apply_directory(entry, overlay_path, lower) {
+            let landed = files.iter().chain(&dirs[i + 1..]).copied();
+            return Err(ApplyFailure::landed(selected_files, landed, e));
         }
+    }
+    Ok(())
+}
 
-        // Copy the file
-        fs::copy(&entry.path, &dest_path)?;
+/// Bytes the files among `entries` take to copy
+fn total_size<'a>(entries: impl Iterator<Item = &'a FileEntry>) -> u64 {
+    entries
+        .filter(|e| !e.is_dir)
+        .filter_map(|e| fs::symlink_metadata(&e.path).ok())
+        .map(|m| m.len())
+        .sum()
+}
 
-        // Verify the copy by comparing file contents
-        let source_content = fs::read(&entry.path)?;
-        let dest_content = fs::read(&dest_path)?;
+/// Run `copy` on each item, in order with one worker, or spread over up to
+/// `workers` threads, each counting the bytes it copies. No more are started
+/// after one fails, and the error of the first failed item in order is
+/// returned, whichever worker got there first, along with the indices of the
+/// items copied, in order. Meanwhile this thread reports the progress every
+/// PROGRESS_INTERVAL, and once more at the end.
+fn copy_all<T: Sync>(
+    items: &[T],
+    workers: usize,
+    copy: impl Fn(&T, &AtomicU64) -> io::Result<()> + Sync,
+    total_bytes: u64,
+    on_progress: &mut dyn FnMut(&ApplyProgress),
+) -> Result<(), (Vec<usize>, io::Error)> {
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::sync::mpsc::{self, RecvTimeoutError};
+    use std::sync::{Mutex, PoisonError};
 
-        if source_content == dest_content {
-            // Files are identical, safe to delete source
-            fs::remove_file(&entry.path)?;
-        } else {
+    let started = Instant::now();
+    let (next, done, copied) = (AtomicUsize::new(0), AtomicUsize::new(0), AtomicU64::new(0));
+    let failed = AtomicBool::new(false);
+    let errors = Mutex::new(Vec::new());
+    let copied_items = Mutex::new(Vec::new());
+    let progress = || ApplyProgress {
+        files: done.load(Ordering::Relaxed),
+        total_files: items.len(),
+        bytes: copied.load(Ordering::Relaxed),
+        total_bytes,
+        elapsed: started.elapsed(),
+    };
+    std::thread::scope(|scope| {
+        let (finished, finishing) = mpsc::channel();
+        for _ in 0..workers.clamp(1, items.len().max(1)) {
+            let finished = finished.clone();
+            let (next, done, copied, failed, errors, copy) = (&next, &done, &copied, &failed, &errors, &copy);
+            let copied_items = &copied_items;
+            scope.spawn(move || {
+                while !failed.load(Ordering::Relaxed) {
+                    let i = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(item) = items.get(i) else {
+                        break;
+                    };
+                    match copy(item, copied) {
+                        Ok(()) => {
+                            done.fetch_add(1, Ordering::Relaxed);
+                            copied_items.lock().unwrap_or_else(PoisonError::into_inner).push(i);
+                            let _ = finished.send(());
+                        }
+                        Err(e) => {
+                            failed.store(true, Ordering::Relaxed);
+                            errors.lock().unwrap_or_else(PoisonError::into_inner).push((i, e));
+                        }
+                    }
+                }
+            });
+        }
+        drop(finished);
+        let mut reported = Instant::now();
+        loop {
+            match finishing.recv_timeout(PROGRESS_INTERVAL) {
+                Err(RecvTimeoutError::Disconnected) => break,
+                _ if reported.elapsed() >= PROGRESS_INTERVAL => {
+                    on_progress(&progress());
+                    reported = Instant::now();
+                }
+                _ => {}
+            }
+        }
+    });
+    on_progress(&progress());
+    let errors = errors.into_inner().unwrap_or_else(PoisonError::into_inner);
+    match errors.into_iter().min_by_key(|(i, _)| *i) {
+        Some((_, e)) => {
+            let mut copied_items = copied_items.into_inner().unwrap_or_else(PoisonError::into_inner);
+            copied_items.sort_unstable();
+            Err((copied_items, e))
+        }
+        None => Ok(()),
+    }
+}
+
+/// Where and how an entry would land in the base, worked out as
+/// `apply_changes` goes about applying it
+#[derive(Debug)]
+pub struct ApplyDestination {
+    pub path: PathBuf,
+    /// Whether something is already there, to be overwritten, or updated in
+    /// the case of a directory
+    pub exists: bool,
+    /// Base directories above it that do not exist yet and would be created
+    pub missing_parents: usize,
+    pub uid: u32,
+    pub gid: u32,
+    pub mode: u32,
+    /// The mode of what is there now, if it differs
+    pub replaced_mode: Option<u32>,
+}
+
+/// Preview applying an entry. Deletions are not applied, so have none.
+pub fn apply_destination(entry: &FileEntry, lower: &LowerView) -> io::Result<Option<ApplyDestination>> {
+    use std::os::unix::fs::MetadataExt;
+
+    if entry.status == FileStatus::Deleted {
+        return Ok(None);
+    }
+    let path = lower.base_path(&entry.rel_path);
+    let source = fs::symlink_metadata(&entry.path)?;
+    let dest = fs::symlink_metadata(&path).ok();
+    let missing_parents = entry.rel_path.parent().map_or(0, |parent| {
+        parent
+            .ancestors()
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .take_while(|dir| !lower.base_path(dir).exists())
+            .count()
+    });
+    let mode = source.mode() & 0o7777;
+
+    // An overwritten file keeps its owner, whether written in place or
+    // renamed over it from a copy given that owner, and a directory keeps
+    // its owner too; a symlink on either side is replaced
+    let kept = dest
+        .as_ref()
+        .filter(|dest| (entry.is_dir && dest.is_dir()) || !(source.is_symlink() || dest.is_symlink()));
+    let (uid, gid) = match kept {
+        Some(dest) => (dest.uid(), dest.gid()),
+        None => new_owner(entry, &source, &path),
+    };
+    Ok(Some(ApplyDestination {
+        exists: dest.is_some(),
+        missing_parents,
+        uid,
+        gid,
+        mode,
+        replaced_mode: dest.map(|dest| dest.mode() & 0o7777).filter(|&current| current != mode),
+        path,
+    }))
+}
+
+/// Owner and group of something created in the base: those of the process
+/// applying, with the group of a setgid parent directory, except that a new
+/// directory is given its overlay owner when the process may do that
+fn new_owner(entry: &FileEntry, source: &fs::Metadata, dest_path: &Path) -> (u32, u32) {
+    use std::os::unix::fs::MetadataExt;
+
+    // SAFETY: geteuid and getegid cannot fail
+    let (euid, egid) = unsafe { (libc::geteuid(), libc::getegid()) };
+    if entry.is_dir && euid == 0 {
+        return (source.uid(), source.gid());
+    }
+    let parent = dest_path.parent().and_then(|parent| fs::metadata(parent).ok());
+    match parent {
+        Some(parent) if parent.mode() & 0o2000 != 0 => (euid, parent.gid()),
+        _ => (euid, egid),
+    }
+}
+
+/// Copy an overlay file to `dest_path` and verify the copy. A symlink is
+/// copied as the link itself rather than the file it points to, and a
+/// symlink already at `dest_path` is replaced rather than written through.
+fn copy_verified(source: &Path, dest_path: &Path, copied: &AtomicU64) -> io::Result<()> {
+    let is_link = fs::symlink_metadata(source)?.is_symlink();
+    if let Ok(dest) = fs::symlink_metadata(dest_path)
+        && (is_link || dest.is_symlink())
+    {
+        fs::remove_file(dest_path)?;
+    }
+
+    let verified = if is_link {
+        let target = fs::read_link(source)?;
+        std::os::unix::fs::symlink(&target, dest_path)?;
+        fs::read_link(dest_path)? == target
+    } else {
+        copy_counted(source, dest_path, copied)?;
+        // Hashed a block at a time, so large files are not read into memory
+        applied::hash_file(source)? == applied::hash_file(dest_path)?
+    };
+    if !verified {
+        return Err(io::Error::other(
+            format!("Verification failed for {}", source.display()),
+        ));
+    }
+    if !is_link {
+        dir_metadata::copy_file_xattrs(source, dest_path)?;
+    }
+    Ok(())
+}
+
+/// Whether a regular file has holes, i.e. fewer blocks allocated than its
+/// length takes, as disk images and databases often do
+pub fn is_sparse(metadata: &fs::Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    metadata.is_file() && metadata.blocks() * 512 < metadata.len()
+}
+
+/// Copy a regular file's contents and mode. The copy shares the source's
+/// blocks where the filesystem can clone them, and is otherwise made in the
+/// kernel with copy_file_range, falling back to reading and writing it
+/// between filesystems that support neither. A sparse file keeps its holes.
+pub fn copy_contents(source: &Path, dest_path: &Path) -> io::Result<()> {
+    copy_counted(source, dest_path, &AtomicU64::new(0))
+}
+
+/// `copy_contents`, adding the bytes copied to `copied` as it goes
+#[cfg(target_os = "linux")]
+fn copy_counted(source: &Path, dest_path: &Path, copied: &AtomicU64) -> io::Result<()> {
+    use std::os::fd::AsRawFd;
+    use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+
+    let mut reader = fs::File::open(source)?;
+    let metadata = reader.metadata()?;
+    let permissions = metadata.permissions();
+    let mut writer = fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(permissions.mode())
+        .open(dest_path)?;
+    writer.set_permissions(permissions)?;
+
+    if clone_counted(&reader, &writer, metadata.len(), copied)? {
+        return Ok(());
+    }
+    if is_sparse(&metadata) {
+        return copy_sparse(&reader, &writer, metadata.len(), copied);
+    }
+    let mut started = false;
+    loop {
+        // SAFETY: the descriptors are open for as long as the calls take;
+        // null offsets use and advance the files' own
+        let len = unsafe {
+            libc::copy_file_range(
+                reader.as_raw_fd(),
+                std::ptr::null_mut(),
+                writer.as_raw_fd(),
+                std::ptr::null_mut(),
+                COPY_CHUNK as usize,
+                0,
+            )
+        };
+        match len {
+            0 => return Ok(()),
+            len if len > 0 => {
+                started = true;
+                copied.fetch_add(len as u64, std::sync::atomic::Ordering::Relaxed);
+            }
+            _ => {
+                let e = io::Error::last_os_error();
+                let unsupported = matches!(
+                    e.raw_os_error(),
+                    Some(libc::EXDEV | libc::ENOSYS | libc::EOPNOTSUPP | libc::EINVAL)
+                );
+                if started || !unsupported {
+                    return Err(e);
+                }
+                let len = io::copy(&mut reader, &mut writer)?;
+                copied.fetch_add(len, std::sync::atomic::Ordering::Relaxed);
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Share the source's blocks with the copy, a chunk at a time so progress
+/// shows during a large file. Returns false, having done nothing, where the
+/// filesystem cannot clone them.
+#[cfg(target_os = "linux")]
+fn clone_counted(reader: &fs::File, writer: &fs::File, len: u64, copied: &AtomicU64) -> io::Result<bool> {
+    use std::os::fd::AsRawFd;
+
+    let mut offset = 0;
+    while offset < len {
+        let remaining = len - offset;
+        let range = libc::file_clone_range {
+            src_fd: i64::from(reader.as_raw_fd()),
+            src_offset: offset,
+            // 0 clones to the end of the file, which need not be aligned
+            src_length: if remaining > COPY_CHUNK { COPY_CHUNK } else { 0 },
+            dest_offset: offset,
+        };
+        // SAFETY: both descriptors are open for as long as the call takes,
+        // and range outlives it
+        if unsafe { libc::ioctl(writer.as_raw_fd(), libc::FICLONERANGE, &range) } < 0 {
+            if offset == 0 {
+                return Ok(false);
+            }
+            return Err(io::Error::last_os_error());
+        }
+        let cloned = remaining.min(COPY_CHUNK);
+        copied.fetch_add(cloned, std::sync::atomic::Ordering::Relaxed);
+        offset += cloned;
+    }
+    Ok(len > 0)
+}
+
+/// Copy the data of a sparse file found with SEEK_DATA and SEEK_HOLE,
+/// leaving the holes between unwritten rather than filling them with zeros,
+/// then extend the copy to the source's length over any trailing hole. The
+/// holes count as copied, so progress adds up to the file's length.
+#[cfg(target_os = "linux")]
+fn copy_sparse(reader: &fs::File, writer: &fs::File, len: u64, copied: &AtomicU64) -> io::Result<()> {
+    use std::os::fd::AsRawFd;
+    use std::os::unix::fs::FileExt;
+
+    let mut buf = vec![0; 1 << 20];
+    let mut offset = 0;
+    while offset < len {
+        // SAFETY: the descriptor is open for as long as the calls take
+        let data = unsafe { libc::lseek(reader.as_raw_fd(), offset as libc::off_t, libc::SEEK_DATA) };
+        if data < 0 {
+            let e = io::Error::last_os_error();
+            // No data past the offset: the rest is a hole
+            if e.raw_os_error() == Some(libc::ENXIO) {
+                break;
+            }
+            return Err(e);
+        }
+        copied.fetch_add(data as u64 - offset, std::sync::atomic::Ordering::Relaxed);
+        // SAFETY: as above
+        let hole = unsafe { libc::lseek(reader.as_raw_fd(), data, libc::SEEK_HOLE) };
+        if hole < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let (mut pos, end) = (data as u64, (hole as u64).min(len));
+        while pos < end {
+            let chunk = (end - pos).min(buf.len() as u64) as usize;
+            let read = reader.read_at(&mut buf[..chunk], pos)?;
+            if read == 0 {
+                break;
+            }
+            writer.write_all_at(&buf[..read], pos)?;
+            copied.fetch_add(read as u64, std::sync::atomic::Ordering::Relaxed);
+            pos += read as u64;
+        }
+        offset = end;
+    }
+    copied.fetch_add(len.saturating_sub(offset), std::sync::atomic::Ordering::Relaxed);
+    writer.set_len(len)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn copy_counted(source: &Path, dest_path: &Path, copied: &AtomicU64) -> io::Result<()> {
+    let len = fs::copy(source, dest_path)?;
+    copied.fetch_add(len, std::sync::atomic::Ordering::Relaxed);
+    Ok(())
+}
+
+/// Apply a directory on its own: an empty one, or one whose mode or xattrs
+/// changed. A new one is created in the base with the overlay directory's
+/// mode; one the base already has is given its mode and xattrs. An empty
+/// overlay copy is then removed, while one with entries in it stays for
+/// those to be applied.
+fn apply_directory(entry: &FileEntry, overlay_path: &Path, lower: &LowerView) -> io::Result<()> {
+    let rel_path = &entry.rel_path;
+    let empty = is_empty_dir(&entry.path)?;
+    if empty {
+        refuse_opaque(entry)?;
+    }
+    let dest_path = lower.base_path(rel_path);
+    if entry.status == FileStatus::New && !dest_path.exists() {
+        let mut created = Vec::new();
+        create_parents(rel_path, overlay_path, lower, &mut created)?;
+        create_dir_like(&entry.path, &dest_path, &mut created)?;
+    } else if dest_path.is_dir() {
+        dir_metadata::apply(&entry.path, &dest_path)?;
+    }
+    if empty {
+        fs::remove_dir(&entry.path)?;
+    }
+    Ok(())
+}
+
+fn is_empty_dir(dir: &Path) -> io::Result<bool> {
+    Ok(fs::read_dir(dir)?.next().is_none())
+}
+
+/// Create the missing base directories above `rel_path`, top down, each like
+/// the same directory in the overlay rather than with whatever mode the
+/// umask gives. Directories created are added to `created`.
+fn create_parents(rel_path: &Path, overlay_path: &Path, lower: &LowerView, created: &mut Vec<PathBuf>) -> io::Result<()> {
+    let Some(rel_parent) = rel_path.parent() else {
+        return Ok(());
+    };
+    let missing: Vec<&Path> = rel_parent
+        .ancestors()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .take_while(|dir| !lower.base_path(dir).exists())
+        .collect();
+    for rel_dir in missing.into_iter().rev() {
+        create_dir_like(&overlay_path.join(rel_dir), &lower.base_path(rel_dir), created)?;
+    }
+    Ok(())
+}
+
+/// Create a directory with the mode of `model` and, when allowed, its owner
+/// and group, e.g. when applying as root. Without a model, e.g. a parent the
+/// overlay does not have, the umask applies as usual.
+fn create_dir_like(model: &Path, dest_path: &Path, created: &mut Vec<PathBuf>) -> io::Result<()> {
+    use std::os::unix::fs::MetadataExt;
+
+    match fs::create_dir(dest_path) {
+        Ok(()) => created.push(dest_path.to_path_buf()),
+        // Created meanwhile, e.g. by the program being audited
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists && dest_path.is_dir() => return Ok(()),
+        Err(e) => return Err(e),
+    }
+    let Ok(metadata) = fs::metadata(model) else {
+        return Ok(());
+    };
+    // Ownership first, as changing it can clear setgid
+    match std::os::unix::fs::chown(dest_path, Some(metadata.uid()), Some(metadata.gid())) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::PermissionDenied => {}
+        Err(e) => return Err(e),
+    }
+    fs::set_permissions(dest_path, metadata.permissions())
+}
+
+/// Refuse to apply anything whose destination is inside the overlay, as
+/// happens when the overlay lies under the base and the sandbox wrote to the
+/// overlay's own path: it would be copied into itself
+fn refuse_into_overlay(entries: &[FileEntry], overlay_path: &Path, lower: &LowerView) -> io::Result<()> {
+    let overlay = std::path::absolute(overlay_path)?;
+    for entry in entries {
+        let rel_path = &entry.rel_path;
+        if std::path::absolute(lower.base_path(rel_path))?.starts_with(&overlay) {
             return Err(io::Error::new(
-                io::ErrorKind::Other,
-                format!("Verification failed for {}", entry.path.display()),
+                io::ErrorKind::InvalidInput,
+                format!("{} would be applied into the overlay itself", rel_path.display()),
             ));
         }
     }
+    Ok(())
+}
+
+/// Refuse to apply anything that would be read from outside the overlay or
+/// written outside the base: a name with `..` in it, or a path that goes
+/// through a symlink pointing elsewhere, as a sandboxed program could craft
+fn refuse_outside_base(entries: &[FileEntry], overlay_path: &Path, lower: &LowerView) -> io::Result<()> {
+    let overlay = fs::canonicalize(overlay_path)?;
+    for entry in entries {
+        let outside = |place: &str| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} leads outside the {}", entry.path.display(), place),
+            )
+        };
+        let rel_path = &entry.rel_path;
+        if !rel_path.components().all(|c| matches!(c, Component::Normal(_))) {
+            return Err(outside("base"));
+        }
+        if !resolves_inside(entry.path.parent().unwrap_or(&entry.path), &overlay)? {
+            return Err(outside("overlay"));
+        }
+
+        // A file replaces whatever is at its destination, so only the
+        // directories leading to it must resolve inside the base, while a
+        // directory's own metadata is set through any symlink
+        let dest_path = lower.base_path(rel_path);
+        let checked = if entry.is_dir { &dest_path } else { dest_path.parent().unwrap_or(&dest_path) };
+        if !resolves_inside(checked, &fs::canonicalize(lower.root_of(rel_path))?)? {
+            return Err(outside("base"));
+        }
+    }
+    Ok(())
+}
+
+/// Whether `path`, with any symlinks on the way resolved, is inside `root`.
+/// For a path that does not exist yet, its nearest existing ancestor counts.
+fn resolves_inside(path: &Path, root: &Path) -> io::Result<bool> {
+    for ancestor in path.ancestors() {
+        match fs::canonicalize(ancestor) {
+            Ok(resolved) => return Ok(resolved.starts_with(root)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(false)
+}
+
+/// Deletions are shown for review but not carried over to the base;
+/// discarding one keeps the base's copy
+fn refuse_deletions(entries: &[FileEntry]) -> io::Result<()> {
+    if let Some(entry) = entries.iter().find(|e| e.status == FileStatus::Deleted) {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!("{} is deleted in the overlay, and applying deletions is not supported", entry.rel_path.display()),
+        ));
+    }
+    Ok(())
+}
+
+/// An empty opaque directory stands for the base directory's contents being
+/// removed, which apply does not do
+fn refuse_opaque(entry: &FileEntry) -> io::Result<()> {
+    if is_opaque(&entry.path) {
+        return Err(io::Error::other(format!(
+            "{} is opaque in the overlay, hiding the base directory's contents",
+            entry.path.display()
+        )));
+    }
+    Ok(())
+}
+
+/// Apply files all-or-nothing. Each file is first copied to a temporary name
+/// beside its destination and verified, by up to `workers` at a time. Only
+/// when every copy is good are they renamed into place, in order, and the
+/// overlay copies removed, so a failed copy leaves the base as it was. Should
+/// a rename fail, the files renamed before it are put back as they were.
+/// The copying is reported to `on_progress` as it goes. Should tidying up
+/// after the renames fail, the files are applied all the same and returned
+/// with the error.
+pub fn apply_transaction(
+    files: &[FileEntry],
+    overlay_path: &Path,
+    lower: &LowerView,
+    workers: usize,
+    on_progress: &mut dyn FnMut(&ApplyProgress),
+) -> Result<(), ApplyFailure> {
+    refuse_into_overlay(files, overlay_path, lower)?;
+    refuse_outside_base(files, overlay_path, lower)?;
+    refuse_deletions(files)?;
+
+    let mut copies: Vec<(PathBuf, PathBuf)> = Vec::new();
+    let mut created_dirs: Vec<PathBuf> = Vec::new();
+
+    for entry in files {
+        if let Err(e) = stage_copy(entry, overlay_path, lower, &mut copies, &mut created_dirs) {
+            roll_back(&copies, &mut created_dirs);
+            return Err(e.into());
+        }
+    }
+    // One copy per file, in the same order
+    let sources = files.iter().filter(|e| !e.is_dir).map(|e| &e.path);
+    let pending: Vec<(&PathBuf, &(PathBuf, PathBuf))> = sources.zip(copies.iter()).collect();
+    let copy = |(source, (temp_path, dest_path)): &(&PathBuf, &(PathBuf, PathBuf)), copied: &AtomicU64| {
+        copy_verified(source, temp_path, copied)?;
+        // Changing the owner clears file capabilities, so they are copied again
+        if keep_owner(temp_path, dest_path)? {
+            dir_metadata::copy_file_xattrs(source, temp_path)?;
+        }
+        Ok(())
+    };
+    if let Err((_, e)) = copy_all(&pending, workers, copy, total_size(files.iter()), on_progress) {
+        roll_back(&copies, &mut created_dirs);
+        return Err(e.into());
+    }
+
+    // Whatever each file replaces is kept aside first, so that if a rename
+    // fails the files renamed before it can be put back
+    let mut backups: Vec<Option<PathBuf>> = Vec::with_capacity(copies.len());
+    for (_, dest_path) in &copies {
+        match back_up(dest_path) {
+            Ok(backup) => backups.push(backup),
+            Err(e) => {
+                discard_backups(&backups);
+                roll_back(&copies, &mut created_dirs);
+                return Err(e.into());
+            }
+        }
+    }
+    for (i, (temp_path, dest_path)) in copies.iter().enumerate() {
+        if let Err(e) = fs::rename(temp_path, dest_path) {
+            let restored = restore(&copies[..i], &backups[..i]);
+            discard_backups(&backups[i..]);
+            roll_back(&copies, &mut created_dirs);
+            return Err(match restored {
+                Ok(()) => io::Error::other(format!("Renaming {} failed, nothing applied: {}", dest_path.display(), e)),
+                Err(not_restored) => io::Error::other(format!(
+                    "Renaming {} failed: {}; the {} files before it were applied, and putting {} back failed",
+                    dest_path.display(),
+                    e,
+                    i,
+                    not_restored.display()
+                )),
+            }
+            .into());
+        }
+    }
+    discard_backups(&backups);
+
+    // Every file is in place now
+    let landed = files.iter().filter(|e| !e.is_dir);
+    for entry in landed.clone() {
+        if let Err(e) = fs::remove_file(&entry.path) {
+            return Err(ApplyFailure::landed(files, landed, e));
+        }
+    }
+    // New directories were created above; the rest get their metadata now,
+    // after the files in them
+    let dirs: Vec<&FileEntry> = files.iter().filter(|e| e.is_dir).collect();
+    for (i, entry) in dirs.iter().enumerate().rev() {
+        if let Err(e) = apply_directory(entry, overlay_path, lower) {
+            return Err(ApplyFailure::landed(files, landed.chain(dirs[i + 1..].iter().copied()), e));
+        }
+    }
+
+    Ok(())
+}
+
+/// Make way for one file's copy next to its destination, recording where it
+/// goes and any directories created for it
+fn stage_copy(
+    entry: &FileEntry,
+    overlay_path: &Path,
+    lower: &LowerView,
+    copies: &mut Vec<(PathBuf, PathBuf)>,
+    created_dirs: &mut Vec<PathBuf>,
+) -> io::Result<()> {
+    let rel_path = &entry.rel_path;
+    let dest_path = lower.base_path(rel_path);
+
+    // Empty directories are created outright, and removed again on roll back
+    if entry.is_dir {
+        if is_empty_dir(&entry.path)? {
+            refuse_opaque(entry)?;
+        }
+        if entry.status == FileStatus::New && !dest_path.exists() {
+            create_parents(rel_path, overlay_path, lower, created_dirs)?;
+            create_dir_like(&entry.path, &dest_path, created_dirs)?;
+        }
+        return Ok(());
+    }
+
+    create_parents(rel_path, overlay_path, lower, created_dirs)?;
 
+    let mut temp_name = std::ffi::OsString::from(".");
+    temp_name.push(dest_path.file_name().unwrap_or_default());
+    temp_name.push(".audit-box-apply");
+    let temp_path = dest_path.with_file_name(temp_name);
+
+    copies.push((temp_path, dest_path));
     Ok(())
 }
 
-pub fn discard_file(path: &Path) -> io::Result<()> {
-    if path.is_file() {
-        fs::remove_file(path)?;
-    } else if path.is_dir() {
-        fs::remove_dir_all(path)?;
+/// Keep what is at `dest_path` aside under a name beside it, hard linked so
+/// the destination stays in place until it is replaced. None if there is
+/// nothing there to keep.
+fn back_up(dest_path: &Path) -> io::Result<Option<PathBuf>> {
+    match fs::symlink_metadata(dest_path) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+        // Renaming a file over a directory fails anyway
+        Ok(metadata) if metadata.is_dir() => return Ok(None),
+        Ok(_) => {}
+    }
+    let mut backup_name = std::ffi::OsString::from(".");
+    backup_name.push(dest_path.file_name().unwrap_or_default());
+    backup_name.push(".audit-box-backup");
+    let backup_path = dest_path.with_file_name(backup_name);
+    let _ = fs::remove_file(&backup_path);
+    fs::hard_link(dest_path, &backup_path)
+        .or_else(|_| fs::copy(dest_path, &backup_path).map(|_| ()))
+        .map_err(|e| io::Error::new(e.kind(), format!("Failed to back up {}: {}", dest_path.display(), e)))?;
+    Ok(Some(backup_path))
+}
+
+/// Give a copy about to be renamed over an existing file that file's owner
+/// and group, as writing it in place would have kept them. A symlink on
+/// either side is replaced outright, so gets a new owner like any new file.
+/// Returns whether the owner was changed.
+fn keep_owner(temp_path: &Path, dest_path: &Path) -> io::Result<bool> {
+    use std::os::unix::fs::MetadataExt;
+
+    let Ok(dest) = fs::symlink_metadata(dest_path) else {
+        return Ok(false);
+    };
+    let temp = fs::symlink_metadata(temp_path)?;
+    if !dest.is_file() || temp.is_symlink() || (temp.uid(), temp.gid()) == (dest.uid(), dest.gid()) {
+        return Ok(false);
+    }
+    std::os::unix::fs::lchown(temp_path, Some(dest.uid()), Some(dest.gid())).map_err(|e| {
+        io::Error::new(e.kind(), format!("Failed to keep the owner of {}: {}", dest_path.display(), e))
+    })?;
+    // Changing the owner clears setuid and setgid bits
+    fs::set_permissions(temp_path, temp.permissions())?;
+    Ok(true)
+}
+
+/// Put back what the files renamed into place replaced, removing the ones
+/// that replaced nothing. Returns the first destination that could not be
+/// put back, having tried them all.
+fn restore(copies: &[(PathBuf, PathBuf)], backups: &[Option<PathBuf>]) -> Result<(), PathBuf> {
+    let mut failed = None;
+    for ((_, dest_path), backup) in copies.iter().zip(backups).rev() {
+        let result = match backup {
+            Some(backup_path) => fs::rename(backup_path, dest_path),
+            None => fs::remove_file(dest_path),
+        };
+        if result.is_err() {
+            failed = Some(dest_path.clone());
+        }
+    }
+    failed.map_or(Ok(()), Err)
+}
+
+fn discard_backups(backups: &[Option<PathBuf>]) {
+    for backup_path in backups.iter().flatten() {
+        let _ = fs::remove_file(backup_path);
+    }
+}
+
+/// Undo `stage_copy`: remove the temporary copies, then the directories
+/// created for them, deepest first
+fn roll_back(copies: &[(PathBuf, PathBuf)], created_dirs: &mut [PathBuf]) {
+    for (temp_path, _) in copies {
+        let _ = fs::remove_file(temp_path);
     }
+    created_dirs.sort_by_key(|dir| std::cmp::Reverse(dir.components().count()));
+    for dir in created_dirs.iter() {
+        let _ = fs::remove_dir(dir);
+    }
+}
+
+/// Discard an overlay entry, then any overlay directories left empty by it,
+/// so a new directory whose contents were all discarded is not later applied
+/// as an empty one
+pub fn discard_file(path: &Path, overlay_path: &Path, lower: &LowerView) -> io::Result<()> {
+    // Whiteouts and symlinks go the way of files
+    match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.is_dir() => fs::remove_dir_all(path)?,
+        Ok(_) => fs::remove_file(path)?,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e),
+    }
+    remove_empty_parents(path, overlay_path, lower);
     Ok(())
 }
 
+/// Remove the directories above `path` that are now empty, stopping at the
+/// first that is not, at an opaque directory or at the overlay root. The
+/// directories of a session's bases are kept too, as they are mounted.
+/// Returns the directories removed.
+pub fn remove_empty_parents(path: &Path, overlay_path: &Path, lower: &LowerView) -> Vec<PathBuf> {
+    let mut removed = Vec::new();
+    for dir in path.ancestors().skip(1) {
+        let Ok(rel_path) = dir.strip_prefix(overlay_path) else {
+            break;
+        };
+        if rel_path.components().count() <= lower.root_depth() || is_opaque(dir) {
+            break;
+        }
+        if fs::remove_dir(dir).is_err() {
+            break;
+        }
+        removed.push(dir.to_path_buf());
+    }
+    removed
+}
+
 pub fn update_or_add_file(
-    files: &mut Vec<FileEntry>,
+    files: &mut FileTree,
     path: &Path,
     overlay_path: &Path,
-    base_path: &Path,
+    lower: &LowerView,
 ) -> io::Result<()> {
-    let rel_path = path.strip_prefix(overlay_path).unwrap_or(path);
-    let base_file = base_path.join(rel_path);
+    let rel_path = relative_path(path, overlay_path)?;
+    let status = status_for(path, lower, rel_path);
 
-    let status = if base_file.exists() {
-        FileStatus::Modified
-    } else {
-        FileStatus::New
+    // Update existing entry, preserving selection and collapse state
+    if let Some(id) = files.find(path) {
+        if let Some(entry) = files.get_mut(id) {
+            entry.status = status;
+        }
+        return Ok(());
+    }
+
+    let parent = match path.parent() {
+        Some(dir) if dir != overlay_path => Some(ensure_directory(files, dir, overlay_path, lower)),
+        _ => None,
     };
 
     let depth = rel_path.components().count() - 1;
-    let name = path.file_name().unwrap().to_string_lossy().to_string();
-
-    let new_entry = FileEntry {
-        path: path.to_path_buf(),
-        name,
-        is_dir: false,
-        depth,
-        status,
-        selected: false,
-        collapsed: false,
-    };
+    let name = rel_path.file_name().unwrap_or_default().to_string_lossy().to_string();
 
-    // Find if the file already exists in the list
-    if let Some(idx) = files.iter().position(|e| e.path == *path) {
-        // Update existing entry, but preserve selection state
-        let was_selected = files[idx].selected;
-        files[idx] = new_entry;
-        files[idx].selected = was_selected;
-    } else {
-        // Insert new entry in sorted position
-        let insert_pos = files
-            .iter()
-            .position(|e| e.path > *path)
-            .unwrap_or(files.len());
-        files.insert(insert_pos, new_entry);
+    files.insert(
+        parent,
+        FileEntry {
+            path: path.to_path_buf(),
+            rel_path: rel_path.to_path_buf(),
+            name,
+            is_dir: false,
+            depth,
+            status,
+            selected: false,
+            collapsed: false,
+        },
+    );
+
+    Ok(())
+}
+
+/// Bring an overlay directory and everything below it in line with the disk:
+/// entries that appeared are added, those that are gone are dropped and the
+/// rest keep their selection and collapse state. Files created in a new
+/// directory before it was watched are picked up this way, and a directory
+/// removed again before it could be read is dropped.
+pub fn update_or_add_directory(
+    files: &mut FileTree,
+    dir: &Path,
+    overlay_path: &Path,
+    lower: &LowerView,
+) -> io::Result<()> {
+    let rel_path = relative_path(dir, overlay_path)?;
+    // A file replaced by a directory of the same name
+    if let Some(id) = files.find(dir)
+        && files.get(id).is_some_and(|e| !e.is_dir)
+    {
+        files.remove(id);
     }
+    let id = ensure_directory(files, dir, overlay_path, lower);
+    if let Some(entry) = files.get_mut(id) {
+        entry.status = status_for(dir, lower, rel_path);
+    }
+    sync_children(files, id, dir, overlay_path, lower)
+}
 
+fn sync_children(
+    files: &mut FileTree,
+    id: NodeId,
+    dir: &Path,
+    overlay_path: &Path,
+    lower: &LowerView,
+) -> io::Result<()> {
+    let items: Vec<_> = match fs::read_dir(dir) {
+        Ok(items) => items.filter_map(|e| e.ok()).collect(),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            files.remove(id);
+            return Ok(());
+        }
+        Err(e) => return Err(e),
+    };
+    let on_disk: HashSet<PathBuf> = items.iter().map(|e| e.path()).collect();
+    for child in files.children(id).to_vec() {
+        if files.get(child).is_some_and(|e| !on_disk.contains(&e.path)) {
+            files.remove(child);
+        }
+    }
+
+    for item in items {
+        let path = item.path();
+        let is_dir = item.file_type().is_ok_and(|t| t.is_dir());
+        let rel_path = relative_path(&path, overlay_path)?;
+        match files.find(&path) {
+            Some(child) if files.get(child).is_some_and(|e| e.is_dir == is_dir) => {
+                if let Some(entry) = files.get_mut(child) {
+                    entry.status = status_for(&path, lower, rel_path);
+                }
+                if is_dir {
+                    sync_children(files, child, &path, overlay_path, lower)?;
+                }
+            }
+            existing => {
+                if let Some(child) = existing {
+                    files.remove(child);
+                }
+                let depth = rel_path.components().count() - 1;
+                let child = files.insert(
+                    Some(id),
+                    FileEntry {
+                        path: path.clone(),
+                        rel_path: rel_path.to_path_buf(),
+                        name: item.file_name().to_string_lossy().to_string(),
+                        is_dir,
+                        depth,
+                        status: status_for(&path, lower, rel_path),
+                        selected: false,
+                        collapsed: false,
+                    },
+                );
+                if is_dir {
+                    scan_subdirectory(overlay_path, &path, lower, depth + 1, child, files)?;
+                }
+            }
+        }
+    }
     Ok(())
 }
 
-pub fn remove_file_from_list(files: &mut Vec<FileEntry>, path: &Path) -> Option<usize> {
-    if let Some(idx) = files.iter().position(|e| e.path == *path) {
-        files.remove(idx);
-        Some(idx)
-    } else {
-        None
+/// Return the node for an overlay directory, adding it (and any missing
+/// ancestors) to the tree if it isn't there yet.
+fn ensure_directory(
+    files: &mut FileTree,
+    dir: &Path,
+    overlay_path: &Path,
+    lower: &LowerView,
+) -> NodeId {
+    if let Some(id) = files.find(dir) {
+        return id;
+    }
+
+    let parent = match dir.parent() {
+        Some(p) if p != overlay_path => Some(ensure_directory(files, p, overlay_path, lower)),
+        _ => None,
+    };
+
+    let rel_path = dir.strip_prefix(overlay_path).unwrap_or(dir);
+    files.insert(
+        parent,
+        FileEntry {
+            path: dir.to_path_buf(),
+            rel_path: rel_path.to_path_buf(),
+            name: dir.file_name().unwrap_or_default().to_string_lossy().to_string(),
+            is_dir: true,
+            depth: rel_path.components().count() - 1,
+            status: status_for(dir, lower, rel_path),
+            selected: false,
+            collapsed: false,
+        },
+    )
+}
+
+pub fn remove_file_from_list(files: &mut FileTree, path: &Path) -> Option<NodeId> {
+    let id = files.find(path)?;
+    files.remove(id);
+    Some(id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file_entry(overlay: &Path, rel_path: &str) -> FileEntry {
+        FileEntry {
+            path: overlay.join(rel_path),
+            rel_path: PathBuf::from(rel_path),
+            name: rel_path.to_string(),
+            is_dir: false,
+            depth: 0,
+            status: FileStatus::New,
+            selected: false,
+            collapsed: false,
+        }
+    }
+
+    #[test]
+    fn failed_apply_returns_the_files_that_landed_in_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let (overlay, base) = (dir.path().join("overlay"), dir.path().join("base"));
+        fs::create_dir_all(&overlay).unwrap();
+        // A directory in the way of the tenth file
+        fs::create_dir_all(base.join("9.txt/occupied")).unwrap();
+        let entries: Vec<FileEntry> = (0..20)
+            .map(|i| {
+                let rel_path = format!("{}.txt", i);
+                fs::write(overlay.join(&rel_path), rel_path.as_bytes()).unwrap();
+                file_entry(&overlay, &rel_path)
+            })
+            .collect();
+
+        let lower = LowerView::new(base.clone(), Vec::new());
+        let failure = apply_changes(&entries, &overlay, &lower, 4, &mut |_| {}).unwrap_err();
+        let landed: Vec<&Path> = failure.applied.iter().map(|e| e.rel_path.as_path()).collect();
+        let mut expected = landed.clone();
+        expected.sort_by_key(|p| p.to_string_lossy().trim_end_matches(".txt").parse::<usize>().unwrap());
+        assert_eq!(landed, expected);
+        assert!(!landed.contains(&Path::new("9.txt")));
+        for entry in &failure.applied {
+            assert!(base.join(&entry.rel_path).is_file());
+            assert!(!entry.path.exists());
+        }
+    }
+
+    #[test]
+    fn failed_apply_with_one_worker_lands_everything_before_the_failure() {
+        let dir = tempfile::tempdir().unwrap();
+        let (overlay, base) = (dir.path().join("overlay"), dir.path().join("base"));
+        fs::create_dir_all(&overlay).unwrap();
+        fs::create_dir_all(base.join("c/occupied")).unwrap();
+        let entries: Vec<FileEntry> = ["a", "b", "c", "d"]
+            .iter()
+            .map(|name| {
+                fs::write(overlay.join(name), name.as_bytes()).unwrap();
+                file_entry(&overlay, name)
+            })
+            .collect();
+
+        let lower = LowerView::new(base, Vec::new());
+        let failure = apply_changes(&entries, &overlay, &lower, 1, &mut |_| {}).unwrap_err();
+        let landed: Vec<&Path> = failure.applied.iter().map(|e| e.rel_path.as_path()).collect();
+        assert_eq!(landed, [Path::new("a"), Path::new("b")]);
+    }
+
+    #[test]
+    fn directory_removed_before_it_is_read_is_dropped() {
+        let dir = tempfile::tempdir().unwrap();
+        let (overlay, base) = (dir.path().join("overlay"), dir.path().join("base"));
+        fs::create_dir_all(overlay.join("kept/gone")).unwrap();
+        fs::create_dir_all(&base).unwrap();
+        let lower = LowerView::new(base, Vec::new());
+        let mut files = FileTree::new();
+        scan_directory(&overlay, &overlay, &lower, 0, None, &mut files).unwrap();
+        assert!(files.find(&overlay.join("kept/gone")).is_some());
+
+        fs::remove_dir(overlay.join("kept/gone")).unwrap();
+        update_or_add_directory(&mut files, &overlay.join("kept/gone"), &overlay, &lower).unwrap();
+        assert!(files.find(&overlay.join("kept/gone")).is_none());
+        assert!(files.find(&overlay.join("kept")).is_some());
     }
 }
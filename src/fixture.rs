@@ -0,0 +1,148 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Directories per level of a generated tree
+const FANOUT: usize = 8;
+/// One in this many lines of a modified file differs from its base copy
+const CHANGED_LINE_EVERY: usize = 10;
+
+/// How big the files of a generated overlay are
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum SizeDistribution {
+    /// 64 B to 4 KiB, like source trees and config
+    Small,
+    /// 64 B to 1 MiB, mostly small: as many files under 1 KiB as between
+    /// 64 KiB and 1 MiB
+    Mixed,
+    /// 256 KiB to 4 MiB
+    Large,
+}
+
+impl SizeDistribution {
+    fn sample(self, rng: &mut Rng) -> usize {
+        match self {
+            SizeDistribution::Small => rng.between(64, 4 * 1024),
+            SizeDistribution::Mixed => (64f64 * (16384f64).powf(rng.fraction())) as usize,
+            SizeDistribution::Large => rng.between(256 * 1024, 4 * 1024 * 1024),
+        }
+    }
+}
+
+/// What to generate
+#[derive(Debug, Clone)]
+pub struct FixtureSpec {
+    pub files: usize,
+    /// Directory levels the files are spread over, 0 for all at the top
+    pub depth: usize,
+    pub sizes: SizeDistribution,
+    /// Share of the files that modify a base file, rather than being new
+    pub modified_percent: u8,
+    /// The same seed generates the same fixture
+    pub seed: u64,
+}
+
+/// A generated overlay and the base it is reviewed against
+#[derive(Debug)]
+pub struct Fixture {
+    pub overlay: PathBuf,
+    pub base: PathBuf,
+    pub new_files: usize,
+    pub modified_files: usize,
+    /// Size of the overlay's files
+    pub bytes: u64,
+}
+
+/// Generate a synthetic overlay and base under `dir`, e.g. to measure how
+/// long scanning, listing and diffing take. `dir` must not exist yet or be
+/// empty.
+pub fn generate(dir: &Path, spec: &FixtureSpec) -> io::Result<Fixture> {
+    if dir.exists() && fs::read_dir(dir)?.next().is_some() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} is not empty", dir.display()),
+        ));
+    }
+    let overlay = dir.join("overlay");
+    let base = dir.join("base");
+    fs::create_dir_all(&overlay)?;
+    fs::create_dir_all(&base)?;
+
+    let mut rng = Rng(spec.seed);
+    let mut fixture = Fixture { overlay, base, new_files: 0, modified_files: 0, bytes: 0 };
+    for index in 0..spec.files {
+        let rel_path = file_path(index, spec.depth);
+        let size = spec.sizes.sample(&mut rng);
+        let content = text(&mut rng, size);
+        let overlay_file = fixture.overlay.join(&rel_path);
+        fs::create_dir_all(overlay_file.parent().unwrap_or(&fixture.overlay))?;
+        fs::write(&overlay_file, &content)?;
+        fixture.bytes += content.len() as u64;
+
+        if rng.between(0, 100) < spec.modified_percent as usize {
+            let base_file = fixture.base.join(&rel_path);
+            fs::create_dir_all(base_file.parent().unwrap_or(&fixture.base))?;
+            fs::write(&base_file, base_version(&content))?;
+            fixture.modified_files += 1;
+        } else {
+            fixture.new_files += 1;
+        }
+    }
+    Ok(fixture)
+}
+
+/// Where the `index`th file goes: spread evenly over `depth` levels of
+/// directories, FANOUT to a level
+fn file_path(index: usize, depth: usize) -> PathBuf {
+    let mut path = PathBuf::new();
+    let mut rest = index;
+    for level in 0..depth {
+        path.push(format!("dir{}-{}", level, rest % FANOUT));
+        rest /= FANOUT;
+    }
+    path.join(format!("file{:06}.txt", index))
+}
+
+/// Lines of text adding up to about `size` bytes
+fn text(rng: &mut Rng, size: usize) -> String {
+    let mut content = String::with_capacity(size + 64);
+    let mut line = 0;
+    while content.len() < size {
+        content.push_str(&format!("line {} {:016x} {:016x}\n", line, rng.next(), rng.next()));
+        line += 1;
+    }
+    content
+}
+
+/// The base copy of a modified file, with every CHANGED_LINE_EVERY'th line
+/// different
+fn base_version(content: &str) -> String {
+    content
+        .lines()
+        .enumerate()
+        .map(|(n, line)| if n % CHANGED_LINE_EVERY == 0 { format!("old {}\n", line) } else { format!("{}\n", line) })
+        .collect()
+}
+
+/// splitmix64, so fixtures are reproducible without a dependency
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in low..high
+    fn between(&mut self, low: usize, high: usize) -> usize {
+        low + (self.next() % (high - low) as u64) as usize
+    }
+
+    /// Uniform in 0..1
+    fn fraction(&mut self) -> f64 {
+        (self.next() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
@@ -0,0 +1,77 @@
+use crate::types::LowerView;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::Match;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// The base's .gitignore files, read as they are needed. Like git, a pattern
+/// in a deeper .gitignore overrides one further up, and nothing below an
+/// ignored directory is looked at. With several bases, each has its own.
+pub struct GitIgnore {
+    lower: LowerView,
+    /// Matcher for each directory of the base, by overlay path
+    matchers: HashMap<PathBuf, Gitignore>,
+}
+
+impl GitIgnore {
+    pub fn new(lower: &LowerView) -> Self {
+        GitIgnore {
+            lower: lower.clone(),
+            matchers: HashMap::new(),
+        }
+    }
+
+    /// Whether the base's .gitignore files ignore an overlay path, relative to
+    /// the overlay root
+    pub fn is_ignored(&mut self, rel_path: &Path, is_dir: bool) -> bool {
+        let components: Vec<_> = rel_path.components().collect();
+        let mut path = PathBuf::new();
+        for (i, component) in components.iter().enumerate() {
+            let dir = path.clone();
+            path.push(component);
+            // The directories naming the bases are not in any of them
+            if i < self.lower.root_depth() {
+                continue;
+            }
+            // Every component but the last is a directory
+            let last = i + 1 == components.len();
+            if self.matches(&dir, &path, !last || is_dir) {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Whether `path`, directly inside `dir`, is ignored by the .gitignore
+    /// files from the base down to `dir`
+    fn matches(&mut self, dir: &Path, path: &Path, is_dir: bool) -> bool {
+        let full_path = self.lower.base_path(path);
+        let root_depth = self.lower.root_depth();
+        let mut dirs: Vec<&Path> = dir.ancestors().take_while(|d| d.components().count() >= root_depth).collect();
+        dirs.reverse();
+
+        let mut ignored = false;
+        for dir in dirs {
+            match self.matcher(dir).matched(&full_path, is_dir) {
+                Match::Ignore(_) => ignored = true,
+                Match::Whitelist(_) => ignored = false,
+                Match::None => {}
+            }
+        }
+        ignored
+    }
+
+    fn matcher(&mut self, dir: &Path) -> &Gitignore {
+        let lower = &self.lower;
+        self.matchers.entry(dir.to_path_buf()).or_insert_with(|| {
+            let root = lower.base_path(dir);
+            let mut builder = GitignoreBuilder::new(&root);
+            // Unreadable or malformed files just contribute no patterns
+            if dir.components().count() == lower.root_depth() {
+                builder.add(root.join(".git/info/exclude"));
+            }
+            builder.add(root.join(".gitignore"));
+            builder.build().unwrap_or_else(|_| Gitignore::empty())
+        })
+    }
+}
@@ -0,0 +1,71 @@
+use crate::config::HashesConfig;
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Whether a file's content is on one of the lists
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashVerdict {
+    Allowed,
+    Denied,
+}
+
+/// SHA-256 hashes of content known to be fine or bad, e.g. from earlier
+/// audits of the same installer
+#[derive(Debug, Default)]
+pub struct HashLists {
+    allowed: HashSet<String>,
+    denied: HashSet<String>,
+}
+
+impl HashLists {
+    pub fn load(config: &HashesConfig) -> io::Result<Self> {
+        let mut lists = HashLists::default();
+        for path in &config.allowlist {
+            lists.allowed.extend(read_list(path)?);
+        }
+        for path in &config.denylist {
+            lists.denied.extend(read_list(path)?);
+        }
+        Ok(lists)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.allowed.is_empty() && self.denied.is_empty()
+    }
+
+    /// The verdict on a hex SHA-256. Being denied wins over being allowed.
+    pub fn verdict(&self, sha256: &str) -> Option<HashVerdict> {
+        if self.denied.contains(sha256) {
+            Some(HashVerdict::Denied)
+        } else if self.allowed.contains(sha256) {
+            Some(HashVerdict::Allowed)
+        } else {
+            None
+        }
+    }
+}
+
+/// Hashes from a list file, one per line. Lines can be `sha256sum` output,
+/// with the file name after the hash, and `#` starts a comment.
+fn read_list(path: &Path) -> io::Result<HashSet<String>> {
+    let content = fs::read_to_string(path).map_err(|e| {
+        io::Error::new(e.kind(), format!("Failed to read hash list '{}': {}", path.display(), e))
+    })?;
+
+    let mut hashes = HashSet::new();
+    for (number, line) in content.lines().enumerate() {
+        let Some(hash) = line.split('#').next().and_then(|line| line.split_whitespace().next()) else {
+            continue;
+        };
+        if hash.len() != 64 || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Not a SHA-256 hash at {}:{}: {}", path.display(), number + 1, hash),
+            ));
+        }
+        hashes.insert(hash.to_ascii_lowercase());
+    }
+    Ok(hashes)
+}
@@ -0,0 +1,74 @@
+use serde::{Deserialize, Serialize};
+use std::ffi::CStr;
+use std::fmt;
+
+/// Who is reviewing, as recorded with the decisions they make: in the audit
+/// log, apply reports and exported patches
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Identity {
+    pub name: String,
+    pub email: String,
+}
+
+impl Identity {
+    /// The configured name and email, each defaulting to what the system
+    /// knows: the login name, and the login name at the host name
+    pub fn new(name: Option<&str>, email: Option<&str>) -> Self {
+        let user = login_name();
+        Identity {
+            name: name.map_or_else(|| user.clone(), str::to_string),
+            email: email.map_or_else(|| format!("{}@{}", user, host_name()), str::to_string),
+        }
+    }
+}
+
+impl fmt::Display for Identity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} <{}>", self.name, self.email)
+    }
+}
+
+/// The user's login name, from the password database or else $USER. Run
+/// as root through sudo, it is the user who ran sudo rather than root.
+fn login_name() -> String {
+    // SAFETY: getuid cannot fail
+    let uid = unsafe { libc::getuid() };
+    if uid == 0
+        && let Ok(sudo_user) = std::env::var("SUDO_USER")
+        && !sudo_user.is_empty()
+    {
+        return sudo_user;
+    }
+    user_name(uid)
+        .or_else(|| std::env::var("USER").ok())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+/// The name of a user ID in the password database
+pub fn user_name(uid: u32) -> Option<String> {
+    // SAFETY: getpwuid returns a pointer into static storage or null, and
+    // the name is copied out before anything else could overwrite it
+    unsafe {
+        let passwd = libc::getpwuid(uid);
+        (!passwd.is_null()).then(|| CStr::from_ptr((*passwd).pw_name).to_string_lossy().into_owned())
+    }
+}
+
+/// The name of a group ID in the group database
+pub fn group_name(gid: u32) -> Option<String> {
+    // SAFETY: as for getpwuid in user_name
+    unsafe {
+        let group = libc::getgrgid(gid);
+        (!group.is_null()).then(|| CStr::from_ptr((*group).gr_name).to_string_lossy().into_owned())
+    }
+}
+
+fn host_name() -> String {
+    let mut buf = [0u8; 256];
+    // SAFETY: the buffer is valid for its whole length, and one byte is kept
+    // back so the name is always NUL-terminated
+    if unsafe { libc::gethostname(buf.as_mut_ptr().cast(), buf.len() - 1) } != 0 {
+        return "localhost".to_string();
+    }
+    CStr::from_bytes_until_nul(&buf).map_or_else(|_| "localhost".to_string(), |name| name.to_string_lossy().into_owned())
+}
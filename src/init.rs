@@ -0,0 +1,292 @@
+use crate::config;
+use crate::sandbox::{self, SandboxProfile};
+use std::env;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// bwrap gained --overlay in 0.11.0
+const MIN_BWRAP_VERSION: (u32, u32, u32) = (0, 11, 0);
+
+/// Unprivileged overlayfs mounts need Linux 5.11 or later
+const MIN_KERNEL_VERSION: (u32, u32, u32) = (5, 11, 0);
+
+/// Check the environment, write the config file and try out a sandbox
+pub fn run_init() -> io::Result<()> {
+    // bubblewrap and overlayfs are Linux-only; comparing directories works anywhere
+    if !cfg!(target_os = "linux") {
+        println!("The sandbox needs Linux, so 'audit-box run' is not available on this system.");
+        println!("'audit-box review --overlay <dir> --base <dir>' still compares any two directories.");
+        println!();
+        write_config()?;
+        return Ok(());
+    }
+
+    println!("Checking sandbox prerequisites:");
+    let bwrap = check_bwrap();
+    check_fuse_overlayfs();
+    let kernel_ok = check_kernel();
+    println!();
+
+    let profile = write_config()?;
+    println!();
+
+    match bwrap {
+        Some(bwrap) => {
+            print!("Verifying sandbox with a throwaway overlay... ");
+            io::stdout().flush()?;
+            match verify_sandbox(&bwrap, profile) {
+                Ok(()) => println!("ok"),
+                Err(e) => {
+                    println!("failed");
+                    if !kernel_ok {
+                        println!("The kernel checks above are the likely cause.");
+                    }
+                    return Err(io::Error::other(format!("Sandbox verification failed: {}", e)));
+                }
+            }
+            println!();
+            println!("Setup complete. Run 'audit-box new' to create a session.");
+        }
+        None => {
+            println!("Skipping sandbox verification: bubblewrap is not available.");
+            println!(
+                "'audit-box run' needs bubblewrap {} or later.",
+                format_version(MIN_BWRAP_VERSION)
+            );
+        }
+    }
+
+    Ok(())
+}
+
+fn report(ok: bool, message: &str) {
+    let tag = if ok { "ok" } else { "warn" };
+    println!("  [{:<4}] {}", tag, message);
+}
+
+/// Returns the path of a usable bwrap
+fn check_bwrap() -> Option<PathBuf> {
+    let Some(path) = find_in_path("bwrap") else {
+        report(false, "bubblewrap (bwrap) not found in PATH");
+        return None;
+    };
+
+    let output = Command::new(&path).arg("--version").output().ok()?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let Some(version) = stdout.split_whitespace().last().and_then(parse_version) else {
+        report(false, &format!("could not determine bubblewrap version ({})", path.display()));
+        return None;
+    };
+
+    if version < MIN_BWRAP_VERSION {
+        report(
+            false,
+            &format!(
+                "bubblewrap {} is too old, {} or later is needed for --overlay ({})",
+                format_version(version),
+                format_version(MIN_BWRAP_VERSION),
+                path.display()
+            ),
+        );
+        return None;
+    }
+
+    report(true, &format!("bubblewrap {} ({})", format_version(version), path.display()));
+    Some(path)
+}
+
+fn check_fuse_overlayfs() {
+    // Only a fallback for kernels without unprivileged overlayfs
+    match find_in_path("fuse-overlayfs") {
+        Some(path) => report(true, &format!("fuse-overlayfs ({})", path.display())),
+        None => report(false, "fuse-overlayfs not found in PATH (optional)"),
+    }
+}
+
+/// Returns false if any kernel check failed
+fn check_kernel() -> bool {
+    let mut all_ok = true;
+
+    let has_overlay = fs::read_to_string("/proc/filesystems")
+        .map(|content| content.lines().any(|line| line.split_whitespace().last() == Some("overlay")))
+        .unwrap_or(false);
+    report(has_overlay, if has_overlay {
+        "kernel supports overlayfs"
+    } else {
+        "overlayfs not listed in /proc/filesystems"
+    });
+    all_ok &= has_overlay;
+
+    // Debian-style kernels can switch off unprivileged user namespaces
+    let userns_clone = read_proc_number("/proc/sys/kernel/unprivileged_userns_clone").unwrap_or(1);
+    let max_userns = read_proc_number("/proc/sys/user/max_user_namespaces").unwrap_or(1);
+    let userns_ok = userns_clone != 0 && max_userns != 0;
+    report(userns_ok, if userns_ok {
+        "unprivileged user namespaces enabled"
+    } else {
+        "unprivileged user namespaces are disabled"
+    });
+    all_ok &= userns_ok;
+
+    match fs::read_to_string("/proc/sys/kernel/osrelease").ok().and_then(|r| parse_version(r.trim())) {
+        Some(version) if version >= MIN_KERNEL_VERSION => {
+            report(true, &format!("kernel {} supports unprivileged overlay mounts", format_version(version)));
+        }
+        Some(version) => {
+            report(
+                false,
+                &format!(
+                    "kernel {} is older than {}, unprivileged overlay mounts may fail",
+                    format_version(version),
+                    format_version(MIN_KERNEL_VERSION)
+                ),
+            );
+            all_ok = false;
+        }
+        None => report(false, "could not determine kernel version"),
+    }
+
+    all_ok
+}
+
+/// Prompt for the settings and write the config file. Returns the chosen
+/// sandbox profile, or the configured one if the file was kept.
+fn write_config() -> io::Result<SandboxProfile> {
+    let config_path = config::get_config_file_path()?;
+
+    if config_path.exists() {
+        println!("Config file already exists: {}", config_path.display());
+        let overwrite = prompt_choice("Overwrite it?", &["no", "yes"])?;
+        if overwrite == "no" {
+            return Ok(config::load_config()?.run.profile);
+        }
+    }
+
+    let theme = prompt_choice("Colour theme", &["dark", "light"])?;
+    let keymap = prompt_choice("Key bindings", &["default", "vim"])?;
+    let profile = prompt_choice("Sandbox profile for 'audit-box run'", &["default", "offline"])?;
+
+    let content = format!(
+        "# audit-box configuration, generated by 'audit-box init'\n\
+         \n\
+         [ui]\n\
+         # Colour theme: \"dark\" or \"light\"\n\
+         theme = \"{}\"\n\
+         # Key bindings: \"default\" or \"vim\"\n\
+         keymap = \"{}\"\n\
+         \n\
+         [session]\n\
+         # Where 'audit-box new' creates session directories (default $TMPDIR or /tmp)\n\
+         # workdir = \"/var/tmp\"\n\
+         # Warn when the overlay grows past this many MiB (0 disables the warning)\n\
+         size_warning_mb = 1024\n\
+         \n\
+         [run]\n\
+         # Sandbox profile: \"default\" or \"offline\" (no network access)\n\
+         profile = \"{}\"\n\
+         \n\
+         [diff]\n\
+         ignore_whitespace = false\n\
+         ignore_eol = false\n",
+        theme, keymap, profile
+    );
+
+    if let Some(parent) = config_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&config_path, content)?;
+    println!("Wrote config file: {}", config_path.display());
+
+    Ok(if profile == "offline" {
+        SandboxProfile::Offline
+    } else {
+        SandboxProfile::Default
+    })
+}
+
+/// Ask until one of `options` is given. The first option is the default,
+/// which is also taken when stdin is closed.
+fn prompt_choice(question: &str, options: &[&str]) -> io::Result<String> {
+    loop {
+        print!("{} [{}] ({}): ", question, options.join("/"), options[0]);
+        io::stdout().flush()?;
+
+        let mut response = String::new();
+        if io::stdin().read_line(&mut response)? == 0 {
+            println!();
+            return Ok(options[0].to_string());
+        }
+
+        let response = response.trim().to_lowercase();
+        if response.is_empty() {
+            return Ok(options[0].to_string());
+        }
+        if let Some(option) = options.iter().find(|o| **o == response || o.starts_with(&response)) {
+            return Ok(option.to_string());
+        }
+        println!("Please answer one of: {}", options.join(", "));
+    }
+}
+
+/// Write a file inside a sandbox over a scratch base directory and check it
+/// lands in the overlay, leaving the base untouched
+fn verify_sandbox(bwrap: &Path, profile: SandboxProfile) -> io::Result<()> {
+    let tmpdir = tempfile::Builder::new()
+        .prefix("audit-box-init-")
+        .tempdir_in(env::temp_dir())?;
+    let base_path = tmpdir.path().join("base");
+    let overlay_path = tmpdir.path().join("overlay");
+    let work_path = tmpdir.path().join("work");
+    fs::create_dir_all(&base_path)?;
+    fs::create_dir_all(&overlay_path)?;
+    fs::create_dir_all(&work_path)?;
+
+    let probe = base_path.join("probe.txt");
+    let mut args = sandbox::bwrap_args(&base_path, &[], &overlay_path, &work_path, profile);
+    args.push("sh".to_string());
+    args.push("-c".to_string());
+    args.push(format!("echo audit-box > '{}'", probe.display()));
+
+    let output = Command::new(bwrap).args(&args).output()?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(io::Error::other(format!("bwrap exited with {}: {}", output.status, stderr.trim())));
+    }
+
+    if probe.exists() {
+        return Err(io::Error::other("a write inside the sandbox reached the base directory"));
+    }
+    if !overlay_path.join("probe.txt").exists() {
+        return Err(io::Error::other("a write inside the sandbox did not reach the overlay"));
+    }
+
+    // Dropping tmpdir tears the sandbox down
+    Ok(())
+}
+
+fn find_in_path(name: &str) -> Option<PathBuf> {
+    let paths = env::var_os("PATH")?;
+    env::split_paths(&paths)
+        .map(|dir| dir.join(name))
+        .find(|path| path.is_file())
+}
+
+fn read_proc_number(path: &str) -> Option<u64> {
+    fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+/// Parse the leading "major.minor[.patch]" of a version string such as
+/// "0.11.0" or "6.8.0-45-generic"
+fn parse_version(s: &str) -> Option<(u32, u32, u32)> {
+    let mut parts = s.split(|c: char| !c.is_ascii_digit());
+    let major = parts.next()?.parse().ok()?;
+    let minor = parts.next()?.parse().ok()?;
+    let patch = parts.next().and_then(|p| p.parse().ok()).unwrap_or(0);
+    Some((major, minor, patch))
+}
+
+fn format_version((major, minor, patch): (u32, u32, u32)) -> String {
+    format!("{}.{}.{}", major, minor, patch)
+}
@@ -0,0 +1,60 @@
+use crossterm::event::KeyCode;
+use serde::Deserialize;
+
+/// Key binding presets for the file list and content panes
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Keymap {
+    #[default]
+    Default,
+    /// hjkl movement, g/G for first/last, d to discard and : to go to a path
+    Vim,
+}
+
+/// Keys the vim preset adds, and the default binding each one stands for
+const VIM_BINDINGS: [(char, KeyCode); 8] = [
+    ('j', KeyCode::Down),
+    ('k', KeyCode::Up),
+    ('h', KeyCode::Left),
+    ('l', KeyCode::Right),
+    ('g', KeyCode::Home),
+    ('G', KeyCode::End),
+    // 'k' moves up, so discard moves to 'd'
+    ('d', KeyCode::Char('k')),
+    // 'g' jumps to the first entry, so the go-to-path prompt moves to ':'
+    (':', KeyCode::Char('g')),
+];
+
+impl Keymap {
+    /// Map a key from this preset onto the equivalent default binding
+    pub fn translate(self, code: KeyCode) -> KeyCode {
+        match self {
+            Keymap::Default => code,
+            Keymap::Vim => VIM_BINDINGS
+                .iter()
+                .find(|(key, _)| KeyCode::Char(*key) == code)
+                .map_or(code, |(_, action)| *action),
+        }
+    }
+
+    /// Name of the key that triggers a default binding in this preset, for
+    /// key hints
+    pub fn key_name(self, action: KeyCode) -> String {
+        let key = match self {
+            Keymap::Default => action,
+            Keymap::Vim => VIM_BINDINGS
+                .iter()
+                .find(|(_, bound)| *bound == action)
+                .map_or(action, |(key, _)| KeyCode::Char(*key)),
+        };
+        match key {
+            KeyCode::Down => "↓".to_string(),
+            KeyCode::Up => "↑".to_string(),
+            KeyCode::Left => "←".to_string(),
+            KeyCode::Right => "→".to_string(),
+            KeyCode::Char(' ') => "Space".to_string(),
+            KeyCode::Char(c) => c.to_string(),
+            other => other.to_string(),
+        }
+    }
+}
@@ -0,0 +1,60 @@
+//! The review, sessions and reports, shared by the audit-box binary and its
+//! benchmarks
+
+// Modes, symlinks, signals, the review lock and the control socket all use
+// the Unix APIs, which Windows does not have
+#[cfg(not(unix))]
+compile_error!("audit-box builds on Unix only: Linux, or macOS for reviews of plain directories");
+
+mod allowed_roots;
+pub mod app;
+mod applied;
+mod apply_hooks;
+pub mod archive;
+pub mod attribution;
+pub mod audit_log;
+mod blame;
+mod changeset;
+mod checks;
+mod classify;
+pub mod config;
+pub mod control;
+pub mod daemon;
+mod dashboard;
+pub mod desktop_notify;
+mod dir_metadata;
+pub mod disk_usage;
+mod elf_summary;
+pub mod events;
+pub mod export;
+mod file_kind;
+pub mod file_operations;
+pub mod fixture;
+pub mod gitignore;
+mod hash_lists;
+mod identity;
+pub mod init;
+mod keymap;
+pub mod line_review;
+pub mod lock;
+mod minified;
+mod mounts;
+mod normalize;
+pub mod pager;
+mod path_display;
+pub mod plan;
+pub mod report;
+pub mod review_state;
+pub mod sandbox;
+pub mod scan_cache;
+mod services;
+pub mod session;
+pub mod template;
+pub mod terminal;
+pub mod timeline;
+mod transient;
+pub mod types;
+pub mod ui;
+mod validate;
+pub mod watch;
+mod worker;
@@ -0,0 +1,254 @@
+use crate::app::App;
+use crate::file_operations;
+use crate::hash_lists::HashVerdict;
+use crate::types::{FileEntry, FileStatus};
+use std::io::{self, BufRead, Write};
+
+const HELP: &str = "a: apply  d: discard  s or Enter: skip  v: show the diff or content  q: quit";
+
+/// Review the changes one at a time with line-based prompts, for when there
+/// is no terminal the TUI can draw on, e.g. a dumb terminal or output piped
+/// elsewhere. Answers are read a line at a time from `input`; the end of it
+/// ends the review.
+pub fn run(app: &mut App, input: &mut impl BufRead, out: &mut impl Write) -> io::Result<()> {
+    review(app, input, out)?;
+    reload(app, input, out)
+}
+
+fn review(app: &mut App, input: &mut impl BufRead, out: &mut impl Write) -> io::Result<()> {
+    let entries = app.change_entries();
+    let total = entries.len();
+    writeln!(out, "No terminal for the review screen, so reviewing line by line ({})", HELP)?;
+    if app.read_only {
+        writeln!(out, "Read-only review: applying and discarding are disabled")?;
+    }
+
+    for (i, entry) in entries.iter().enumerate() {
+        // An earlier answer may have taken it along, e.g. discarding a
+        // directory, or the sandbox may have removed it meanwhile
+        app.check_fs_events();
+        app.process_pending_updates();
+        if app.files.find(&entry.path).is_none() {
+            continue;
+        }
+
+        writeln!(out, "\n[{}/{}] {}", i + 1, total, summary(app, entry))?;
+        loop {
+            write!(out, "Apply, discard, skip, view or quit? [a/d/s/v/q] ")?;
+            out.flush()?;
+            let Some(answer) = read_answer(input)? else {
+                writeln!(out)?;
+                return Ok(());
+            };
+            match answer.as_str() {
+                "a" | "apply" => {
+                    apply(app, entry, input, out)?;
+                    break;
+                }
+                "d" | "discard" => {
+                    match app.discard_paths(std::slice::from_ref(&entry.rel_path)) {
+                        Ok(()) => writeln!(out, "Discarded")?,
+                        Err(e) => writeln!(out, "Error: {}", e)?,
+                    }
+                    break;
+                }
+                "s" | "skip" | "" => break,
+                "v" | "view" => view(app, entry, out)?,
+                "q" | "quit" => return Ok(()),
+                _ => writeln!(out, "{}", HELP)?,
+            }
+        }
+    }
+    writeln!(out, "\nNo more changes")
+}
+
+/// One line about a change: its status, path and what to look out for,
+/// e.g. "M  etc/hosts  (+2 -1) [setuid]"
+fn summary(app: &App, entry: &FileEntry) -> String {
+    let mut line = format!("{}  {}", entry.status.letter(), app.display_path(&entry.rel_path));
+    if entry.is_dir {
+        line.push('/');
+    } else if entry.status != FileStatus::Deleted {
+        let base_file = app.lower.resolve(&entry.rel_path);
+        let (added, removed) = file_operations::diff_stats(&entry.path, base_file.as_deref());
+        line.push_str(&format!("  (+{} -{})", added, removed));
+    }
+    if let Some(privileges) = app.privileges(entry) {
+        line.push_str(&format!(" [{}]", privileges.describe()));
+    }
+    match app.hash_verdict(entry) {
+        Some(HashVerdict::Allowed) => line.push_str(" [known good]"),
+        Some(HashVerdict::Denied) => line.push_str(" [DENYLISTED]"),
+        None => {}
+    }
+    line
+}
+
+/// Apply a change, asking again first if it grants privileges, as the
+/// apply dialog does. Only the entry itself is applied: a directory's own
+/// mode and xattrs, not what is under it, which comes up on its own.
+fn apply(app: &mut App, entry: &FileEntry, input: &mut impl BufRead, out: &mut impl Write) -> io::Result<()> {
+    let rel_paths = std::slice::from_ref(&entry.rel_path);
+    let result = match app.privileges(entry) {
+        Some(privileges) => {
+            write!(
+                out,
+                "It is {} and keeps that in the base. Type yes to apply it anyway: ",
+                privileges.describe()
+            )?;
+            out.flush()?;
+            if read_answer(input)?.as_deref() != Some("yes") {
+                return writeln!(out, "Not applied");
+            }
+            app.apply_confirmed_entries(rel_paths)
+        }
+        None => app.apply_entries(rel_paths),
+    };
+    match result {
+        Ok(_) => writeln!(out, "Applied"),
+        Err(e) => writeln!(out, "Error: {}", e),
+    }
+}
+
+/// Offer to run the reloads the applied files call for, as the reload
+/// dialog does
+fn reload(app: &mut App, input: &mut impl BufRead, out: &mut impl Write) -> io::Result<()> {
+    if app.pending_reloads.is_empty() {
+        return Ok(());
+    }
+    writeln!(out, "\nThe files applied need these run to take effect:")?;
+    for action in &app.pending_reloads {
+        writeln!(out, "  {}  (for {})", action.command_line(), app.display_path(&action.because))?;
+    }
+    write!(out, "Run them now? [y/n] ")?;
+    out.flush()?;
+    if !matches!(read_answer(input)?.as_deref(), Some("y" | "yes")) {
+        writeln!(out, "Not run")?;
+        return app.skip_reloads();
+    }
+    for result in app.run_reloads()? {
+        writeln!(out, "{}: {}", result.name, result.outcome)?;
+        if !result.passed {
+            for line in &result.output {
+                writeln!(out, "  {}", line)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Print what the content pane would show: the diff of a modified file, the
+/// content of a new one
+fn view(app: &App, entry: &FileEntry, out: &mut impl Write) -> io::Result<()> {
+    if entry.is_dir {
+        return writeln!(out, "{} is a directory", app.display_path(&entry.rel_path));
+    }
+    match app.content_of(&entry.rel_path) {
+        Ok(lines) => {
+            for line in lines {
+                writeln!(out, "{}", line)?;
+            }
+            Ok(())
+        }
+        Err(e) => writeln!(out, "Error: {}", e),
+    }
+}
+
+/// The next answer, trimmed and lowercased, or None at the end of input
+fn read_answer(input: &mut impl BufRead) -> io::Result<Option<String>> {
+    let mut answer = String::new();
+    if input.read_line(&mut answer)? == 0 {
+        return Ok(None);
+    }
+    Ok(Some(answer.trim().to_lowercase()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::types::LowerView;
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+    use std::path::Path;
+    use std::sync::mpsc::channel;
+
+    /// Review `overlay` against `base` answering with `answers`, returning
+    /// what was printed
+    fn review_with(overlay: &Path, base: &Path, answers: &str) -> String {
+        let (_tx, rx) = channel();
+        let lower = LowerView::new(base.to_path_buf(), Vec::new());
+        let mut app = App::new(overlay, lower, rx, &Config::default(), None).unwrap();
+        let mut out = Vec::new();
+        run(&mut app, &mut answers.as_bytes(), &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    fn overlay_and_base() -> (tempfile::TempDir, std::path::PathBuf, std::path::PathBuf) {
+        let dir = tempfile::tempdir().unwrap();
+        let (overlay, base) = (dir.path().join("overlay"), dir.path().join("base"));
+        fs::create_dir_all(&overlay).unwrap();
+        fs::create_dir_all(&base).unwrap();
+        (dir, overlay, base)
+    }
+
+    #[test]
+    fn answers_apply_discard_and_skip_each_change_in_turn() {
+        let (_dir, overlay, base) = overlay_and_base();
+        fs::write(base.join("a.txt"), "old\n").unwrap();
+        fs::write(overlay.join("a.txt"), "new\n").unwrap();
+        fs::write(overlay.join("b.txt"), "junk\n").unwrap();
+        fs::write(overlay.join("c.txt"), "later\n").unwrap();
+
+        let out = review_with(&overlay, &base, "a\nd\ns\n");
+
+        assert!(out.contains("[1/3] M  a.txt  (+1 -1)"));
+        assert!(out.contains("No more changes"));
+        assert_eq!(fs::read_to_string(base.join("a.txt")).unwrap(), "new\n");
+        assert!(!overlay.join("b.txt").exists() && !base.join("b.txt").exists());
+        assert!(overlay.join("c.txt").exists() && !base.join("c.txt").exists());
+    }
+
+    #[test]
+    fn quitting_leaves_the_rest_alone() {
+        let (_dir, overlay, base) = overlay_and_base();
+        fs::write(overlay.join("a.txt"), "new\n").unwrap();
+        fs::write(overlay.join("b.txt"), "new\n").unwrap();
+
+        let out = review_with(&overlay, &base, "s\nq\n");
+
+        assert!(!out.contains("No more changes"));
+        assert!(overlay.join("a.txt").exists() && overlay.join("b.txt").exists());
+        assert!(!base.join("a.txt").exists() && !base.join("b.txt").exists());
+    }
+
+    #[test]
+    fn applying_a_directory_applies_only_its_own_mode() {
+        let (_dir, overlay, base) = overlay_and_base();
+        fs::create_dir(base.join("conf.d")).unwrap();
+        fs::set_permissions(base.join("conf.d"), fs::Permissions::from_mode(0o755)).unwrap();
+        fs::create_dir(overlay.join("conf.d")).unwrap();
+        fs::write(overlay.join("conf.d/site.conf"), "listen 80;\n").unwrap();
+        fs::set_permissions(overlay.join("conf.d"), fs::Permissions::from_mode(0o700)).unwrap();
+
+        review_with(&overlay, &base, "a\ns\n");
+
+        assert_eq!(fs::metadata(base.join("conf.d")).unwrap().permissions().mode() & 0o7777, 0o700);
+        assert!(!base.join("conf.d/site.conf").exists());
+        assert!(overlay.join("conf.d/site.conf").exists());
+    }
+
+    #[test]
+    fn a_setuid_file_is_only_applied_once_confirmed() {
+        let (_dir, overlay, base) = overlay_and_base();
+        fs::write(overlay.join("tool"), "#!/bin/sh\n").unwrap();
+        fs::set_permissions(overlay.join("tool"), fs::Permissions::from_mode(0o4755)).unwrap();
+
+        let out = review_with(&overlay, &base, "a\nno\n");
+        assert!(out.contains("Not applied"));
+        assert!(!base.join("tool").exists());
+
+        review_with(&overlay, &base, "a\nyes\n");
+        assert!(base.join("tool").exists());
+    }
+}
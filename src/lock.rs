@@ -0,0 +1,62 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::Duration;
+
+/// File name of the review lock, stored in the session directory
+pub const LOCK_FILE: &str = "review.lock";
+
+/// Held by the review of a session for as long as it runs, so a second
+/// review cannot apply or discard the same files. The file is locked with
+/// `flock`, which the kernel releases when the holder exits however it
+/// exits, so there is never a stale lock to take over.
+#[derive(Debug)]
+pub struct SessionLock {
+    _file: File,
+}
+
+impl SessionLock {
+    /// Take the session's lock, or return the pid of the review that holds
+    /// it
+    pub fn acquire(session_dir: &Path) -> io::Result<Result<SessionLock, u32>> {
+        use std::os::fd::AsRawFd;
+
+        let path = session_dir.join(LOCK_FILE);
+        let mut file = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(&path)?;
+        // SAFETY: the descriptor is open for as long as the call takes
+        if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } < 0 {
+            let e = io::Error::last_os_error();
+            if e.raw_os_error() != Some(libc::EWOULDBLOCK) {
+                return Err(e);
+            }
+            return Ok(Err(holder(&path)));
+        }
+        file.set_len(0)?;
+        writeln!(file, "{}", std::process::id())?;
+        Ok(Ok(SessionLock { _file: file }))
+    }
+}
+
+/// The pid the holder of the lock wrote into it, waiting a moment for one
+/// that has only just taken it to do so
+fn holder(path: &Path) -> u32 {
+    for _ in 0..10 {
+        if let Some(pid) = fs::read_to_string(path).ok().and_then(|s| s.trim().parse().ok()) {
+            return pid;
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    0
+}
+
+/// Whether a process exists, even if it belongs to another user
+pub fn is_alive(pid: u32) -> bool {
+    let Ok(pid) = libc::pid_t::try_from(pid) else {
+        return false;
+    };
+    if pid <= 0 {
+        return false;
+    }
+    // SAFETY: signal 0 only checks whether the process can be signalled
+    unsafe { libc::kill(pid, 0) == 0 || io::Error::last_os_error().raw_os_error() == Some(libc::EPERM) }
+}
@@ -1,26 +1,34 @@
-mod app;
-mod file_operations;
-mod session;
-mod types;
-mod ui;
-
-use app::App;
-use clap::Parser;
-use crossterm::{
-    event::{self, Event, KeyCode},
-    execute,
-    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+use audit_box::{
+    app, archive, attribution, audit_log, config, control, daemon, desktop_notify, disk_usage, events, export, file_operations,
+    fixture, gitignore, init, line_review, lock, pager, plan, report, review_state, sandbox, scan_cache, session, template,
+    terminal, timeline, types, ui, watch,
 };
-use notify::{Config, RecommendedWatcher, RecursiveMode, Watcher};
+use app::{describe_entries, App, Modal};
+use clap::Parser;
+use crossterm::event::{Event, KeyCode, KeyModifiers};
+use events::{Events, Wake};
 use ratatui::{
     backend::CrosstermBackend,
     layout::{Constraint, Direction, Layout},
-    Terminal,
+    Frame, Terminal,
 };
-use std::io;
-use std::path::PathBuf;
+use std::collections::BTreeMap;
+use std::io::{self, IsTerminal};
+use std::path::{Path, PathBuf};
+use chrono::Local;
+use export::PatchGrouping;
+use fixture::{FixtureSpec, SizeDistribution};
+use gitignore::GitIgnore;
+use globset::{GlobBuilder, GlobSetBuilder};
+use report::ReportFormat;
+use sandbox::SandboxProfile;
+use session::RunRecord;
+use std::os::unix::process::ExitStatusExt;
 use std::sync::mpsc::channel;
-use types::{ActivePane, DialogButton};
+use std::time::{Duration, Instant};
+use types::{ActivePane, DialogButton, FileTree, HiddenFiles, LowerView, QuitChoice};
+use ui::input::InputEvent;
+use watch::OverlayWatcher;
 
 #[derive(Parser, Debug)]
 #[command(name = "audit-box")]
@@ -30,20 +38,50 @@ struct Args {
     command: Commands,
 }
 
+/// Help for `--lower`, which every command that takes a base also takes
+const LOWER_HELP: &str = "Extra lower layer stacked on the base, e.g. another session's overlay (repeatable, bottom to top)";
+
 #[derive(clap::Subcommand, Debug)]
 enum Commands {
+    /// Check sandbox prerequisites and create the config file
+    Init,
     /// Create a new audit-box session with temporary overlay directories
     New {
-        /// Path to the base filesystem directory (defaults to current directory)
+        /// Path to the base filesystem directory (defaults to current directory; repeatable, e.g. /etc and $HOME)
         #[arg(long)]
-        base: Option<PathBuf>,
+        base: Vec<PathBuf>,
+
+        /// Directory to create the session in (defaults to the configured workdir, or the system temp directory)
+        #[arg(long)]
+        workdir: Option<PathBuf>,
+
+        #[arg(long = "lower", value_name = "PATH", help = LOWER_HELP)]
+        lower_layers: Vec<PathBuf>,
+
+        /// Directory whose files are copied into the overlay before anything runs, e.g. config to test
+        #[arg(long, value_name = "DIR")]
+        template: Option<PathBuf>,
     },
     /// Run a command in bubblewrap using the current session
     Run {
+        /// Sandbox profile (defaults to the configured profile)
+        #[arg(long, value_enum)]
+        profile: Option<SandboxProfile>,
+
+        /// Record which processes write each overlay file (Linux, needs CAP_SYS_ADMIN)
+        #[arg(long)]
+        attribute: bool,
+
         /// Command and arguments to run in bubblewrap
         #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
         command: Vec<String>,
     },
+    /// Supervise the current session: run the sandbox, keep watching and serve a control socket
+    Daemon {
+        /// Arguments for `audit-box run`, e.g. `-- --profile strict make`; no sandbox is started without them
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        run_args: Vec<String>,
+    },
     /// Review and manage overlay filesystem changes
     Review {
         /// Path to the overlay filesystem directory (uses saved session if not specified)
@@ -53,64 +91,427 @@ enum Commands {
         /// Path to the base filesystem directory (uses saved session if not specified)
         #[arg(long)]
         base: Option<PathBuf>,
+
+        #[arg(long = "lower", value_name = "PATH", requires = "base", help = LOWER_HELP)]
+        lower_layers: Vec<PathBuf>,
+
+        /// Hide paths the base's .gitignore files ignore (I shows them)
+        #[arg(long)]
+        gitignore: bool,
+
+        /// Show, hide or list last the overlay's dotfiles and hidden directories (. switches)
+        #[arg(long, value_enum)]
+        hidden_files: Option<HiddenFiles>,
+
+        /// Look at the session without applying or discarding, e.g. while another review has it open
+        #[arg(long)]
+        read_only: bool,
+
+        /// Let editors list, diff, apply and discard changes over JSON-RPC on this Unix socket
+        #[arg(long, value_name = "SOCKET")]
+        listen: Option<PathBuf>,
+
+        /// Review even if the overlay and base are the same directory or inside one another
+        #[arg(long)]
+        force: bool,
+
+        /// Look at every entry afresh instead of reusing what the last review of the session found
+        #[arg(long)]
+        rescan: bool,
+
+        /// Apply outside the config's apply.allowed_roots all the same, recording it in the audit log
+        #[arg(long)]
+        allow_outside_roots: bool,
+    },
+    /// Print the overlay changes without starting the TUI
+    List {
+        /// Path to the overlay filesystem directory (uses saved session if not specified)
+        #[arg(long)]
+        overlay: Option<PathBuf>,
+
+        /// Path to the base filesystem directory (uses saved session if not specified)
+        #[arg(long)]
+        base: Option<PathBuf>,
+
+        #[arg(long = "lower", value_name = "PATH", requires = "base", help = LOWER_HELP)]
+        lower_layers: Vec<PathBuf>,
+
+        /// Output format
+        #[arg(long, value_enum, default_value_t = ReportFormat::Text)]
+        format: ReportFormat,
+
+        /// Include file sizes and added/removed line counts
+        #[arg(long)]
+        stats: bool,
+
+        /// Only list the files of this changeset (uses the saved session's changesets)
+        #[arg(long)]
+        changeset: Option<String>,
+    },
+    /// Export the overlay changes as a git patch series
+    Export {
+        /// Path to the overlay filesystem directory (uses saved session if not specified)
+        #[arg(long)]
+        overlay: Option<PathBuf>,
+
+        /// Path to the base filesystem directory (uses saved session if not specified)
+        #[arg(long)]
+        base: Option<PathBuf>,
+
+        #[arg(long = "lower", value_name = "PATH", requires = "base", help = LOWER_HELP)]
+        lower_layers: Vec<PathBuf>,
+
+        /// How to split the changes into patches
+        #[arg(long, value_enum, default_value_t = PatchGrouping::Single)]
+        group_by: PatchGrouping,
+
+        /// Only export files with this label (uses the saved session's labels)
+        #[arg(long)]
+        label: Option<String>,
+
+        /// Only export the files of this changeset, as one patch described by it (uses the saved session's changesets)
+        #[arg(long)]
+        changeset: Option<String>,
+
+        /// Directory to write numbered patch files to (prints the series to stdout if not specified)
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+
+        /// Print the series straight to stdout, rather than through $PAGER when it is a terminal
+        #[arg(long)]
+        no_pager: bool,
+
+        /// Only export changes under these paths, relative to the overlay root
+        paths: Vec<PathBuf>,
+    },
+    /// Discard overlay paths without starting the TUI, e.g. build output before a review
+    Discard {
+        /// Path to the overlay filesystem directory (uses saved session if not specified)
+        #[arg(long)]
+        overlay: Option<PathBuf>,
+
+        /// Path to the base filesystem directory (uses saved session if not specified)
+        #[arg(long)]
+        base: Option<PathBuf>,
+
+        #[arg(long = "lower", value_name = "PATH", requires = "base", help = LOWER_HELP)]
+        lower_layers: Vec<PathBuf>,
+
+        /// Also discard the paths the base's .gitignore files ignore, which `review --gitignore` hides
+        #[arg(long)]
+        gitignore: bool,
+
+        /// Discard without listing the paths and asking first
+        #[arg(long, short)]
+        yes: bool,
+
+        /// Globs of paths to discard, relative to the overlay root, e.g. 'target/**'; a directory goes with everything under it
+        #[arg(required_unless_present = "gitignore")]
+        patterns: Vec<String>,
+    },
+    /// Carry out the decisions written from a review with W, e.g. in CI
+    Apply {
+        /// Path to the overlay filesystem directory (uses saved session if not specified)
+        #[arg(long)]
+        overlay: Option<PathBuf>,
+
+        /// Path to the base filesystem directory (uses saved session if not specified)
+        #[arg(long)]
+        base: Option<PathBuf>,
+
+        #[arg(long = "lower", value_name = "PATH", requires = "base", help = LOWER_HELP)]
+        lower_layers: Vec<PathBuf>,
+
+        /// JSON file listing paths with an apply, discard or skip verdict
+        #[arg(long, value_name = "FILE")]
+        plan: PathBuf,
+
+        /// Check the plan against the overlay and print what it would do, without doing it
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Apply outside the config's apply.allowed_roots all the same, recording it in the audit log
+        #[arg(long)]
+        allow_outside_roots: bool,
     },
     /// Delete the current session directory and clear the session file
     Delete,
+    /// Hand a session over to another machine
+    #[command(subcommand)]
+    Session(SessionCommand),
+    /// Tools for developing audit-box
+    #[command(hide = true, subcommand)]
+    Dev(DevCommand),
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum SessionCommand {
+    /// Bundle the current session's overlay, review state and audit log into a .tar.gz archive
+    Export {
+        /// Archive to write
+        file: PathBuf,
+    },
+    /// Unpack a session archive into a new session and make it the current one
+    Import {
+        /// Archive written by `audit-box session export`
+        file: PathBuf,
+
+        /// Directory to create the session in (defaults to the configured workdir, or the system temp directory)
+        #[arg(long)]
+        workdir: Option<PathBuf>,
+    },
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum DevCommand {
+    /// Generate a synthetic overlay and base, e.g. to measure scanning and diffing
+    Fixture {
+        /// Directory to create `overlay` and `base` in; must not exist yet or be empty
+        dir: PathBuf,
+
+        /// Number of files in the overlay
+        #[arg(long, default_value_t = 1000)]
+        files: usize,
+
+        /// Directory levels to spread the files over, 8 directories to a level
+        #[arg(long, default_value_t = 3)]
+        depth: usize,
+
+        /// How big the files are
+        #[arg(long, value_enum, default_value_t = SizeDistribution::Small)]
+        sizes: SizeDistribution,
+
+        /// Percentage of the files that modify a base file rather than being new
+        #[arg(long, default_value_t = 50, value_parser = clap::value_parser!(u8).range(0..=100))]
+        modified: u8,
+
+        /// Seed for the generated content; the same seed gives the same fixture
+        #[arg(long, default_value_t = 1)]
+        seed: u64,
+    },
+}
+
+/// Overlay and base paths to work on, given explicitly or taken from the saved session
+struct Target {
+    overlay_path: PathBuf,
+    lower: LowerView,
+    /// Session directory, when using the saved session
+    session_dir: Option<PathBuf>,
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let args = Args::parse();
 
     match args.command {
-        Commands::New { base } => {
-            run_new(base)?;
+        Commands::Init => {
+            init::run_init()?;
+        }
+        Commands::New { base, workdir, lower_layers, template } => {
+            run_new(base, workdir, lower_layers, template)?;
+        }
+        Commands::Run { profile, attribute, command } => {
+            run_run(profile, attribute, command)?;
+        }
+        Commands::Daemon { run_args } => {
+            let Target { overlay_path, lower, session_dir } = resolve_target(None, None, Vec::new())?;
+            let session_dir = session_dir.ok_or("The daemon needs a saved session")?;
+            daemon::run_daemon(&overlay_path, lower, &session_dir, run_args)?;
         }
-        Commands::Run { command } => {
-            run_run(command)?;
+        Commands::Review {
+            overlay,
+            base,
+            lower_layers,
+            gitignore,
+            hidden_files,
+            read_only,
+            listen,
+            force,
+            rescan,
+            allow_outside_roots,
+        } => {
+            let target = resolve_target(overlay, base, lower_layers)?;
+            if !force {
+                check_nesting(&target)?;
+            }
+            if rescan && let Some(dir) = &target.session_dir {
+                scan_cache::clear(dir)?;
+            }
+            run_review(target, gitignore, hidden_files, read_only, listen, allow_outside_roots)?;
+        }
+        Commands::List { overlay, base, lower_layers, format, stats, changeset } => {
+            run_list(overlay, base, lower_layers, format, stats, changeset)?;
         }
-        Commands::Review { overlay, base } => {
-            run_review(overlay, base)?;
+        Commands::Export { overlay, base, lower_layers, group_by, label, changeset, output, no_pager, paths } => {
+            let target = resolve_target(overlay, base, lower_layers)?;
+            run_export(target, group_by, label, changeset, output, no_pager, paths)?;
+        }
+        Commands::Discard { overlay, base, lower_layers, gitignore, yes, patterns } => {
+            let target = resolve_target(overlay, base, lower_layers)?;
+            run_discard(target, &patterns, gitignore, yes)?;
+        }
+        Commands::Apply { overlay, base, lower_layers, plan, dry_run, allow_outside_roots } => {
+            let target = resolve_target(overlay, base, lower_layers)?;
+            run_apply_plan(target, &plan, dry_run, allow_outside_roots).map_err(|e| e.to_string())?;
         }
         Commands::Delete => {
             run_delete()?;
         }
+        Commands::Session(SessionCommand::Export { file }) => {
+            let session = session::load_session()?;
+            archive::export(&session, &file)?;
+            println!("Exported session {} to {}", session.tmpdir.display(), file.display());
+        }
+        Commands::Session(SessionCommand::Import { file, workdir }) => {
+            run_import(file, workdir)?;
+        }
+        Commands::Dev(DevCommand::Fixture { dir, files, depth, sizes, modified, seed }) => {
+            let spec = FixtureSpec { files, depth, sizes, modified_percent: modified, seed };
+            let fixture = fixture::generate(&dir, &spec).map_err(|e| e.to_string())?;
+            println!(
+                "Generated {} files ({} new, {} modified, {})",
+                fixture.new_files + fixture.modified_files,
+                fixture.new_files,
+                fixture.modified_files,
+                disk_usage::format_size(fixture.bytes)
+            );
+            println!(
+                "Review with: audit-box review --overlay {} --base {}",
+                fixture.overlay.display(),
+                fixture.base.display()
+            );
+        }
     }
 
     Ok(())
 }
 
-fn run_new(base: Option<PathBuf>) -> Result<(), Box<dyn std::error::Error>> {
-    // Resolve base path
-    let base_path = base.unwrap_or_else(|| std::env::current_dir().expect("Failed to get current directory"));
+/// Free space below which `new` warns about the session location
+const LOW_SPACE_WARNING: u64 = 1024 * 1024 * 1024;
+
+fn run_new(
+    base: Vec<PathBuf>,
+    workdir: Option<PathBuf>,
+    lower_layers: Vec<PathBuf>,
+    template: Option<PathBuf>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    // Resolve base paths
+    let bases = if base.is_empty() {
+        vec![std::env::current_dir().expect("Failed to get current directory")]
+    } else {
+        base
+    };
 
-    // Check if base path exists
-    if !base_path.exists() {
-        return Err(format!("Base path '{}' does not exist", base_path.display()).into());
+    // Check if base paths exist
+    for base_path in &bases {
+        if !base_path.exists() {
+            return Err(format!("Base path '{}' does not exist", base_path.display()).into());
+        }
+    }
+    check_lower_layers(&lower_layers)?;
+    // The session outlives the current directory, so keep canonical paths
+    let bases = bases.into_iter().map(std::fs::canonicalize).collect::<Result<Vec<_>, _>>()?;
+    let lower_layers = lower_layers.into_iter().map(std::fs::canonicalize).collect::<Result<Vec<_>, _>>()?;
+    if let Some(template) = &template
+        && !template.is_dir()
+    {
+        return Err(format!("Template '{}' is not a directory", template.display()).into());
     }
+    // With several bases the overlay root holds one directory per base
+    let roots = if bases.len() > 1 {
+        if !lower_layers.is_empty() || template.is_some() {
+            return Err("--lower and --template need a single --base".into());
+        }
+        session::root_names(&bases)
+    } else {
+        BTreeMap::new()
+    };
+    let base_path = &bases[0];
 
     // Create the session directories
-    let tmpdir = session::create_session_dir()?;
+    let config = config::load_config()?;
+    let workdir = workdir
+        .or(config.session.workdir)
+        .unwrap_or_else(session::default_workdir);
+    let tmpdir = session::create_session_dir(&workdir)?;
 
     // Save the session
-    session::save_session(&tmpdir, &base_path)?;
+    session::save_session(&tmpdir, base_path, &lower_layers)?;
+    if !roots.is_empty() {
+        for name in roots.keys() {
+            std::fs::create_dir_all(tmpdir.join("overlay").join(name))?;
+            std::fs::create_dir_all(tmpdir.join("work").join(name))?;
+        }
+        let mut metadata = session::load_metadata(&tmpdir)?;
+        metadata.roots = roots.clone();
+        session::save_metadata(&tmpdir, &metadata)?;
+    }
+
+    // Seed the overlay, remembering the seeded files so the review can tell
+    // them apart from what the sandbox produces
+    let mut seeded_count = None;
+    if let Some(template) = &template {
+        let seeded = template::seed(template, &tmpdir.join("overlay"))
+            .map_err(|e| format!("Failed to copy template '{}': {}", template.display(), e))?;
+        seeded_count = Some(seeded.len());
+        let mut metadata = session::load_metadata(&tmpdir)?;
+        metadata.template = Some(template.clone());
+        metadata.seeded = seeded;
+        session::save_metadata(&tmpdir, &metadata)?;
+    }
 
     println!("Created new audit-box session:");
     println!("  Session directory: {}", tmpdir.display());
     println!("  Overlay directory: {}", tmpdir.join("overlay").display());
     println!("  Work directory: {}", tmpdir.join("work").display());
-    println!("  Base filesystem: {}", base_path.display());
+    if roots.is_empty() {
+        println!("  Base filesystem: {}", base_path.display());
+    }
+    for (name, root) in &roots {
+        println!("  Base filesystem: {} (overlay/{})", root.display(), name);
+    }
+    for layer in &lower_layers {
+        println!("  Lower layer: {}", layer.display());
+    }
+    if let (Some(template), Some(count)) = (&template, seeded_count) {
+        println!("  Template: {} ({} file{})", template.display(), count, if count == 1 { "" } else { "s" });
+    }
     println!();
+
+    // Sandbox runs that fill the overlay fail part way through
+    if let Ok(available) = session::available_space(&tmpdir)
+        && available < LOW_SPACE_WARNING
+    {
+        println!(
+            "Warning: only {} MiB free in '{}'. Use --workdir to put the session on a larger disk.",
+            available / (1024 * 1024),
+            workdir.display()
+        );
+        println!();
+    }
+
     println!("You can now use 'audit-box review' to review changes.");
     println!();
     println!("To use this session with bubblewrap:");
     println!("  bwrap --ro-bind / / \\");
     println!("        --tmpfs /tmp \\");
     println!("        --unshare-pid \\");
-    println!("        --overlay-src {} \\", base_path.display());
-    println!("        --overlay {} {} {} \\",
-             tmpdir.join("overlay").display(),
-             tmpdir.join("work").display(),
-             base_path.display());
+    if roots.is_empty() {
+        println!("        --overlay-src {} \\", base_path.display());
+        for layer in &lower_layers {
+            println!("        --overlay-src {} \\", layer.display());
+        }
+        println!("        --overlay {} {} {} \\",
+                 tmpdir.join("overlay").display(),
+                 tmpdir.join("work").display(),
+                 base_path.display());
+    }
+    for (name, root) in &roots {
+        println!("        --overlay-src {} \\", root.display());
+        println!("        --overlay {} {} {} \\",
+                 tmpdir.join("overlay").join(name).display(),
+                 tmpdir.join("work").join(name).display(),
+                 root.display());
+    }
     println!("        --dev /dev \\");
     println!("        --new-session \\");
     println!("        /bin/bash");
@@ -118,6 +519,185 @@ fn run_new(base: Option<PathBuf>) -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
+fn run_discard(target: Target, patterns: &[String], gitignore: bool, yes: bool) -> Result<(), Box<dyn std::error::Error>> {
+    use std::io::Write;
+
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        let glob = GlobBuilder::new(pattern.trim_matches('/'))
+            .literal_separator(true)
+            .build()
+            .map_err(|e| format!("Invalid pattern '{}': {}", pattern, e))?;
+        builder.add(glob);
+    }
+    let globs = builder.build()?;
+
+    // Like applying and discarding in a review, only while no review has the session
+    let _lock = match &target.session_dir {
+        Some(dir) => match lock::SessionLock::acquire(dir)? {
+            Ok(lock) => Some(lock),
+            Err(pid) => {
+                return Err(format!("Session is being reviewed (pid {}). Discard from that review instead.", pid).into());
+            }
+        },
+        None => None,
+    };
+
+    let mut files = FileTree::new();
+    file_operations::scan_directory(&target.overlay_path, &target.overlay_path, &target.lower, 0, None, &mut files)?;
+    let mut ignore = gitignore.then(|| GitIgnore::new(&target.lower));
+
+    // A matching directory is discarded whole, so nothing under it is looked at
+    let mut chosen: Vec<&types::FileEntry> = Vec::new();
+    for (_, entry) in files.iter() {
+        if chosen.iter().any(|dir| dir.is_dir && entry.rel_path.starts_with(&dir.rel_path)) {
+            continue;
+        }
+        // The directories of a session's bases are mounted, not changes
+        if entry.rel_path.components().count() <= target.lower.root_depth() {
+            continue;
+        }
+        let ignored = ignore.as_mut().is_some_and(|ignore| ignore.is_ignored(&entry.rel_path, entry.is_dir));
+        if ignored || globs.is_match(&entry.rel_path) {
+            chosen.push(entry);
+        }
+    }
+    if chosen.is_empty() {
+        println!("Nothing to discard");
+        return Ok(());
+    }
+
+    if !yes {
+        for entry in &chosen {
+            println!("  {}{}", entry.rel_path.display(), if entry.is_dir { "/" } else { "" });
+        }
+        print!("Discard {} from the overlay? (yes/no): ", describe_entries(chosen.len()));
+        io::stdout().flush()?;
+        let mut response = String::new();
+        io::stdin().read_line(&mut response)?;
+        let response = response.trim().to_lowercase();
+        if response != "yes" && response != "y" {
+            println!("Discard cancelled.");
+            return Ok(());
+        }
+    }
+
+    let identity = config::load_config()?.identity.identity();
+    for entry in &chosen {
+        file_operations::discard_file(&entry.path, &target.overlay_path, &target.lower)?;
+        if let Some(dir) = &target.session_dir {
+            audit_log::append(dir, &identity, &format!("discarded {}", entry.rel_path.display()))?;
+        }
+    }
+    println!("Discarded {}", describe_entries(chosen.len()));
+    Ok(())
+}
+
+fn run_apply_plan(
+    target: Target,
+    plan_path: &Path,
+    dry_run: bool,
+    allow_outside_roots: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let plan = plan::load(plan_path)?;
+
+    // Like a review, only while no other review has the session
+    let _lock = match &target.session_dir {
+        Some(dir) if !dry_run => match lock::SessionLock::acquire(dir)? {
+            Ok(lock) => Some(lock),
+            Err(pid) => return Err(format!("Session is being reviewed (pid {})", pid).into()),
+        },
+        _ => None,
+    };
+
+    // Carry out nothing unless all of it can be, as reviewed
+    let unverified = plan.unverified(&target.overlay_path);
+    if !unverified.is_empty() {
+        for (rel_path, mismatch) in &unverified {
+            eprintln!("{}: {}", mismatch, rel_path.display());
+        }
+        return Err(format!(
+            "{} cannot be checked against the plan; review again",
+            describe_entries(unverified.len())
+        )
+        .into());
+    }
+
+    let config = config::load_config()?;
+    let (_tx, rx) = channel();
+    let mut app = App::new(&target.overlay_path, target.lower, rx, &config, target.session_dir)?;
+    if allow_outside_roots {
+        app.set_allow_outside_roots();
+    }
+    let missing: Vec<_> = plan
+        .decisions
+        .iter()
+        .filter(|d| d.verdict != plan::Verdict::Skip && app.files.find(&target.overlay_path.join(&d.path)).is_none())
+        .collect();
+    if !missing.is_empty() {
+        for decision in &missing {
+            eprintln!("Not in the overlay: {}", decision.path.display());
+        }
+        return Err(format!("{} in the plan not in the overlay", describe_entries(missing.len())).into());
+    }
+
+    let to_apply = plan.paths(plan::Verdict::Apply);
+    let to_discard = plan.paths(plan::Verdict::Discard);
+    let skipped = plan.paths(plan::Verdict::Skip).len();
+    if dry_run {
+        for rel_path in &to_apply {
+            println!("apply    {}", rel_path.display());
+        }
+        for rel_path in &to_discard {
+            println!("discard  {}", rel_path.display());
+        }
+        println!("Would apply {}, discard {} and skip {}", to_apply.len(), to_discard.len(), skipped);
+        return Ok(());
+    }
+
+    let applied = if to_apply.is_empty() { Vec::new() } else { app.apply_entries(&to_apply)? };
+    if !to_discard.is_empty() {
+        app.discard_paths(&to_discard)?;
+    }
+    app.save_progress()?;
+    println!(
+        "Applied {}, discarded {} and skipped {}",
+        describe_entries(applied.len()),
+        to_discard.len(),
+        skipped
+    );
+    skip_reloads(&mut app)?;
+    for failure in app.wait_for_apply_hooks() {
+        eprintln!("Warning: {}", failure);
+    }
+    Ok(())
+}
+
+fn run_import(file: PathBuf, workdir: Option<PathBuf>) -> Result<(), Box<dyn std::error::Error>> {
+    let config = config::load_config()?;
+    let workdir = workdir
+        .or(config.session.workdir)
+        .unwrap_or_else(session::default_workdir);
+    let imported = archive::import(&file, &workdir)?;
+
+    println!("Imported session to {}", imported.tmpdir.display());
+    // The review compares the overlay with the same paths it had where it
+    // was exported, which this machine may not have
+    let metadata = session::load_metadata(&imported.tmpdir)?;
+    let bases: Vec<_> = if metadata.roots.is_empty() {
+        vec![&imported.base_path]
+    } else {
+        metadata.roots.values().collect()
+    };
+    for path in bases.into_iter().chain(&imported.lower_layers) {
+        if !path.exists() {
+            eprintln!("Warning: '{}' does not exist here; the review compares the overlay with it", path.display());
+        }
+    }
+    println!("Review with: audit-box review");
+    Ok(())
+}
+
 fn run_delete() -> Result<(), Box<dyn std::error::Error>> {
     use std::io::{self, Write};
 
@@ -163,58 +743,185 @@ fn run_delete() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
-fn run_run(command: Vec<String>) -> Result<(), Box<dyn std::error::Error>> {
+fn run_run(profile: Option<SandboxProfile>, attribute: bool, command: Vec<String>) -> Result<(), Box<dyn std::error::Error>> {
+    if !cfg!(target_os = "linux") {
+        return Err("'audit-box run' needs Linux for bubblewrap and overlayfs; 'review --overlay --base' works on any two directories".into());
+    }
+
     // Load the session
     let session = session::load_session()?;
+    let config = config::load_config()?;
 
     let overlay_path = session.tmpdir.join("overlay");
     let work_path = session.tmpdir.join("work");
+    let mut metadata = session::load_metadata(&session.tmpdir)?;
 
     // Build bwrap command
-    let mut bwrap_args = vec![
-        "--ro-bind".to_string(),
-        "/".to_string(),
-        "/".to_string(),
-        "--tmpfs".to_string(),
-        "/tmp".to_string(),
-        "--unshare-pid".to_string(),
-        "--overlay-src".to_string(),
-        session.base_path.display().to_string(),
-        "--overlay".to_string(),
-        overlay_path.display().to_string(),
-        work_path.display().to_string(),
-        session.base_path.display().to_string(),
-        "--dev".to_string(),
-        "/dev".to_string(),
-        "--new-session".to_string(),
-    ];
+    let profile = profile.unwrap_or(config.run.profile);
+    let mut bwrap_args = if metadata.roots.is_empty() {
+        sandbox::bwrap_args(
+            &session.base_path,
+            &session.lower_layers,
+            &overlay_path,
+            &work_path,
+            profile,
+        )
+    } else {
+        // Applying everything under a base removes its emptied overlay directory
+        for name in metadata.roots.keys() {
+            std::fs::create_dir_all(overlay_path.join(name))?;
+            std::fs::create_dir_all(work_path.join(name))?;
+        }
+        sandbox::bwrap_root_args(&metadata.roots, &overlay_path, &work_path, profile)
+    };
 
     // Add user-provided command/arguments
-    bwrap_args.extend(command);
+    bwrap_args.extend(command.iter().cloned());
+
+    // Record the run so reviewers know what produced the changes
+    metadata.runs.push(RunRecord {
+        command,
+        profile,
+        started: Local::now(),
+        finished: None,
+        exit_code: None,
+        signal: None,
+        pid: None,
+    });
+    session::save_metadata(&session.tmpdir, &metadata)?;
+    let command_line = metadata.runs.last().map(|r| r.command_line()).unwrap_or_default();
+    let identity = config.identity.identity();
+    audit_log::append(&session.tmpdir, &identity, &format!("run started: {} (profile {})", command_line, profile))?;
+
+    // Record when files appear and change in the overlay while the command runs
+    let mut recorder = timeline::Recorder::open(&session.tmpdir, &overlay_path)?;
+    let (tx, rx) = channel();
+    let (watcher, warning) = OverlayWatcher::watch(&overlay_path, tx, config.watch.poll_interval())?;
+    if let Some(warning) = warning {
+        eprintln!("Warning: {}", warning);
+    }
+    let recording = std::thread::spawn(move || {
+        for event in rx {
+            let event = match event {
+                Ok(event) => event,
+                Err(e) => {
+                    eprintln!("Warning: {}", e);
+                    continue;
+                }
+            };
+            if let Err(e) = recorder.record(&event) {
+                eprintln!("Warning: failed to record filesystem event: {}", e);
+            }
+        }
+    });
+
+    // Optionally record which processes write each file
+    let monitor = if attribute || config.run.attribution {
+        match attribution::Monitor::start(&session.tmpdir, &overlay_path) {
+            Ok(monitor) => Some(monitor),
+            Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => {
+                eprintln!("Warning: process attribution needs CAP_SYS_ADMIN, continuing without it");
+                None
+            }
+            Err(e) => {
+                eprintln!("Warning: process attribution unavailable: {}", e);
+                None
+            }
+        }
+    } else {
+        None
+    };
 
     // Execute bwrap
-    use std::os::unix::process::CommandExt;
-    let error = std::process::Command::new("bwrap")
+    let mut child = std::process::Command::new("bwrap")
         .args(&bwrap_args)
-        .exec();
+        .spawn()
+        .map_err(|e| format!("Failed to execute bwrap: {}", e))?;
+    if let Some(monitor) = &monitor {
+        monitor.attribute_to(child.id());
+    }
+    if let Some(run) = metadata.runs.last_mut() {
+        run.pid = Some(child.id());
+    }
+    session::save_metadata(&session.tmpdir, &metadata)?;
+
+    // Let Ctrl-C reach the sandbox without killing us, so the outcome is still recorded.
+    // Being asked to stop, e.g. by `audit-box daemon`, passes the request on to bwrap
+    // and waits for it the same way.
+    SANDBOX_PID.store(child.id() as i32, std::sync::atomic::Ordering::Relaxed);
+    // SAFETY: setting a signal disposition to SIG_IGN has no other preconditions, and
+    // the handler only loads an atomic and calls kill, which are async-signal-safe
+    unsafe {
+        libc::signal(libc::SIGINT, libc::SIG_IGN);
+        libc::signal(libc::SIGQUIT, libc::SIG_IGN);
+        for signal in [libc::SIGTERM, libc::SIGHUP] {
+            libc::signal(signal, forward_to_sandbox as *const () as libc::sighandler_t);
+        }
+    }
+    let status = child.wait()?;
+
+    // Dropping the watcher ends the recording thread once it has caught up
+    drop(watcher);
+    let _ = recording.join();
+    if let Some(monitor) = monitor {
+        monitor.stop();
+    }
 
-    // If exec returns, there was an error
-    Err(format!("Failed to execute bwrap: {}", error).into())
+    if let Some(run) = metadata.runs.last_mut() {
+        run.finished = Some(Local::now());
+        run.exit_code = status.code();
+        run.signal = status.signal();
+        audit_log::append(&session.tmpdir, &identity, &format!("run finished: {} ({})", command_line, run.outcome()))?;
+    }
+    session::save_metadata(&session.tmpdir, &metadata)?;
+
+    // Pass the command's exit status on, using the shell convention for signals
+    std::process::exit(status.code().unwrap_or_else(|| 128 + status.signal().unwrap_or(0)));
+}
+
+/// The bwrap process `run` waits for, once it has started
+static SANDBOX_PID: std::sync::atomic::AtomicI32 = std::sync::atomic::AtomicI32::new(0);
+
+extern "C" fn forward_to_sandbox(signal: libc::c_int) {
+    let pid = SANDBOX_PID.load(std::sync::atomic::Ordering::Relaxed);
+    if pid > 0 {
+        // SAFETY: kill is async-signal-safe; the pid is our child, not reaped until run ends
+        unsafe {
+            libc::kill(pid, signal);
+        }
+    }
 }
 
-fn run_review(overlay: Option<PathBuf>, base: Option<PathBuf>) -> Result<(), Box<dyn std::error::Error>> {
+fn resolve_target(
+    overlay: Option<PathBuf>,
+    base: Option<PathBuf>,
+    lower_layers: Vec<PathBuf>,
+) -> Result<Target, Box<dyn std::error::Error>> {
     // Resolve overlay and base paths
-    let (overlay_path, base_path) = match (overlay, base) {
+    let target = match (overlay, base) {
         (Some(overlay), Some(base)) => {
             // Both provided explicitly
-            (overlay, base)
+            Target {
+                overlay_path: overlay,
+                lower: LowerView::new(base, lower_layers),
+                session_dir: None,
+            }
         }
         (None, None) => {
             // Load from saved session
             let session = session::load_session()?;
-            let overlay = session.tmpdir.join("overlay");
+            let metadata = session::load_metadata(&session.tmpdir)?;
+            let lower = if metadata.roots.is_empty() {
+                LowerView::new(session.base_path, session.lower_layers)
+            } else {
+                LowerView::with_roots(metadata.roots)
+            };
 
-            (overlay, session.base_path)
+            Target {
+                overlay_path: session.tmpdir.join("overlay"),
+                lower,
+                session_dir: Some(session.tmpdir),
+            }
         }
         _ => {
             return Err("Both --overlay and --base must be provided together, or neither (to use saved session)".into());
@@ -222,178 +929,705 @@ fn run_review(overlay: Option<PathBuf>, base: Option<PathBuf>) -> Result<(), Box
     };
 
     // Validate paths exist
-    if !overlay_path.exists() {
-        return Err(format!("Overlay path '{}' does not exist", overlay_path.display()).into());
+    if !target.overlay_path.exists() {
+        return Err(format!("Overlay path '{}' does not exist", target.overlay_path.display()).into());
+    }
+    for base in std::iter::once(&target.lower.base).chain(target.lower.roots.values()) {
+        if !base.exists() {
+            return Err(format!("Base path '{}' does not exist", base.display()).into());
+        }
+    }
+    check_lower_layers(&target.lower.layers)?;
+
+    // Relative paths into the overlay are worked out by stripping its root,
+    // and the watcher reports canonical paths, so resolve symlinks and
+    // relative paths once here for scan, diff and apply to agree
+    Ok(Target {
+        overlay_path: std::fs::canonicalize(&target.overlay_path)?,
+        lower: target.lower.canonicalize()?,
+        session_dir: target.session_dir,
+    })
+}
+
+/// Refuse an overlay and base that are the same directory or inside one
+/// another, as applying would copy files into the overlay itself or over
+/// the files being applied. A session's overlay may be inside its base,
+/// e.g. a base of / or $HOME, as apply skips files that would land in it.
+fn check_nesting(target: &Target) -> Result<(), Box<dyn std::error::Error>> {
+    let overlay = &target.overlay_path;
+    for base in std::iter::once(&target.lower.base).chain(target.lower.roots.values()) {
+        let problem = if base == overlay {
+            "Overlay and base are the same directory".to_string()
+        } else if base.starts_with(overlay) {
+            format!("Base '{}' is inside the overlay '{}'", base.display(), overlay.display())
+        } else if overlay.starts_with(base) && target.session_dir.is_none() {
+            format!("Overlay '{}' is inside the base '{}'", overlay.display(), base.display())
+        } else {
+            continue;
+        };
+        return Err(format!("{}, so applying could copy files into themselves. Use --force to review anyway.", problem).into());
+    }
+    Ok(())
+}
+
+fn check_lower_layers(lower_layers: &[PathBuf]) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(layer) = lower_layers.iter().find(|layer| !layer.is_dir()) {
+        return Err(format!("Lower layer '{}' is not a directory", layer.display()).into());
+    }
+    Ok(())
+}
+
+fn run_list(
+    overlay: Option<PathBuf>,
+    base: Option<PathBuf>,
+    lower_layers: Vec<PathBuf>,
+    format: ReportFormat,
+    stats: bool,
+    changeset: Option<String>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let target = resolve_target(overlay, base, lower_layers)?;
+    if changeset.is_some() && target.session_dir.is_none() {
+        return Err("Changesets are only available when using the saved session".into());
+    }
+
+    let mut files = FileTree::new();
+    file_operations::scan_directory(&target.overlay_path, &target.overlay_path, &target.lower, 0, None, &mut files)?;
+
+    let mut report = report::build_report(&files, &target.overlay_path, &target.lower, stats);
+    if let Some(session_dir) = &target.session_dir {
+        let metadata = session::load_metadata(session_dir)?;
+        report.mark_seeded(&template::unchanged(&metadata.seeded, &target.overlay_path));
+        report.runs = metadata.runs;
+        let review = review_state::load_review_state(&session_dir.join(review_state::REVIEW_STATE_FILE))?;
+        if let Some(name) = &changeset
+            && !review.changesets.contains_key(name)
+        {
+            return Err(format!("No changeset '{}' in the session", name).into());
+        }
+        report.mark_changesets(&review.changesets, changeset.as_deref());
+    }
+    print!("{}", report::render_report(&report, format)?);
+
+    // Warn on stderr so the report itself stays parseable
+    let config = config::load_config()?;
+    if let Some(limit) = config.session.size_warning()
+        && report.overlay_size > limit
+    {
+        eprintln!(
+            "Warning: overlay uses {}, over the {} warning threshold",
+            disk_usage::format_size(report.overlay_size),
+            disk_usage::format_size(limit)
+        );
+    }
+
+    Ok(())
+}
+
+fn run_export(
+    target: Target,
+    group_by: PatchGrouping,
+    label: Option<String>,
+    changeset: Option<String>,
+    output: Option<PathBuf>,
+    no_pager: bool,
+    paths: Vec<PathBuf>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    // Labels and changesets are kept in the session's review state
+    let review = match &target.session_dir {
+        Some(dir) => review_state::load_review_state(&dir.join(review_state::REVIEW_STATE_FILE))?,
+        None if label.is_some() || group_by == PatchGrouping::Label => {
+            return Err("Labels are only available when using the saved session".into());
+        }
+        None if changeset.is_some() || group_by == PatchGrouping::Changeset => {
+            return Err("Changesets are only available when using the saved session".into());
+        }
+        None => Default::default(),
+    };
+    if let Some(name) = &changeset
+        && !review.changesets.contains_key(name)
+    {
+        return Err(format!("No changeset '{}' in the session", name).into());
+    }
+
+    let mut files = FileTree::new();
+    file_operations::scan_directory(&target.overlay_path, &target.overlay_path, &target.lower, 0, None, &mut files)?;
+
+    let series = export::build_series(&files, &paths, &review, label.as_deref(), changeset.as_deref(), group_by);
+    if series.is_empty() {
+        return Err("No changes to export".into());
+    }
+
+    if let Some(dir) = &output {
+        std::fs::create_dir_all(dir)?;
+    }
+    // The patches are authored by whoever exports them
+    let author = config::load_config()?.identity.identity();
+    let mut stdout = String::new();
+    for (i, patch) in series.iter().enumerate() {
+        let rendered = export::render_patch(patch, i + 1, series.len(), &target.overlay_path, &target.lower, &author)?;
+        match &output {
+            Some(dir) => {
+                let path = dir.join(export::patch_file_name(patch, i + 1));
+                std::fs::write(&path, rendered.content)?;
+                println!("{}", path.display());
+            }
+            None => stdout.push_str(&rendered.content),
+        }
+        for rel_path in &rendered.skipped {
+            eprintln!("Warning: binary file '{}' is not included in the patch", rel_path.display());
+        }
+    }
+    if output.is_none() {
+        pager::page(&stdout, !no_pager)?;
+    }
+
+    Ok(())
+}
+
+fn run_review(
+    target: Target,
+    gitignore: bool,
+    hidden_files: Option<HiddenFiles>,
+    read_only: bool,
+    listen: Option<PathBuf>,
+    allow_outside_roots: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let Target {
+        overlay_path,
+        lower,
+        session_dir,
+    } = target;
+
+    // A daemon supervising the session hands it over until the review ends
+    let _attachment = match &session_dir {
+        Some(dir) if !read_only => daemon::attach(dir)?,
+        _ => None,
+    };
+
+    // Only one review of a session may change it at a time
+    let _lock = match &session_dir {
+        Some(dir) if !read_only => match lock::SessionLock::acquire(dir)? {
+            Ok(lock) => Some(lock),
+            Err(pid) => {
+                return Err(format!(
+                    "Session is already being reviewed (pid {}). Use 'audit-box review --read-only' to look without applying.",
+                    pid
+                )
+                .into());
+            }
+        },
+        _ => None,
+    };
+
+    let mut config = config::load_config()?;
+    config.ui.gitignore |= gitignore;
+    if let Some(hidden_files) = hidden_files {
+        config.ui.hidden_files = hidden_files;
     }
-    if !base_path.exists() {
-        return Err(format!("Base path '{}' does not exist", base_path.display()).into());
+    if config.ui.desktop_notifications && !desktop_notify::Notifier::is_supported() {
+        eprintln!("Warning: desktop_notifications is set, but audit-box was built without the desktop-notifications feature");
     }
 
-    // Setup filesystem watcher
+    let control = listen
+        .map(|path| control::ControlSocket::listen(&path).map_err(|e| e.to_string()))
+        .transpose()?;
+
+    // Setup filesystem watcher, waking the event loop as changes arrive
+    let mut events = Events::new(TICK);
     let (tx, rx) = channel();
-    let mut watcher = RecommendedWatcher::new(tx, Config::default())?;
-    watcher.watch(&overlay_path, RecursiveMode::Recursive)?;
+    let waker = events.waker();
+    let (_watcher, watch_warning) = OverlayWatcher::watch(
+        &overlay_path,
+        move |event| {
+            let _ = tx.send(event);
+            let _ = waker.send(Ok(Wake::Overlay));
+        },
+        config.watch.poll_interval(),
+    )?;
+
+    // Create app
+    let mut app = App::new(&overlay_path, lower, rx, &config, session_dir)?;
+    if read_only {
+        app.set_read_only();
+    }
+    if allow_outside_roots {
+        app.set_allow_outside_roots();
+    }
+
+    // Without a terminal to draw on, e.g. over a pipe or on a dumb terminal,
+    // review with line-based prompts instead
+    if !io::stdout().is_terminal() || std::env::var("TERM").is_ok_and(|term| term == "dumb") {
+        if let Some(warning) = watch_warning {
+            eprintln!("Warning: {}", warning);
+        }
+        if control.is_some() {
+            eprintln!("Warning: --listen is ignored without the review screen");
+        }
+        line_review::run(&mut app, &mut io::stdin().lock(), &mut io::stdout())?;
+        app.save_review_state()?;
+        for failure in app.wait_for_apply_hooks() {
+            eprintln!("Warning: {}", failure);
+        }
+        return Ok(());
+    }
+    if let Some(warning) = watch_warning {
+        app.show_toast(warning);
+    }
 
     // Setup terminal
-    enable_raw_mode()?;
-    let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen)?;
-    let backend = CrosstermBackend::new(stdout);
+    terminal::install_panic_hook();
+    terminal::install_suspend_handler();
+    terminal::enter()?;
+    events.read_terminal();
+    let backend = CrosstermBackend::new(io::stdout());
     let mut terminal = Terminal::new(backend)?;
 
-    // Create app
-    let mut app = App::new(&overlay_path, base_path, rx)?;
-
     // Run app
-    let res = run_app(&mut terminal, &mut app);
+    let frame_interval = Duration::from_secs(1) / config.ui.max_fps.max(1);
+    let res = run_app(&mut terminal, &mut app, &mut events, frame_interval, control.as_ref());
 
-    // Restore terminal
-    disable_raw_mode()?;
-    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
-    terminal.show_cursor()?;
+    // Restore terminal, once nothing reads from it any more
+    drop(events);
+    terminal::leave()?;
 
     if let Err(err) = res {
         println!("Error: {:?}", err);
     }
 
+    app.save_review_state()?;
+    skip_reloads(&mut app)?;
+    for failure in app.wait_for_apply_hooks() {
+        eprintln!("Warning: {}", failure);
+    }
+
     Ok(())
 }
 
+/// Say which reloads applied files called for that were not run, e.g.
+/// applying on the way out, so they can be run by hand
+fn skip_reloads(app: &mut App) -> io::Result<()> {
+    for action in &app.pending_reloads {
+        eprintln!("Not run: {} (for {})", action.command_line(), action.because.display());
+    }
+    app.skip_reloads()
+}
+
+/// Draw an apply's progress straight to the terminal over the last frame, as
+/// the app is busy applying and cannot draw one. The screen has to be
+/// cleared afterwards, so the next frame is drawn in full over it.
+fn draw_apply_progress<B: ratatui::backend::Backend>(
+    terminal: &mut Terminal<B>,
+    progress: &file_operations::ApplyProgress,
+) -> io::Result<()> {
+    let size = terminal.size()?;
+    let area = ui::apply_progress::area(ratatui::layout::Rect::new(0, 0, size.width, size.height));
+    let mut buf = ratatui::buffer::Buffer::empty(area);
+    ui::apply_progress::render(&mut buf, area, progress);
+    let cells = buf.content.iter().enumerate().map(|(i, cell)| {
+        let (x, y) = buf.pos_of(i);
+        (x, y, cell)
+    });
+    terminal.backend_mut().draw(cells)?;
+    ratatui::backend::Backend::flush(terminal.backend_mut())
+}
+
+fn draw(f: &mut Frame, app: &mut App) {
+    // The panes and dialogs need a minimum size to be laid out
+    if !ui::too_small::fits(f.area()) {
+        ui::too_small::render(f);
+        return;
+    }
+
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(0), Constraint::Length(1), Constraint::Length(1)])
+        .split(f.area());
+    let constraints = if app.show_staging_pane {
+        vec![Constraint::Percentage(35), Constraint::Percentage(40), Constraint::Percentage(25)]
+    } else {
+        vec![Constraint::Percentage(40), Constraint::Percentage(60)]
+    };
+    let chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints(constraints)
+        .split(rows[0]);
+
+    if app.dashboard.is_some() {
+        // The overview replaces the panes while it is open
+        ui::dashboard::render(f, app, rows[0]);
+    } else if app.timeline.is_some() {
+        ui::timeline_view::render(f, app, rows[0]);
+    } else if app.show_changeset_queue {
+        ui::changeset_queue::render(f, app, rows[0]);
+    } else if app.files.is_empty() {
+        ui::empty_state::render(f, app, rows[0]);
+    } else {
+        // Render file list pane
+        ui::file_list::render(f, app, chunks[0]);
+
+        // Render content viewer pane
+        ui::content_viewer::render(f, app, chunks[1]);
+
+        // Render staging pane (if shown)
+        if app.show_staging_pane {
+            ui::staging_pane::render(f, app, chunks[2]);
+        }
+    }
+
+    // Render status bar and the keys valid right now
+    ui::status_bar::render(f, app, rows[1]);
+    ui::footer::render(f, app, rows[2]);
+
+    // Render dialogs (if visible)
+    ui::apply_dialog::render(f, app);
+    ui::discard_dialog::render(f, app);
+    ui::help_dialog::render(f, app);
+    ui::details_dialog::render(f, app);
+    ui::label_dialog::render(f, app);
+    ui::changeset_dialog::render(f, app);
+    ui::path_dialog::render(f, app);
+    ui::plan_dialog::render(f, app);
+    ui::quit_dialog::render(f, app);
+    ui::reload_dialog::render(f, app);
+}
+
+/// The button a key picks in the apply, discard and reload dialogs: y and n answer
+/// straight away, Enter takes the highlighted one
+fn dialog_answer(code: KeyCode, highlighted: DialogButton) -> Option<DialogButton> {
+    match code {
+        KeyCode::Char('y') => Some(DialogButton::Ok),
+        KeyCode::Char('n') => Some(DialogButton::Cancel),
+        KeyCode::Enter => Some(highlighted),
+        _ => None,
+    }
+}
+
+/// Suspend to the shell, then redraw everything once resumed
+fn suspend<B: ratatui::backend::Backend>(terminal: &mut Terminal<B>) -> io::Result<()> {
+    terminal::suspend()?;
+    terminal.clear()
+}
+
+/// How often the review looks at work finished in the background, such as
+/// checks and settled transient files, when nothing wakes it sooner
+const TICK: Duration = Duration::from_millis(100);
+
 fn run_app<B: ratatui::backend::Backend>(
     terminal: &mut Terminal<B>,
     app: &mut App,
+    events: &mut Events,
+    frame_interval: Duration,
+    control: Option<&control::ControlSocket>,
 ) -> io::Result<()> {
+    let mut last_frame: Option<Instant> = None;
     loop {
         // Check for filesystem events and process targeted updates
         app.check_fs_events();
-        app.process_pending_updates()?;
-
-        terminal.draw(|f| {
-            let chunks = Layout::default()
-                .direction(Direction::Horizontal)
-                .constraints([Constraint::Percentage(40), Constraint::Percentage(60)])
-                .split(f.area());
-
-            // Render file list pane
-            ui::file_list::render(f, app, chunks[0]);
-
-            // Render content viewer pane
-            ui::content_viewer::render(f, app, chunks[1]);
-
-            // Render dialogs (if visible)
-            ui::apply_dialog::render(f, app);
-            ui::discard_dialog::render(f, app);
-            ui::help_dialog::render(f, app);
-        })?;
-
-        if event::poll(std::time::Duration::from_millis(100))? {
-            if let Event::Key(key) = event::read()? {
-                if app.show_confirm_dialog {
-                    // Handle apply dialog navigation
-                    match key.code {
-                        KeyCode::Left | KeyCode::Right | KeyCode::Tab => {
-                            app.dialog_button = match app.dialog_button {
-                                DialogButton::Ok => DialogButton::Cancel,
-                                DialogButton::Cancel => DialogButton::Ok,
-                            };
-                        }
-                        KeyCode::Enter => {
-                            if app.dialog_button == DialogButton::Ok {
-                                if let Err(e) = app.apply_changes() {
-                                    eprintln!("Error applying changes: {}", e);
+        app.process_pending_updates();
+        app.receive_check_results();
+        app.receive_normalized_diffs();
+        app.receive_hashes();
+        app.receive_blame();
+        app.send_notifications();
+        app.check_apply_hooks();
+        app.offer_reloads();
+        app.sync_marks();
+        if let Some(control) = control {
+            control.answer(app);
+        }
+
+        if terminal::suspend_requested() {
+            suspend(terminal)?;
+            app.request_redraw();
+        }
+
+        // Only draw when something changed, rather than on every wake-up,
+        // and no more often than the frame rate allows
+        let frame_due = last_frame.map(|last| last + frame_interval).filter(|due| *due > Instant::now());
+        if frame_due.is_none() && app.take_redraw() {
+            terminal.draw(|f| draw(f, app))?;
+            last_frame = Some(Instant::now());
+        }
+
+        let event = match events.next(frame_due)? {
+            Wake::Terminal(event) => {
+                app.request_redraw();
+                Some(event)
+            }
+            Wake::Overlay | Wake::Tick => None,
+        };
+
+        match event {
+            Some(Event::FocusGained) => app.set_focused(true),
+            Some(Event::FocusLost) => app.set_focused(false),
+            _ => {}
+        }
+
+        if let Some(Event::Resize(..)) = event {
+            // Lay everything out again for the new size before the next draw
+            terminal.autoresize()?;
+        }
+
+        if let Some(Event::Key(key)) = event {
+            if key.code == KeyCode::Char('z') && key.modifiers.contains(KeyModifiers::CONTROL) {
+                // Raw mode turns Ctrl-Z into a key press rather than SIGTSTP
+                suspend(terminal)?;
+            } else if let Modal::Quit(choice) = app.modal {
+                // Handle quit dialog navigation
+                match key.code {
+                    KeyCode::Right | KeyCode::Tab => app.next_quit_choice(),
+                    KeyCode::Left | KeyCode::BackTab => app.previous_quit_choice(),
+                    KeyCode::Char('y') => return Ok(()),
+                    KeyCode::Char('n') => app.close_dialog(),
+                    KeyCode::Enter => match choice {
+                        QuitChoice::Quit => return Ok(()),
+                        QuitChoice::ApplyAndQuit => match app.apply_before_quit() {
+                            Ok(()) => return Ok(()),
+                            Err(e) => {
+                                app.close_dialog();
+                                app.show_toast(format!("Apply failed, not quitting: {}", e));
+                            }
+                        },
+                        QuitChoice::Cancel => app.close_dialog(),
+                    },
+                    KeyCode::Char('q') => return Ok(()),
+                    KeyCode::Esc => app.close_dialog(),
+                    _ => {}
+                }
+            } else if let Modal::Apply(state) = &mut app.modal {
+                // Handle apply dialog navigation
+                match key.code {
+                    KeyCode::Left | KeyCode::Right | KeyCode::Tab => state.button = state.button.other(),
+                    KeyCode::Esc => app.close_dialog(),
+                    code => match dialog_answer(code, state.button) {
+                        Some(DialogButton::Ok) => {
+                            let mut progress_shown = false;
+                            let result = app.confirm_apply(&mut |progress| {
+                                if progress.elapsed >= ui::apply_progress::SHOW_AFTER {
+                                    progress_shown |= draw_apply_progress(terminal, progress).is_ok();
                                 }
+                            });
+                            if progress_shown {
+                                terminal.clear()?;
+                            }
+                            if let Err(e) = result {
+                                app.show_toast(format!("Apply failed: {}", e));
                             }
-                            app.show_confirm_dialog = false;
-                            app.dialog_button = DialogButton::Ok;
-                        }
-                        KeyCode::Esc => {
-                            app.show_confirm_dialog = false;
-                            app.dialog_button = DialogButton::Ok;
-                        }
-                        _ => {}
-                    }
-                } else if app.show_discard_dialog {
-                    // Handle discard dialog navigation
-                    match key.code {
-                        KeyCode::Left | KeyCode::Right | KeyCode::Tab => {
-                            app.dialog_button = match app.dialog_button {
-                                DialogButton::Ok => DialogButton::Cancel,
-                                DialogButton::Cancel => DialogButton::Ok,
-                            };
                         }
-                        KeyCode::Enter => {
-                            if app.dialog_button == DialogButton::Ok {
-                                if let Err(e) = app.discard_selected_file() {
-                                    eprintln!("Error discarding file: {}", e);
-                                }
+                        Some(DialogButton::Cancel) => app.close_dialog(),
+                        None => {}
+                    },
+                }
+            } else if let Modal::Discard(state) = &mut app.modal {
+                // Handle discard dialog navigation
+                match key.code {
+                    KeyCode::Left | KeyCode::Right | KeyCode::Tab => state.button = state.button.other(),
+                    KeyCode::Esc => app.close_dialog(),
+                    code => match dialog_answer(code, state.button) {
+                        Some(DialogButton::Ok) => {
+                            if let Err(e) = app.confirm_discard() {
+                                eprintln!("Error discarding file: {}", e);
                             }
-                            app.show_discard_dialog = false;
-                            app.dialog_button = DialogButton::Ok;
                         }
-                        KeyCode::Esc => {
-                            app.show_discard_dialog = false;
-                            app.dialog_button = DialogButton::Ok;
+                        Some(DialogButton::Cancel) => app.close_dialog(),
+                        None => {}
+                    },
+                }
+            } else if let Modal::Reload(button) = &mut app.modal {
+                // Handle reload dialog navigation
+                match key.code {
+                    KeyCode::Left | KeyCode::Right | KeyCode::Tab => *button = button.other(),
+                    code => {
+                        let answer = match code {
+                            KeyCode::Esc => Some(DialogButton::Cancel),
+                            code => dialog_answer(code, *button),
+                        };
+                        let result = match answer {
+                            Some(DialogButton::Ok) => app.confirm_reload(),
+                            Some(DialogButton::Cancel) => app.decline_reload(),
+                            None => Ok(()),
+                        };
+                        if let Err(e) = result {
+                            app.show_toast(format!("Could not record the reload: {}", e));
                         }
-                        _ => {}
                     }
-                } else if app.show_help_dialog {
-                    // Handle help dialog - close on Esc or any key
+                }
+            } else if let Modal::Label(input) = &mut app.modal {
+                // Handle typing in the label prompt
+                match input.handle_key(key) {
+                    InputEvent::Submit(text) => app.submit_label_input(&text),
+                    InputEvent::Cancel => app.close_dialog(),
+                    InputEvent::Edited | InputEvent::Ignored => {}
+                }
+            } else if let Modal::Changeset(input) = &mut app.modal {
+                // Handle typing in the changeset prompt
+                match input.handle_key(key) {
+                    InputEvent::Submit(text) => app.submit_changeset_input(&text),
+                    InputEvent::Cancel => app.close_dialog(),
+                    InputEvent::Edited | InputEvent::Ignored => {}
+                }
+            } else if let Modal::Describe(input) = &mut app.modal {
+                // Handle typing in the changeset description prompt
+                match input.handle_key(key) {
+                    InputEvent::Submit(text) => app.submit_describe_input(&text),
+                    InputEvent::Cancel => app.close_dialog(),
+                    InputEvent::Edited | InputEvent::Ignored => {}
+                }
+            } else if let Modal::Path(input) = &mut app.modal {
+                // Handle typing in the jump-to-path prompt
+                if key.code == KeyCode::Tab {
+                    app.complete_path_input();
+                } else {
+                    match input.handle_key(key) {
+                        InputEvent::Submit(text) => app.submit_path_input(&text),
+                        InputEvent::Cancel => app.close_dialog(),
+                        InputEvent::Edited | InputEvent::Ignored => {}
+                    }
+                }
+            } else if let Modal::Plan(input) = &mut app.modal {
+                // Handle typing in the decisions file prompt
+                match input.handle_key(key) {
+                    InputEvent::Submit(text) => app.submit_plan_input(&text),
+                    InputEvent::Cancel => app.close_dialog(),
+                    InputEvent::Edited | InputEvent::Ignored => {}
+                }
+            } else if app.details.is_some() {
+                // Any of the keys that open the details popup close it again
+                if matches!(app.keymap.translate(key.code), KeyCode::Esc | KeyCode::Enter | KeyCode::Char('i')) {
+                    app.details = None;
+                }
+            } else if matches!(app.modal, Modal::Help) {
+                // Handle help dialog - close on Esc or any key
+                match key.code {
+                    KeyCode::Esc | KeyCode::Char('h') | KeyCode::Char('?') => {
+                        app.close_dialog();
+                    }
+                    _ => {}
+                }
+            } else if app.timeline.is_some() {
+                // Handle the timeline view
+                match app.keymap.translate(key.code) {
+                    KeyCode::Down => app.timeline_next(),
+                    KeyCode::Up => app.timeline_previous(),
+                    KeyCode::Char('o') => app.cycle_timeline_sort(),
+                    KeyCode::Enter => app.jump_to_timeline_entry(),
+                    KeyCode::Char('T') | KeyCode::Esc => app.toggle_timeline(),
+                    KeyCode::Char('q') if app.request_quit() => return Ok(()),
+                    _ => {}
+                }
+            } else if app.show_changeset_queue {
+                // Handle the changeset queue
+                match app.keymap.translate(key.code) {
+                    KeyCode::Down => app.queue_next(),
+                    KeyCode::Up => app.queue_previous(),
+                    KeyCode::Char('K') => app.move_queued(true),
+                    KeyCode::Char('J') => app.move_queued(false),
+                    KeyCode::Char('s') => app.squash_queued(),
+                    KeyCode::Char('e') => app.open_describe_input(),
+                    KeyCode::Char(' ') => app.toggle_hold(),
+                    KeyCode::Char('C') => app.open_apply_queue_dialog(),
+                    KeyCode::Enter => app.show_queued_files(),
+                    KeyCode::Esc => app.toggle_changeset_queue(),
+                    KeyCode::Char('q') if app.request_quit() => return Ok(()),
+                    _ => {}
+                }
+            } else if let Some(prefix) = app.pending_key.take() {
+                // Handle the second key of a two-key sequence
+                if prefix == 'z' {
                     match key.code {
-                        KeyCode::Esc | KeyCode::Char('h') | KeyCode::Char('?') => {
-                            app.show_help_dialog = false;
+                        KeyCode::Char('M') | KeyCode::Char('m') => app.collapse_all(),
+                        KeyCode::Char('R') | KeyCode::Char('r') => app.expand_all(),
+                        KeyCode::Char(c @ '1'..='9') => {
+                            app.expand_to_level(c as usize - '0' as usize);
                         }
                         _ => {}
                     }
-                } else {
-                    // Handle normal navigation
+                } else if prefix == 't' {
                     match key.code {
-                        KeyCode::Char('q') => return Ok(()),
-                        KeyCode::Char('h') | KeyCode::Char('?') => {
-                            app.show_help_dialog = true;
-                        }
-                        KeyCode::Char('a') => {
-                            app.show_confirm_dialog = true;
-                        }
-                        KeyCode::Char('k') => {
-                            app.show_discard_dialog = true;
-                        }
-                        KeyCode::Tab => app.toggle_pane(),
-                        KeyCode::Char(' ') => {
-                            if app.active_pane == ActivePane::FileList {
-                                app.toggle_selection();
-                            }
-                        }
-                        KeyCode::Down => match app.active_pane {
-                            ActivePane::FileList => app.next(),
-                            ActivePane::FileContent => app.scroll_content_down(),
-                        },
-                        KeyCode::Up => match app.active_pane {
-                            ActivePane::FileList => app.previous(),
-                            ActivePane::FileContent => app.scroll_content_up(),
-                        },
-                        KeyCode::Home => {
-                            if app.active_pane == ActivePane::FileList {
-                                app.jump_to_first();
-                            }
-                        }
-                        KeyCode::End => {
-                            if app.active_pane == ActivePane::FileList {
-                                app.jump_to_last();
-                            }
-                        }
-                        KeyCode::Left => {
-                            if app.active_pane == ActivePane::FileList {
-                                app.collapse_directory();
-                            }
-                        }
-                        KeyCode::Right => {
-                            if app.active_pane == ActivePane::FileList {
-                                app.expand_directory();
-                            }
-                        }
+                        KeyCode::Char('t') => app.open_label_input(),
+                        KeyCode::Char('f') => app.cycle_label_filter(),
+                        KeyCode::Char('a') => app.open_apply_label_dialog(),
+                        KeyCode::Char('x') => app.open_discard_label_dialog(),
+                        KeyCode::Char('g') => app.open_changeset_input(),
+                        KeyCode::Char('s') => app.suggest_changeset(),
+                        KeyCode::Char('q') => app.toggle_changeset_queue(),
+                        KeyCode::Char('c') => app.cycle_changeset_filter(),
+                        KeyCode::Char('C') => app.open_apply_changeset_dialog(),
                         _ => {}
                     }
                 }
+            } else {
+                // Handle normal navigation
+                match app.keymap.translate(key.code) {
+                    KeyCode::Char('q') if app.request_quit() => return Ok(()),
+                    KeyCode::Char('h') | KeyCode::Char('?') => {
+                        app.modal = Modal::Help;
+                    }
+                    KeyCode::Char('a') => app.open_apply_dialog(),
+                    KeyCode::Char('A') => app.open_apply_subtree_dialog(),
+                    KeyCode::Char('C') => app.open_apply_staged_dialog(),
+                    KeyCode::Char('W') => app.open_plan_input(),
+                    KeyCode::Char('s') => app.toggle_staged(),
+                    KeyCode::Char('S') => app.toggle_staging_pane(),
+                    KeyCode::Char('D') => app.toggle_dashboard(),
+                    KeyCode::Char('T') => app.toggle_timeline(),
+                    KeyCode::Esc if app.dashboard.is_some() => app.toggle_dashboard(),
+                    KeyCode::Char('u') if app.active_pane == ActivePane::Staging => {
+                        app.unstage_at_cursor();
+                    }
+                    KeyCode::Char('K') if app.active_pane == ActivePane::Staging => {
+                        app.move_staged_up();
+                    }
+                    KeyCode::Char('J') if app.active_pane == ActivePane::Staging => {
+                        app.move_staged_down();
+                    }
+                    KeyCode::Char('k') => app.open_discard_dialog(),
+                    KeyCode::Char('g') => app.open_path_input(),
+                    KeyCode::Char('m') => app.toggle_bookmark(),
+                    KeyCode::Char('\'') => app.next_bookmark(),
+                    KeyCode::Char('z') => app.pending_key = Some('z'),
+                    KeyCode::Char('t') => app.pending_key = Some('t'),
+                    KeyCode::Char('c') => app.toggle_changed_only(),
+                    KeyCode::Char('I') => app.toggle_show_ignored(),
+                    KeyCode::Char('.') => app.cycle_hidden_files(),
+                    KeyCode::Char('y') => app.toggle_group_by_kind(),
+                    KeyCode::Char('w') => app.toggle_ignore_whitespace(),
+                    KeyCode::Char('e') => app.toggle_ignore_eol(),
+                    KeyCode::Char('p') => app.toggle_pretty_print(),
+                    KeyCode::Char('N') => app.toggle_normalize(),
+                    KeyCode::Char('F') => app.compute_full_diff(),
+                    KeyCode::Char('v') => app.cycle_content_view(),
+                    KeyCode::Char('b') => app.toggle_blame(),
+                    KeyCode::Tab => app.toggle_pane(),
+                    KeyCode::Char(' ') if app.active_pane == ActivePane::FileList => {
+                        app.toggle_selection();
+                    }
+                    KeyCode::Enter if app.active_pane == ActivePane::FileList => app.open_details(),
+                    KeyCode::Char('i') => app.open_details(),
+                    KeyCode::Down => match app.active_pane {
+                        ActivePane::FileList => app.next(),
+                        ActivePane::FileContent => app.scroll_content_down(),
+                        ActivePane::Staging => app.staging_next(),
+                    },
+                    KeyCode::Up => match app.active_pane {
+                        ActivePane::FileList => app.previous(),
+                        ActivePane::FileContent => app.scroll_content_up(),
+                        ActivePane::Staging => app.staging_previous(),
+                    },
+                    KeyCode::Home if app.active_pane == ActivePane::FileList => {
+                        app.jump_to_first();
+                    }
+                    KeyCode::End if app.active_pane == ActivePane::FileList => {
+                        app.jump_to_last();
+                    }
+                    KeyCode::Left if app.active_pane == ActivePane::FileList => {
+                        app.collapse_directory();
+                    }
+                    KeyCode::Right if app.active_pane == ActivePane::FileList => {
+                        app.expand_directory();
+                    }
+                    _ => {}
+                }
             }
         }
     }
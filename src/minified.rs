@@ -0,0 +1,148 @@
+use std::path::Path;
+
+/// Lines longer than this many characters are split for viewing
+pub const LONG_LINE_CHARS: usize = 1000;
+
+/// Characters in each piece of a split line, give or take a break point
+pub const CHUNK_CHARS: usize = 160;
+
+/// Whether a file is JSON or JavaScript by its extension, so can be
+/// pretty-printed for viewing
+pub fn can_pretty_print(path: &Path) -> bool {
+    let extension = path.extension().and_then(|e| e.to_str()).unwrap_or_default();
+    matches!(extension.to_ascii_lowercase().as_str(), "json" | "map" | "js" | "mjs" | "cjs" | "jsx" | "ts" | "tsx")
+}
+
+/// JSON or JavaScript laid out a statement or member to a line and indented
+/// by nesting, for viewing only. Strings and comments are left as they are,
+/// and existing line breaks are kept, so code relying on them still reads
+/// the same. Regular expression literals are not recognised, so one with a
+/// quote in it can throw the layout off for the rest of the file.
+pub fn pretty_print(text: &str) -> String {
+    let mut out = String::with_capacity(text.len() * 2);
+    // Open brackets, innermost last; commas only break lines directly in
+    // braces or square brackets, and semicolons not in parentheses
+    let mut open: Vec<char> = Vec::new();
+    let depth = |open: &[char]| open.iter().filter(|c| **c != '(').count();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' | '\'' | '`' => {
+                out.push(c);
+                while let Some(c2) = chars.next() {
+                    out.push(c2);
+                    if c2 == '\\' {
+                        out.extend(chars.next());
+                    } else if c2 == c {
+                        break;
+                    }
+                }
+            }
+            '/' if chars.peek() == Some(&'/') => {
+                out.push(c);
+                while let Some(c2) = chars.next_if(|c2| *c2 != '\n') {
+                    out.push(c2);
+                }
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                out.push(c);
+                let mut previous = '\0';
+                for c2 in chars.by_ref() {
+                    out.push(c2);
+                    if previous == '*' && c2 == '/' {
+                        break;
+                    }
+                    previous = c2;
+                }
+            }
+            '{' | '[' => {
+                out.push(c);
+                // Empty ones stay on one line
+                while chars.next_if(|c2| c2.is_whitespace()).is_some() {}
+                let close = if c == '{' { '}' } else { ']' };
+                if chars.next_if_eq(&close).is_some() {
+                    out.push(close);
+                } else {
+                    open.push(c);
+                    break_line(&mut out, depth(&open));
+                }
+            }
+            '}' | ']' => {
+                open.pop();
+                break_line(&mut out, depth(&open));
+                out.push(c);
+            }
+            '(' => {
+                open.push(c);
+                out.push(c);
+            }
+            ')' => {
+                if open.last() == Some(&'(') {
+                    open.pop();
+                }
+                out.push(c);
+            }
+            ',' if open.last() != Some(&'(') => {
+                out.push(c);
+                break_line(&mut out, depth(&open));
+            }
+            ';' if open.last() != Some(&'(') => {
+                out.push(c);
+                break_line(&mut out, depth(&open));
+            }
+            ':' => {
+                out.push(c);
+                if chars.peek().is_some_and(|c2| !c2.is_whitespace()) {
+                    out.push(' ');
+                }
+            }
+            '\n' => break_line(&mut out, depth(&open)),
+            c if c.is_whitespace() => {
+                // Runs collapse to one space, and none starts a line
+                if !out.ends_with(char::is_whitespace) && !out.is_empty() {
+                    out.push(' ');
+                }
+            }
+            c => out.push(c),
+        }
+    }
+    out.truncate(out.trim_end().len());
+    out.push('\n');
+    out
+}
+
+/// End the line being written, unless it is empty, and indent the next to
+/// `depth`
+fn break_line(out: &mut String, depth: usize) {
+    out.truncate(out.trim_end_matches([' ', '\t']).len());
+    if !out.is_empty() && !out.ends_with('\n') {
+        out.push('\n');
+    }
+    out.extend(std::iter::repeat_n("  ", depth));
+}
+
+/// A long line in pieces of about `CHUNK_CHARS` characters, each ending
+/// after a comma, semicolon, bracket or space where there is one in its
+/// second half, and anywhere where there is not
+pub fn split_long_line(line: &str) -> Vec<&str> {
+    let mut pieces = Vec::new();
+    let (mut start, mut chars, mut last_break) = (0, 0, None);
+    for (i, c) in line.char_indices() {
+        chars += 1;
+        let end = i + c.len_utf8();
+        if matches!(c, ',' | ';' | '{' | '}' | '[' | ']' | ' ') && chars > CHUNK_CHARS / 2 {
+            last_break = Some((end, chars));
+        }
+        if chars >= CHUNK_CHARS {
+            let (cut, taken) = last_break.unwrap_or((end, chars));
+            pieces.push(&line[start..cut]);
+            start = cut;
+            chars -= taken;
+            last_break = None;
+        }
+    }
+    if start < line.len() {
+        pieces.push(&line[start..]);
+    }
+    pieces
+}
@@ -0,0 +1,70 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+/// A process that still has an overlay mounted on `overlay_path`, or on a
+/// directory below it for a session with several bases. bwrap mounts the
+/// overlay in its own mount namespace, so every process's mounts are looked
+/// at, once per namespace.
+pub fn overlay_mounted(overlay_path: &Path) -> Option<u32> {
+    let mut namespaces = HashSet::new();
+    for entry in fs::read_dir("/proc").ok()?.flatten() {
+        let Some(pid) = entry.file_name().to_str().and_then(|name| name.parse::<u32>().ok()) else {
+            continue;
+        };
+        // Processes of other users, or that have exited, cannot be looked at
+        let Ok(namespace) = fs::read_link(entry.path().join("ns/mnt")) else {
+            continue;
+        };
+        if !namespaces.insert(namespace) {
+            continue;
+        }
+        let Ok(mountinfo) = fs::read_to_string(entry.path().join("mountinfo")) else {
+            continue;
+        };
+        if mountinfo.lines().any(|line| mounts_upper(line, overlay_path)) {
+            return Some(pid);
+        }
+    }
+    None
+}
+
+/// Whether a line of /proc/<pid>/mountinfo is an overlay with its upper
+/// directory at or below `overlay_path`
+fn mounts_upper(line: &str, overlay_path: &Path) -> bool {
+    // The filesystem type, source and super block options follow the separator
+    let Some((_, fs_fields)) = line.split_once(" - ") else {
+        return false;
+    };
+    let mut fields = fs_fields.split(' ');
+    if fields.next() != Some("overlay") {
+        return false;
+    }
+    fields
+        .nth(1)
+        .into_iter()
+        .flat_map(|options| options.split(','))
+        .filter_map(|option| option.strip_prefix("upperdir="))
+        .any(|upper| Path::new(&unescape(upper)).starts_with(overlay_path))
+}
+
+/// Undo the octal escapes of spaces, tabs, newlines and backslashes
+fn unescape(field: &str) -> String {
+    let mut bytes = Vec::with_capacity(field.len());
+    let raw = field.as_bytes();
+    let mut i = 0;
+    while i < raw.len() {
+        let octal = raw.get(i + 1..i + 4).and_then(|digits| std::str::from_utf8(digits).ok());
+        match octal.and_then(|digits| u8::from_str_radix(digits, 8).ok()) {
+            Some(byte) if raw[i] == b'\\' => {
+                bytes.push(byte);
+                i += 4;
+            }
+            _ => {
+                bytes.push(raw[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
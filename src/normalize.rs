@@ -0,0 +1,74 @@
+use crate::checks;
+use crate::config::NormalizerConfig;
+use std::fs::{self, File};
+use std::io;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// The first configured normalizer for a file, if any
+pub fn for_path<'a>(normalizers: &'a [NormalizerConfig], path: &Path) -> Option<&'a NormalizerConfig> {
+    normalizers.iter().find(|normalizer| normalizer.matches(path))
+}
+
+/// Run a normalizer on a file and return what it wrote. It fails, with the
+/// last line the command wrote to stderr, unless it exits with 0 in time.
+///
+/// The command is given a copy of the file in an empty directory of its
+/// own, never the path the sandbox wrote it at: formatters such as
+/// prettier look for config files next to the file, some of which are code
+/// they run.
+pub fn run(normalizer: &NormalizerConfig, path: &Path) -> io::Result<String> {
+    let Some(program) = normalizer.command.first() else {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "Normalizer has no command"));
+    };
+    // Opening a FIFO would wait for a writer
+    if !fs::metadata(path)?.is_file() {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("{} is not a regular file", path.display())));
+    }
+    let scratch = tempfile::tempdir()?;
+    let name = match path.extension() {
+        Some(extension) => format!("file.{}", extension.to_string_lossy()),
+        None => "file".to_string(),
+    };
+    let copy = scratch.path().join(name);
+    fs::copy(path, &copy)?;
+
+    let file = copy.to_string_lossy();
+    let args = normalizer.command[1..].iter().map(|arg| arg.replace("{file}", &file));
+    let mut child = Command::new(program)
+        .args(args)
+        .current_dir(scratch.path())
+        .stdin(File::open(&copy)?)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| io::Error::new(e.kind(), format!("Failed to run {}: {}", program, e)))?;
+    let stdout = checks::read_all(child.stdout.take());
+    let stderr = checks::read_all(child.stderr.take());
+
+    let timeout = Duration::from_secs(normalizer.timeout_secs);
+    let started = Instant::now();
+    let status = loop {
+        match child.try_wait()? {
+            Some(status) => break status,
+            None if started.elapsed() < timeout => thread::sleep(Duration::from_millis(10)),
+            None => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    format!("{} timed out after {}s", program, normalizer.timeout_secs),
+                ));
+            }
+        }
+    };
+    let output = stdout.join().unwrap_or_default();
+    let errors = stderr.join().unwrap_or_default();
+    if !status.success() {
+        let reason = errors.lines().rev().find(|line| !line.trim().is_empty()).unwrap_or("no output");
+        return Err(io::Error::other(format!("{} failed ({}): {}", program, status, reason.trim())));
+    }
+    Ok(output)
+}
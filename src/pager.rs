@@ -0,0 +1,42 @@
+use std::env;
+use std::io::{self, IsTerminal, Write};
+use std::process::{Command, Stdio};
+
+/// Print output through the user's pager, like git does: `$PAGER`, or
+/// `less` if unset, and only when stdout is a terminal. Unless LESS is set,
+/// less is told to quit when everything fits on one screen, keep colours
+/// and leave the output on the screen.
+pub fn page(text: &str, enabled: bool) -> io::Result<()> {
+    let pager = env::var("PAGER").unwrap_or_else(|_| "less".to_string());
+    if !enabled || !io::stdout().is_terminal() || pager.is_empty() || pager == "cat" {
+        return print_all(&mut io::stdout(), text);
+    }
+
+    let mut command = Command::new("sh");
+    command.arg("-c").arg(&pager).stdin(Stdio::piped());
+    if env::var_os("LESS").is_none() {
+        command.env("LESS", "FRX");
+    }
+    if env::var_os("LV").is_none() {
+        command.env("LV", "-c");
+    }
+    let mut child = match command.spawn() {
+        Ok(child) => child,
+        // Better the output unpaged than none at all
+        Err(_) => return print_all(&mut io::stdout(), text),
+    };
+    if let Some(mut stdin) = child.stdin.take() {
+        print_all(&mut stdin, text)?;
+    }
+    child.wait()?;
+    Ok(())
+}
+
+/// Write everything, stopping quietly if the reader goes away, e.g. quitting
+/// the pager early or piping into head
+fn print_all(out: &mut impl Write, text: &str) -> io::Result<()> {
+    match out.write_all(text.as_bytes()).and_then(|_| out.flush()) {
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+        result => result,
+    }
+}
@@ -0,0 +1,82 @@
+use crate::types::LowerView;
+use std::path::{Component, Path, PathBuf};
+
+/// How overlay paths are shown. When the base is the root or home directory
+/// they are shown as the sandboxed program saw them, with the home directory
+/// abbreviated to `~`. So are paths in a session with several bases.
+#[derive(Debug, Clone)]
+pub enum PathDisplay {
+    /// Several bases, routed by the lower view; the home directory, if known
+    Roots { lower: LowerView, home: Option<PathBuf> },
+    /// The base is `/`; the home directory, if known, relative to it
+    Root { home: Option<PathBuf> },
+    /// The base is the home directory
+    Home,
+    /// Paths relative to the base
+    Relative,
+}
+
+impl PathDisplay {
+    pub fn new(lower: &LowerView) -> Self {
+        let home = dirs::home_dir();
+        let base = lower.base.as_path();
+        if !lower.roots.is_empty() {
+            PathDisplay::Roots { lower: lower.clone(), home }
+        } else if base == Path::new("/") {
+            PathDisplay::Root {
+                home: home.and_then(|home| home.strip_prefix("/").ok().map(Path::to_path_buf)),
+            }
+        } else if home.is_some_and(|home| home == base) {
+            PathDisplay::Home
+        } else {
+            PathDisplay::Relative
+        }
+    }
+
+    /// A path relative to the overlay root, for display
+    pub fn show(&self, rel_path: &Path) -> String {
+        match self {
+            PathDisplay::Roots { lower, home } => {
+                let path = lower.base_path(rel_path);
+                match home.as_deref().and_then(|home| path.strip_prefix(home).ok()) {
+                    Some(rest) => tilde(rest),
+                    None => path.components().collect::<PathBuf>().display().to_string(),
+                }
+            }
+            PathDisplay::Root { home: Some(home) } if rel_path.starts_with(home) => {
+                tilde(rel_path.strip_prefix(home).unwrap_or(rel_path))
+            }
+            PathDisplay::Root { .. } => Path::new("/").join(rel_path).display().to_string(),
+            PathDisplay::Home => tilde(rel_path),
+            PathDisplay::Relative => rel_path.display().to_string(),
+        }
+    }
+
+    /// The top-level area a path belongs to, e.g. `/etc` or `~` for a root
+    /// base, `~/.config` for a home base and the base itself with several.
+    /// Paths relative to any other base have none.
+    pub fn root_of(&self, rel_path: &Path) -> Option<String> {
+        match self {
+            PathDisplay::Roots { .. } => rel_path.components().next().map(|root| self.show(Path::new(&root))),
+            PathDisplay::Root { home: Some(home) } if rel_path.starts_with(home) => Some("~".to_string()),
+            PathDisplay::Root { .. } => first_component(rel_path).map(|name| format!("/{}", name)),
+            PathDisplay::Home => first_component(rel_path).map(|name| format!("~/{}", name)),
+            PathDisplay::Relative => None,
+        }
+    }
+}
+
+fn tilde(rel_path: &Path) -> String {
+    if rel_path.as_os_str().is_empty() {
+        "~".to_string()
+    } else {
+        format!("~/{}", rel_path.display())
+    }
+}
+
+fn first_component(rel_path: &Path) -> Option<String> {
+    match rel_path.components().next()? {
+        Component::Normal(name) => Some(name.to_string_lossy().to_string()),
+        _ => None,
+    }
+}
@@ -0,0 +1,212 @@
+use crate::applied;
+use crate::identity::Identity;
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// What to do with an overlay entry
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Verdict {
+    Apply,
+    Discard,
+    Skip,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Decision {
+    /// Path relative to the overlay root. Applying takes the entry alone, a
+    /// directory only for its own creation, mode or xattrs; discarding a
+    /// directory takes everything under it.
+    pub path: PathBuf,
+    pub verdict: Verdict,
+    /// SHA-256 of the file as it was reviewed. The plan is refused if the
+    /// file no longer has it, or if a file to apply has none.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+}
+
+/// Decisions made in a review, to be carried out later and elsewhere with
+/// `audit-box apply --plan`, e.g. in CI
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Plan {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created: Option<DateTime<Local>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reviewer: Option<Identity>,
+    pub decisions: Vec<Decision>,
+}
+
+impl Plan {
+    /// Paths with a verdict, in the order the plan lists them
+    pub fn paths(&self, verdict: Verdict) -> Vec<PathBuf> {
+        self.decisions
+            .iter()
+            .filter(|d| d.verdict == verdict)
+            .map(|d| d.path.clone())
+            .collect()
+    }
+
+    /// Entries that cannot be carried out as they were reviewed: files
+    /// applied without a SHA-256, and files whose SHA-256 in the plan is not
+    /// that of the file in the overlay any more, or cannot be computed.
+    /// Entries no longer in the overlay are left to the caller.
+    pub fn unverified(&self, overlay_path: &Path) -> Vec<(PathBuf, Mismatch)> {
+        self.decisions
+            .iter()
+            .filter(|d| d.verdict != Verdict::Skip)
+            .filter_map(|d| {
+                let path = overlay_path.join(&d.path);
+                let Ok(metadata) = fs::symlink_metadata(&path) else {
+                    return None;
+                };
+                let mismatch = match &d.sha256 {
+                    None if d.verdict == Verdict::Apply && metadata.is_file() => Some(Mismatch::Unhashed),
+                    None => None,
+                    // Replaced by a symlink, a directory or a whiteout
+                    Some(_) if !metadata.is_file() => Some(Mismatch::Changed),
+                    Some(sha256) => match applied::hash_file(&path) {
+                        Ok(current) if current == *sha256 => None,
+                        Ok(_) => Some(Mismatch::Changed),
+                        Err(_) => Some(Mismatch::Unreadable),
+                    },
+                };
+                mismatch.map(|m| (d.path.clone(), m))
+            })
+            .collect()
+    }
+}
+
+/// Why an entry in a plan cannot be carried out as reviewed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Mismatch {
+    /// A file to apply with no SHA-256 to check it against
+    Unhashed,
+    /// A file whose SHA-256 cannot be computed
+    Unreadable,
+    /// A file that is not what was reviewed any more
+    Changed,
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Mismatch::Unhashed => "No SHA-256 to check",
+            Mismatch::Unreadable => "Cannot be hashed",
+            Mismatch::Changed => "Changed since it was reviewed",
+        })
+    }
+}
+
+/// Whether a plan file is YAML rather than JSON, by its extension
+fn is_yaml(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("yaml") || ext.eq_ignore_ascii_case("yml"))
+}
+
+/// Read a plan, as YAML for a `.yaml` or `.yml` file and JSON otherwise
+pub fn load(path: &Path) -> io::Result<Plan> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| io::Error::new(e.kind(), format!("Failed to read plan '{}': {}", path.display(), e)))?;
+    let parsed = if is_yaml(path) {
+        serde_yaml_ng::from_str(&content).map_err(|e| e.to_string())
+    } else {
+        serde_json::from_str(&content).map_err(|e| e.to_string())
+    };
+    parsed.map_err(|e| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Failed to parse plan '{}': {}", path.display(), e),
+        )
+    })
+}
+
+/// Write a plan in the format its extension names, as `load` reads it
+pub fn save(path: &Path, plan: &Plan) -> io::Result<()> {
+    let content = if is_yaml(path) {
+        serde_yaml_ng::to_string(plan).map_err(io::Error::other)?
+    } else {
+        serde_json::to_string_pretty(plan).map_err(io::Error::other)? + "\n"
+    };
+    fs::write(path, content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decision(path: &str, verdict: Verdict, sha256: Option<&str>) -> Decision {
+        Decision { path: PathBuf::from(path), verdict, sha256: sha256.map(str::to_string) }
+    }
+
+    fn plan(decisions: Vec<Decision>) -> Plan {
+        Plan { created: None, reviewer: None, decisions }
+    }
+
+    #[test]
+    fn unverified_sorts_out_what_cannot_be_carried_out_as_reviewed() {
+        let overlay = tempfile::tempdir().unwrap();
+        for name in ["reviewed", "changed", "unhashed", "junk", "later"] {
+            fs::write(overlay.path().join(name), name).unwrap();
+        }
+        fs::create_dir(overlay.path().join("now-a-dir")).unwrap();
+        let reviewed = applied::hash_file(&overlay.path().join("reviewed")).unwrap();
+        let plan = plan(vec![
+            decision("reviewed", Verdict::Apply, Some(&reviewed)),
+            decision("changed", Verdict::Apply, Some(&reviewed)),
+            decision("unhashed", Verdict::Apply, None),
+            decision("now-a-dir", Verdict::Apply, Some(&reviewed)),
+            decision("junk", Verdict::Discard, None),
+            decision("later", Verdict::Skip, Some("0000")),
+            decision("gone", Verdict::Apply, Some(&reviewed)),
+        ]);
+
+        assert_eq!(
+            plan.unverified(overlay.path()),
+            [
+                (PathBuf::from("changed"), Mismatch::Changed),
+                (PathBuf::from("unhashed"), Mismatch::Unhashed),
+                (PathBuf::from("now-a-dir"), Mismatch::Changed),
+            ]
+        );
+    }
+
+    #[test]
+    fn unverified_lets_a_directory_be_applied_without_a_hash() {
+        let overlay = tempfile::tempdir().unwrap();
+        fs::create_dir(overlay.path().join("conf.d")).unwrap();
+        let plan = plan(vec![decision("conf.d", Verdict::Apply, None)]);
+        assert!(plan.unverified(overlay.path()).is_empty());
+    }
+
+    #[test]
+    fn plans_round_trip_as_json_and_yaml() {
+        let dir = tempfile::tempdir().unwrap();
+        let written = plan(vec![
+            decision("etc/hosts", Verdict::Apply, Some("9f86d0")),
+            decision("var/log", Verdict::Discard, None),
+        ]);
+        for name in ["plan.json", "plan.yaml", "plan.YML"] {
+            let path = dir.path().join(name);
+            save(&path, &written).unwrap();
+            let read = load(&path).unwrap();
+            assert_eq!(read.paths(Verdict::Apply), [PathBuf::from("etc/hosts")]);
+            assert_eq!(read.paths(Verdict::Discard), [PathBuf::from("var/log")]);
+            assert_eq!(read.decisions[0].sha256.as_deref(), Some("9f86d0"));
+        }
+        assert!(fs::read_to_string(dir.path().join("plan.yaml")).unwrap().contains("verdict: apply"));
+    }
+
+    #[test]
+    fn load_names_the_plan_it_cannot_parse() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("plan.yaml");
+        fs::write(&path, "decisions: [{path: x, verdict: maybe}]").unwrap();
+        let e = load(&path).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+        assert!(e.to_string().starts_with(&format!("Failed to parse plan '{}'", path.display())));
+    }
+}
@@ -0,0 +1,203 @@
+use crate::changeset::{self, Changesets};
+use crate::disk_usage::DiskUsage;
+use crate::file_operations;
+use crate::session::RunRecord;
+use crate::types::{FileStatus, FileTree, LowerView};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::Write;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+pub enum ReportFormat {
+    /// One line per entry: status and relative path
+    Text,
+    /// Machine-readable JSON document
+    Json,
+    /// Indented tree, like the review file list
+    Tree,
+}
+
+/// Summary of the changes in an overlay, independent of the TUI
+#[derive(Debug, Serialize)]
+pub struct Report {
+    pub overlay: PathBuf,
+    pub base: PathBuf,
+    /// Extra lower layers stacked on the base, bottom to top
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub lower_layers: Vec<PathBuf>,
+    /// Bases of a session with several, by the top-level overlay directory
+    /// their entries are under
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub roots: BTreeMap<String, PathBuf>,
+    /// Disk space used by the overlay, in bytes
+    pub overlay_size: u64,
+    /// Commands run in the session that produced the changes
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub runs: Vec<RunRecord>,
+    /// Descriptions of the changesets the entries were grouped into, by name
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub changesets: BTreeMap<String, String>,
+    pub entries: Vec<ReportEntry>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReportEntry {
+    /// Path relative to the overlay root
+    pub path: PathBuf,
+    pub status: FileStatus,
+    pub is_dir: bool,
+    #[serde(skip)]
+    pub depth: usize,
+    /// Copied in from the session template and not changed since
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub seeded: bool,
+    /// Name of the changeset it was grouped into during review
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub changeset: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stats: Option<EntryStats>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct EntryStats {
+    pub size: u64,
+    pub added: usize,
+    pub removed: usize,
+}
+
+pub fn build_report(files: &FileTree, overlay_path: &Path, lower: &LowerView, with_stats: bool) -> Report {
+    let entries = files
+        .iter()
+        .map(|(_, entry)| {
+            let rel_path = &entry.rel_path;
+
+            let stats = (with_stats && !entry.is_dir).then(|| {
+                let base_file = lower.resolve(rel_path);
+                let (added, removed) = file_operations::diff_stats(&entry.path, base_file.as_deref());
+                EntryStats {
+                    size: fs::metadata(&entry.path).map(|m| m.len()).unwrap_or(0),
+                    added,
+                    removed,
+                }
+            });
+
+            ReportEntry {
+                path: rel_path.to_path_buf(),
+                status: entry.status.clone(),
+                is_dir: entry.is_dir,
+                depth: entry.depth,
+                seeded: false,
+                changeset: None,
+                stats,
+            }
+        })
+        .collect();
+
+    Report {
+        overlay: overlay_path.to_path_buf(),
+        base: lower.base.clone(),
+        lower_layers: lower.layers.clone(),
+        roots: lower.roots.clone(),
+        overlay_size: DiskUsage::scan(overlay_path).total(),
+        runs: Vec::new(),
+        changesets: BTreeMap::new(),
+        entries,
+    }
+}
+
+impl Report {
+    /// Mark the entries still as the session template left them
+    pub fn mark_seeded(&mut self, seeded: &HashSet<PathBuf>) {
+        for entry in &mut self.entries {
+            entry.seeded = seeded.contains(&entry.path);
+        }
+    }
+
+    /// Mark the entries with the changesets they were grouped into, and
+    /// keep only those of `only` if given
+    pub fn mark_changesets(&mut self, changesets: &Changesets, only: Option<&str>) {
+        for entry in &mut self.entries {
+            entry.changeset = changeset::of(changesets, &entry.path).map(str::to_string);
+        }
+        if let Some(name) = only {
+            self.entries.retain(|entry| entry.changeset.as_deref() == Some(name));
+        }
+        self.changesets = changesets
+            .iter()
+            .filter(|(name, _)| only.is_none_or(|only| only == name.as_str()))
+            .filter(|(name, _)| self.entries.iter().any(|entry| entry.changeset.as_ref() == Some(name)))
+            .map(|(name, changeset)| (name.clone(), changeset.description.clone()))
+            .collect();
+    }
+}
+
+pub fn render_report(report: &Report, format: ReportFormat) -> io::Result<String> {
+    let mut out = String::new();
+
+    match format {
+        ReportFormat::Json => {
+            out = serde_json::to_string_pretty(report).map_err(io::Error::other)?;
+            out.push('\n');
+        }
+        ReportFormat::Text => {
+            push_runs(&mut out, report);
+            for entry in &report.entries {
+                let slash = if entry.is_dir { "/" } else { "" };
+                let _ = write!(out, "{}  {}{}", status_letter(entry), entry.path.display(), slash);
+                push_stats(&mut out, entry);
+                push_changeset(&mut out, entry);
+                out.push('\n');
+            }
+        }
+        ReportFormat::Tree => {
+            push_runs(&mut out, report);
+            for entry in &report.entries {
+                let name = entry.path.file_name().unwrap_or_default().to_string_lossy();
+                let slash = if entry.is_dir { "/" } else { "" };
+                let indent = "  ".repeat(entry.depth);
+                let _ = write!(out, "{}[{}] {}{}", indent, status_letter(entry), name, slash);
+                push_stats(&mut out, entry);
+                push_changeset(&mut out, entry);
+                out.push('\n');
+            }
+        }
+    }
+
+    Ok(out)
+}
+
+fn status_letter(entry: &ReportEntry) -> char {
+    if entry.seeded {
+        return 'T';
+    }
+    entry.status.letter()
+}
+
+/// Runs and changesets go first as comment lines, leaving one line per entry
+/// below
+fn push_runs(out: &mut String, report: &Report) {
+    for run in &report.runs {
+        let _ = writeln!(out, "# run: {}", run.summary());
+    }
+    for (name, description) in &report.changesets {
+        let _ = match description.as_str() {
+            "" => writeln!(out, "# changeset: {}", name),
+            description => writeln!(out, "# changeset: {}: {}", name, description),
+        };
+    }
+}
+
+fn push_changeset(out: &mut String, entry: &ReportEntry) {
+    if let Some(name) = &entry.changeset {
+        let _ = write!(out, "  [{}]", name);
+    }
+}
+
+fn push_stats(out: &mut String, entry: &ReportEntry) {
+    if let Some(stats) = &entry.stats {
+        let _ = write!(out, "  (+{} -{}, {} bytes)", stats.added, stats.removed, stats.size);
+    }
+}
@@ -0,0 +1,184 @@
+use crate::changeset::{self, Changesets};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, Write};
+use std::os::unix::fs::MetadataExt;
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+
+/// File name of the review state, stored in the session directory
+pub const REVIEW_STATE_FILE: &str = "review-state.json";
+
+/// Review progress that outlives a single `review` run. All paths are
+/// relative to the overlay root. Reviews of the same session running side
+/// by side share it, labels, bookmarks and changesets as they are made.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReviewState {
+    /// Directories collapsed in the file list
+    pub collapsed: BTreeSet<PathBuf>,
+    /// Overlay files as they were when the last review ended, or None
+    /// before the first review has finished
+    pub snapshot: Option<BTreeMap<PathBuf, Fingerprint>>,
+    /// Files queued for apply, in the order they will be applied
+    pub staged: Vec<PathBuf>,
+    /// User-assigned labels of files
+    pub labels: BTreeMap<PathBuf, BTreeSet<String>>,
+    /// Entries bookmarked to come back to
+    pub bookmarks: BTreeSet<PathBuf>,
+    /// Files grouped into logical changes, by name
+    pub changesets: Changesets,
+}
+
+/// Enough of a file's metadata to tell whether it changed between reviews.
+/// The sandbox can set a file's mtime back after rewriting it, but not its
+/// ctime, nor keep its inode when replacing it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Fingerprint {
+    pub size: u64,
+    pub mtime: i64,
+    pub mtime_nsec: i64,
+    #[serde(default)]
+    pub ctime: i64,
+    #[serde(default)]
+    pub ctime_nsec: i64,
+    #[serde(default)]
+    pub dev: u64,
+    #[serde(default)]
+    pub ino: u64,
+}
+
+impl Fingerprint {
+    pub fn of(metadata: &fs::Metadata) -> Self {
+        Fingerprint {
+            size: metadata.len(),
+            mtime: metadata.mtime(),
+            mtime_nsec: metadata.mtime_nsec(),
+            ctime: metadata.ctime(),
+            ctime_nsec: metadata.ctime_nsec(),
+            dev: metadata.dev(),
+            ino: metadata.ino(),
+        }
+    }
+}
+
+pub fn fingerprint(path: &Path) -> Option<Fingerprint> {
+    fs::symlink_metadata(path).ok().as_ref().map(Fingerprint::of)
+}
+
+/// A label, bookmark or changeset added or removed in one review, to be
+/// shared with the others reviewing the same session
+#[derive(Debug, Clone)]
+pub enum MarkChange {
+    Label { rel_path: PathBuf, label: String, added: bool },
+    Bookmark { rel_path: PathBuf, added: bool },
+    Group { name: String, description: String, files: Vec<PathBuf> },
+    Ungroup { files: Vec<PathBuf> },
+    Reorder { names: Vec<String> },
+    Squash { name: String },
+    Describe { name: String, description: String },
+    Hold { name: String, hold: bool },
+}
+
+impl MarkChange {
+    pub fn apply(&self, state: &mut ReviewState) {
+        match self {
+            MarkChange::Label { rel_path, label, added: true } => {
+                state.labels.entry(rel_path.clone()).or_default().insert(label.clone());
+            }
+            MarkChange::Label { rel_path, label, added: false } => {
+                if let Some(labels) = state.labels.get_mut(rel_path) {
+                    labels.remove(label);
+                    if labels.is_empty() {
+                        state.labels.remove(rel_path);
+                    }
+                }
+            }
+            MarkChange::Bookmark { rel_path, added: true } => {
+                state.bookmarks.insert(rel_path.clone());
+            }
+            MarkChange::Bookmark { rel_path, added: false } => {
+                state.bookmarks.remove(rel_path);
+            }
+            MarkChange::Group { name, description, files } => {
+                changeset::group(&mut state.changesets, name, description, files);
+            }
+            MarkChange::Ungroup { files } => changeset::ungroup(&mut state.changesets, files),
+            MarkChange::Reorder { names } => changeset::reorder(&mut state.changesets, names),
+            MarkChange::Squash { name } => changeset::squash(&mut state.changesets, name),
+            MarkChange::Describe { name, description } => {
+                if let Some(changeset) = state.changesets.get_mut(name) {
+                    changeset.description = description.clone();
+                }
+            }
+            MarkChange::Hold { name, hold } => {
+                if let Some(changeset) = state.changesets.get_mut(name) {
+                    changeset.hold = *hold;
+                }
+            }
+        }
+    }
+}
+
+pub fn load_review_state(path: &Path) -> io::Result<ReviewState> {
+    Ok(load_shared(path)?.0)
+}
+
+/// The review state, and the fingerprint of the file it was read from to
+/// tell when another review changes it
+pub fn load_shared(path: &Path) -> io::Result<(ReviewState, Option<Fingerprint>)> {
+    // No state yet means a fresh review
+    let mut file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok((ReviewState::default(), None)),
+        Err(e) => return Err(e),
+    };
+    // Shared, so a review writing the state is never read halfway
+    lock(&file, libc::LOCK_SH)?;
+    let state = read(&mut file, path)?;
+    Ok((state, Some(Fingerprint::of(&file.metadata()?))))
+}
+
+/// Read the review state, change it and write it back, locking out the
+/// other reviews of the session in between so none of their changes are
+/// lost. Returns the state as written, with its file's fingerprint.
+pub fn update_review_state(
+    path: &Path,
+    change: impl FnOnce(&mut ReviewState),
+) -> io::Result<(ReviewState, Option<Fingerprint>)> {
+    let mut file = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(path)?;
+    lock(&file, libc::LOCK_EX)?;
+    let mut state = read(&mut file, path)?;
+    change(&mut state);
+
+    // Rewritten in place: the others lock this file, not whatever replaces it
+    let content = serde_json::to_string_pretty(&state).map_err(io::Error::other)?;
+    file.set_len(0)?;
+    file.rewind()?;
+    file.write_all(content.as_bytes())?;
+    Ok((state, Some(Fingerprint::of(&file.metadata()?))))
+}
+
+fn read(file: &mut File, path: &Path) -> io::Result<ReviewState> {
+    let mut content = String::new();
+    file.read_to_string(&mut content)?;
+    if content.is_empty() {
+        return Ok(ReviewState::default());
+    }
+    serde_json::from_str(&content).map_err(|e| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Failed to parse review state '{}': {}", path.display(), e),
+        )
+    })
+}
+
+/// Lock a file with flock(2) until it is closed
+fn lock(file: &File, operation: libc::c_int) -> io::Result<()> {
+    // SAFETY: flock only acts on the descriptor, which the file keeps open
+    if unsafe { libc::flock(file.as_raw_fd(), operation) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
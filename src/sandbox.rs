@@ -0,0 +1,101 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum SandboxProfile {
+    /// Host filesystem read-only, base directory writable through the overlay
+    #[default]
+    Default,
+    /// Like default, but without network access
+    Offline,
+}
+
+impl fmt::Display for SandboxProfile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SandboxProfile::Default => write!(f, "default"),
+            SandboxProfile::Offline => write!(f, "offline"),
+        }
+    }
+}
+
+/// bwrap arguments that mount `base_path` with a writable overlay, to be
+/// followed by the command to run. `lower_layers` are stacked on the base,
+/// bottom to top, beneath the overlay.
+pub fn bwrap_args(
+    base_path: &Path,
+    lower_layers: &[PathBuf],
+    overlay_path: &Path,
+    work_path: &Path,
+    profile: SandboxProfile,
+) -> Vec<String> {
+    let mut args = common_args();
+    push_overlay(&mut args, base_path, lower_layers, overlay_path, work_path);
+    push_isolation(&mut args, profile);
+    args
+}
+
+/// bwrap arguments for a session with several bases, each mounted with its
+/// own overlay and work directory, named as in `roots`
+pub fn bwrap_root_args(
+    roots: &BTreeMap<String, PathBuf>,
+    overlay_path: &Path,
+    work_path: &Path,
+    profile: SandboxProfile,
+) -> Vec<String> {
+    // A base inside another has to be mounted after it
+    let mut mounts: Vec<(&String, &PathBuf)> = roots.iter().collect();
+    mounts.sort_by_key(|(_, base)| base.components().count());
+
+    let mut args = common_args();
+    for (name, base) in mounts {
+        push_overlay(&mut args, base, &[], &overlay_path.join(name), &work_path.join(name));
+    }
+    push_isolation(&mut args, profile);
+    args
+}
+
+fn common_args() -> Vec<String> {
+    vec![
+        "--ro-bind".to_string(),
+        "/".to_string(),
+        "/".to_string(),
+        "--tmpfs".to_string(),
+        "/tmp".to_string(),
+        "--unshare-pid".to_string(),
+        // Stopping bwrap stops everything in the sandbox
+        "--die-with-parent".to_string(),
+    ]
+}
+
+fn push_overlay(args: &mut Vec<String>, base_path: &Path, lower_layers: &[PathBuf], overlay_path: &Path, work_path: &Path) {
+    args.push("--overlay-src".to_string());
+    args.push(base_path.display().to_string());
+
+    for layer in lower_layers {
+        args.push("--overlay-src".to_string());
+        args.push(layer.display().to_string());
+    }
+
+    args.extend([
+        "--overlay".to_string(),
+        overlay_path.display().to_string(),
+        work_path.display().to_string(),
+        base_path.display().to_string(),
+    ]);
+}
+
+fn push_isolation(args: &mut Vec<String>, profile: SandboxProfile) {
+    args.extend([
+        "--dev".to_string(),
+        "/dev".to_string(),
+        "--new-session".to_string(),
+    ]);
+
+    if profile == SandboxProfile::Offline {
+        args.push("--unshare-net".to_string());
+    }
+}
@@ -0,0 +1,54 @@
+use crate::review_state::Fingerprint;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// File name of the scan cache, stored in the session directory
+pub const SCAN_CACHE_FILE: &str = "scan-cache.json";
+
+/// What the last review found out about an overlay file. Only what depends
+/// on the file alone is kept: its status goes by the base too, which may
+/// have changed since, and is cheap to look up again.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedEntry {
+    #[serde(flatten)]
+    pub fingerprint: Fingerprint,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+}
+
+/// Overlay files as the last review left them, by path relative to the
+/// overlay root, so the next one only hashes again the files that changed.
+/// `review --rescan` starts afresh.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ScanCache {
+    pub entries: BTreeMap<PathBuf, CachedEntry>,
+}
+
+/// The session's scan cache, or an empty one if there is none or it cannot
+/// be read: it only saves time
+pub fn load(session_dir: &Path) -> ScanCache {
+    fs::read_to_string(session_dir.join(SCAN_CACHE_FILE))
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+pub fn save(session_dir: &Path, cache: &ScanCache) -> io::Result<()> {
+    let content = serde_json::to_string(cache).map_err(io::Error::other)?;
+    // Written aside and renamed into place, so a review that ends midway
+    // does not leave half a cache
+    let temp_path = session_dir.join(format!(".{}.tmp", SCAN_CACHE_FILE));
+    fs::write(&temp_path, content)?;
+    fs::rename(&temp_path, session_dir.join(SCAN_CACHE_FILE))
+}
+
+/// Forget the session's scan cache, so the next review scans from scratch
+pub fn clear(session_dir: &Path) -> io::Result<()> {
+    match fs::remove_file(session_dir.join(SCAN_CACHE_FILE)) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,222 @@
+use crate::checks::{self, CheckResult};
+use crate::config::ServiceConfig;
+use crate::types::{FileEntry, LowerView};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Directories systemd loads system units from
+const UNIT_DIRS: [&str; 4] = [
+    "/etc/systemd/system",
+    "/run/systemd/system",
+    "/usr/lib/systemd/system",
+    "/lib/systemd/system",
+];
+
+/// Extensions of unit files, as listed in systemd.unit(5)
+const UNIT_EXTENSIONS: [&str; 11] = [
+    "service",
+    "socket",
+    "device",
+    "mount",
+    "automount",
+    "swap",
+    "target",
+    "path",
+    "timer",
+    "slice",
+    "scope",
+];
+
+/// Seconds before a reload that has not finished is stopped
+const TIMEOUT_SECS: u64 = 60;
+
+/// A command to run once applied files are in place, so whatever reads
+/// them picks them up
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServiceAction {
+    pub command: Vec<String>,
+    /// The applied file that called for it, the first if several did
+    pub because: PathBuf,
+}
+
+impl ServiceAction {
+    pub fn command_line(&self) -> String {
+        self.command.join(" ")
+    }
+}
+
+struct Service {
+    config: ServiceConfig,
+    files: GlobSet,
+}
+
+/// The configured services and the files belonging to each
+pub struct Services {
+    services: Vec<Service>,
+}
+
+impl Services {
+    pub fn new(configs: &[ServiceConfig]) -> io::Result<Services> {
+        let mut services = Vec::new();
+        for config in configs {
+            let mut files = GlobSetBuilder::new();
+            for pattern in &config.files {
+                files.add(Glob::new(pattern).map_err(|e| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("Invalid file pattern '{}' for service '{}': {}", pattern, config.name, e),
+                    )
+                })?);
+            }
+            services.push(Service {
+                config: config.clone(),
+                files: files.build().map_err(io::Error::other)?,
+            });
+        }
+        Ok(Services { services })
+    }
+
+    /// What applying `entries` calls for: `systemctl daemon-reload` if a
+    /// unit file changed, then reloading each service a file belongs to
+    pub fn actions(&self, entries: &[FileEntry], lower: &LowerView) -> Vec<ServiceAction> {
+        let files: Vec<&FileEntry> = entries.iter().filter(|e| !e.is_dir).collect();
+        let mut actions = Vec::new();
+        if let Some(unit) = files.iter().find(|e| is_unit(&lower.base_path(&e.rel_path))) {
+            actions.push(ServiceAction { command: daemon_reload(), because: unit.rel_path.clone() });
+        }
+        for service in &self.services {
+            if let Some(entry) = files.iter().find(|e| service.files.is_match(&e.rel_path)) {
+                let command = if service.config.command.is_empty() {
+                    vec!["systemctl".to_string(), "reload".to_string(), service.config.name.clone()]
+                } else {
+                    service.config.command.clone()
+                };
+                actions.push(ServiceAction { command, because: entry.rel_path.clone() });
+            }
+        }
+        actions
+    }
+}
+
+fn daemon_reload() -> Vec<String> {
+    vec!["systemctl".to_string(), "daemon-reload".to_string()]
+}
+
+/// Add actions to those pending that are not there already, keeping
+/// `systemctl daemon-reload` ahead of the reloads that rely on it
+pub fn merge(pending: &mut Vec<ServiceAction>, actions: Vec<ServiceAction>) {
+    for action in actions {
+        if pending.iter().any(|p| p.command == action.command) {
+            continue;
+        }
+        if action.command == daemon_reload() {
+            pending.insert(0, action);
+        } else {
+            pending.push(action);
+        }
+    }
+}
+
+/// Whether a file is a systemd unit, or a drop-in for one, in a directory
+/// systemd loads units from, e.g. /etc/systemd/system/nginx.service.d/override.conf
+pub fn is_unit(path: &Path) -> bool {
+    if !UNIT_DIRS.iter().any(|dir| path.starts_with(dir)) {
+        return false;
+    }
+    let extension = path.extension().map(|ext| ext.to_string_lossy()).unwrap_or_default();
+    let in_drop_in = path
+        .parent()
+        .and_then(Path::file_name)
+        .is_some_and(|dir| dir.to_string_lossy().ends_with(".d"));
+    UNIT_EXTENSIONS.contains(&extension.as_ref()) || (in_drop_in && extension == "conf")
+}
+
+pub fn run(action: &ServiceAction) -> CheckResult {
+    checks::run_command(&action.command_line(), &action.command, None, TIMEOUT_SECS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn action(command: &[&str], because: &str) -> ServiceAction {
+        ServiceAction { command: command.iter().map(|s| s.to_string()).collect(), because: PathBuf::from(because) }
+    }
+
+    #[test]
+    fn is_unit_recognises_units_and_drop_ins() {
+        assert!(is_unit(Path::new("/etc/systemd/system/nginx.service")));
+        assert!(is_unit(Path::new("/usr/lib/systemd/system/backup.timer")));
+        assert!(is_unit(Path::new("/etc/systemd/system/nginx.service.d/override.conf")));
+    }
+
+    #[test]
+    fn is_unit_leaves_out_other_files() {
+        assert!(!is_unit(Path::new("/etc/nginx/nginx.conf")));
+        assert!(!is_unit(Path::new("/home/user/nginx.service")));
+        assert!(!is_unit(Path::new("/etc/systemd/system/notes.conf")));
+        assert!(!is_unit(Path::new("/etc/systemd/system/nginx.service.d/README")));
+    }
+
+    #[test]
+    fn merge_skips_actions_already_pending() {
+        let mut pending = vec![action(&["systemctl", "reload", "nginx"], "etc/nginx/nginx.conf")];
+        merge(
+            &mut pending,
+            vec![
+                action(&["systemctl", "reload", "nginx"], "etc/nginx/sites/default"),
+                action(&["systemctl", "reload", "sshd"], "etc/ssh/sshd_config"),
+            ],
+        );
+        assert_eq!(
+            pending,
+            [
+                action(&["systemctl", "reload", "nginx"], "etc/nginx/nginx.conf"),
+                action(&["systemctl", "reload", "sshd"], "etc/ssh/sshd_config"),
+            ]
+        );
+    }
+
+    #[test]
+    fn merge_puts_daemon_reload_first() {
+        let mut pending = vec![action(&["systemctl", "reload", "nginx"], "etc/nginx/nginx.conf")];
+        merge(&mut pending, vec![action(&["systemctl", "daemon-reload"], "etc/systemd/system/nginx.service")]);
+        merge(&mut pending, vec![action(&["systemctl", "daemon-reload"], "etc/systemd/system/sshd.service")]);
+        assert_eq!(pending.len(), 2);
+        assert_eq!(pending[0].command, daemon_reload());
+        assert_eq!(pending[0].because, Path::new("etc/systemd/system/nginx.service"));
+    }
+
+    #[test]
+    fn actions_reload_the_services_a_file_belongs_to() {
+        let services = Services::new(&[ServiceConfig {
+            name: "nginx".to_string(),
+            files: vec!["etc/nginx/**".to_string()],
+            command: Vec::new(),
+        }])
+        .unwrap();
+        let entry = |rel_path: &str| FileEntry {
+            path: PathBuf::from("/overlay").join(rel_path),
+            rel_path: PathBuf::from(rel_path),
+            name: rel_path.to_string(),
+            is_dir: false,
+            depth: 0,
+            status: crate::types::FileStatus::Modified,
+            selected: false,
+            collapsed: false,
+        };
+        let lower = LowerView::new(PathBuf::from("/"), Vec::new());
+        let actions = services.actions(
+            &[entry("etc/hosts"), entry("etc/nginx/nginx.conf"), entry("etc/systemd/system/nginx.service")],
+            &lower,
+        );
+        assert_eq!(
+            actions,
+            [
+                action(&["systemctl", "daemon-reload"], "etc/systemd/system/nginx.service"),
+                action(&["systemctl", "reload", "nginx"], "etc/nginx/nginx.conf"),
+            ]
+        );
+    }
+}
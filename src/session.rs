@@ -1,13 +1,109 @@
+use crate::review_state::Fingerprint;
+use crate::sandbox::SandboxProfile;
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fs;
 use std::io::{self, Write};
 use std::path::{Path, PathBuf};
 
 const SESSION_FILE: &str = ".config/audit-box/sessions";
 
+/// File name of the session metadata, stored in the session directory
+pub const METADATA_FILE: &str = "session.json";
+
 #[derive(Debug)]
 pub struct Session {
     pub tmpdir: PathBuf,
     pub base_path: PathBuf,
+    /// Extra lower layers stacked on the base, bottom to top
+    pub lower_layers: Vec<PathBuf>,
+}
+
+/// What happened in a session, kept alongside the overlay
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SessionMetadata {
+    /// Commands launched with `run`, oldest first
+    pub runs: Vec<RunRecord>,
+    /// Template directory the overlay was seeded from by `new --template`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub template: Option<PathBuf>,
+    /// Files copied from the template, by path relative to the overlay root,
+    /// as they were when seeded
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub seeded: BTreeMap<PathBuf, Fingerprint>,
+    /// Bases of a session created with more than one, by the name of the
+    /// overlay and work directories each is overlaid with
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub roots: BTreeMap<String, PathBuf>,
+}
+
+/// A command launched in the sandbox
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunRecord {
+    pub command: Vec<String>,
+    pub profile: SandboxProfile,
+    pub started: DateTime<Local>,
+    /// None while the command is still running, or if audit-box was killed
+    pub finished: Option<DateTime<Local>>,
+    pub exit_code: Option<i32>,
+    /// Signal that terminated the command, if any
+    pub signal: Option<i32>,
+    /// Process id of bwrap, to tell whether the sandbox is still running
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pid: Option<u32>,
+}
+
+impl RunRecord {
+    pub fn command_line(&self) -> String {
+        self.command.join(" ")
+    }
+
+    /// One-line description of how the run ended
+    pub fn outcome(&self) -> String {
+        match (self.finished, self.exit_code, self.signal) {
+            (None, _, _) => "still running or interrupted".to_string(),
+            (Some(_), Some(code), _) => format!("exit {}", code),
+            (Some(_), None, Some(signal)) => format!("killed by signal {}", signal),
+            (Some(_), None, None) => "unknown exit status".to_string(),
+        }
+    }
+
+    /// e.g. "npm install (exit 0, 12:03:10 - 12:05:42)"
+    pub fn summary(&self) -> String {
+        let started = self.started.format("%Y-%m-%d %H:%M:%S");
+        match self.finished {
+            Some(finished) => format!(
+                "{} ({}, {} - {})",
+                self.command_line(),
+                self.outcome(),
+                started,
+                finished.format("%H:%M:%S")
+            ),
+            None => format!("{} ({}, started {})", self.command_line(), self.outcome(), started),
+        }
+    }
+}
+
+pub fn load_metadata(session_dir: &Path) -> io::Result<SessionMetadata> {
+    let path = session_dir.join(METADATA_FILE);
+    if !path.exists() {
+        return Ok(SessionMetadata::default());
+    }
+
+    let content = fs::read_to_string(&path)?;
+    serde_json::from_str(&content).map_err(|e| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Failed to parse session metadata '{}': {}", path.display(), e),
+        )
+    })
+}
+
+pub fn save_metadata(session_dir: &Path, metadata: &SessionMetadata) -> io::Result<()> {
+    let content = serde_json::to_string_pretty(metadata).map_err(io::Error::other)?;
+    fs::write(session_dir.join(METADATA_FILE), content)
 }
 
 pub fn get_session_file_path() -> io::Result<PathBuf> {
@@ -16,7 +112,7 @@ pub fn get_session_file_path() -> io::Result<PathBuf> {
     Ok(home.join(SESSION_FILE))
 }
 
-pub fn save_session(tmpdir: &Path, base_path: &Path) -> io::Result<()> {
+pub fn save_session(tmpdir: &Path, base_path: &Path, lower_layers: &[PathBuf]) -> io::Result<()> {
     let session_path = get_session_file_path()?;
 
     // Create parent directory if it doesn't exist
@@ -24,10 +120,13 @@ pub fn save_session(tmpdir: &Path, base_path: &Path) -> io::Result<()> {
         fs::create_dir_all(parent)?;
     }
 
-    // Write the tmpdir and base path to the session file (one per line)
+    // Write the tmpdir, base path and any extra lower layers to the session file (one per line)
     let mut file = fs::File::create(&session_path)?;
     writeln!(file, "{}", tmpdir.display())?;
     writeln!(file, "{}", base_path.display())?;
+    for layer in lower_layers {
+        writeln!(file, "{}", layer.display())?;
+    }
 
     Ok(())
 }
@@ -54,6 +153,7 @@ pub fn load_session() -> io::Result<Session> {
 
     let tmpdir = PathBuf::from(lines[0]);
     let base_path = PathBuf::from(lines[1]);
+    let lower_layers = lines[2..].iter().map(PathBuf::from).collect();
 
     // Check if the directory still exists
     if !tmpdir.exists() {
@@ -68,18 +168,55 @@ pub fn load_session() -> io::Result<Session> {
         ));
     }
 
-    Ok(Session { tmpdir, base_path })
+    Ok(Session {
+        tmpdir,
+        base_path,
+        lower_layers,
+    })
 }
 
-pub fn create_session_dir() -> io::Result<PathBuf> {
-    // Create a unique temporary directory in /tmp
+/// Name the overlay directory of each base after its last component, e.g.
+/// `etc` for `/etc`, numbering any that would clash
+pub fn root_names(bases: &[PathBuf]) -> BTreeMap<String, PathBuf> {
+    let mut roots = BTreeMap::new();
+    for base in bases {
+        let name = base
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| "root".to_string());
+        let mut unique = name.clone();
+        let mut n = 2;
+        while roots.contains_key(&unique) {
+            unique = format!("{}-{}", name, n);
+            n += 1;
+        }
+        roots.insert(unique, base.clone());
+    }
+    roots
+}
+
+/// Default parent directory for session directories: the system's
+/// temporary directory, i.e. $TMPDIR or /tmp on Unix
+pub fn default_workdir() -> PathBuf {
+    std::env::temp_dir()
+}
+
+pub fn create_session_dir(workdir: &Path) -> io::Result<PathBuf> {
+    if !workdir.is_dir() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("Work directory '{}' does not exist", workdir.display()),
+        ));
+    }
+
+    // Create a unique temporary directory in the work directory
     let tmpdir = tempfile::Builder::new()
         .prefix("audit-box-")
-        .tempdir_in("/tmp")?;
+        .tempdir_in(workdir)?;
 
     // Keep the temp directory (don't delete on drop) and get its path
     #[allow(deprecated)]
-    let tmpdir_path = tmpdir.into_path();
+    let tmpdir_path = fs::canonicalize(tmpdir.into_path())?;
 
     // Create overlay and work subdirectories
     fs::create_dir_all(tmpdir_path.join("overlay"))?;
@@ -88,6 +225,24 @@ pub fn create_session_dir() -> io::Result<PathBuf> {
     Ok(tmpdir_path)
 }
 
+/// Free space in bytes available to unprivileged users on the filesystem
+/// containing `path`
+pub fn available_space(path: &Path) -> io::Result<u64> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let c_path = CString::new(path.as_os_str().as_bytes())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    // SAFETY: c_path is a valid NUL-terminated string and stat is a valid out pointer
+    if unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    #[allow(clippy::unnecessary_cast)]
+    Ok(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
 pub fn clear_session() -> io::Result<()> {
     let session_path = get_session_file_path()?;
     if session_path.exists() {
@@ -0,0 +1,56 @@
+use crate::review_state::{self, Fingerprint};
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::io;
+use std::os::unix::fs::symlink;
+use std::path::{Path, PathBuf};
+
+/// Copy a template directory into a new session's overlay, returning the
+/// fingerprints of the seeded files by path relative to the overlay root
+pub fn seed(template: &Path, overlay_path: &Path) -> io::Result<BTreeMap<PathBuf, Fingerprint>> {
+    let mut seeded = BTreeMap::new();
+    copy_tree(template, template, overlay_path, &mut seeded)?;
+    Ok(seeded)
+}
+
+fn copy_tree(
+    template: &Path,
+    dir: &Path,
+    overlay_path: &Path,
+    seeded: &mut BTreeMap<PathBuf, Fingerprint>,
+) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let source = entry.path();
+        let rel_path = source.strip_prefix(template).unwrap_or(&source).to_path_buf();
+        let dest = overlay_path.join(&rel_path);
+        let file_type = entry.file_type()?;
+
+        if file_type.is_dir() {
+            fs::create_dir_all(&dest)?;
+            fs::set_permissions(&dest, entry.metadata()?.permissions())?;
+            copy_tree(template, &source, overlay_path, seeded)?;
+            continue;
+        }
+
+        if file_type.is_symlink() {
+            symlink(fs::read_link(&source)?, &dest)?;
+        } else {
+            fs::copy(&source, &dest)?;
+        }
+        if let Some(fingerprint) = review_state::fingerprint(&dest) {
+            seeded.insert(rel_path, fingerprint);
+        }
+    }
+    Ok(())
+}
+
+/// Seeded files still exactly as the template left them. Files the sandbox
+/// rewrote are reviewed like any other change.
+pub fn unchanged(seeded: &BTreeMap<PathBuf, Fingerprint>, overlay_path: &Path) -> HashSet<PathBuf> {
+    seeded
+        .iter()
+        .filter(|(path, fingerprint)| review_state::fingerprint(&overlay_path.join(path)).as_ref() == Some(*fingerprint))
+        .map(|(path, _)| path.clone())
+        .collect()
+}
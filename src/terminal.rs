@@ -0,0 +1,65 @@
+use crossterm::{
+    cursor::Show,
+    event::{DisableFocusChange, EnableFocusChange},
+    execute,
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Set by the SIGTSTP handler, e.g. for `kill -TSTP`; Ctrl-Z itself arrives
+/// as a key press in raw mode
+static SUSPEND_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn request_suspend(_signal: libc::c_int) {
+    SUSPEND_REQUESTED.store(true, Ordering::Relaxed);
+}
+
+/// Switch to raw mode and the alternate screen, with focus changes reported
+pub fn enter() -> io::Result<()> {
+    enable_raw_mode()?;
+    execute!(io::stdout(), EnterAlternateScreen, EnableFocusChange)
+}
+
+/// Give the terminal back to the shell
+pub fn leave() -> io::Result<()> {
+    disable_raw_mode()?;
+    execute!(io::stdout(), DisableFocusChange, LeaveAlternateScreen, Show)
+}
+
+/// Restore the terminal before a panic message is printed, so a crash never
+/// leaves the shell in raw mode
+pub fn install_panic_hook() {
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let _ = leave();
+        previous(info);
+    }));
+}
+
+/// Catch SIGTSTP so the terminal is restored before the process stops
+pub fn install_suspend_handler() {
+    // SAFETY: the handler only stores to an atomic, which is async-signal-safe
+    unsafe {
+        libc::signal(libc::SIGTSTP, request_suspend as *const () as libc::sighandler_t);
+    }
+}
+
+/// Whether a SIGTSTP arrived since the last check
+pub fn suspend_requested() -> bool {
+    SUSPEND_REQUESTED.swap(false, Ordering::Relaxed)
+}
+
+/// Stop the process like a shell's job control would, restoring the terminal
+/// first and taking it over again once resumed with SIGCONT
+pub fn suspend() -> io::Result<()> {
+    leave()?;
+    // SAFETY: resetting and raising a signal has no other preconditions.
+    // raise() returns once SIGCONT resumes the process.
+    unsafe {
+        libc::signal(libc::SIGTSTP, libc::SIG_DFL);
+        libc::raise(libc::SIGTSTP);
+    }
+    install_suspend_handler();
+    enter()
+}
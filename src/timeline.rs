@@ -0,0 +1,328 @@
+use chrono::{DateTime, Local, TimeDelta};
+use notify::EventKind;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// File name of the event timeline, stored in the session directory
+pub const TIMELINE_FILE: &str = "timeline.jsonl";
+
+/// Repeated writes to a file within this many milliseconds are recorded once
+const MODIFY_COALESCE_MS: i64 = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Activity {
+    Created,
+    Modified,
+    Removed,
+}
+
+/// One filesystem event in the overlay, one JSON object per line on disk
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FsEvent {
+    pub time: DateTime<Local>,
+    /// Path relative to the overlay root
+    pub path: PathBuf,
+    pub activity: Activity,
+}
+
+/// Appends overlay events to a session's timeline
+pub struct Recorder {
+    writer: BufWriter<File>,
+    overlay_path: PathBuf,
+    last_modified: HashMap<PathBuf, DateTime<Local>>,
+}
+
+impl Recorder {
+    pub fn open(session_dir: &Path, overlay_path: &Path) -> io::Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(session_dir.join(TIMELINE_FILE))?;
+        Ok(Recorder {
+            writer: BufWriter::new(file),
+            overlay_path: overlay_path.to_path_buf(),
+            last_modified: HashMap::new(),
+        })
+    }
+
+    pub fn record(&mut self, event: &notify::Event) -> io::Result<()> {
+        let activity = match event.kind {
+            EventKind::Create(_) => Activity::Created,
+            EventKind::Modify(_) => Activity::Modified,
+            EventKind::Remove(_) => Activity::Removed,
+            _ => return Ok(()),
+        };
+        let time = Local::now();
+
+        for path in &event.paths {
+            let Ok(rel_path) = path.strip_prefix(&self.overlay_path) else {
+                continue;
+            };
+
+            // Installers write in many small chunks; one event per burst is enough
+            if activity == Activity::Modified {
+                if let Some(last) = self.last_modified.get(rel_path)
+                    && (time - *last).num_milliseconds() < MODIFY_COALESCE_MS
+                {
+                    continue;
+                }
+                self.last_modified.insert(rel_path.to_path_buf(), time);
+            }
+
+            let event = FsEvent {
+                time,
+                path: rel_path.to_path_buf(),
+                activity,
+            };
+            let line = serde_json::to_string(&event).map_err(io::Error::other)?;
+            writeln!(self.writer, "{}", line)?;
+        }
+        self.writer.flush()
+    }
+}
+
+pub fn load_events(session_dir: &Path) -> io::Result<Vec<FsEvent>> {
+    let path = session_dir.join(TIMELINE_FILE);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    // Skip lines cut short by a crash rather than losing the whole timeline
+    let reader = BufReader::new(File::open(path)?);
+    let mut events = Vec::new();
+    for line in reader.lines() {
+        if let Ok(event) = serde_json::from_str(&line?) {
+            events.push(event);
+        }
+    }
+    Ok(events)
+}
+
+/// What happened to one path over the session
+#[derive(Debug, Clone)]
+pub struct PathActivity {
+    pub path: PathBuf,
+    pub created: Option<DateTime<Local>>,
+    pub last_modified: Option<DateTime<Local>>,
+    pub removed: Option<DateTime<Local>>,
+    /// Whether the file is still in the overlay. Files removed with their
+    /// directory may have no removal event of their own.
+    pub present: bool,
+    pub events: usize,
+}
+
+impl PathActivity {
+    /// Time of the most recent activity
+    pub fn latest(&self) -> Option<DateTime<Local>> {
+        [self.created, self.last_modified, self.removed].into_iter().flatten().max()
+    }
+}
+
+/// Fold the events into one entry per path. Files in `current` without any
+/// recorded events, e.g. from runs before the timeline was kept, fall back to
+/// their modification time.
+pub fn summarize(events: &[FsEvent], current: &[(PathBuf, Option<DateTime<Local>>)]) -> Vec<PathActivity> {
+    let mut by_path: BTreeMap<PathBuf, PathActivity> = BTreeMap::new();
+
+    for event in events {
+        let entry = by_path.entry(event.path.clone()).or_insert_with(|| PathActivity {
+            path: event.path.clone(),
+            created: None,
+            last_modified: None,
+            removed: None,
+            present: false,
+            events: 0,
+        });
+        entry.events += 1;
+        match event.activity {
+            Activity::Created => {
+                entry.created.get_or_insert(event.time);
+                entry.last_modified = Some(event.time);
+                entry.removed = None;
+            }
+            Activity::Modified => {
+                entry.last_modified = Some(event.time);
+                entry.removed = None;
+            }
+            Activity::Removed => entry.removed = Some(event.time),
+        }
+    }
+
+    for (path, mtime) in current {
+        let entry = by_path.entry(path.clone()).or_insert_with(|| PathActivity {
+            path: path.clone(),
+            created: None,
+            last_modified: *mtime,
+            removed: None,
+            present: true,
+            events: 0,
+        });
+        // A file that is present again was recreated after the removal
+        entry.removed = None;
+        entry.present = true;
+    }
+
+    by_path.into_values().collect()
+}
+
+/// Files written close together in time, likely by one operation of the
+/// sandboxed program, e.g. a package install, as a suggested changeset
+#[derive(Debug, Clone)]
+pub struct Cluster {
+    /// Paths relative to the overlay root, in the order they were written
+    pub files: Vec<PathBuf>,
+    pub first: DateTime<Local>,
+    pub last: DateTime<Local>,
+}
+
+impl Cluster {
+    /// The directory its files have in common, if they have one
+    pub fn common_dir(&self) -> Option<PathBuf> {
+        let mut dirs = self.files.iter().map(|path| path.parent().unwrap_or(Path::new("")));
+        let mut common = dirs.next()?.to_path_buf();
+        for dir in dirs {
+            while !dir.starts_with(&common) {
+                if !common.pop() {
+                    break;
+                }
+            }
+        }
+        (!common.as_os_str().is_empty()).then_some(common)
+    }
+}
+
+/// Group the files still present by when they were last written, starting a
+/// new group wherever more than `gap` passes between one write and the next.
+/// Files on their own are left out, as there is nothing to group them with.
+pub fn cluster(entries: &[PathActivity], gap: TimeDelta) -> Vec<Cluster> {
+    let mut written: Vec<(DateTime<Local>, &Path)> = entries
+        .iter()
+        .filter(|entry| entry.present)
+        .filter_map(|entry| Some((entry.last_modified.or(entry.created)?, entry.path.as_path())))
+        .collect();
+    written.sort();
+
+    let mut clusters: Vec<Cluster> = Vec::new();
+    for (time, path) in written {
+        match clusters.last_mut() {
+            Some(cluster) if time - cluster.last <= gap => {
+                cluster.files.push(path.to_path_buf());
+                cluster.last = time;
+            }
+            _ => clusters.push(Cluster { files: vec![path.to_path_buf()], first: time, last: time }),
+        }
+    }
+    clusters.retain(|cluster| cluster.files.len() > 1);
+    clusters
+}
+
+/// Modification time of a file, for paths without recorded events
+pub fn mtime(path: &Path) -> Option<DateTime<Local>> {
+    fs::symlink_metadata(path).and_then(|m| m.modified()).ok().map(DateTime::from)
+}
+
+/// Order of the timeline view
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum TimelineSort {
+    #[default]
+    Recent,
+    Oldest,
+    Path,
+}
+
+impl TimelineSort {
+    pub fn next(self) -> Self {
+        match self {
+            TimelineSort::Recent => TimelineSort::Oldest,
+            TimelineSort::Oldest => TimelineSort::Path,
+            TimelineSort::Path => TimelineSort::Recent,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            TimelineSort::Recent => "most recent first",
+            TimelineSort::Oldest => "oldest first",
+            TimelineSort::Path => "by path",
+        }
+    }
+
+    pub fn sort(self, entries: &mut [PathActivity]) {
+        match self {
+            TimelineSort::Recent => entries.sort_by_key(|e| std::cmp::Reverse(e.latest())),
+            TimelineSort::Oldest => entries.sort_by_key(|e| e.latest()),
+            TimelineSort::Path => entries.sort_by(|a, b| a.path.cmp(&b.path)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn written(path: &str, secs: i64) -> PathActivity {
+        PathActivity {
+            path: PathBuf::from(path),
+            created: None,
+            last_modified: Some(Local.timestamp_opt(1_700_000_000 + secs, 0).unwrap()),
+            removed: None,
+            present: true,
+            events: 1,
+        }
+    }
+
+    fn cluster_of(files: &[&str]) -> Cluster {
+        let time = Local.timestamp_opt(1_700_000_000, 0).unwrap();
+        Cluster { files: files.iter().map(PathBuf::from).collect(), first: time, last: time }
+    }
+
+    #[test]
+    fn cluster_splits_at_gaps() {
+        let entries = [
+            written("usr/bin/b", 1),
+            written("usr/bin/a", 0),
+            written("etc/c", 3),
+            written("var/lib/d", 10),
+            written("var/lib/e", 12),
+        ];
+        let clusters = cluster(&entries, TimeDelta::seconds(2));
+        let files: Vec<Vec<PathBuf>> = clusters.into_iter().map(|c| c.files).collect();
+        assert_eq!(
+            files,
+            [
+                vec![PathBuf::from("usr/bin/a"), PathBuf::from("usr/bin/b"), PathBuf::from("etc/c")],
+                vec![PathBuf::from("var/lib/d"), PathBuf::from("var/lib/e")],
+            ]
+        );
+    }
+
+    #[test]
+    fn cluster_leaves_out_lone_and_removed_files() {
+        let mut removed = written("tmp/gone", 1);
+        removed.present = false;
+        let entries = [written("a", 0), removed, written("b", 100)];
+        assert!(cluster(&entries, TimeDelta::seconds(3)).is_empty());
+    }
+
+    #[test]
+    fn common_dir_of_files_in_one_directory() {
+        assert_eq!(cluster_of(&["etc/nginx/a.conf", "etc/nginx/b.conf"]).common_dir(), Some(PathBuf::from("etc/nginx")));
+    }
+
+    #[test]
+    fn common_dir_stops_at_the_shared_ancestor() {
+        assert_eq!(cluster_of(&["usr/lib/x/1", "usr/lib/y/2", "usr/share/3"]).common_dir(), Some(PathBuf::from("usr")));
+        assert_eq!(cluster_of(&["usr/lib/x/1", "usr/lib/x/y/2"]).common_dir(), Some(PathBuf::from("usr/lib/x")));
+    }
+
+    #[test]
+    fn common_dir_of_unrelated_files_is_none() {
+        assert_eq!(cluster_of(&["etc/a", "var/b"]).common_dir(), None);
+        assert_eq!(cluster_of(&["a", "b"]).common_dir(), None);
+    }
+}
@@ -0,0 +1,49 @@
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use std::io;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+/// How long a transient file has to be left alone before it is listed. Swap
+/// and backup files an editor is using are rewritten or removed well within
+/// this.
+pub const SETTLE_TIME: Duration = Duration::from_secs(3);
+
+/// Editor swap files, backups and other temporary files that programs in the
+/// sandbox create and remove again while they work. They are kept out of the
+/// review until they settle, so they do not flash in and out of the list.
+/// The ones left behind are listed like any other file.
+pub struct TransientFiles {
+    patterns: GlobSet,
+}
+
+impl TransientFiles {
+    /// Transient files by file name, e.g. `*.swp`
+    pub fn new(patterns: &[String]) -> io::Result<Self> {
+        let mut builder = GlobSetBuilder::new();
+        for pattern in patterns {
+            let glob = Glob::new(pattern).map_err(|e| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("Invalid transient file pattern '{}': {}", pattern, e),
+                )
+            })?;
+            builder.add(glob);
+        }
+        let patterns = builder.build().map_err(io::Error::other)?;
+        Ok(TransientFiles { patterns })
+    }
+
+    /// Whether a path's file name is one of the patterns
+    pub fn matches(&self, path: &Path) -> bool {
+        path.file_name().is_some_and(|name| self.patterns.is_match(name))
+    }
+
+    /// Whether a path is a transient file changed too recently to list
+    pub fn is_unsettled(&self, path: &Path) -> bool {
+        self.matches(path)
+            && path
+                .symlink_metadata()
+                .and_then(|m| m.modified())
+                .is_ok_and(|modified| SystemTime::now().duration_since(modified).is_ok_and(|age| age < SETTLE_TIME))
+    }
+}
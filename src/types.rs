@@ -1,26 +1,168 @@
-use std::path::PathBuf;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io;
+use std::os::unix::fs::{FileTypeExt, MetadataExt};
+use std::path::{Component, Path, PathBuf};
+use std::time::Instant;
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum FileStatus {
     New,
     Modified,
+    /// Removed by the sandbox: an overlayfs whiteout hides the base's copy
+    Deleted,
+    /// A different kind of entry than the base's, e.g. a regular file where
+    /// the base has a symlink, so there is no diff between the two
+    #[serde(rename = "type-changed")]
+    TypeChanged,
+}
+
+impl FileStatus {
+    /// The letter a change is listed with, e.g. 'M' in "M  etc/hosts"
+    pub fn letter(&self) -> char {
+        match self {
+            FileStatus::New => 'N',
+            FileStatus::Modified => 'M',
+            FileStatus::Deleted => 'D',
+            FileStatus::TypeChanged => 'K',
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum ActivePane {
     FileList,
     FileContent,
+    Staging,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+/// Buttons of the apply and discard dialogs. Cancel, the safe one, is
+/// where Enter starts unless configured otherwise for the dialog.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum DialogButton {
     Ok,
+    #[default]
+    Cancel,
+}
+
+impl DialogButton {
+    /// The other button, for moving between them
+    pub fn other(self) -> Self {
+        match self {
+            DialogButton::Ok => DialogButton::Cancel,
+            DialogButton::Cancel => DialogButton::Ok,
+        }
+    }
+}
+
+/// Buttons of the quit dialog, left to right
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum QuitChoice {
+    Quit,
+    ApplyAndQuit,
     Cancel,
 }
 
+/// What the apply dialog applies
+#[derive(Debug, Clone, PartialEq)]
+pub enum ApplyScope {
+    /// The checked files
+    Selected,
+    /// Everything under a directory
+    Subtree(NodeId),
+    /// The staging queue, in queue order and all-or-nothing
+    Staged,
+    /// Every file with a label
+    Label(String),
+    /// Every file in a changeset, all-or-nothing
+    Changeset(String),
+    /// The changesets not on hold, in queue order, each all-or-nothing
+    Queue,
+    /// Entries and everything under them, as a control socket client asked
+    Paths(Vec<NodeId>),
+    /// Exactly these entries, nothing under them, as a decisions file lists
+    /// them
+    Entries(Vec<NodeId>),
+}
+
+/// What the content pane shows for a modified file
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum ContentView {
+    /// Changes from the base to the overlay
+    #[default]
+    Diff,
+    /// The file as the sandbox left it
+    Overlay,
+    /// The file as it is in the base
+    Base,
+}
+
+impl ContentView {
+    pub fn next(self) -> Self {
+        match self {
+            ContentView::Diff => ContentView::Overlay,
+            ContentView::Overlay => ContentView::Base,
+            ContentView::Base => ContentView::Diff,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ContentView::Diff => "diff",
+            ContentView::Overlay => "overlay",
+            ContentView::Base => "base",
+        }
+    }
+}
+
+/// How dotfiles and hidden directories in the overlay are listed
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum HiddenFiles {
+    /// In their usual place
+    #[default]
+    Show,
+    /// Not at all
+    Hide,
+    /// After the other entries of their directory
+    Last,
+}
+
+impl HiddenFiles {
+    /// The policy `.` switches to next
+    pub fn next(self) -> Self {
+        match self {
+            HiddenFiles::Show => HiddenFiles::Hide,
+            HiddenFiles::Hide => HiddenFiles::Last,
+            HiddenFiles::Last => HiddenFiles::Show,
+        }
+    }
+
+    pub fn describe(self) -> &'static str {
+        match self {
+            HiddenFiles::Show => "shown",
+            HiddenFiles::Hide => "hidden",
+            HiddenFiles::Last => "grouped last",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SelectionState {
+    Unselected,
+    Partial,
+    Selected,
+}
+
 #[derive(Debug, Clone)]
 pub struct FileEntry {
     pub path: PathBuf,
+    /// The path relative to the overlay root, by which the entry is known
+    /// everywhere but on disk: in the list, the diff, apply and saved state
+    pub rel_path: PathBuf,
     pub name: String,
     pub is_dir: bool,
     pub depth: usize,
@@ -28,3 +170,459 @@ pub struct FileEntry {
     pub selected: bool,
     pub collapsed: bool,
 }
+
+impl FileEntry {
+    /// Dotfiles and hidden directories
+    pub fn is_hidden(&self) -> bool {
+        self.name.starts_with('.')
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct DiffOptions {
+    /// Treat lines that differ only in whitespace as unchanged
+    pub ignore_whitespace: bool,
+    /// Treat lines that differ only in line endings as unchanged
+    pub ignore_eol: bool,
+    /// Files larger than this many KiB are diffed a line at a time from disk,
+    /// showing only the changes; 0 always diffs in full
+    pub large_file_kb: u64,
+    /// Lay out JSON and JavaScript a statement or member to a line before
+    /// showing or diffing them, e.g. minified files on a single line
+    pub pretty_print: bool,
+    /// Run both sides of a diff through the configured normalizer for their
+    /// file type first, if there is one
+    pub normalize: bool,
+}
+
+impl Default for DiffOptions {
+    fn default() -> Self {
+        DiffOptions {
+            ignore_whitespace: false,
+            ignore_eol: false,
+            large_file_kb: 1024,
+            pretty_print: false,
+            normalize: true,
+        }
+    }
+}
+
+/// Short-lived message shown in the status bar
+#[derive(Debug, Clone)]
+pub struct Toast {
+    pub message: String,
+    pub expires: Instant,
+}
+
+/// The read-only view under the overlay: the base directory, plus any further
+/// lower layers stacked on top of it, listed bottom to top
+#[derive(Debug, Clone)]
+pub struct LowerView {
+    pub base: PathBuf,
+    pub layers: Vec<PathBuf>,
+    /// Bases of a session with more than one, by the name of the top-level
+    /// overlay directory each is overlaid by. `base` is the first of them.
+    pub roots: BTreeMap<String, PathBuf>,
+}
+
+impl LowerView {
+    pub fn new(base: PathBuf, layers: Vec<PathBuf>) -> Self {
+        LowerView {
+            base,
+            layers,
+            roots: BTreeMap::new(),
+        }
+    }
+
+    /// The lower view of a session with several bases
+    pub fn with_roots(roots: BTreeMap<String, PathBuf>) -> Self {
+        LowerView {
+            base: roots.values().next().cloned().unwrap_or_default(),
+            layers: Vec::new(),
+            roots,
+        }
+    }
+
+    /// The same view with every directory resolved to its canonical path, so
+    /// a base given through a symlink or as a relative path lines up with
+    /// the paths the filesystem reports back
+    pub fn canonicalize(self) -> io::Result<Self> {
+        let roots = self
+            .roots
+            .into_iter()
+            .map(|(name, root)| Ok((name, fs::canonicalize(root)?)))
+            .collect::<io::Result<_>>()?;
+        Ok(LowerView {
+            base: fs::canonicalize(self.base)?,
+            layers: self.layers.into_iter().map(fs::canonicalize).collect::<io::Result<_>>()?,
+            roots,
+        })
+    }
+
+    /// How many leading components of an overlay path name its base: one
+    /// with several bases, none otherwise
+    pub fn root_depth(&self) -> usize {
+        if self.roots.is_empty() { 0 } else { 1 }
+    }
+
+    /// Where `rel_path` lands in the base, e.g. when it is applied. With
+    /// several bases its first component picks the base.
+    pub fn base_path(&self, rel_path: &Path) -> PathBuf {
+        let mut components = rel_path.components();
+        if let Some(Component::Normal(name)) = components.next()
+            && let Some(root) = self.roots.get(&*name.to_string_lossy())
+        {
+            return root.join(components.as_path());
+        }
+        self.base.join(rel_path)
+    }
+
+    /// The base directory `rel_path` lands under
+    pub fn root_of(&self, rel_path: &Path) -> &Path {
+        if let Some(Component::Normal(name)) = rel_path.components().next()
+            && let Some(root) = self.roots.get(&*name.to_string_lossy())
+        {
+            return root;
+        }
+        &self.base
+    }
+
+    /// Where `rel_path` comes from in the merged lower view. The topmost layer
+    /// that has the path wins, and an overlayfs whiteout, or an opaque
+    /// directory above it, hides it from the layers below.
+    pub fn resolve(&self, rel_path: &Path) -> Option<PathBuf> {
+        if !self.roots.is_empty() {
+            let path = self.base_path(rel_path);
+            let metadata = fs::symlink_metadata(&path).ok()?;
+            return (!is_whiteout(&metadata)).then_some(path);
+        }
+        for root in self.layers.iter().rev() {
+            let path = root.join(rel_path);
+            let Ok(metadata) = fs::symlink_metadata(&path) else {
+                if hides_below(root, rel_path) {
+                    return None;
+                }
+                continue;
+            };
+            if is_whiteout(&metadata) {
+                return None;
+            }
+            return Some(path);
+        }
+        let path = self.base.join(rel_path);
+        let metadata = fs::symlink_metadata(&path).ok()?;
+        (!is_whiteout(&metadata)).then_some(path)
+    }
+}
+
+/// Whether a directory above `rel_path` in an upper layer keeps the layers
+/// below from showing it: an opaque directory, or a whiteout in its place
+fn hides_below(root: &Path, rel_path: &Path) -> bool {
+    rel_path.ancestors().skip(1).filter(|dir| !dir.as_os_str().is_empty()).any(|dir| {
+        let path = root.join(dir);
+        fs::symlink_metadata(&path).is_ok_and(|metadata| {
+            is_whiteout(&metadata) || (metadata.is_dir() && is_opaque(&path))
+        })
+    })
+}
+
+/// overlayfs marks deletions in an upper layer with a 0/0 character device
+pub fn is_whiteout(metadata: &fs::Metadata) -> bool {
+    metadata.file_type().is_char_device() && metadata.rdev() == 0
+}
+
+/// overlayfs marks a directory that hides the lower layers' contents with an
+/// opaque xattr, so removing it, even when empty, would bring those back
+#[cfg(target_os = "linux")]
+pub fn is_opaque(dir: &Path) -> bool {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let Ok(path) = CString::new(dir.as_os_str().as_bytes()) else {
+        return false;
+    };
+    ["trusted.overlay.opaque", "user.overlay.opaque"].iter().any(|name| {
+        let name = CString::new(*name).unwrap();
+        let mut value = [0u8; 1];
+        // SAFETY: both strings are NUL-terminated and value is valid for writes of value.len() bytes
+        let len = unsafe {
+            libc::lgetxattr(path.as_ptr(), name.as_ptr(), value.as_mut_ptr().cast(), value.len())
+        };
+        len == 1 && value[0] == b'y'
+    })
+}
+
+/// Without overlayfs, a directory is just a directory
+#[cfg(not(target_os = "linux"))]
+pub fn is_opaque(_dir: &Path) -> bool {
+    false
+}
+
+pub type NodeId = usize;
+
+#[derive(Debug, Clone)]
+struct FileNode {
+    entry: FileEntry,
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+    /// Files below a directory, and how many of them are selected, kept up
+    /// to date so a directory's checkbox does not walk its subtree
+    files_below: usize,
+    selected_below: usize,
+}
+
+/// Overlay entries arranged as a tree. Nodes live in an arena and are addressed
+/// by `NodeId`; ids are never reused, so a stale id simply resolves to nothing.
+#[derive(Debug, Default)]
+pub struct FileTree {
+    nodes: Vec<Option<FileNode>>,
+    roots: Vec<NodeId>,
+    index: HashMap<PathBuf, NodeId>,
+    /// Sort hidden entries after their siblings
+    hidden_last: bool,
+}
+
+impl FileTree {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn len(&self) -> usize {
+        self.index.len()
+    }
+
+    fn node(&self, id: NodeId) -> Option<&FileNode> {
+        self.nodes.get(id).and_then(Option::as_ref)
+    }
+
+    pub fn get(&self, id: NodeId) -> Option<&FileEntry> {
+        self.node(id).map(|n| &n.entry)
+    }
+
+    /// The entry to change. Whether it is selected is changed with
+    /// `set_selected` instead, which keeps its directories' counts.
+    pub fn get_mut(&mut self, id: NodeId) -> Option<&mut FileEntry> {
+        self.nodes.get_mut(id).and_then(Option::as_mut).map(|n| &mut n.entry)
+    }
+
+    /// Look up an entry by its absolute overlay path
+    pub fn find(&self, path: &Path) -> Option<NodeId> {
+        self.index.get(path).copied()
+    }
+
+    pub fn parent(&self, id: NodeId) -> Option<NodeId> {
+        self.node(id).and_then(|n| n.parent)
+    }
+
+    pub fn children(&self, id: NodeId) -> &[NodeId] {
+        self.node(id).map(|n| n.children.as_slice()).unwrap_or(&[])
+    }
+
+    pub fn roots(&self) -> &[NodeId] {
+        &self.roots
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.roots.is_empty()
+    }
+
+    /// Ids of all ancestors of a node, nearest first
+    pub fn ancestors(&self, id: NodeId) -> impl Iterator<Item = NodeId> + '_ {
+        std::iter::successors(self.parent(id), move |&p| self.parent(p))
+    }
+
+    /// Insert an entry below `parent` (or at the top level), keeping siblings
+    /// sorted by path, with hidden entries last if `set_hidden_last` asked so.
+    pub fn insert(&mut self, parent: Option<NodeId>, entry: FileEntry) -> NodeId {
+        let id = self.nodes.len();
+        let siblings = match parent {
+            Some(p) => self.children(p),
+            None => &self.roots,
+        };
+        let key = self.sort_key(&entry);
+        let pos = siblings.partition_point(|&s| {
+            self.get(s).is_some_and(|e| self.sort_key(e) < key)
+        });
+
+        let counts = if entry.is_dir { (0, 0) } else { (1, usize::from(entry.selected)) };
+        self.index.insert(entry.path.clone(), id);
+        self.nodes.push(Some(FileNode {
+            entry,
+            parent,
+            children: Vec::new(),
+            files_below: 0,
+            selected_below: 0,
+        }));
+        self.add_below(id, counts, true);
+
+        match parent.and_then(|p| self.nodes.get_mut(p)).and_then(Option::as_mut) {
+            Some(parent_node) => parent_node.children.insert(pos, id),
+            None => self.roots.insert(pos, id),
+        }
+        id
+    }
+
+    /// Add files and selected files to, or take them from, the counts of
+    /// the directories above a node
+    fn add_below(&mut self, id: NodeId, (files, selected): (usize, usize), add: bool) {
+        let ancestors: Vec<NodeId> = self.ancestors(id).collect();
+        for ancestor in ancestors {
+            if let Some(node) = self.nodes.get_mut(ancestor).and_then(Option::as_mut) {
+                if add {
+                    node.files_below += files;
+                    node.selected_below += selected;
+                } else {
+                    node.files_below -= files;
+                    node.selected_below -= selected;
+                }
+            }
+        }
+    }
+
+    /// Select or deselect an entry on its own, i.e. not what is below it
+    pub fn set_selected(&mut self, id: NodeId, selected: bool) {
+        let Some(entry) = self.get_mut(id) else {
+            return;
+        };
+        if entry.selected == selected {
+            return;
+        }
+        entry.selected = selected;
+        if !entry.is_dir {
+            self.add_below(id, (0, 1), selected);
+        }
+    }
+
+    fn sort_key<'a>(&self, entry: &'a FileEntry) -> (bool, &'a Path) {
+        (self.hidden_last && entry.is_hidden(), &entry.path)
+    }
+
+    /// Sort hidden entries after the other entries of their directory, or
+    /// back in among them
+    pub fn set_hidden_last(&mut self, hidden_last: bool) {
+        if self.hidden_last == hidden_last {
+            return;
+        }
+        self.hidden_last = hidden_last;
+
+        let mut roots = std::mem::take(&mut self.roots);
+        self.sort_siblings(&mut roots);
+        self.roots = roots;
+        for id in 0..self.nodes.len() {
+            let Some(mut children) = self.nodes[id].as_mut().map(|n| std::mem::take(&mut n.children)) else {
+                continue;
+            };
+            self.sort_siblings(&mut children);
+            if let Some(node) = self.nodes[id].as_mut() {
+                node.children = children;
+            }
+        }
+    }
+
+    fn sort_siblings(&self, ids: &mut [NodeId]) {
+        ids.sort_by(|&a, &b| match (self.get(a), self.get(b)) {
+            (Some(a), Some(b)) => self.sort_key(a).cmp(&self.sort_key(b)),
+            _ => std::cmp::Ordering::Equal,
+        });
+    }
+
+    /// Whether an entry or any directory above it is hidden
+    pub fn is_hidden(&self, id: NodeId) -> bool {
+        std::iter::once(id)
+            .chain(self.ancestors(id))
+            .any(|id| self.get(id).is_some_and(FileEntry::is_hidden))
+    }
+
+    /// Remove a node together with everything below it
+    pub fn remove(&mut self, id: NodeId) {
+        if let Some(node) = self.node(id) {
+            let counts = if node.entry.is_dir {
+                (node.files_below, node.selected_below)
+            } else {
+                (1, usize::from(node.entry.selected))
+            };
+            self.add_below(id, counts, false);
+        }
+        match self.parent(id).and_then(|p| self.nodes.get_mut(p)).and_then(Option::as_mut) {
+            Some(parent_node) => parent_node.children.retain(|&c| c != id),
+            None => self.roots.retain(|&c| c != id),
+        }
+        self.drop_subtree(id);
+    }
+
+    fn drop_subtree(&mut self, id: NodeId) {
+        if let Some(node) = self.nodes.get_mut(id).and_then(Option::take) {
+            self.index.remove(&node.entry.path);
+            for child in node.children {
+                self.drop_subtree(child);
+            }
+        }
+    }
+
+    fn walk(&self, ids: &[NodeId], skip_collapsed: bool, out: &mut Vec<NodeId>) {
+        for &id in ids {
+            if let Some(node) = self.node(id) {
+                out.push(id);
+                if !(skip_collapsed && node.entry.collapsed) {
+                    self.walk(&node.children, skip_collapsed, out);
+                }
+            }
+        }
+    }
+
+    /// All node ids in display (pre-order) order
+    pub fn ids(&self) -> Vec<NodeId> {
+        let mut out = Vec::with_capacity(self.len());
+        self.walk(&self.roots, false, &mut out);
+        out
+    }
+
+    /// Node ids in display order, skipping the contents of collapsed directories
+    pub fn visible(&self) -> Vec<NodeId> {
+        let mut out = Vec::new();
+        self.walk(&self.roots, true, &mut out);
+        out
+    }
+
+    /// Ids of every node below `id`, in display order
+    pub fn descendants(&self, id: NodeId) -> Vec<NodeId> {
+        let mut out = Vec::new();
+        self.walk(self.children(id), false, &mut out);
+        out
+    }
+
+    /// Checkbox state of a node. A directory reflects the files below it, so it
+    /// is only fully selected when every file in it is.
+    pub fn selection_state(&self, id: NodeId) -> SelectionState {
+        let Some(entry) = self.get(id) else {
+            return SelectionState::Unselected;
+        };
+
+        let own_state = if entry.selected {
+            SelectionState::Selected
+        } else {
+            SelectionState::Unselected
+        };
+        if !entry.is_dir {
+            return own_state;
+        }
+
+        let (selected, total) = self.node(id).map_or((0, 0), |n| (n.selected_below, n.files_below));
+
+        match (selected, total) {
+            // Empty directories keep their own checkbox
+            (_, 0) => own_state,
+            (0, _) => SelectionState::Unselected,
+            (s, t) if s == t => SelectionState::Selected,
+            _ => SelectionState::Partial,
+        }
+    }
+
+    /// Iterate over all entries in display order
+    pub fn iter(&self) -> impl Iterator<Item = (NodeId, &FileEntry)> + '_ {
+        self.ids()
+            .into_iter()
+            .filter_map(move |id| self.get(id).map(|e| (id, e)))
+    }
+}
@@ -0,0 +1,50 @@
+use crate::app::App;
+use ratatui::{
+    layout::{Position, Rect},
+    style::{Color, Modifier, Style},
+    text::Span,
+    Frame,
+};
+
+/// Style of the highlighted row of a list: the theme's background, or
+/// reverse video in accessible mode, which stays readable in any colours
+pub fn highlight(app: &App) -> Style {
+    if app.accessible {
+        Style::default().add_modifier(Modifier::REVERSED | Modifier::BOLD)
+    } else {
+        Style::default().bg(app.theme.highlight_bg()).add_modifier(Modifier::BOLD)
+    }
+}
+
+/// A pane's title. In accessible mode the focused pane says so, rather than
+/// only having its border coloured.
+pub fn pane_title(app: &App, title: String, focused: bool) -> String {
+    if app.accessible && focused {
+        format!("[focus] {}", title)
+    } else {
+        title
+    }
+}
+
+/// A dialog button, filled with `colour` when highlighted. In accessible
+/// mode the highlighted one is bracketed and in reverse video instead.
+pub fn button(app: &App, label: &str, highlighted: bool, colour: Color) -> Span<'static> {
+    match (app.accessible, highlighted) {
+        (true, true) => Span::styled(
+            format!("[{}]", label),
+            Style::default().add_modifier(Modifier::REVERSED | Modifier::BOLD),
+        ),
+        (false, true) => Span::styled(format!(" {} ", label), Style::default().bg(colour).fg(Color::Black)),
+        (_, false) => Span::raw(format!(" {} ", label)),
+    }
+}
+
+/// In accessible mode, put the terminal cursor at the start of `row` of a
+/// bordered pane, so screen readers that follow the cursor read the
+/// highlighted entry. Outside it the cursor stays hidden.
+pub fn place_cursor(f: &mut Frame, app: &App, area: Rect, row: usize) {
+    let inner_height = usize::from(area.height.saturating_sub(2));
+    if app.accessible && row < inner_height {
+        f.set_cursor_position(Position::new(area.x + 1, area.y + 1 + row as u16));
+    }
+}
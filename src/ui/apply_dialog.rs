@@ -1,24 +1,40 @@
-use crate::app::App;
-use crate::types::DialogButton;
+use crate::app::{describe_count, App, Modal};
+use crate::disk_usage::format_size;
+use crate::hash_lists::HashVerdict;
+use crate::types::{ApplyScope, DialogButton, FileStatus};
+use crate::ui::accessibility;
+use crate::ui::apply_progress::describe_duration;
 use ratatui::{
     layout::{Alignment, Constraint, Direction, Layout, Rect},
-    style::{Color, Style},
+    style::{Color, Modifier, Style},
     text::{Line, Span},
     widgets::{Block, Borders, Clear, Paragraph, Wrap},
     Frame,
 };
 
 pub fn render(f: &mut Frame, app: &App) {
-    if !app.show_confirm_dialog {
+    let Modal::Apply(state) = &app.modal else {
         return;
-    }
+    };
 
-    let selected_files = app.get_selected_files();
+    let selected_files = app.apply_targets();
 
     // Create centered dialog area
     let area = f.area();
     let dialog_width = area.width.min(60);
-    let dialog_height = (selected_files.len() as u16 + 8).min(area.height - 4);
+    let privileged = app.privileged_targets();
+    let denied = selected_files.iter().filter(|e| app.hash_verdict(e) == Some(HashVerdict::Denied)).count();
+    let outside = selected_files.iter().filter(|e| app.is_outside_allowed_roots(e)).count();
+    let warning_height = [state.sandbox_warning.is_some(), !privileged.is_empty(), denied > 0, outside > 0]
+        .iter()
+        .filter(|shown| **shown)
+        .count() as u16
+        * 3;
+    let size = state.stats.values().map(|s| s.size).sum();
+    let estimate = app.apply_estimate(size);
+    let estimate_height = u16::from(estimate.is_some());
+    let dialog_height =
+        (selected_files.len() as u16 + 11 + warning_height + estimate_height).min(area.height.saturating_sub(4));
     let dialog_x = (area.width.saturating_sub(dialog_width)) / 2;
     let dialog_y = (area.height.saturating_sub(dialog_height)) / 2;
 
@@ -50,11 +66,141 @@ pub fn render(f: &mut Frame, app: &App) {
     let file_list: Vec<Line> = if selected_files.is_empty() {
         vec![Line::from("No files selected")]
     } else {
-        let mut lines = vec![Line::from("The following files will be applied:")];
+        let subtree = match state.scope {
+            ApplyScope::Subtree(id) => app.files.get(id),
+            _ => None,
+        };
+        let mut lines = match subtree {
+            Some(root) => {
+                let new = selected_files.iter().filter(|e| e.status == FileStatus::New).count();
+                vec![
+                    Line::from(format!("Apply everything under {}:", app.display_path(&root.rel_path))),
+                    Line::from(format!(
+                        "{} file{} ({} new, {} modified)",
+                        selected_files.len(),
+                        if selected_files.len() == 1 { "" } else { "s" },
+                        new,
+                        selected_files.len() - new
+                    )),
+                ]
+            }
+            None if let ApplyScope::Label(label) = &state.scope => {
+                vec![Line::from(format!("Apply every file labelled #{}:", label))]
+            }
+            None if let ApplyScope::Changeset(name) = &state.scope => {
+                let mut lines = vec![Line::from(format!("Apply changeset {} as a unit:", name))];
+                if let Some(changeset) = app.changesets.get(name).filter(|c| !c.description.is_empty()) {
+                    lines.push(Line::from(Span::styled(
+                        changeset.description.clone(),
+                        Style::default().add_modifier(Modifier::ITALIC),
+                    )));
+                }
+                lines.push(Line::from("If any file fails, none are applied:"));
+                lines
+            }
+            None if state.scope == ApplyScope::Queue => vec![
+                Line::from(format!("Apply the changesets in order: {}.", app.queued().join(", "))),
+                Line::from("Each is applied whole or not at all, stopping at the first to fail:"),
+            ],
+            None if state.scope == ApplyScope::Staged => vec![
+                Line::from("Commit the staging queue, in this order."),
+                Line::from("If any file fails, none are applied:"),
+            ],
+            None => vec![Line::from("The following files will be applied:")],
+        };
         lines.push(Line::from(""));
+        if let Some(activity) = &state.sandbox_warning {
+            lines.push(Line::from(Span::styled(
+                format!("Warning: {}; files may be half-written.", activity),
+                Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+            )));
+            lines.push(Line::from(""));
+        }
+        if denied > 0 {
+            lines.push(Line::from(Span::styled(
+                format!(
+                    "{} denylisted content; nothing can be applied until {} deselected or discarded.",
+                    describe_files(denied),
+                    if denied == 1 { "it is" } else { "they are" }
+                ),
+                Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+            )));
+            lines.push(Line::from(""));
+        }
+        if outside > 0 {
+            let text = if app.blocks_outside_roots() {
+                format!(
+                    "{} outside apply.allowed_roots; nothing can be applied until {} deselected.",
+                    describe_files_land(outside),
+                    if outside == 1 { "it is" } else { "they are" }
+                )
+            } else {
+                format!("{} outside apply.allowed_roots, allowed by --allow-outside-roots.", describe_files_land(outside))
+            };
+            lines.push(Line::from(Span::styled(text, Style::default().fg(Color::Red).add_modifier(Modifier::BOLD))));
+            lines.push(Line::from(""));
+        }
+        if !privileged.is_empty() {
+            let (text, style) = if state.privileged_confirmed {
+                (
+                    "They keep these privileges in the base. Enter or y to apply them.".to_string(),
+                    Style::default().fg(Color::Red),
+                )
+            } else {
+                (
+                    format!(
+                        "{} setuid, setgid or capabilities. Enter or y once more to confirm.",
+                        describe_files(privileged.len())
+                    ),
+                    Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+                )
+            };
+            lines.push(Line::from(Span::styled(text, style)));
+            lines.push(Line::from(""));
+        }
         for file in selected_files.iter() {
-            let rel_path = file.path.strip_prefix(&app.overlay_path).unwrap();
-            lines.push(Line::from(format!("  • {}", rel_path.display())));
+            // Anything the dialog warns about stands out in the list too
+            let path_style = if app.is_flagged(file) { Style::default().fg(Color::Red) } else { Style::default() };
+            let mut line = vec![Span::styled(format!("  • {}", app.display_path(&file.rel_path)), path_style)];
+            if let Some(stats) = state.stats.get(&file.rel_path) {
+                line.extend(stats_spans(stats.added, stats.removed));
+                line.push(Span::styled(format!(", {}", format_size(stats.size)), Style::default().fg(Color::DarkGray)));
+            }
+            if app.hash_verdict(file) == Some(HashVerdict::Denied) {
+                line.push(Span::styled(" [DENYLISTED]", Style::default().fg(Color::Red).add_modifier(Modifier::BOLD)));
+            }
+            if app.is_outside_allowed_roots(file) {
+                line.push(Span::styled(" [outside allowed roots]", Style::default().fg(Color::Red).add_modifier(Modifier::BOLD)));
+            }
+            if let Some(privileges) = app.privileges(file) {
+                line.push(Span::styled(
+                    format!(" [{}]", privileges.describe()),
+                    Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+                ));
+            }
+            if state.conflicts.contains(&file.rel_path) {
+                line.push(Span::styled(" [base changed since apply]", Style::default().fg(Color::Red).add_modifier(Modifier::BOLD)));
+            }
+            lines.push(Line::from(line));
+        }
+
+        let (added, removed, size) = state
+            .stats
+            .values()
+            .fold((0, 0, 0), |(added, removed, size), s| (added + s.added, removed + s.removed, size + s.size));
+        lines.push(Line::from(""));
+        let mut total = vec![Span::styled(
+            format!("Total: {}", describe_count(state.stats.len())),
+            Style::default().add_modifier(Modifier::BOLD),
+        )];
+        total.extend(stats_spans(added, removed));
+        total.push(Span::styled(format!(", {}", format_size(size)), Style::default().add_modifier(Modifier::BOLD)));
+        lines.push(Line::from(total));
+        if let Some(estimate) = estimate {
+            lines.push(Line::from(Span::styled(
+                format!("About {} to copy, going by the last apply", describe_duration(estimate)),
+                Style::default().fg(Color::DarkGray),
+            )));
         }
         lines
     };
@@ -63,24 +209,37 @@ pub fn render(f: &mut Frame, app: &App) {
     f.render_widget(file_paragraph, dialog_chunks[0]);
 
     // Render buttons
-    let ok_style = if app.dialog_button == DialogButton::Ok {
-        Style::default().bg(Color::Green).fg(Color::Black)
-    } else {
-        Style::default()
-    };
-    let cancel_style = if app.dialog_button == DialogButton::Cancel {
-        Style::default().bg(Color::Red).fg(Color::Black)
-    } else {
-        Style::default()
-    };
-
     let buttons = Paragraph::new(Line::from(vec![
         Span::raw("   "),
-        Span::styled(" OK ", ok_style),
+        accessibility::button(app, "OK (y)", state.button == DialogButton::Ok, Color::Green),
         Span::raw("   "),
-        Span::styled(" Cancel ", cancel_style),
+        accessibility::button(app, "Cancel (n)", state.button == DialogButton::Cancel, Color::Red),
     ]))
     .alignment(Alignment::Center);
 
     f.render_widget(buttons, dialog_chunks[1]);
 }
+
+/// "  +A −R", coloured like a diff
+fn stats_spans(added: usize, removed: usize) -> [Span<'static>; 2] {
+    [
+        Span::styled(format!("  +{}", added), Style::default().fg(Color::Green)),
+        Span::styled(format!(" −{}", removed), Style::default().fg(Color::Red)),
+    ]
+}
+
+
+/// "1 file has" or "N files have"
+fn describe_files(count: usize) -> String {
+    match count {
+        1 => "1 file has".to_string(),
+        n => format!("{} files have", n),
+    }
+}
+
+fn describe_files_land(count: usize) -> String {
+    match count {
+        1 => "1 file lands".to_string(),
+        n => format!("{} files land", n),
+    }
+}
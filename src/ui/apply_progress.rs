@@ -0,0 +1,71 @@
+use crate::disk_usage::format_size;
+use crate::file_operations::ApplyProgress;
+use ratatui::{
+    buffer::Buffer,
+    layout::Rect,
+    style::{Color, Style},
+    text::Line,
+    widgets::{Block, Borders, Clear, Paragraph, Widget},
+};
+use std::time::Duration;
+
+/// How long an apply runs before its progress is shown, so a quick one does
+/// not flash a dialog
+pub const SHOW_AFTER: Duration = Duration::from_millis(300);
+
+/// Where the progress dialog goes on a screen of `screen`
+pub fn area(screen: Rect) -> Rect {
+    let width = screen.width.min(50);
+    let height = screen.height.min(5);
+    Rect {
+        x: (screen.width - width) / 2,
+        y: (screen.height - height) / 2,
+        width,
+        height,
+    }
+}
+
+/// Draw how far an apply has got into `buf`. The app cannot draw itself
+/// while it is busy applying, so this stands alone.
+pub fn render(buf: &mut Buffer, area: Rect, progress: &ApplyProgress) {
+    let percent = match progress.total_bytes {
+        0 => 100,
+        total => progress.bytes.min(total) * 100 / total,
+    };
+    let speed = match (progress.throughput(), progress.remaining()) {
+        (Some(throughput), Some(remaining)) => {
+            format!("{}/s, about {} left", format_size(throughput as u64), describe_duration(remaining))
+        }
+        _ => "Starting…".to_string(),
+    };
+    let lines = vec![
+        Line::from(format!("{} of {} files", progress.files, progress.total_files)),
+        Line::from(format!(
+            "{} of {} ({}%)",
+            format_size(progress.bytes),
+            format_size(progress.total_bytes),
+            percent
+        )),
+        Line::from(speed),
+    ];
+
+    Clear.render(area, buf);
+    Paragraph::new(lines)
+        .block(
+            Block::default()
+                .title("Applying")
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(Color::Yellow)),
+        )
+        .render(area, buf);
+}
+
+/// A duration to the second, e.g. "45s", "3m 20s" or "1h 5m"
+pub fn describe_duration(duration: Duration) -> String {
+    let secs = duration.as_secs().max(1);
+    match secs {
+        0..60 => format!("{}s", secs),
+        60..3600 => format!("{}m {}s", secs / 60, secs % 60),
+        _ => format!("{}h {}m", secs / 3600, secs % 3600 / 60),
+    }
+}
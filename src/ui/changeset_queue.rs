@@ -0,0 +1,60 @@
+use crate::app::App;
+use crate::changeset;
+use ratatui::{
+    layout::Rect,
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, List, ListItem, ListState},
+    Frame,
+};
+
+pub fn render(f: &mut Frame, app: &App, area: Rect) {
+    let queue = changeset::queue(&app.changesets);
+
+    let mut items = vec![ListItem::new(Line::from(Span::styled(
+        format!("{:>3}  {:<5}  {:>6}  {}", "#", "", "Files", "Changeset"),
+        Style::default().add_modifier(Modifier::BOLD).fg(Color::Yellow),
+    )))];
+    let mut position = 0;
+    items.extend(queue.iter().map(|name| {
+        let Some(changeset) = app.changesets.get(*name) else {
+            return ListItem::new(Line::from(name.to_string()));
+        };
+        // Held changesets have no place in the order they are applied in
+        let (number, action) = if changeset.hold {
+            (String::new(), "hold")
+        } else {
+            position += 1;
+            (position.to_string(), "apply")
+        };
+        let row = format!("{:>3}  {:<5}  {:>6}  {}", number, action, app.changeset_entries(name).len(), name);
+        let style = if changeset.hold { Style::default().fg(Color::DarkGray) } else { Style::default() };
+        let mut spans = vec![Span::styled(row, style)];
+        if !changeset.description.is_empty() {
+            spans.push(Span::styled(format!("  {}", changeset.description), Style::default().fg(Color::DarkGray)));
+        }
+        ListItem::new(Line::from(spans))
+    }));
+
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(app.theme.accent()))
+                .title("Changesets, applied top to bottom [K/J: move, s: squash, e: describe, Space: hold, C: apply, Enter: files, Esc: close]"),
+        )
+        .highlight_style(
+            Style::default()
+                .bg(app.theme.highlight_bg())
+                .add_modifier(Modifier::BOLD),
+        )
+        .highlight_symbol(">> ");
+
+    // Row 0 is the column header
+    let mut state = ListState::default();
+    if !queue.is_empty() {
+        state.select(Some(app.queue_cursor + 1));
+    }
+
+    f.render_stateful_widget(list, area, &mut state);
+}
@@ -1,50 +1,198 @@
-use crate::app::App;
-use crate::types::ActivePane;
+use crate::app::{App, Modal};
+use crate::blame;
+use crate::classify::RiskLevel;
+use crate::minified;
+use crate::types::{ActivePane, FileStatus};
+use crate::ui::{accessibility, text};
 use ratatui::{
     layout::Rect,
-    style::{Color, Style},
+    style::{Color, Modifier, Style},
     text::{Line, Span},
     widgets::{Block, Borders, Paragraph, Wrap},
     Frame,
 };
 
+/// Columns a path in the title keeps, however much else the title says
+const MIN_PATH_WIDTH: usize = 20;
+
 pub fn render(f: &mut Frame, app: &App, area: Rect) {
-    let content_border_style = if app.active_pane == ActivePane::FileContent {
-        Style::default().fg(Color::Cyan)
+    let focused = app.active_pane == ActivePane::FileContent;
+    let content_border_style = if focused {
+        Style::default().fg(app.theme.accent())
     } else {
         Style::default()
     };
 
-    let content_text: Vec<Line> = app
-        .file_content
-        .iter()
-        .skip(app.content_scroll)
-        .map(|line| {
-            // Colorize diff lines only when viewing a diff
-            if app.is_diff_view {
-                if line.starts_with('+') && !line.starts_with("+++") {
-                    Line::from(Span::styled(line.as_str(), Style::default().fg(Color::Green)))
-                } else if line.starts_with('-') && !line.starts_with("---") {
-                    Line::from(Span::styled(line.as_str(), Style::default().fg(Color::Red)))
-                } else if line.starts_with("---") || line.starts_with("+++") {
-                    Line::from(Span::styled(line.as_str(), Style::default().fg(Color::Cyan)))
-                } else {
-                    Line::from(line.as_str())
-                }
-            } else {
-                Line::from(line.as_str())
+    let mut content_text: Vec<Line> = Vec::new();
+    if let Some(risk) = &app.cursor_risk {
+        let badge_colour = match risk.level {
+            RiskLevel::High => Color::Red,
+            RiskLevel::Medium => Color::Yellow,
+        };
+        content_text.push(Line::from(vec![
+            Span::styled(
+                format!(" {} ", risk.level.badge()),
+                Style::default().fg(Color::Black).bg(badge_colour).add_modifier(Modifier::BOLD),
+            ),
+            Span::styled(format!(" {}: {}", risk.kind, risk.explanation), Style::default().fg(badge_colour)),
+        ]));
+    }
+
+    // A deletion shows what it takes away, which is easy to mistake for a new file
+    if app.cursor_entry().is_some_and(|e| e.status == FileStatus::Deleted) {
+        content_text.push(Line::from(Span::styled(
+            "Deleted in the overlay; the base's copy below would be lost",
+            Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+        )));
+        content_text.push(Line::from(""));
+    }
+
+    // Nor is there a diff between a file and the symlink it replaces
+    if app.cursor_entry().is_some_and(|e| e.status == FileStatus::TypeChanged && !e.is_dir) {
+        content_text.push(Line::from(Span::styled(
+            "Type changed in the overlay; there is no diff against the base",
+            Style::default().fg(Color::Magenta).add_modifier(Modifier::BOLD),
+        )));
+        content_text.push(Line::from(""));
+    }
+
+    // What the configured external checks made of the file
+    if let Some(entry) = app.cursor_entry() {
+        if let Some(results) = app.check_results(entry) {
+            content_text.push(Line::from(Span::styled("Checks:", Style::default().add_modifier(Modifier::BOLD))));
+            for result in results {
+                let (mark, colour) = if result.passed { ("✓", Color::Green) } else { ("✗", Color::Red) };
+                content_text.push(Line::from(Span::styled(
+                    format!("  {} {}: {}", mark, result.name, result.outcome),
+                    Style::default().fg(colour),
+                )));
+                content_text.extend(result.output.iter().map(|line| {
+                    Line::from(Span::styled(format!("      {}", line), Style::default().fg(Color::DarkGray)))
+                }));
             }
-        })
-        .collect();
+            content_text.push(Line::from(""));
+        } else if app.is_being_checked(entry) {
+            content_text.push(Line::from(Span::styled("Checks: running…", Style::default().fg(Color::DarkGray))));
+        }
+    }
+
+    // Earlier decisions on this path, so a file that is back after being
+    // applied or discarded is not mistaken for one never seen before
+    let history = app.cursor_history();
+    if !history.is_empty() {
+        content_text.push(Line::from(Span::styled(
+            format!("History: {} → in the overlay again", history.join(" → ")),
+            Style::default().fg(Color::Magenta),
+        )));
+    }
+
+    // Who last changed each removed line, in a gutter before the diff lines
+    let gutter = app.show_blame && app.blame.iter().any(Option::is_some);
+    content_text.extend(
+        app.file_content
+            .iter()
+            .enumerate()
+            .skip(app.content_scroll)
+            .map(|(i, line)| {
+                let mut diff_line = diff_line(app, shown(app, line));
+                if gutter {
+                    let annotation = app.blame.get(i).cloned().flatten().unwrap_or_default();
+                    diff_line.spans.insert(
+                        0,
+                        Span::styled(
+                            format!("{:<width$} │", annotation, width = blame::WIDTH),
+                            Style::default().fg(Color::DarkGray),
+                        ),
+                    );
+                }
+                diff_line
+            }),
+    );
+
+    let mut title = String::from(" [Tab: switch, ↑↓: scroll");
+    if app.is_diff_view {
+        if app.diff_options.ignore_whitespace {
+            title.push_str(", w: whitespace ignored");
+        }
+        if app.diff_options.ignore_eol {
+            title.push_str(", e: line endings ignored");
+        }
+        if app.diff_is_partial() {
+            title.push_str(", F: full diff");
+        }
+    }
+    if app.cursor_entry().is_some_and(|e| !e.is_dir && e.status == FileStatus::Modified) {
+        title.push_str(&format!(", v: {}", app.content_view.label()));
+    }
+    if app.cursor_entry().is_some_and(|e| !e.is_dir && minified::can_pretty_print(&e.path)) {
+        title.push_str(if app.diff_options.pretty_print { ", p: as is" } else { ", p: pretty-print" });
+    }
+    if app.cursor_entry().is_some_and(|e| e.status == FileStatus::Modified && app.has_normalizer(e)) {
+        title.push_str(if app.diff_options.normalize { ", N: raw diff" } else { ", N: normalized" });
+    }
+    if app.blame.iter().any(Option::is_some) {
+        title.push_str(if app.show_blame { ", b: hide blame" } else { ", b: blame" });
+    }
+    title.push(']');
+    if let Some(writers) = app.cursor_writers() {
+        title.push_str(&format!(" Written by: {}", writers));
+    }
+    // A long path gives up its middle components rather than the rest of
+    // the title; i shows it in full
+    let path = match app.cursor_entry() {
+        Some(entry) => {
+            let room = usize::from(area.width.saturating_sub(2)).saturating_sub(text::width(&title));
+            text::truncate_middle(&app.display_path(&entry.rel_path), room.max(MIN_PATH_WIDTH))
+        }
+        None => String::from("Content"),
+    };
+    let title = accessibility::pane_title(app, path + &title, focused);
 
     let paragraph = Paragraph::new(content_text)
         .block(
             Block::default()
                 .borders(Borders::ALL)
                 .border_style(content_border_style)
-                .title("Content [Tab: switch, ↑↓: scroll]"),
+                .title(title),
         )
         .wrap(Wrap { trim: false });
 
     f.render_widget(paragraph, area);
+    // Screen readers start reading the content from its top
+    if focused && matches!(app.modal, Modal::None) {
+        accessibility::place_cursor(f, app, area, 0);
+    }
+}
+
+/// A line of file content as drawn. In a diff, tabs line up after the sign.
+fn shown(app: &App, line: &str) -> String {
+    match line.chars().next() {
+        Some(sign) if app.is_diff_view => {
+            let rest = &line[sign.len_utf8()..];
+            format!("{}{}", sign, text::printable(rest, app.tab_width))
+        }
+        _ => text::printable(line, app.tab_width),
+    }
+}
+
+/// A line of the content pane, colourized when viewing a diff
+fn diff_line(app: &App, line: String) -> Line<'static> {
+    if !app.is_diff_view {
+        return Line::from(line);
+    }
+    if line.starts_with('+') && !line.starts_with("+++") {
+        Line::from(Span::styled(line, Style::default().fg(Color::Green)))
+    } else if line.starts_with('-') && !line.starts_with("---") {
+        Line::from(Span::styled(line, Style::default().fg(Color::Red)))
+    } else if line.starts_with('~') {
+        // Whitespace-only change, hidden by the current diff options
+        Line::from(Span::styled(line, Style::default().fg(Color::DarkGray)))
+    } else if line.starts_with('#') {
+        // File-wide notes such as encoding or line ending changes
+        Line::from(Span::styled(line, Style::default().fg(Color::Yellow)))
+    } else if line.starts_with("---") || line.starts_with("+++") || line.starts_with("@@") {
+        Line::from(Span::styled(line, Style::default().fg(Color::Cyan)))
+    } else {
+        Line::from(line)
+    }
 }
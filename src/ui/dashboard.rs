@@ -0,0 +1,108 @@
+use crate::app::App;
+use crate::disk_usage::format_size;
+use ratatui::{
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Paragraph},
+    Frame,
+};
+
+pub fn render(f: &mut Frame, app: &App, area: Rect) {
+    let Some(dashboard) = &app.dashboard else {
+        return;
+    };
+
+    let outer = Block::default()
+        .title("Session Overview [D/Esc: close]")
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(app.theme.accent()));
+    let inner = outer.inner(area);
+    f.render_widget(outer, area);
+
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+        .split(inner);
+    let top = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+        .split(rows[0]);
+    let bottom = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+        .split(rows[1]);
+
+    // Changes by status and by top-level directory
+    let mut summary = vec![
+        Line::from(vec![
+            Span::styled(format!("{} new", dashboard.new_files), Style::default().fg(Color::Green)),
+            Span::raw(", "),
+            Span::styled(format!("{} modified", dashboard.modified_files), Style::default().fg(Color::Yellow)),
+            Span::raw(", "),
+            Span::styled(format!("{} deleted", dashboard.deleted_files), Style::default().fg(Color::Red)),
+            Span::raw(format!(", {} directories", dashboard.directories)),
+        ]),
+        Line::from(format!("{} in changed files", format_size(dashboard.total_size))),
+        Line::from(""),
+        heading("By top-level directory"),
+    ];
+    for (dir, count, size) in &dashboard.by_dir {
+        let files = format!("{} file{}", count, if *count == 1 { "" } else { "s" });
+        summary.push(Line::from(format!("  {:>10}  {:>11}  {}", format_size(*size), files, dir)));
+    }
+    render_section(f, "Summary", summary, top[0]);
+
+    // When files were last modified, as a bar per slice of time, one slice
+    // per line that fits
+    let slices = dashboard.timeline(top[1].height.saturating_sub(2).max(1) as usize);
+    let max_count = slices.iter().map(|(_, count)| *count).max().unwrap_or(0);
+    let spans_days = slices
+        .first()
+        .zip(slices.last())
+        .is_some_and(|((first, _), (last, _))| first.date_naive() != last.date_naive());
+    let time_format = if spans_days { "%m-%d %H:%M:%S" } else { "%H:%M:%S" };
+    let bar_width = top[1].width.saturating_sub(24) as usize;
+    let mut timeline = Vec::new();
+    for (start, count) in &slices {
+        let bar = (count * bar_width).div_ceil(max_count.max(1));
+        timeline.push(Line::from(vec![
+            Span::raw(format!("{} ", start.format(time_format))),
+            Span::styled(
+                if app.accessible { "#" } else { "█" }.repeat(bar),
+                Style::default().fg(app.theme.accent()),
+            ),
+            Span::raw(format!(" {}", count)),
+        ]));
+    }
+    render_section(f, "Files by Modification Time", timeline, top[1]);
+
+    let largest = dashboard
+        .largest
+        .iter()
+        .map(|(path, size)| Line::from(format!("{:>10}  {}", format_size(*size), path.display())))
+        .collect();
+    render_section(f, "Largest Files", largest, bottom[0]);
+
+    let most_changed = dashboard
+        .most_changed
+        .iter()
+        .map(|(path, added, removed)| {
+            Line::from(vec![
+                Span::styled(format!("{:>6}", format!("+{}", added)), Style::default().fg(Color::Green)),
+                Span::styled(format!(" {:>6}", format!("-{}", removed)), Style::default().fg(Color::Red)),
+                Span::raw(format!("  {}", path.display())),
+            ])
+        })
+        .collect();
+    render_section(f, "Most Changed Files", most_changed, bottom[1]);
+}
+
+fn heading(text: &str) -> Line<'_> {
+    Line::from(Span::styled(text, Style::default().add_modifier(Modifier::BOLD).fg(Color::Yellow)))
+}
+
+fn render_section(f: &mut Frame, title: &str, lines: Vec<Line>, area: Rect) {
+    let paragraph = Paragraph::new(lines).block(Block::default().title(title).borders(Borders::ALL));
+    f.render_widget(paragraph, area);
+}
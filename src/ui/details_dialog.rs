@@ -0,0 +1,55 @@
+use crate::app::App;
+use ratatui::{
+    layout::Rect,
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, Paragraph, Wrap},
+    Frame,
+};
+
+/// Width of the field names, so the values line up
+const NAME_WIDTH: usize = 14;
+
+pub fn render(f: &mut Frame, app: &App) {
+    let Some(details) = &app.details else {
+        return;
+    };
+
+    let lines: Vec<Line> = details
+        .iter()
+        .map(|(name, value)| {
+            Line::from(vec![
+                Span::styled(format!("{:<width$}", name, width = NAME_WIDTH), Style::default().add_modifier(Modifier::BOLD)),
+                Span::raw(value.as_str()),
+            ])
+        })
+        .collect();
+
+    // Create centered dialog area, tall enough for values that wrap
+    let area = f.area();
+    let dialog_width = area.width.min(90);
+    let inner_width = usize::from(dialog_width.saturating_sub(2)).max(1);
+    let wrapped: usize = details
+        .iter()
+        .map(|(_, value)| (NAME_WIDTH + value.chars().count()).div_ceil(inner_width))
+        .sum();
+    let dialog_height = (wrapped as u16 + 2).min(area.height);
+    let dialog_area = Rect {
+        x: (area.width.saturating_sub(dialog_width)) / 2,
+        y: (area.height.saturating_sub(dialog_height)) / 2,
+        width: dialog_width,
+        height: dialog_height,
+    };
+
+    // Clear the area and render dialog
+    f.render_widget(Clear, dialog_area);
+    let paragraph = Paragraph::new(lines)
+        .block(
+            Block::default()
+                .title("Details [Esc: close]")
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(Color::Cyan)),
+        )
+        .wrap(Wrap { trim: false });
+    f.render_widget(paragraph, dialog_area);
+}
@@ -1,5 +1,6 @@
-use crate::app::App;
+use crate::app::{App, Modal};
 use crate::types::DialogButton;
+use crate::ui::accessibility;
 use ratatui::{
     layout::{Alignment, Constraint, Direction, Layout, Rect},
     style::{Color, Style},
@@ -9,81 +10,86 @@ use ratatui::{
 };
 
 pub fn render(f: &mut Frame, app: &App) {
-    if !app.show_discard_dialog {
+    let Modal::Discard(state) = &app.modal else {
         return;
-    }
+    };
 
-    if let Some(selected) = app.list_state.selected() {
-        if let Some(entry) = app.files.get(selected) {
-            // Create centered dialog area
-            let area = f.area();
-            let dialog_width = area.width.min(60);
-            let dialog_height = 10;
-            let dialog_x = (area.width.saturating_sub(dialog_width)) / 2;
-            let dialog_y = (area.height.saturating_sub(dialog_height)) / 2;
+    let labelled = state.label.as_ref().map(|label| (label, app.labelled_entries(label)));
 
-            let dialog_area = Rect {
-                x: dialog_x,
-                y: dialog_y,
-                width: dialog_width,
-                height: dialog_height,
-            };
+    if let Some(entry) = app.cursor_entry() {
+        // Create centered dialog area
+        let area = f.area();
+        let dialog_width = area.width.min(60);
+        let dialog_height = match &labelled {
+            Some((_, files)) => (files.len() as u16 + 10).min(area.height.saturating_sub(4)),
+            None => 10,
+        };
+        let dialog_x = (area.width.saturating_sub(dialog_width)) / 2;
+        let dialog_y = (area.height.saturating_sub(dialog_height)) / 2;
 
-            // Clear the area and render dialog
-            f.render_widget(Clear, dialog_area);
+        let dialog_area = Rect {
+            x: dialog_x,
+            y: dialog_y,
+            width: dialog_width,
+            height: dialog_height,
+        };
 
-            let dialog_block = Block::default()
-                .title("Discard File")
-                .borders(Borders::ALL)
-                .border_style(Style::default().fg(Color::Red));
+        // Clear the area and render dialog
+        f.render_widget(Clear, dialog_area);
 
-            f.render_widget(dialog_block, dialog_area);
+        let dialog_block = Block::default()
+            .title("Discard File")
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(Color::Red));
 
-            // Split dialog into content and buttons
-            let dialog_chunks = Layout::default()
-                .direction(Direction::Vertical)
-                .margin(1)
-                .constraints([Constraint::Min(3), Constraint::Length(3)])
-                .split(dialog_area);
+        f.render_widget(dialog_block, dialog_area);
 
-            // Render confirmation message
-            let rel_path = entry.path.strip_prefix(&app.overlay_path).unwrap();
-            let file_type = if entry.is_dir { "directory" } else { "file" };
-            let message = vec![
-                Line::from("Are you sure you want to discard this file?"),
-                Line::from(""),
-                Line::from(format!("  {} {}", file_type, rel_path.display())),
-                Line::from(""),
-                Line::from(Span::styled(
-                    "This action cannot be undone!",
-                    Style::default().fg(Color::Red),
-                )),
-            ];
+        // Split dialog into content and buttons
+        let dialog_chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .margin(1)
+            .constraints([Constraint::Min(3), Constraint::Length(3)])
+            .split(dialog_area);
 
-            let message_paragraph = Paragraph::new(message).wrap(Wrap { trim: false });
-            f.render_widget(message_paragraph, dialog_chunks[0]);
+        // Render confirmation message
+        let mut message = match &labelled {
+            Some((label, files)) => {
+                let mut lines = vec![
+                    Line::from(format!("Are you sure you want to discard every file labelled #{}?", label)),
+                    Line::from(""),
+                ];
+                for file in files {
+                    lines.push(Line::from(format!("  • {}", app.display_path(&file.rel_path))));
+                }
+                lines
+            }
+            None => {
+                let file_type = if entry.is_dir { "directory" } else { "file" };
+                vec![
+                    Line::from("Are you sure you want to discard this file?"),
+                    Line::from(""),
+                    Line::from(format!("  {} {}", file_type, app.display_path(&entry.rel_path))),
+                ]
+            }
+        };
+        message.push(Line::from(""));
+        message.push(Line::from(Span::styled(
+            "This action cannot be undone!",
+            Style::default().fg(Color::Red),
+        )));
 
-            // Render buttons
-            let ok_style = if app.dialog_button == DialogButton::Ok {
-                Style::default().bg(Color::Red).fg(Color::Black)
-            } else {
-                Style::default()
-            };
-            let cancel_style = if app.dialog_button == DialogButton::Cancel {
-                Style::default().bg(Color::Green).fg(Color::Black)
-            } else {
-                Style::default()
-            };
+        let message_paragraph = Paragraph::new(message).wrap(Wrap { trim: false });
+        f.render_widget(message_paragraph, dialog_chunks[0]);
 
-            let buttons = Paragraph::new(Line::from(vec![
-                Span::raw("   "),
-                Span::styled(" Discard ", ok_style),
-                Span::raw("   "),
-                Span::styled(" Cancel ", cancel_style),
-            ]))
-            .alignment(Alignment::Center);
+        // Render buttons
+        let buttons = Paragraph::new(Line::from(vec![
+            Span::raw("   "),
+            accessibility::button(app, "Discard (y)", state.button == DialogButton::Ok, Color::Red),
+            Span::raw("   "),
+            accessibility::button(app, "Cancel (n)", state.button == DialogButton::Cancel, Color::Green),
+        ]))
+        .alignment(Alignment::Center);
 
-            f.render_widget(buttons, dialog_chunks[1]);
-        }
+        f.render_widget(buttons, dialog_chunks[1]);
     }
 }
@@ -0,0 +1,75 @@
+use crate::app::App;
+use ratatui::{
+    layout::Rect,
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Paragraph, Wrap},
+    Frame,
+};
+
+/// Shown instead of the panes while the overlay has no changes. The file
+/// watcher refreshes the list, so the panes replace this as soon as the
+/// first file appears.
+pub fn render(f: &mut Frame, app: &App, area: Rect) {
+    let label = Style::default().fg(Color::DarkGray);
+    let key = Style::default().fg(Color::Green);
+
+    let mut lines = vec![
+        Line::from(""),
+        Line::from(Span::styled("  No changes yet", Style::default().add_modifier(Modifier::BOLD).fg(Color::Yellow))),
+        Line::from(""),
+    ];
+    if let Some(dir) = app.session_dir() {
+        lines.push(Line::from(vec![Span::styled("  Session:  ", label), Span::raw(dir.display().to_string())]));
+    }
+    lines.push(Line::from(vec![
+        Span::styled("  Overlay:  ", label),
+        Span::raw(app.overlay_path.display().to_string()),
+    ]));
+    if app.lower.roots.is_empty() {
+        lines.push(Line::from(vec![
+            Span::styled("  Base:     ", label),
+            Span::raw(app.lower.base.display().to_string()),
+        ]));
+    }
+    for base in app.lower.roots.values() {
+        lines.push(Line::from(vec![Span::styled("  Base:     ", label), Span::raw(base.display().to_string())]));
+    }
+    for layer in &app.lower.layers {
+        lines.push(Line::from(vec![Span::styled("  Lower:    ", label), Span::raw(layer.display().to_string())]));
+    }
+    if let Some(run) = &app.last_run {
+        lines.push(Line::from(vec![Span::styled("  Last run: ", label), Span::raw(run.summary())]));
+    }
+    lines.push(Line::from(""));
+
+    // What to do next depends on whether there is a session to run in
+    if app.session_dir().is_some() {
+        lines.push(Line::from(vec![
+            Span::raw("  Run a command in the sandbox, e.g. "),
+            Span::styled("audit-box run make install", key),
+            Span::raw(", and its changes will appear here."),
+        ]));
+    } else {
+        lines.push(Line::from("  Run a command in a sandbox on this overlay to see its changes here."));
+    }
+    lines.push(Line::from(""));
+    lines.push(Line::from(vec![
+        Span::styled("  ?", key),
+        Span::raw(" help   "),
+        Span::styled("T", key),
+        Span::raw(" timeline   "),
+        Span::styled("q", key),
+        Span::raw(" quit"),
+    ]));
+
+    let paragraph = Paragraph::new(lines)
+        .block(
+            Block::default()
+                .title("Files")
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(app.theme.accent())),
+        )
+        .wrap(Wrap { trim: false });
+    f.render_widget(paragraph, area);
+}
@@ -1,5 +1,7 @@
-use crate::app::App;
-use crate::types::{ActivePane, FileStatus};
+use crate::app::{App, Modal};
+use crate::hash_lists::HashVerdict;
+use crate::types::{ActivePane, FileStatus, SelectionState};
+use crate::ui::{accessibility, text};
 use ratatui::{
     layout::Rect,
     style::{Color, Modifier, Style},
@@ -8,76 +10,236 @@ use ratatui::{
     Frame,
 };
 
+const HIGHLIGHT_SYMBOL: &str = ">> ";
+
+/// Names keep at least this many columns before badges are pushed off the
+/// edge of the pane
+const MIN_NAME_WIDTH: usize = 12;
+
 pub fn render(f: &mut Frame, app: &mut App, area: Rect) {
     let visible_files = app.get_visible_files();
 
-    let items: Vec<ListItem> = visible_files
+    // Grouped by kind, each kind's files follow a heading with their count
+    let mut headings = Vec::new();
+    if app.group_by_kind {
+        for (position, (id, _)) in visible_files.iter().enumerate() {
+            let kind = app.kind_of(*id);
+            if position == 0 || app.kind_of(visible_files[position - 1].0) != kind {
+                let count = visible_files[position..].iter().take_while(|(id, _)| app.kind_of(*id) == kind).count();
+                headings.push((position, format!("{} ({})", kind.label(), count)));
+            }
+        }
+    }
+
+    // Inside the borders and after the highlight symbol
+    let row_width = usize::from(area.width.saturating_sub(2 + HIGHLIGHT_SYMBOL.len() as u16));
+    let mut items: Vec<ListItem> = visible_files
         .iter()
-        .map(|(_, entry)| {
-            let indent = "  ".repeat(entry.depth);
+        .map(|(id, entry)| {
+            let indent = if app.group_by_kind { String::new() } else { "  ".repeat(entry.depth) };
 
             // Directory expand/collapse indicator
-            let dir_indicator = if entry.is_dir {
-                if entry.collapsed {
-                    "▶ "
-                } else {
-                    "▼ "
-                }
-            } else {
-                "  "
-            };
-
-            let icon = if entry.is_dir { "📁" } else { "📄" };
+            let dir_indicator = if entry.is_dir { app.icons.expander(entry.collapsed) } else { " " };
+            let icon = app.icons.icon(entry.is_dir, entry.collapsed);
+            // Files seeded from the session template were not produced by the sandbox
+            let seeded = app.is_seeded(entry);
             let status_indicator = match entry.status {
+                _ if seeded => "[T]",
                 FileStatus::New => "[N]",
                 FileStatus::Modified => "[M]",
+                FileStatus::Deleted => "[D]",
+                FileStatus::TypeChanged => "[K]",
             };
             let status_color = match entry.status {
+                _ if seeded => Color::Blue,
                 FileStatus::New => Color::Green,
                 FileStatus::Modified => Color::Yellow,
+                FileStatus::Deleted => Color::Red,
+                FileStatus::TypeChanged => Color::Magenta,
+            };
+            let selection_indicator = match app.files.selection_state(*id) {
+                SelectionState::Selected if app.accessible => "[x] ",
+                SelectionState::Selected => "[✓] ",
+                SelectionState::Partial => "[~] ",
+                SelectionState::Unselected => "[ ] ",
+            };
+
+            // Marks entries changed since the last review
+            let changed_indicator = match (app.is_changed_since_review(*id), app.accessible) {
+                (true, false) => "● ",
+                (true, true) => "* ",
+                (false, _) => " ",
             };
-            let selection_indicator = if entry.selected { "[✓] " } else { "[ ] " };
 
-            let content = vec![
+            let prefix = vec![
                 Span::raw(selection_indicator),
-                Span::raw(format!("{}{}{} ", indent, dir_indicator, icon)),
+                Span::raw(format!("{}{} {}", indent, dir_indicator, icon)),
                 Span::styled(status_indicator, Style::default().fg(status_color)),
-                Span::raw(format!(" {}", entry.name)),
+                Span::styled(changed_indicator, Style::default().fg(app.theme.accent())),
             ];
+            let (name, name_style) = if app.group_by_kind {
+                (
+                    app.display_path(&entry.rel_path),
+                    if seeded { Style::default().fg(Color::DarkGray) } else { Style::default() },
+                )
+            } else if seeded {
+                (entry.name.to_string(), Style::default().fg(Color::DarkGray))
+            } else if entry.depth < app.lower.root_depth() {
+                // Top-level directories stand for the bases of the session
+                (app.display_path(&entry.rel_path), Style::default().add_modifier(Modifier::BOLD))
+            } else {
+                (entry.name.to_string(), Style::default())
+            };
 
-            ListItem::new(Line::from(content))
+            // Badges after the name
+            let mut content = Vec::new();
+            if let Some(privileges) = app.privileges(entry) {
+                content.push(Span::styled(
+                    format!(" [{}]", privileges.describe()),
+                    Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+                ));
+            }
+            if app.dir_changes(entry).is_some() {
+                content.push(Span::styled(" [metadata changed]", Style::default().fg(Color::Yellow)));
+            }
+            match app.hash_verdict(entry) {
+                Some(HashVerdict::Denied) => content.push(Span::styled(
+                    " [DENYLISTED]",
+                    Style::default().fg(Color::White).bg(Color::Red).add_modifier(Modifier::BOLD),
+                )),
+                Some(HashVerdict::Allowed) => content.push(Span::styled(" [known good]", Style::default().fg(Color::Green))),
+                None => {}
+            }
+            if let Some(results) = app.check_results(entry) {
+                let failed: Vec<&str> = results.iter().filter(|r| !r.passed).map(|r| r.name.as_str()).collect();
+                content.push(if failed.is_empty() {
+                    Span::styled(" [checks ok]", Style::default().fg(Color::Green))
+                } else {
+                    Span::styled(format!(" [failed: {}]", failed.join(", ")), Style::default().fg(Color::Red))
+                });
+            } else if app.is_being_checked(entry) {
+                content.push(Span::styled(" [checking…]", Style::default().fg(Color::DarkGray)));
+            }
+            if app.is_ignored(*id) {
+                content.push(Span::styled(" [ignored]", Style::default().fg(Color::DarkGray)));
+            }
+            if app.applied_record(entry).is_some() {
+                content.push(Span::styled(" [modified since apply]", Style::default().fg(Color::Cyan)));
+            }
+            if app.is_bookmarked(entry) {
+                content.push(Span::styled(
+                    if app.accessible { " [bookmark]" } else { " ★" },
+                    Style::default().fg(Color::Yellow),
+                ));
+            }
+            if let Some(labels) = app.labels_for(entry) {
+                let tags: Vec<String> = labels.iter().map(|l| format!("#{}", l)).collect();
+                content.push(Span::styled(format!(" {}", tags.join(" ")), Style::default().fg(Color::Magenta)));
+            }
+            if let Some(name) = app.changeset_of(entry) {
+                content.push(Span::styled(format!(" [{}]", name), Style::default().fg(Color::Blue)));
+            }
+            if app.is_staged(entry) {
+                content.push(Span::styled(" [staged]", Style::default().fg(Color::DarkGray)));
+            }
+
+            ListItem::new(Line::from(layout_row(prefix, &name, name_style, content, row_width)))
         })
         .collect();
+    for (position, heading) in headings.iter().rev() {
+        items.insert(
+            *position,
+            ListItem::new(Line::from(Span::styled(
+                heading.clone(),
+                Style::default().fg(app.theme.accent()).add_modifier(Modifier::BOLD),
+            ))),
+        );
+    }
 
-    let file_list_border_style = if app.active_pane == ActivePane::FileList {
-        Style::default().fg(Color::Cyan)
+    let focused = app.active_pane == ActivePane::FileList;
+    let file_list_border_style = if focused {
+        Style::default().fg(app.theme.accent())
     } else {
         Style::default()
     };
 
+    let title = match (&app.label_filter, app.changed_only) {
+        _ if let Some(name) = &app.changeset_filter => format!("Files [changeset {}, tc: next, tC: apply]", name),
+        (Some(label), _) => format!("Files [label #{}, tf: next label]", label),
+        (None, true) => "Files [changed since last review, c: show all]".to_string(),
+        (None, false) if app.group_by_kind => "Files by kind [y: by directory, ↑↓: navigate, q: quit]".to_string(),
+        (None, false) => {
+            // Where the changes are, when the base is the root or home directory
+            let roots: Vec<String> = app
+                .root_summary()
+                .iter()
+                .map(|(root, count)| format!("{} {}", root, count))
+                .collect();
+            if roots.is_empty() {
+                "Files [Space: select, ←→: collapse/expand, ↑↓: navigate, Tab: switch, q: quit]".to_string()
+            } else {
+                format!("Files: {} [Space: select, ↑↓: navigate, q: quit]", roots.join(" · "))
+            }
+        }
+    };
+
     let items = List::new(items)
         .block(
             Block::default()
                 .borders(Borders::ALL)
                 .border_style(file_list_border_style)
-                .title("Files [Space: select, ←→: collapse/expand, ↑↓: navigate, Tab: switch, q: quit]"),
-        )
-        .highlight_style(
-            Style::default()
-                .bg(Color::DarkGray)
-                .add_modifier(Modifier::BOLD),
+                .title(accessibility::pane_title(app, title, focused)),
         )
-        .highlight_symbol(">> ");
+        .highlight_style(accessibility::highlight(app))
+        .highlight_symbol(HIGHLIGHT_SYMBOL);
 
-    // Convert the selected index from the full file list to the visible list position
+    // Convert the cursor node to its position in the visible list
     let mut render_state = ratatui::widgets::ListState::default();
-    if let Some(selected_idx) = app.list_state.selected() {
-        // Find the position of the selected index in the visible files
+    if let Some(cursor) = app.cursor {
+        // Find the position of the cursor in the visible files
         let visible_position = visible_files
             .iter()
-            .position(|(idx, _)| *idx == selected_idx);
-        render_state.select(visible_position);
+            .position(|(id, _)| *id == cursor);
+        // Headings above the cursor push it down
+        render_state.select(
+            visible_position.map(|position| position + headings.iter().filter(|(at, _)| *at <= position).count()),
+        );
     }
 
     f.render_stateful_widget(items, area, &mut render_state);
+    if focused && matches!(app.modal, Modal::None)
+        && let Some(selected) = render_state.selected()
+    {
+        accessibility::place_cursor(f, app, area, selected.saturating_sub(render_state.offset()));
+    }
+}
+
+/// Lay out a file list row in `width` columns: the name after its prefix,
+/// cut short with … when it does not fit, and the badges right-aligned at
+/// the edge of the pane, whatever the name's width. The full path is in
+/// the details popup.
+fn layout_row<'a>(
+    prefix: Vec<Span<'a>>,
+    name: &str,
+    name_style: Style,
+    badges: Vec<Span<'a>>,
+    width: usize,
+) -> Vec<Span<'a>> {
+    let prefix_width: usize = prefix.iter().map(|span| text::width(&span.content)).sum();
+    let badges_width: usize = badges.iter().map(|span| text::width(&span.content)).sum();
+    let room = width.saturating_sub(prefix_width + badges_width);
+    let room_for_name = room.max(MIN_NAME_WIDTH.min(text::width(name)));
+    // Paths, shown when grouping by kind, keep their first and last components
+    let name = if name.contains('/') {
+        text::truncate_middle(name, room_for_name)
+    } else {
+        text::truncate_end(name, room_for_name)
+    };
+    let padding = if badges.is_empty() { 0 } else { room.saturating_sub(text::width(&name)) };
+
+    let mut row = prefix;
+    row.push(Span::styled(name, name_style));
+    row.push(Span::raw(" ".repeat(padding)));
+    row.extend(badges);
+    row
 }
@@ -0,0 +1,190 @@
+use crate::app::{App, Modal};
+use crate::types::ActivePane;
+use crossterm::event::KeyCode;
+use ratatui::{
+    layout::Rect,
+    style::{Color, Style},
+    text::{Line, Span},
+    widgets::Paragraph,
+    Frame,
+};
+
+/// One line of the keys valid right now, named after the configured keymap
+pub fn render(f: &mut Frame, app: &App, area: Rect) {
+    let mut spans = vec![Span::raw(" ")];
+    let mut width = 1;
+    for (key, action) in hints(app) {
+        // Drop the hints that do not fit rather than cutting one off
+        let action = format!(" {}  ", action);
+        width += key.chars().count() + action.chars().count() - 2;
+        if width > area.width as usize {
+            break;
+        }
+        width += 2;
+        spans.push(Span::styled(key, Style::default().fg(Color::Green)));
+        spans.push(Span::styled(action, Style::default().fg(Color::DarkGray)));
+    }
+    f.render_widget(Paragraph::new(Line::from(spans)), area);
+}
+
+/// Keys and what they do, following the same precedence as the key handling
+fn hints(app: &App) -> Vec<(String, &'static str)> {
+    let key = |code| app.keymap.key_name(code);
+    let pair = |first, second| format!("{}/{}", key(first), key(second));
+    let fixed = |name: &str| name.to_string();
+
+    // Dialogs and the label prompt read keys without the keymap
+    if matches!(app.modal, Modal::Quit(_)) {
+        return vec![
+            (fixed("←/→"), "choose"),
+            (fixed("Enter"), "confirm"),
+            (fixed("y/q"), "quit"),
+            (fixed("n/Esc"), "cancel"),
+        ];
+    }
+    if matches!(app.modal, Modal::Apply(_) | Modal::Discard(_) | Modal::Reload(_)) {
+        return vec![
+            (fixed("←/→"), "choose"),
+            (fixed("Enter"), "confirm"),
+            (fixed("y"), "yes"),
+            (fixed("n/Esc"), "cancel"),
+        ];
+    }
+    if matches!(app.modal, Modal::Label(_)) {
+        return vec![
+            (fixed("name"), "add label"),
+            (fixed("-name"), "remove label"),
+            (fixed("↑/↓"), "history"),
+            (fixed("Enter"), "save"),
+            (fixed("Esc"), "cancel"),
+        ];
+    }
+    if matches!(app.modal, Modal::Changeset(_)) {
+        return vec![
+            (fixed("name: description"), "group"),
+            (fixed("-"), "ungroup"),
+            (fixed("↑/↓"), "history"),
+            (fixed("Enter"), "save"),
+            (fixed("Esc"), "cancel"),
+        ];
+    }
+    if matches!(app.modal, Modal::Describe(_)) {
+        return vec![(fixed("Enter"), "save"), (fixed("Esc"), "cancel")];
+    }
+    if matches!(app.modal, Modal::Plan(_)) {
+        return vec![
+            (fixed("↑/↓"), "history"),
+            (fixed("Enter"), "write"),
+            (fixed("Esc"), "cancel"),
+        ];
+    }
+    if matches!(app.modal, Modal::Path(_)) {
+        return vec![
+            (fixed("Tab"), "complete"),
+            (fixed("↑/↓"), "history"),
+            (fixed("Enter"), "go to path"),
+            (fixed("Esc"), "cancel"),
+        ];
+    }
+    if app.details.is_some() {
+        return vec![(fixed("Esc/i"), "close details")];
+    }
+    if matches!(app.modal, Modal::Help) {
+        return vec![(fixed("Esc/?"), "close help")];
+    }
+    if app.timeline.is_some() {
+        return vec![
+            (pair(KeyCode::Down, KeyCode::Up), "move"),
+            (key(KeyCode::Char('o')), "sort"),
+            (key(KeyCode::Enter), "go to file"),
+            (pair(KeyCode::Char('T'), KeyCode::Esc), "close"),
+            (key(KeyCode::Char('q')), "quit"),
+        ];
+    }
+    if app.show_changeset_queue {
+        return vec![
+            (pair(KeyCode::Down, KeyCode::Up), "move"),
+            (fixed("K/J"), "reorder"),
+            (fixed("s"), "squash"),
+            (fixed("e"), "describe"),
+            (fixed("Space"), "hold"),
+            (fixed("C"), "apply"),
+            (fixed("Enter"), "files"),
+            (fixed("Esc"), "close"),
+        ];
+    }
+    match app.pending_key {
+        Some('z') => {
+            return vec![
+                (fixed("zM"), "collapse all"),
+                (fixed("zR"), "expand all"),
+                (fixed("z1-z9"), "expand to depth"),
+            ];
+        }
+        Some('t') => {
+            return vec![
+                (fixed("tt"), "label"),
+                (fixed("tf"), "filter by label"),
+                (fixed("ta"), "apply label"),
+                (fixed("tx"), "discard label"),
+                (fixed("tg"), "group"),
+                (fixed("ts"), "suggest"),
+                (fixed("tc"), "filter by changeset"),
+                (fixed("tC"), "apply changeset"),
+                (fixed("tq"), "changeset queue"),
+            ];
+        }
+        _ => {}
+    }
+    if app.dashboard.is_some() {
+        return vec![
+            (pair(KeyCode::Char('D'), KeyCode::Esc), "close"),
+            (key(KeyCode::Char('q')), "quit"),
+        ];
+    }
+
+    let mut hints = match app.active_pane {
+        ActivePane::FileList => vec![
+            (pair(KeyCode::Down, KeyCode::Up), "move"),
+            (pair(KeyCode::Left, KeyCode::Right), "collapse/expand"),
+            (key(KeyCode::Char(' ')), "select"),
+            (key(KeyCode::Char('a')), "apply"),
+            (key(KeyCode::Char('s')), "stage"),
+            (key(KeyCode::Char('k')), "discard"),
+            (key(KeyCode::Char('i')), "details"),
+            (key(KeyCode::Char('g')), "go to path"),
+            (key(KeyCode::Char('m')), "bookmark"),
+            (key(KeyCode::Char('\'')), "next bookmark"),
+            (fixed("t"), "labels…"),
+            (fixed("z"), "folds…"),
+        ],
+        ActivePane::FileContent => vec![
+            (pair(KeyCode::Down, KeyCode::Up), "scroll"),
+            (key(KeyCode::Char('v')), "diff/overlay/base"),
+            (key(KeyCode::Char('w')), "whitespace"),
+            (key(KeyCode::Char('e')), "line endings"),
+        ],
+        ActivePane::Staging => vec![
+            (pair(KeyCode::Down, KeyCode::Up), "move"),
+            (key(KeyCode::Char('u')), "unstage"),
+            (pair(KeyCode::Char('J'), KeyCode::Char('K')), "reorder"),
+            (key(KeyCode::Char('C')), "commit"),
+        ],
+    };
+    if app.active_pane == ActivePane::FileContent && app.diff_is_partial() {
+        hints.push((key(KeyCode::Char('F')), "full diff"));
+    }
+    if app.active_pane == ActivePane::FileContent && app.blame.iter().any(Option::is_some) {
+        hints.push((key(KeyCode::Char('b')), "blame"));
+    }
+    // Grouped by kind, there are no directories to collapse
+    if app.group_by_kind {
+        hints.retain(|(_, action)| *action != "collapse/expand");
+    }
+    hints.extend([
+        (key(KeyCode::Tab), "switch pane"),
+        (key(KeyCode::Char('?')), "help"),
+        (key(KeyCode::Char('q')), "quit"),
+    ]);
+    hints
+}
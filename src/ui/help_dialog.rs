@@ -1,4 +1,4 @@
-use crate::app::App;
+use crate::app::{App, Modal};
 use ratatui::{
     layout::{Alignment, Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
@@ -8,14 +8,14 @@ use ratatui::{
 };
 
 pub fn render(f: &mut Frame, app: &App) {
-    if !app.show_help_dialog {
+    if !matches!(app.modal, Modal::Help) {
         return;
     }
 
     // Create centered dialog area
     let area = f.area();
     let dialog_width = area.width.min(70);
-    let dialog_height = area.height.min(25);
+    let dialog_height = area.height.min(54);
     let dialog_x = (area.width.saturating_sub(dialog_width)) / 2;
     let dialog_y = (area.height.saturating_sub(dialog_height)) / 2;
 
@@ -61,13 +61,53 @@ pub fn render(f: &mut Frame, app: &App) {
             Span::styled("  End          ", Style::default().fg(Color::Green)),
             Span::raw("Jump to last file in list"),
         ]),
+        Line::from(vec![
+            Span::styled("  g            ", Style::default().fg(Color::Green)),
+            Span::raw("Go to a path, with Tab completion"),
+        ]),
+        Line::from(vec![
+            Span::styled("  m / '        ", Style::default().fg(Color::Green)),
+            Span::raw("Bookmark entry / go to the next bookmark"),
+        ]),
         Line::from(vec![
             Span::styled("  Left/Right   ", Style::default().fg(Color::Green)),
             Span::raw("Collapse/expand directory"),
         ]),
+        Line::from(vec![
+            Span::styled("  zM / zR      ", Style::default().fg(Color::Green)),
+            Span::raw("Collapse/expand all directories"),
+        ]),
+        Line::from(vec![
+            Span::styled("  z1 - z9      ", Style::default().fg(Color::Green)),
+            Span::raw("Expand directories down to the given depth"),
+        ]),
+        Line::from(vec![
+            Span::styled("  c            ", Style::default().fg(Color::Green)),
+            Span::raw("Show only entries changed since the last review"),
+        ]),
+        Line::from(vec![
+            Span::styled("  I            ", Style::default().fg(Color::Green)),
+            Span::raw("Show or hide entries the base's .gitignore ignores"),
+        ]),
+        Line::from(vec![
+            Span::styled("  .            ", Style::default().fg(Color::Green)),
+            Span::raw("Show, hide or list last the hidden files"),
+        ]),
+        Line::from(vec![
+            Span::styled("  y            ", Style::default().fg(Color::Green)),
+            Span::raw("Group files by kind (scripts, configs, ...) or by directory"),
+        ]),
+        Line::from(vec![
+            Span::styled("  tf           ", Style::default().fg(Color::Green)),
+            Span::raw("Show only files with a label, cycling through labels"),
+        ]),
+        Line::from(vec![
+            Span::styled("  Enter, i     ", Style::default().fg(Color::Green)),
+            Span::raw("Details: full path, sizes, hashes and times"),
+        ]),
         Line::from(vec![
             Span::styled("  Tab          ", Style::default().fg(Color::Green)),
-            Span::raw("Switch between file list and content panes"),
+            Span::raw("Switch between file list, content and staging panes"),
         ]),
         Line::from(""),
         Line::from(vec![
@@ -82,15 +122,95 @@ pub fn render(f: &mut Frame, app: &App) {
             Span::styled("  a            ", Style::default().fg(Color::Green)),
             Span::raw("Apply selected changes to base filesystem"),
         ]),
+        Line::from(vec![
+            Span::styled("  A            ", Style::default().fg(Color::Green)),
+            Span::raw("Apply everything under the current directory"),
+        ]),
+        Line::from(vec![
+            Span::styled("  s / S        ", Style::default().fg(Color::Green)),
+            Span::raw("Stage/unstage for apply, show/hide staging pane"),
+        ]),
+        Line::from(vec![
+            Span::styled("  u, J/K       ", Style::default().fg(Color::Green)),
+            Span::raw("In the staging pane: unstage, move down/up"),
+        ]),
+        Line::from(vec![
+            Span::styled("  C            ", Style::default().fg(Color::Green)),
+            Span::raw("Commit the staging queue in one transaction"),
+        ]),
+        Line::from(vec![
+            Span::styled("  W            ", Style::default().fg(Color::Green)),
+            Span::raw("Write selected and staged files to a decisions file"),
+        ]),
+        Line::from(vec![
+            Span::styled("  tt           ", Style::default().fg(Color::Green)),
+            Span::raw("Add a label to the current entry (-name removes it)"),
+        ]),
+        Line::from(vec![
+            Span::styled("  ta / tx      ", Style::default().fg(Color::Green)),
+            Span::raw("Apply/discard every file with the filtered label"),
+        ]),
+        Line::from(vec![
+            Span::styled("  tg           ", Style::default().fg(Color::Green)),
+            Span::raw("Group the selected files into a changeset (- ungroups)"),
+        ]),
+        Line::from(vec![
+            Span::styled("  ts           ", Style::default().fg(Color::Green)),
+            Span::raw("Suggest files written close together as a changeset"),
+        ]),
+        Line::from(vec![
+            Span::styled("  tc / tC      ", Style::default().fg(Color::Green)),
+            Span::raw("Show only a changeset, cycling; apply it as a unit"),
+        ]),
+        Line::from(vec![
+            Span::styled("  tq           ", Style::default().fg(Color::Green)),
+            Span::raw("Changeset queue: reorder, squash, describe, hold, apply"),
+        ]),
         Line::from(vec![
             Span::styled("  k            ", Style::default().fg(Color::Green)),
             Span::raw("Discard currently selected file"),
         ]),
+        Line::from(vec![
+            Span::styled("  w            ", Style::default().fg(Color::Green)),
+            Span::raw("Toggle ignoring whitespace-only changes in diffs"),
+        ]),
+        Line::from(vec![
+            Span::styled("  e            ", Style::default().fg(Color::Green)),
+            Span::raw("Toggle ignoring line ending changes in diffs"),
+        ]),
+        Line::from(vec![
+            Span::styled("  p            ", Style::default().fg(Color::Green)),
+            Span::raw("Pretty-print JSON and JavaScript, e.g. minified files"),
+        ]),
+        Line::from(vec![
+            Span::styled("  N            ", Style::default().fg(Color::Green)),
+            Span::raw("Diff through the configured normalizer, or raw"),
+        ]),
+        Line::from(vec![
+            Span::styled("  F            ", Style::default().fg(Color::Green)),
+            Span::raw("Diff a large file in full, not just its changes"),
+        ]),
+        Line::from(vec![
+            Span::styled("  v            ", Style::default().fg(Color::Green)),
+            Span::raw("Show modified files as a diff, overlay or base version"),
+        ]),
+        Line::from(vec![
+            Span::styled("  b            ", Style::default().fg(Color::Green)),
+            Span::raw("Show/hide git blame for removed lines (git bases)"),
+        ]),
         Line::from(""),
         Line::from(vec![
             Span::styled("General", Style::default().add_modifier(Modifier::BOLD).fg(Color::Yellow)),
         ]),
         Line::from(""),
+        Line::from(vec![
+            Span::styled("  D            ", Style::default().fg(Color::Green)),
+            Span::raw("Show/hide the session overview"),
+        ]),
+        Line::from(vec![
+            Span::styled("  T            ", Style::default().fg(Color::Green)),
+            Span::raw("Show/hide the timeline of file activity"),
+        ]),
         Line::from(vec![
             Span::styled("  h, ?         ", Style::default().fg(Color::Green)),
             Span::raw("Show this help dialog"),
@@ -99,9 +219,13 @@ pub fn render(f: &mut Frame, app: &App) {
             Span::styled("  Esc          ", Style::default().fg(Color::Green)),
             Span::raw("Close dialogs"),
         ]),
+        Line::from(vec![
+            Span::styled("  Ctrl-Z       ", Style::default().fg(Color::Green)),
+            Span::raw("Suspend to the shell (resume with fg)"),
+        ]),
         Line::from(vec![
             Span::styled("  q            ", Style::default().fg(Color::Green)),
-            Span::raw("Quit application"),
+            Span::raw("Quit, asking first if there is pending work"),
         ]),
         Line::from(""),
         Line::from(vec![
@@ -0,0 +1,41 @@
+use serde::Deserialize;
+
+/// The symbols drawn before file list entries. Emoji take two columns on
+/// some terminals and one on others, which misaligns the list, so the
+/// default keeps to ASCII.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum IconSet {
+    #[default]
+    Ascii,
+    Unicode,
+    /// Glyphs of a Nerd Font patched font, which the terminal has to use
+    NerdFont,
+}
+
+impl IconSet {
+    /// Whether a directory is collapsed or expanded
+    pub fn expander(self, collapsed: bool) -> &'static str {
+        match (self, collapsed) {
+            (IconSet::Ascii, true) => "+",
+            (IconSet::Ascii, false) => "-",
+            (IconSet::Unicode, true) => "▶",
+            (IconSet::Unicode, false) => "▼",
+            (IconSet::NerdFont, true) => "\u{f105}",
+            (IconSet::NerdFont, false) => "\u{f107}",
+        }
+    }
+
+    /// The icon of a directory or file, with the space after it; none in
+    /// ASCII, where the expander already tells directories apart
+    pub fn icon(self, is_dir: bool, collapsed: bool) -> &'static str {
+        match (self, is_dir, collapsed) {
+            (IconSet::Ascii, _, _) => "",
+            (IconSet::Unicode, true, _) => "📁 ",
+            (IconSet::Unicode, false, _) => "📄 ",
+            (IconSet::NerdFont, true, true) => "\u{f07b} ",
+            (IconSet::NerdFont, true, false) => "\u{f07c} ",
+            (IconSet::NerdFont, false, _) => "\u{f15b} ",
+        }
+    }
+}
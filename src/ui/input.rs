@@ -0,0 +1,183 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui::{
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+};
+
+/// How many entries a prompt's history keeps
+const MAX_HISTORY: usize = 50;
+
+/// Checks typed text before it is submitted, returning why it cannot be
+pub type Validator = fn(&str) -> Result<(), String>;
+
+/// A line of text typed into a prompt, with a cursor, the usual editing
+/// keys and the prompt's earlier entries to recall with Up and Down
+#[derive(Debug, Clone, Default)]
+pub struct Input {
+    text: String,
+    /// Byte offset of the cursor into text, on a char boundary
+    cursor: usize,
+    /// Earlier entries, oldest first
+    history: Vec<String>,
+    /// The history entry shown while recalling, and what was typed before
+    recalled: Option<(usize, String)>,
+    validator: Option<Validator>,
+    /// Why the text was not submitted, until it is edited
+    error: Option<String>,
+}
+
+/// What a key did to the prompt
+#[derive(Debug, PartialEq)]
+pub enum InputEvent {
+    /// Enter with text that passed validation
+    Submit(String),
+    /// Esc
+    Cancel,
+    /// The text or the cursor moved
+    Edited,
+    /// Not an editing key, for the prompt to handle itself
+    Ignored,
+}
+
+impl Input {
+    pub fn new(history: &[String]) -> Self {
+        Input {
+            history: history.to_vec(),
+            ..Input::default()
+        }
+    }
+
+    pub fn with_validator(mut self, validator: Validator) -> Self {
+        self.validator = Some(validator);
+        self
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Replace the text, e.g. with a completion, leaving the cursor at the end
+    pub fn set_text(&mut self, text: impl Into<String>) {
+        self.text = text.into();
+        self.cursor = self.text.len();
+        self.error = None;
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) -> InputEvent {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Enter => {
+                if let Some(validator) = self.validator
+                    && let Err(e) = validator(&self.text)
+                {
+                    self.error = Some(e);
+                    return InputEvent::Edited;
+                }
+                return InputEvent::Submit(self.text.clone());
+            }
+            KeyCode::Esc => return InputEvent::Cancel,
+            KeyCode::Left => self.cursor = self.previous_boundary(),
+            KeyCode::Right => self.cursor = self.next_boundary(),
+            KeyCode::Home => self.cursor = 0,
+            KeyCode::Char('a') if ctrl => self.cursor = 0,
+            KeyCode::End => self.cursor = self.text.len(),
+            KeyCode::Char('e') if ctrl => self.cursor = self.text.len(),
+            KeyCode::Backspace => {
+                let start = self.previous_boundary();
+                self.text.replace_range(start..self.cursor, "");
+                self.cursor = start;
+            }
+            KeyCode::Delete => {
+                let end = self.next_boundary();
+                self.text.replace_range(self.cursor..end, "");
+            }
+            KeyCode::Char('u') if ctrl => {
+                self.text.replace_range(..self.cursor, "");
+                self.cursor = 0;
+            }
+            KeyCode::Char('k') if ctrl => self.text.truncate(self.cursor),
+            KeyCode::Char('w') if ctrl => {
+                let before = self.text[..self.cursor].trim_end();
+                let start = before.rfind(char::is_whitespace).map_or(0, |i| i + 1);
+                self.text.replace_range(start..self.cursor, "");
+                self.cursor = start;
+            }
+            KeyCode::Up => self.recall_older(),
+            KeyCode::Down => self.recall_newer(),
+            KeyCode::Char(c) if !ctrl => {
+                self.text.insert(self.cursor, c);
+                self.cursor += c.len_utf8();
+            }
+            _ => return InputEvent::Ignored,
+        }
+        self.error = None;
+        InputEvent::Edited
+    }
+
+    fn previous_boundary(&self) -> usize {
+        self.text[..self.cursor].char_indices().next_back().map_or(0, |(i, _)| i)
+    }
+
+    fn next_boundary(&self) -> usize {
+        self.text[self.cursor..].chars().next().map_or(self.cursor, |c| self.cursor + c.len_utf8())
+    }
+
+    fn recall_older(&mut self) {
+        let older = match &self.recalled {
+            Some((index, _)) => index.checked_sub(1),
+            None => self.history.len().checked_sub(1),
+        };
+        let Some(index) = older else {
+            return;
+        };
+        let typed = self.recalled.take().map_or_else(|| self.text.clone(), |(_, typed)| typed);
+        self.recalled = Some((index, typed));
+        self.set_text(self.history[index].clone());
+    }
+
+    fn recall_newer(&mut self) {
+        let Some((index, typed)) = self.recalled.take() else {
+            return;
+        };
+        if index + 1 < self.history.len() {
+            self.recalled = Some((index + 1, typed));
+            self.set_text(self.history[index + 1].clone());
+        } else {
+            self.set_text(typed);
+        }
+    }
+
+    /// The prompt line, with the cursor shown in reverse video
+    pub fn line(&self) -> Line<'_> {
+        let (before, rest) = self.text.split_at(self.cursor);
+        let mut chars = rest.chars();
+        let at_cursor = chars.next().map_or(" ".to_string(), String::from);
+        Line::from(vec![
+            Span::raw("> "),
+            Span::raw(before),
+            Span::styled(at_cursor, Style::default().add_modifier(Modifier::REVERSED)),
+            Span::raw(chars.as_str()),
+        ])
+    }
+
+    /// The validation error if there is one, otherwise the prompt's hint
+    pub fn hint_line<'a>(&'a self, hint: &'a str) -> Line<'a> {
+        match &self.error {
+            Some(error) => Line::from(Span::styled(error.as_str(), Style::default().fg(Color::Red))),
+            None => Line::from(Span::styled(hint, Style::default().fg(Color::Gray).add_modifier(Modifier::ITALIC))),
+        }
+    }
+}
+
+/// Add a submitted entry to a prompt's history, dropping an earlier copy of
+/// it and the oldest entries beyond the limit
+pub fn remember(history: &mut Vec<String>, entry: &str) {
+    if entry.trim().is_empty() {
+        return;
+    }
+    history.retain(|e| e != entry);
+    history.push(entry.to_string());
+    if history.len() > MAX_HISTORY {
+        history.drain(..history.len() - MAX_HISTORY);
+    }
+}
@@ -0,0 +1,53 @@
+use crate::app::{App, Modal};
+use ratatui::{
+    layout::{Constraint, Direction, Layout, Rect},
+    style::Style,
+    widgets::{Block, Borders, Clear, Paragraph},
+    Frame,
+};
+
+pub fn render(f: &mut Frame, app: &App) {
+    let Modal::Label(input) = &app.modal else {
+        return;
+    };
+
+    // Create centered dialog area
+    let area = f.area();
+    let dialog_width = area.width.min(50);
+    let dialog_height = 5.min(area.height);
+    let dialog_x = (area.width.saturating_sub(dialog_width)) / 2;
+    let dialog_y = (area.height.saturating_sub(dialog_height)) / 2;
+
+    let dialog_area = Rect {
+        x: dialog_x,
+        y: dialog_y,
+        width: dialog_width,
+        height: dialog_height,
+    };
+
+    // Clear the area and render dialog
+    f.render_widget(Clear, dialog_area);
+
+    let target = match app.cursor_entry() {
+        Some(entry) if entry.is_dir => format!("Label files under {}", entry.name),
+        Some(entry) => format!("Label {}", entry.name),
+        None => "Label".to_string(),
+    };
+    let dialog_block = Block::default()
+        .title(target)
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(app.theme.accent()));
+
+    f.render_widget(dialog_block, dialog_area);
+
+    let dialog_chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(1)
+        .constraints([Constraint::Length(2), Constraint::Min(1)])
+        .split(dialog_area);
+
+    f.render_widget(Paragraph::new(input.line()), dialog_chunks[0]);
+
+    let hint = Paragraph::new(input.hint_line("Enter: add  -name: remove  Esc: cancel"));
+    f.render_widget(hint, dialog_chunks[1]);
+}
@@ -1,5 +1,26 @@
+pub mod accessibility;
 pub mod apply_dialog;
+pub mod apply_progress;
+pub mod changeset_dialog;
+pub mod changeset_queue;
 pub mod content_viewer;
+pub mod dashboard;
+pub mod details_dialog;
 pub mod discard_dialog;
+pub mod empty_state;
 pub mod file_list;
+pub mod footer;
 pub mod help_dialog;
+pub mod icons;
+pub mod input;
+pub mod label_dialog;
+pub mod path_dialog;
+pub mod plan_dialog;
+pub mod quit_dialog;
+pub mod reload_dialog;
+pub mod staging_pane;
+pub mod status_bar;
+pub mod text;
+pub mod theme;
+pub mod timeline_view;
+pub mod too_small;
@@ -0,0 +1,67 @@
+use crate::app::{App, Modal};
+use ratatui::{
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, Paragraph},
+    Frame,
+};
+
+/// How many completions the prompt lists
+const MAX_COMPLETIONS: usize = 8;
+
+pub fn render(f: &mut Frame, app: &App) {
+    let Modal::Path(input) = &app.modal else {
+        return;
+    };
+
+    let completions = app.path_completions();
+    let shown = completions.len().min(MAX_COMPLETIONS);
+
+    // Create centered dialog area
+    let area = f.area();
+    let dialog_width = area.width.min(60);
+    let dialog_height = (shown as u16 + 6).min(area.height);
+    let dialog_x = (area.width.saturating_sub(dialog_width)) / 2;
+    let dialog_y = (area.height.saturating_sub(dialog_height)) / 2;
+
+    let dialog_area = Rect {
+        x: dialog_x,
+        y: dialog_y,
+        width: dialog_width,
+        height: dialog_height,
+    };
+
+    // Clear the area and render dialog
+    f.render_widget(Clear, dialog_area);
+
+    let dialog_block = Block::default()
+        .title("Go to Path")
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(app.theme.accent()));
+
+    f.render_widget(dialog_block, dialog_area);
+
+    let dialog_chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(1)
+        .constraints([Constraint::Length(2), Constraint::Min(1), Constraint::Length(1)])
+        .split(dialog_area);
+
+    f.render_widget(Paragraph::new(input.line()), dialog_chunks[0]);
+
+    let mut lines: Vec<Line> = completions
+        .iter()
+        .take(MAX_COMPLETIONS)
+        .map(|path| Line::from(Span::styled(format!("  {}", path), Style::default().fg(Color::Gray))))
+        .collect();
+    if completions.len() > MAX_COMPLETIONS {
+        lines.push(Line::from(format!("  … {} more", completions.len() - MAX_COMPLETIONS)));
+    } else if completions.is_empty() {
+        lines.push(Line::from(Span::styled("  No matching entries", Style::default().fg(Color::Red))));
+    }
+    f.render_widget(Paragraph::new(lines), dialog_chunks[1]);
+
+    let hint = Paragraph::new(input.hint_line("Tab: complete  Enter: go  Esc: cancel"));
+    f.render_widget(hint, dialog_chunks[2]);
+}
@@ -0,0 +1,75 @@
+use crate::app::{App, Modal};
+use crate::types::QuitChoice;
+use crate::ui::accessibility;
+use ratatui::{
+    layout::{Alignment, Constraint, Direction, Layout, Rect},
+    style::{Color, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, Paragraph, Wrap},
+    Frame,
+};
+
+pub fn render(f: &mut Frame, app: &App) {
+    let Modal::Quit(quit_choice) = app.modal else {
+        return;
+    };
+
+    let pending = app.pending_work();
+
+    // Create centered dialog area
+    let area = f.area();
+    let dialog_width = area.width.min(60);
+    let dialog_height = (pending.len() as u16 + 8).min(area.height.saturating_sub(4));
+    let dialog_x = (area.width.saturating_sub(dialog_width)) / 2;
+    let dialog_y = (area.height.saturating_sub(dialog_height)) / 2;
+
+    let dialog_area = Rect {
+        x: dialog_x,
+        y: dialog_y,
+        width: dialog_width,
+        height: dialog_height,
+    };
+
+    // Clear the area and render dialog
+    f.render_widget(Clear, dialog_area);
+
+    let dialog_block = Block::default()
+        .title("Quit")
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Yellow));
+
+    f.render_widget(dialog_block, dialog_area);
+
+    // Split dialog into content and buttons
+    let dialog_chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(1)
+        .constraints([Constraint::Min(3), Constraint::Length(3)])
+        .split(dialog_area);
+
+    // Render the pending work
+    let mut message = vec![Line::from("There is pending work in this review:"), Line::from("")];
+    for item in &pending {
+        message.push(Line::from(format!("  • {}", item)));
+    }
+
+    let message_paragraph = Paragraph::new(message).wrap(Wrap { trim: false });
+    f.render_widget(message_paragraph, dialog_chunks[0]);
+
+    // Render buttons, offering to apply only when there is something to apply
+    let button = |label: &str, choice: QuitChoice, color: Color| {
+        accessibility::button(app, label, quit_choice == choice, color)
+    };
+
+    let mut buttons = vec![Span::raw("   "), button("Quit (y)", QuitChoice::Quit, Color::Red)];
+    if app.can_apply_before_quit() {
+        buttons.push(Span::raw("   "));
+        buttons.push(button("Apply & Quit", QuitChoice::ApplyAndQuit, Color::Yellow));
+    }
+    buttons.push(Span::raw("   "));
+    buttons.push(button("Cancel (n)", QuitChoice::Cancel, Color::Green));
+
+    let buttons = Paragraph::new(Line::from(buttons)).alignment(Alignment::Center);
+
+    f.render_widget(buttons, dialog_chunks[1]);
+}
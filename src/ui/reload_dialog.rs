@@ -0,0 +1,73 @@
+use crate::app::{App, Modal};
+use crate::types::DialogButton;
+use crate::ui::accessibility;
+use ratatui::{
+    layout::{Alignment, Constraint, Direction, Layout, Rect},
+    style::{Color, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, Paragraph, Wrap},
+    Frame,
+};
+
+pub fn render(f: &mut Frame, app: &App) {
+    let Modal::Reload(button) = app.modal else {
+        return;
+    };
+
+    // Create centered dialog area
+    let area = f.area();
+    let dialog_width = area.width.min(70);
+    let dialog_height = (app.pending_reloads.len() as u16 * 2 + 8).min(area.height.saturating_sub(4));
+    let dialog_x = (area.width.saturating_sub(dialog_width)) / 2;
+    let dialog_y = (area.height.saturating_sub(dialog_height)) / 2;
+
+    let dialog_area = Rect {
+        x: dialog_x,
+        y: dialog_y,
+        width: dialog_width,
+        height: dialog_height,
+    };
+
+    // Clear the area and render dialog
+    f.render_widget(Clear, dialog_area);
+
+    let dialog_block = Block::default()
+        .title("Reload Services")
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Yellow));
+
+    f.render_widget(dialog_block, dialog_area);
+
+    // Split dialog into content and buttons
+    let dialog_chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(1)
+        .constraints([Constraint::Min(3), Constraint::Length(3)])
+        .split(dialog_area);
+
+    // Each command, and the applied file that calls for it
+    let mut message = vec![
+        Line::from("The files just applied need these run to take effect:"),
+        Line::from(""),
+    ];
+    for action in &app.pending_reloads {
+        message.push(Line::from(format!("  $ {}", action.command_line())));
+        message.push(Line::from(Span::styled(
+            format!("      for {}", app.display_path(&action.because)),
+            Style::default().fg(Color::DarkGray),
+        )));
+    }
+    let message_paragraph = Paragraph::new(message).wrap(Wrap { trim: false });
+    f.render_widget(message_paragraph, dialog_chunks[0]);
+
+    // Render buttons
+    let buttons = Paragraph::new(Line::from(vec![
+        Span::raw("   "),
+        accessibility::button(app, "Run (y)", button == DialogButton::Ok, Color::Yellow),
+        Span::raw("   "),
+        accessibility::button(app, "Not now (n)", button == DialogButton::Cancel, Color::Green),
+    ]))
+    .alignment(Alignment::Center);
+
+    f.render_widget(buttons, dialog_chunks[1]);
+}
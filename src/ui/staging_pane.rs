@@ -0,0 +1,67 @@
+use crate::app::{App, Modal};
+use crate::types::{ActivePane, FileStatus};
+use crate::ui::accessibility;
+use ratatui::{
+    layout::Rect,
+    style::{Color, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, List, ListItem, ListState},
+    Frame,
+};
+
+pub fn render(f: &mut Frame, app: &App, area: Rect) {
+    let staged = app.staged_entries();
+
+    let items: Vec<ListItem> = staged
+        .iter()
+        .enumerate()
+        .map(|(i, entry)| {
+            let (status_indicator, status_color) = match entry.status {
+                FileStatus::New => ("[N]", Color::Green),
+                FileStatus::Modified => ("[M]", Color::Yellow),
+                FileStatus::Deleted => ("[D]", Color::Red),
+                FileStatus::TypeChanged => ("[K]", Color::Magenta),
+            };
+
+            ListItem::new(Line::from(vec![
+                Span::raw(format!("{:>2}. ", i + 1)),
+                Span::styled(status_indicator, Style::default().fg(status_color)),
+                Span::raw(format!(" {}", app.display_path(&entry.rel_path))),
+            ]))
+        })
+        .collect();
+
+    let is_active = app.active_pane == ActivePane::Staging;
+    let border_style = if is_active {
+        Style::default().fg(app.theme.accent())
+    } else {
+        Style::default()
+    };
+
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(border_style)
+                .title(accessibility::pane_title(
+                    app,
+                    format!("Staged ({}) [u: unstage, J/K: reorder, C: commit]", staged.len()),
+                    is_active,
+                )),
+        )
+        .highlight_style(accessibility::highlight(app))
+        .highlight_symbol(">> ");
+
+    // Only show the staging cursor while the pane has focus
+    let mut state = ListState::default();
+    if is_active && !staged.is_empty() {
+        state.select(Some(app.staging_cursor));
+    }
+
+    f.render_stateful_widget(list, area, &mut state);
+    if matches!(app.modal, Modal::None)
+        && let Some(selected) = state.selected()
+    {
+        accessibility::place_cursor(f, app, area, selected.saturating_sub(state.offset()));
+    }
+}
@@ -0,0 +1,55 @@
+use crate::app::App;
+use crate::disk_usage;
+use ratatui::{
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::Paragraph,
+    Frame,
+};
+
+pub fn render(f: &mut Frame, app: &App, area: Rect) {
+    let usage = disk_usage::format_size(app.disk_usage.total());
+    let mut usage_text = match app.size_warning {
+        Some(limit) => format!(" Overlay: {} / {} ", usage, disk_usage::format_size(limit)),
+        None => format!(" Overlay: {} ", usage),
+    };
+    // Otherwise only its colour says so
+    if app.accessible && app.is_over_size_warning() {
+        usage_text.push_str("(over) ");
+    }
+    let usage_style = if app.is_over_size_warning() {
+        Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)
+    } else {
+        Style::default()
+    };
+
+    let mut right = vec![Span::styled(usage_text, usage_style)];
+    if app.read_only {
+        right.insert(0, Span::styled(" READ-ONLY ", Style::default().fg(Color::White).bg(Color::Red).add_modifier(Modifier::BOLD)));
+    }
+    let right_width: usize = right.iter().map(|span| span.content.chars().count()).sum();
+
+    let chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Min(0), Constraint::Length(right_width as u16)])
+        .split(area);
+
+    // Toasts take over the left side until they expire
+    let left = match app.active_toast() {
+        Some(message) => Line::from(Span::styled(
+            format!(" {}", message),
+            Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD),
+        )),
+        None => match &app.last_run {
+            Some(run) => Line::from(vec![
+                Span::raw(format!(" Last run: {}", run.summary())),
+                Span::styled("  ? help", Style::default().fg(Color::DarkGray)),
+            ]),
+            None => Line::from(Span::styled(" Press ? for help", Style::default().fg(Color::DarkGray))),
+        },
+    };
+
+    f.render_widget(Paragraph::new(left), chunks[0]);
+    f.render_widget(Paragraph::new(Line::from(right)), chunks[1]);
+}
@@ -0,0 +1,114 @@
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+/// A line of file content made safe to draw: ANSI escape sequences are
+/// dropped, tabs expanded to the next multiple of `tab_width` columns and
+/// other control characters shown in caret notation, e.g. `^[` for a lone
+/// escape, so a file cannot move the cursor or recolour the screen
+pub fn printable(line: &str, tab_width: usize) -> String {
+    let tab_width = tab_width.max(1);
+    let mut out = String::with_capacity(line.len());
+    let mut column = 0;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\t' => {
+                let spaces = tab_width - column % tab_width;
+                out.extend(std::iter::repeat_n(' ', spaces));
+                column += spaces;
+            }
+            // CSI sequence, e.g. colours: ESC [ parameters final byte
+            '\x1b' if chars.peek() == Some(&'[') => {
+                chars.next();
+                for c in chars.by_ref() {
+                    if ('\x40'..='\x7e').contains(&c) {
+                        break;
+                    }
+                }
+            }
+            // OSC sequence, e.g. a window title or hyperlink: ESC ] ... BEL or ESC \
+            '\x1b' if chars.peek() == Some(&']') => {
+                chars.next();
+                while let Some(c) = chars.next() {
+                    if c == '\x07' || (c == '\x1b' && chars.next_if_eq(&'\\').is_some()) {
+                        break;
+                    }
+                }
+            }
+            '\x00'..='\x1f' | '\x7f' => {
+                out.push('^');
+                out.push(((c as u8) ^ 0x40) as char);
+                column += 2;
+            }
+            // C1 controls, which some terminals act on too
+            '\u{80}'..='\u{9f}' => {
+                let escaped = format!("<U+{:04X}>", c as u32);
+                column += escaped.len();
+                out.push_str(&escaped);
+            }
+            c => {
+                out.push(c);
+                column += c.width().unwrap_or(1);
+            }
+        }
+    }
+    out
+}
+
+/// Columns a string takes on screen, counting wide characters such as CJK
+/// and emoji as two
+pub fn width(text: &str) -> usize {
+    UnicodeWidthStr::width(text)
+}
+
+/// Cut a string down to `max` columns, ending it with … if anything was cut
+pub fn truncate_end(text: &str, max: usize) -> String {
+    if width(text) <= max {
+        return text.to_string();
+    }
+    let mut out = String::new();
+    let mut used = 0;
+    for c in text.chars() {
+        let w = c.width().unwrap_or(0);
+        // Leave a column for the ellipsis
+        if used + w + 1 > max {
+            break;
+        }
+        out.push(c);
+        used += w;
+    }
+    if max > 0 {
+        out.push('…');
+    }
+    out
+}
+
+/// Cut a path down to `max` columns by replacing components in the middle
+/// with …, keeping the first and last so it stays recognisable, e.g.
+/// `~/.config/…/nvim/init.lua`
+pub fn truncate_middle(path: &str, max: usize) -> String {
+    if width(path) <= max {
+        return path.to_string();
+    }
+    let components: Vec<&str> = path.split('/').collect();
+    if components.len() < 3 {
+        return truncate_end(path, max);
+    }
+    let first = components[0];
+    let mut tail: Vec<&str> = vec![components[components.len() - 1]];
+    let shown = |tail: &[&str]| {
+        let tail: Vec<&str> = tail.iter().rev().copied().collect();
+        format!("{}/…/{}", first, tail.join("/"))
+    };
+    if width(&shown(&tail)) > max {
+        return truncate_end(&format!("…/{}", tail[0]), max);
+    }
+    // Keep as many of the components before the last as still fit
+    for component in components[1..components.len() - 1].iter().rev() {
+        tail.push(component);
+        if width(&shown(&tail)) > max {
+            tail.pop();
+            break;
+        }
+    }
+    shown(&tail)
+}
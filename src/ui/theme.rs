@@ -0,0 +1,28 @@
+use ratatui::style::Color;
+use serde::Deserialize;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Theme {
+    #[default]
+    Dark,
+    Light,
+}
+
+impl Theme {
+    /// Border colour of the focused pane
+    pub fn accent(self) -> Color {
+        match self {
+            Theme::Dark => Color::Cyan,
+            Theme::Light => Color::Blue,
+        }
+    }
+
+    /// Background of the highlighted row in the file list
+    pub fn highlight_bg(self) -> Color {
+        match self {
+            Theme::Dark => Color::DarkGray,
+            Theme::Light => Color::Gray,
+        }
+    }
+}
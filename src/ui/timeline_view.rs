@@ -0,0 +1,74 @@
+use crate::app::App;
+use chrono::{DateTime, Local};
+use ratatui::{
+    layout::Rect,
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, List, ListItem, ListState},
+    Frame,
+};
+
+pub fn render(f: &mut Frame, app: &App, area: Rect) {
+    let Some(entries) = &app.timeline else {
+        return;
+    };
+
+    let mut items = vec![ListItem::new(Line::from(Span::styled(
+        format!("{:<14}  {:<14}  {:>6}  {}", "Created", "Last modified", "Events", "Path"),
+        Style::default().add_modifier(Modifier::BOLD).fg(Color::Yellow),
+    )))];
+    items.extend(entries.iter().map(|entry| {
+        let row = format!(
+            "{:<14}  {:<14}  {:>6}  {}",
+            format_time(entry.created),
+            format_time(entry.last_modified),
+            entry.events,
+            app.display_path(&entry.path)
+        );
+        if entry.present {
+            ListItem::new(Line::from(row))
+        } else {
+            // Files that no longer exist, e.g. an installer's temporary files
+            let removed = match entry.removed {
+                Some(time) => format!("  (removed {})", format_time(Some(time))),
+                None => "  (removed)".to_string(),
+            };
+            ListItem::new(Line::from(vec![
+                Span::styled(row, Style::default().fg(Color::DarkGray)),
+                Span::styled(removed, Style::default().fg(Color::Red)),
+            ]))
+        }
+    }));
+
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(app.theme.accent()))
+                .title(format!(
+                    "Timeline, {} [o: sort, Enter: go to file, T/Esc: close]",
+                    app.timeline_sort.label()
+                )),
+        )
+        .highlight_style(
+            Style::default()
+                .bg(app.theme.highlight_bg())
+                .add_modifier(Modifier::BOLD),
+        )
+        .highlight_symbol(">> ");
+
+    // Row 0 is the column header
+    let mut state = ListState::default();
+    if !entries.is_empty() {
+        state.select(Some(app.timeline_cursor + 1));
+    }
+
+    f.render_stateful_widget(list, area, &mut state);
+}
+
+fn format_time(time: Option<DateTime<Local>>) -> String {
+    match time {
+        Some(time) => time.format("%m-%d %H:%M:%S").to_string(),
+        None => "-".to_string(),
+    }
+}
@@ -0,0 +1,35 @@
+use ratatui::{
+    layout::{Alignment, Rect},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Paragraph, Wrap},
+    Frame,
+};
+
+/// Smallest terminal the panes and dialogs are laid out for
+pub const MIN_WIDTH: u16 = 60;
+pub const MIN_HEIGHT: u16 = 15;
+
+pub fn fits(area: Rect) -> bool {
+    area.width >= MIN_WIDTH && area.height >= MIN_HEIGHT
+}
+
+/// Placeholder shown instead of the UI until the terminal is made larger
+pub fn render(f: &mut Frame) {
+    let area = f.area();
+    let lines = vec![
+        Line::from(Span::styled("Terminal too small", Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD))),
+        Line::from(format!("{}x{}, need {}x{}", area.width, area.height, MIN_WIDTH, MIN_HEIGHT)),
+    ];
+
+    // Centre vertically when there is room
+    let top = area.height.saturating_sub(lines.len() as u16) / 2;
+    let text_area = Rect {
+        y: area.y + top,
+        height: area.height - top,
+        ..area
+    };
+
+    let paragraph = Paragraph::new(lines).alignment(Alignment::Center).wrap(Wrap { trim: true });
+    f.render_widget(paragraph, text_area);
+}
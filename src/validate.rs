@@ -0,0 +1,155 @@
+use crate::checks::{self, CheckResult};
+use crate::config::ValidateConfig;
+use crate::file_operations;
+use crate::types::{FileEntry, FileStatus, LowerView};
+use std::fs;
+use std::io;
+use std::path::{Component, Path};
+
+/// Refuse validations whose root is not a subtree of a base. Copying a whole
+/// base, let alone `/` with /proc and /sys and the scratch directory itself,
+/// on every apply is never what was meant.
+pub fn check(validations: &[ValidateConfig], lower: &LowerView) -> io::Result<()> {
+    for validation in validations {
+        let relative = validation.root.components().all(|c| matches!(c, Component::Normal(_)));
+        let base_root = lower.base_path(&validation.root);
+        if !relative || validation.root.as_os_str().is_empty() || base_root == lower.root_of(&validation.root) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "Validation '{}' needs a root below the base, relative to the overlay root, e.g. \"etc/nginx\"",
+                    validation.name
+                ),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Try applying `entries` on scratch copies of the base first: for each
+/// configured validation whose root they touch, copy that subtree of the
+/// base, apply the entries under it to the copy, and run the command there.
+/// The base itself is left alone.
+pub fn run(validations: &[ValidateConfig], entries: &[FileEntry], lower: &LowerView) -> io::Result<Vec<CheckResult>> {
+    let mut results = Vec::new();
+    for validation in validations {
+        let touched: Vec<&FileEntry> = entries
+            .iter()
+            .filter(|e| e.status != FileStatus::Deleted && e.rel_path.starts_with(&validation.root))
+            .collect();
+        if touched.is_empty() {
+            continue;
+        }
+        if validation.command.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Validation '{}' has no command", validation.name),
+            ));
+        }
+
+        let scratch = tempfile::tempdir()?;
+        let base_root = lower.base_path(&validation.root);
+        if base_root.is_dir() {
+            copy_tree(&base_root, scratch.path())?;
+        }
+        for entry in touched {
+            let rel_path = entry.rel_path.strip_prefix(&validation.root).unwrap_or(&entry.rel_path);
+            copy_entry(&entry.path, &scratch.path().join(rel_path), entry.is_dir)?;
+        }
+
+        let path = scratch.path().to_string_lossy();
+        let args: Vec<String> = validation.command.iter().map(|arg| arg.replace("{scratch}", &path)).collect();
+        results.push(checks::run_command(&validation.name, &args, Some(scratch.path()), validation.timeout_secs));
+    }
+    Ok(results)
+}
+
+/// Copy a directory's contents with modes, timestamps and symlinks kept,
+/// sharing blocks with the original where the filesystem can. Sockets,
+/// FIFOs and devices are left out, as no validation reads them.
+fn copy_tree(source: &Path, dest: &Path) -> io::Result<()> {
+    let copy_failed = |path: &Path, e: io::Error| {
+        io::Error::new(e.kind(), format!("Failed to copy {} for validation: {}", path.display(), e))
+    };
+    for item in fs::read_dir(source).map_err(|e| copy_failed(source, e))? {
+        let path = item.map_err(|e| copy_failed(source, e))?.path();
+        let dest_path = dest.join(path.file_name().unwrap_or_default());
+        let metadata = fs::symlink_metadata(&path).map_err(|e| copy_failed(&path, e))?;
+        if metadata.is_dir() {
+            fs::create_dir(&dest_path).map_err(|e| copy_failed(&path, e))?;
+            copy_tree(&path, &dest_path)?;
+        } else if metadata.is_symlink() {
+            let target = fs::read_link(&path).map_err(|e| copy_failed(&path, e))?;
+            std::os::unix::fs::symlink(target, &dest_path).map_err(|e| copy_failed(&path, e))?;
+        } else if metadata.is_file() {
+            file_operations::copy_contents(&path, &dest_path).map_err(|e| copy_failed(&path, e))?;
+            keep_times(&dest_path, &metadata);
+        }
+    }
+    // Last, once nothing more is written into it
+    let metadata = fs::metadata(source).map_err(|e| copy_failed(source, e))?;
+    fs::set_permissions(dest, metadata.permissions()).map_err(|e| copy_failed(source, e))?;
+    keep_times(dest, &metadata);
+    Ok(())
+}
+
+/// Give a copy the original's access and modification times. Only tidier
+/// for the validation command, so a copy it cannot open is left as it is.
+fn keep_times(path: &Path, original: &fs::Metadata) {
+    let (Ok(accessed), Ok(modified)) = (original.accessed(), original.modified()) else {
+        return;
+    };
+    if let Ok(file) = fs::File::open(path) {
+        let _ = file.set_times(fs::FileTimes::new().set_accessed(accessed).set_modified(modified));
+    }
+}
+
+/// Put an overlay entry in place in the scratch copy, as apply would in the
+/// base: a symlink as the link itself, a file with its mode
+fn copy_entry(source: &Path, dest: &Path, is_dir: bool) -> io::Result<()> {
+    if is_dir {
+        return fs::create_dir_all(dest);
+    }
+    if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    if fs::symlink_metadata(dest).is_ok_and(|m| !m.is_dir()) {
+        fs::remove_file(dest)?;
+    }
+    if fs::symlink_metadata(source)?.is_symlink() {
+        std::os::unix::fs::symlink(fs::read_link(source)?, dest)
+    } else {
+        file_operations::copy_contents(source, dest)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+    use std::time::{Duration, SystemTime};
+
+    #[test]
+    fn copy_tree_keeps_modes_times_and_symlinks() {
+        let dir = tempfile::tempdir().unwrap();
+        let (source, dest) = (dir.path().join("source"), dir.path().join("dest"));
+        fs::create_dir_all(source.join("conf.d")).unwrap();
+        fs::write(source.join("conf.d/site.conf"), "listen 80;\n").unwrap();
+        fs::set_permissions(source.join("conf.d/site.conf"), fs::Permissions::from_mode(0o640)).unwrap();
+        std::os::unix::fs::symlink("conf.d/site.conf", source.join("default")).unwrap();
+        let old = SystemTime::UNIX_EPOCH + Duration::from_secs(1_600_000_000);
+        fs::File::open(source.join("conf.d/site.conf"))
+            .unwrap()
+            .set_times(fs::FileTimes::new().set_accessed(old).set_modified(old))
+            .unwrap();
+        fs::create_dir(&dest).unwrap();
+
+        copy_tree(&source, &dest).unwrap();
+
+        let copied = fs::metadata(dest.join("conf.d/site.conf")).unwrap();
+        assert_eq!(fs::read_to_string(dest.join("conf.d/site.conf")).unwrap(), "listen 80;\n");
+        assert_eq!(copied.permissions().mode() & 0o7777, 0o640);
+        assert_eq!(copied.modified().unwrap(), old);
+        assert_eq!(fs::read_link(dest.join("default")).unwrap(), Path::new("conf.d/site.conf"));
+    }
+}
@@ -0,0 +1,174 @@
+use notify::{Config, ErrorKind, Event, EventHandler, PollWatcher, RecommendedWatcher, RecursiveMode, Watcher};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, PoisonError, Weak};
+use std::thread;
+use std::time::Duration;
+
+/// Watches an overlay for changes, with inotify or, once its limits are
+/// reached, by polling
+pub struct OverlayWatcher {
+    /// The watcher in use, kept for as long as the overlay is watched
+    _current: Shared,
+}
+
+/// The watcher in use, which the native one swaps for polling
+type Shared = Arc<Mutex<Option<Box<dyn Watcher + Send>>>>;
+
+impl OverlayWatcher {
+    /// Watch everything below `overlay_path`, sending events to `handler`.
+    /// When inotify runs out of watches or instances, the overlay is polled
+    /// every `poll_interval` instead, and the returned warning says which
+    /// limit to raise. Running out later, while watching directories created
+    /// since, switches to polling the same way, with the warning sent to
+    /// `handler` as an error.
+    pub fn watch<F>(overlay_path: &Path, handler: F, poll_interval: Duration) -> io::Result<(Self, Option<String>)>
+    where
+        F: EventHandler + Clone,
+    {
+        let current: Shared = Arc::new(Mutex::new(None));
+        let fallback = Fallback {
+            current: Arc::downgrade(&current),
+            handler: handler.clone(),
+            overlay_path: overlay_path.to_path_buf(),
+            poll_interval,
+            switched: Arc::new(AtomicBool::new(false)),
+        };
+        let mut native_handler = handler.clone();
+        let native = RecommendedWatcher::new(
+            move |event: notify::Result<Event>| match &event {
+                Err(e) if let Some(limit) = exceeded_limit(e) => fallback.switch(limit),
+                _ => native_handler.handle_event(event),
+            },
+            Config::default(),
+        )
+        .and_then(|mut watcher| {
+            watcher.watch(overlay_path, RecursiveMode::Recursive)?;
+            Ok(watcher)
+        });
+        let limit = match native {
+            Ok(watcher) => {
+                *current.lock().unwrap_or_else(PoisonError::into_inner) = Some(Box::new(watcher));
+                return Ok((OverlayWatcher { _current: current }, None));
+            }
+            Err(e) => match exceeded_limit(&e) {
+                Some(limit) => limit,
+                None => return Err(io::Error::other(e)),
+            },
+        };
+
+        let watcher = poll(handler, overlay_path, poll_interval).map_err(io::Error::other)?;
+        *current.lock().unwrap_or_else(PoisonError::into_inner) = Some(Box::new(watcher));
+        Ok((OverlayWatcher { _current: current }, Some(limit.warning(overlay_path, poll_interval))))
+    }
+}
+
+/// What the native watcher needs to hand over to polling once it runs into
+/// a limit
+struct Fallback<F> {
+    current: Weak<Mutex<Option<Box<dyn Watcher + Send>>>>,
+    handler: F,
+    overlay_path: PathBuf,
+    poll_interval: Duration,
+    switched: Arc<AtomicBool>,
+}
+
+impl<F: EventHandler + Clone> Fallback<F> {
+    /// Replace the native watcher with polling, once. This runs on the
+    /// native watcher's own thread, which dropping it stops, so the switch
+    /// is made from another.
+    fn switch(&self, limit: Limit) {
+        if self.switched.swap(true, Ordering::Relaxed) {
+            return;
+        }
+        let (current, mut handler) = (self.current.clone(), self.handler.clone());
+        let (overlay_path, poll_interval) = (self.overlay_path.clone(), self.poll_interval);
+        thread::spawn(move || {
+            let Some(current) = current.upgrade() else {
+                return;
+            };
+            match poll(handler.clone(), &overlay_path, poll_interval) {
+                Ok(watcher) => {
+                    *current.lock().unwrap_or_else(PoisonError::into_inner) = Some(Box::new(watcher));
+                    handler.handle_event(Err(notify::Error::generic(&limit.warning(&overlay_path, poll_interval))));
+                }
+                Err(e) => handler.handle_event(Err(e)),
+            }
+        });
+    }
+}
+
+/// Watch an overlay by polling it every `poll_interval`
+fn poll<F: EventHandler>(handler: F, overlay_path: &Path, poll_interval: Duration) -> notify::Result<PollWatcher> {
+    let mut watcher = PollWatcher::new(handler, Config::default().with_poll_interval(poll_interval))?;
+    watcher.watch(overlay_path, RecursiveMode::Recursive)?;
+    Ok(watcher)
+}
+
+/// An inotify limit the native watcher ran into
+struct Limit {
+    what: &'static str,
+    sysctl: &'static str,
+}
+
+impl Limit {
+    /// Why the overlay is polled, and how to raise the limit
+    fn warning(&self, overlay_path: &Path, poll_interval: Duration) -> String {
+        format!(
+            "Watching the overlay needs more inotify {} than fs.inotify.{} allows, so it is polled every {}s instead. \
+             Raise the limit with: sudo sysctl fs.inotify.{}={}",
+            self.what,
+            self.sysctl,
+            poll_interval.as_secs(),
+            self.sysctl,
+            self.needed(overlay_path),
+        )
+    }
+
+    /// A value of the limit that leaves room for the overlay: a watch for
+    /// each of its directories on top of the current limit, or twice as many
+    /// instances
+    fn needed(&self, overlay_path: &Path) -> u64 {
+        let current = fs::read_to_string(Path::new("/proc/sys/fs/inotify").join(self.sysctl))
+            .ok()
+            .and_then(|value| value.trim().parse().ok())
+            .unwrap_or(8192);
+        if self.sysctl == "max_user_watches" {
+            current + count_dirs(overlay_path)
+        } else {
+            current * 2
+        }
+    }
+}
+
+fn exceeded_limit(error: &notify::Error) -> Option<Limit> {
+    match &error.kind {
+        ErrorKind::MaxFilesWatch => Some(Limit {
+            what: "watches",
+            sysctl: "max_user_watches",
+        }),
+        ErrorKind::Io(e) if e.raw_os_error() == Some(libc::ENOSPC) => Some(Limit {
+            what: "watches",
+            sysctl: "max_user_watches",
+        }),
+        ErrorKind::Io(e) if e.raw_os_error() == Some(libc::EMFILE) => Some(Limit {
+            what: "instances",
+            sysctl: "max_user_instances",
+        }),
+        _ => None,
+    }
+}
+
+/// Directories in a tree, each of which takes an inotify watch
+fn count_dirs(dir: &Path) -> u64 {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return 0;
+    };
+    1 + entries
+        .flatten()
+        .filter(|entry| entry.file_type().is_ok_and(|t| t.is_dir()))
+        .map(|entry| count_dirs(&entry.path()))
+        .sum::<u64>()
+}
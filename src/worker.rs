@@ -0,0 +1,42 @@
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread;
+
+/// Runs jobs on a background thread, one at a time, so slow work on a file
+/// does not hold up the review. Results are picked up without waiting.
+pub struct Worker<J, R> {
+    jobs: Sender<J>,
+    results: Receiver<R>,
+}
+
+impl<J: Send + 'static, R: Send + 'static> Worker<J, R> {
+    /// Start the thread. With `latest_only`, jobs that queued up while one
+    /// ran are dropped for the last of them, for work where only the most
+    /// recent request matters, e.g. the file under the cursor.
+    pub fn start(latest_only: bool, work: impl Fn(J) -> R + Send + 'static) -> Self {
+        let (jobs, job_rx) = channel::<J>();
+        let (result_tx, results) = channel();
+        thread::spawn(move || {
+            while let Ok(mut job) = job_rx.recv() {
+                if latest_only {
+                    while let Ok(next) = job_rx.try_recv() {
+                        job = next;
+                    }
+                }
+                if result_tx.send(work(job)).is_err() {
+                    break;
+                }
+            }
+        });
+        Worker { jobs, results }
+    }
+
+    pub fn request(&self, job: J) {
+        // The thread only stops once the review does
+        let _ = self.jobs.send(job);
+    }
+
+    /// Results finished since the last call, without waiting
+    pub fn finished(&self) -> Vec<R> {
+        self.results.try_iter().collect()
+    }
+}
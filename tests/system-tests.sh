@@ -104,7 +104,7 @@ setup_test_env
 cleanup
 
 # Count total tests
-TOTAL_TESTS=20
+TOTAL_TESTS=89
 echo "1..$TOTAL_TESTS"
 
 echo "# Testing help commands"
@@ -112,6 +112,8 @@ run_test "audit-box --help shows help" "$AUDIT_BOX --help"
 run_test "audit-box new --help shows help" "$AUDIT_BOX new --help"
 run_test "audit-box run --help shows help" "$AUDIT_BOX run --help"
 run_test "audit-box review --help shows help" "$AUDIT_BOX review --help"
+run_test "audit-box list --help shows help" "$AUDIT_BOX list --help"
+run_test "audit-box init --help shows help" "$AUDIT_BOX init --help"
 
 echo "# Testing new command"
 run_test_should_fail "review fails when no session exists" "$AUDIT_BOX review"
@@ -127,6 +129,18 @@ run_test "overlay directory exists" "test -d '$SESSION_DIR/overlay'"
 run_test "work directory exists" "test -d '$SESSION_DIR/work'"
 run_test "session file contains base path" "test '$BASE_PATH' = '$HOME'"
 
+WORKDIR=$(mktemp -d /tmp/audit-box-workdir-XXXXXX)
+run_test "new --workdir creates session in workdir" "$AUDIT_BOX new --base $HOME --workdir '$WORKDIR'"
+run_test "session directory is inside workdir" "head -1 $TEST_SESSION_FILE | grep -q '^$WORKDIR/audit-box-'"
+
+TEMPLATE_DIR=$(mktemp -d /tmp/audit-box-template-XXXXXX)
+mkdir -p "$TEMPLATE_DIR/conf"
+echo "seeded" > "$TEMPLATE_DIR/conf/app.conf"
+run_test "new --template seeds the overlay" "$AUDIT_BOX new --base $HOME --template '$TEMPLATE_DIR' && grep -q seeded \"\$(head -1 $TEST_SESSION_FILE)/overlay/conf/app.conf\""
+run_test_output_contains "list marks unchanged template files" "$AUDIT_BOX list" "T  conf/app.conf"
+run_test_should_fail "new fails for a missing template" "$AUDIT_BOX new --base $HOME --template /tmp/nonexistent-template"
+rm -rf "$TEMPLATE_DIR"
+
 echo "# Testing session file validation"
 run_test_output_contains "new command output mentions session directory" "$AUDIT_BOX new --base $HOME" "Session directory:"
 run_test_output_contains "new command output mentions overlay directory" "$AUDIT_BOX new --base $HOME" "Overlay directory:"
@@ -134,7 +148,17 @@ run_test_output_contains "new command output mentions overlay directory" "$AUDIT
 echo "# Testing review command with session"
 run_test_should_fail "review fails with only --overlay" "$AUDIT_BOX review --overlay /tmp/test"
 run_test_should_fail "review fails with only --base" "$AUDIT_BOX review --base /tmp/test"
+run_test_output_contains "review refuses an overlay inside the base" "$AUDIT_BOX review --overlay '$SESSION_DIR/overlay' --base '$SESSION_DIR' < /dev/null" "is inside the base"
 run_test "review accepts both --overlay and --base" "$AUDIT_BOX review --overlay '$SESSION_DIR/overlay' --base '$BASE_PATH' < /dev/null & sleep 0.5; pkill -f 'audit-box review' || true"
+LOCK_FILE="$(head -1 $TEST_SESSION_FILE)/review.lock"
+echo $$ > "$LOCK_FILE"
+flock -o "$LOCK_FILE" sleep 30 &
+LOCK_HOLDER=$!
+sleep 0.2
+run_test_output_contains "review refuses a session another review has locked" "$AUDIT_BOX review < /dev/null" "already being reviewed (pid $$)"
+kill $LOCK_HOLDER 2>/dev/null || true
+wait $LOCK_HOLDER 2>/dev/null || true
+run_test "review takes a lock left by a review that is not running" "echo 999999999 > '$LOCK_FILE'; $AUDIT_BOX review < /dev/null && grep -qv 999999999 '$LOCK_FILE'"
 
 echo "# Testing error handling"
 echo "/tmp/nonexistent-session-dir" > "$TEST_SESSION_FILE"
@@ -144,6 +168,207 @@ run_test_output_contains "review shows error for missing session directory" "$AU
 rm -f "$TEST_SESSION_FILE"
 run_test_output_contains "review shows error when no session file exists" "$AUDIT_BOX review" "No active session found"
 
+echo "# Testing list command"
+LIST_DIR=$(mktemp -d /tmp/audit-box-list-XXXXXX)
+mkdir -p "$LIST_DIR/base/dir" "$LIST_DIR/overlay/dir/sub"
+echo "original" > "$LIST_DIR/base/dir/changed.txt"
+echo "modified" > "$LIST_DIR/overlay/dir/changed.txt"
+echo "new" > "$LIST_DIR/overlay/dir/sub/added.txt"
+LIST_ARGS="--overlay '$LIST_DIR/overlay' --base '$LIST_DIR/base'"
+
+run_test_output_contains "list shows new files" "$AUDIT_BOX list $LIST_ARGS" "N  dir/sub/added.txt"
+run_test_output_contains "list shows modified files" "$AUDIT_BOX list $LIST_ARGS" "M  dir/changed.txt"
+run_test_output_contains "list --format json reports status" "$AUDIT_BOX list $LIST_ARGS --format json" '"status": "new"'
+run_test_output_contains "list --format tree indents nested entries" "$AUDIT_BOX list $LIST_ARGS --format tree" "^    \[N\] added.txt"
+run_test_output_contains "list --stats shows line counts" "$AUDIT_BOX list $LIST_ARGS --stats" "dir/changed.txt  (+1 -1"
+mkdir -p "$LIST_DIR/lower/dir/sub"
+echo "layered" > "$LIST_DIR/lower/dir/sub/added.txt"
+run_test_output_contains "list --lower resolves status through stacked layers" "$AUDIT_BOX list $LIST_ARGS --lower '$LIST_DIR/lower'" "M  dir/sub/added.txt"
+run_test_should_fail "list --lower rejects a missing layer" "$AUDIT_BOX list $LIST_ARGS --lower '$LIST_DIR/missing'"
+run_test_output_contains "new --lower records the layer in the session file" "$AUDIT_BOX new --base '$LIST_DIR/base' --lower '$LIST_DIR/lower' >/dev/null && sed -n 3p $TEST_SESSION_FILE" "$LIST_DIR/lower"
+run_test_output_contains "list --format json reports overlay size" "$AUDIT_BOX list $LIST_ARGS --format json" '"overlay_size": [1-9]'
+ln -s "$LIST_DIR/base" "$LIST_DIR/base-link"
+ln -s "$LIST_DIR/overlay" "$LIST_DIR/overlay-link"
+run_test_output_contains "list resolves a base given through a symlink" "$AUDIT_BOX list --overlay '$LIST_DIR/overlay' --base '$LIST_DIR/base-link'" "M  dir/changed.txt"
+run_test_output_contains "export resolves an overlay given through a symlink" "$AUDIT_BOX export --overlay '$LIST_DIR/overlay-link' --base '$LIST_DIR/base'" "^+++ b/dir/changed.txt"
+run_test_output_contains "new records a relative base as an absolute path" "(cd '$LIST_DIR' && $(pwd)/target/debug/audit-box new --base base-link >/dev/null) && sed -n 2p $TEST_SESSION_FILE" "^$LIST_DIR/base\$"
+ln -s "$LIST_DIR/lower" "$LIST_DIR/overlay/escape"
+run_test "list does not follow a symlink out of the overlay" "$AUDIT_BOX list $LIST_ARGS | grep -q '^N  escape\$' && ! $AUDIT_BOX list $LIST_ARGS | grep -q 'escape/'"
+rm "$LIST_DIR/overlay/escape"
+mkdir -p "$LIST_DIR/etc"
+echo "setting=1" > "$LIST_DIR/etc/app.conf"
+run_test "new with several --base creates an overlay directory per base" "$AUDIT_BOX new --base '$LIST_DIR/etc' --base '$LIST_DIR/base' >/dev/null && test -d \"\$(head -1 $TEST_SESSION_FILE)/overlay/etc\" && test -d \"\$(head -1 $TEST_SESSION_FILE)/work/base\""
+run_test_output_contains "list resolves each base's entries against that base" "echo 'setting=2' > \"\$(head -1 $TEST_SESSION_FILE)/overlay/etc/app.conf\" && $AUDIT_BOX list" "M  etc/app.conf"
+
+echo "# Testing export command"
+run_test_output_contains "export prints a patch for modified files" "$AUDIT_BOX export $LIST_ARGS" "^+++ b/dir/changed.txt"
+run_test_output_contains "export marks new files" "$AUDIT_BOX export $LIST_ARGS" "^new file mode 100644"
+run_test_output_contains "export skips \$PAGER when stdout is not a terminal" "PAGER=false $AUDIT_BOX export $LIST_ARGS" "^+++ b/dir/changed.txt"
+run_test_output_contains "export --group-by dir writes numbered patch files" "$AUDIT_BOX export $LIST_ARGS --group-by dir -o '$LIST_DIR/patches' && ls '$LIST_DIR/patches'" "0001-dir.patch"
+run_test_should_fail "export --label needs the saved session" "$AUDIT_BOX export $LIST_ARGS --label config"
+IDENTITY_HOME=$(mktemp -d /tmp/audit-box-identity-XXXXXX)
+mkdir -p "$IDENTITY_HOME/.config/audit-box"
+printf '[identity]\nname = "Alice Example"\nemail = "alice@example.com"\n' > "$IDENTITY_HOME/.config/audit-box/config.toml"
+run_test_output_contains "export authors patches as the configured identity" "HOME='$IDENTITY_HOME' $AUDIT_BOX export $LIST_ARGS" "^From: Alice Example <alice@example.com>\$"
+mkdir -p "$LIST_DIR/wh-base" "$LIST_DIR/wh-overlay"
+echo "doomed" > "$LIST_DIR/wh-base/gone.txt"
+WH_ARGS="--overlay '$LIST_DIR/wh-overlay' --base '$LIST_DIR/wh-base'"
+# overlayfs whiteouts are 0/0 character devices, which need CAP_MKNOD to create
+if mknod "$LIST_DIR/wh-overlay/gone.txt" c 0 0 2>/dev/null; then
+    run_test_output_contains "list marks a whiteout as deleted" "$AUDIT_BOX list $WH_ARGS" "D  gone.txt"
+    run_test_output_contains "export renders a whiteout as a deletion" "$AUDIT_BOX export $WH_ARGS" "^deleted file mode 100644"
+else
+    echo "ok $((TEST_NUM + 1)) - list marks a whiteout as deleted # SKIP mknod not permitted"
+    echo "ok $((TEST_NUM + 2)) - export renders a whiteout as a deletion # SKIP mknod not permitted"
+    TEST_NUM=$((TEST_NUM + 2))
+fi
+mkdir -p "$LIST_DIR/tc-base" "$LIST_DIR/tc-overlay"
+echo "real" > "$LIST_DIR/tc-base/target.txt"
+ln -s target.txt "$LIST_DIR/tc-base/link.txt"
+echo "copied" > "$LIST_DIR/tc-overlay/link.txt"
+TC_ARGS="--overlay '$LIST_DIR/tc-overlay' --base '$LIST_DIR/tc-base'"
+run_test_output_contains "list marks a file replacing a base symlink as type changed" "$AUDIT_BOX list $TC_ARGS" "K  link.txt"
+run_test_output_contains "export renders a type change as deletion then creation" "$AUDIT_BOX export $TC_ARGS" "^deleted file mode 120000"
+
+echo "# Testing discard command"
+DISCARD_DIR=$(mktemp -d /tmp/audit-box-discard-XXXXXX)
+mkdir -p "$DISCARD_DIR/base/src" "$DISCARD_DIR/overlay/src" "$DISCARD_DIR/overlay/target/debug" "$DISCARD_DIR/overlay/logs"
+echo "logs/" > "$DISCARD_DIR/base/.gitignore"
+echo "original" > "$DISCARD_DIR/base/src/main.rs"
+echo "changed" > "$DISCARD_DIR/overlay/src/main.rs"
+echo "build" > "$DISCARD_DIR/overlay/target/debug/app"
+echo "log" > "$DISCARD_DIR/overlay/logs/run.log"
+DISCARD_ARGS="--overlay '$DISCARD_DIR/overlay' --base '$DISCARD_DIR/base'"
+run_test_output_contains "discard asks before discarding without --yes" "echo no | $AUDIT_BOX discard $DISCARD_ARGS 'target/**' && test -f '$DISCARD_DIR/overlay/target/debug/app'" "Discard cancelled"
+run_test "discard --yes removes the paths matching a glob" "$AUDIT_BOX discard $DISCARD_ARGS 'target/**' --yes >/dev/null && ! test -e '$DISCARD_DIR/overlay/target' && $AUDIT_BOX list $DISCARD_ARGS | grep -q '^M  src/main.rs\$'"
+run_test "discard --gitignore removes what the base's .gitignore ignores" "$AUDIT_BOX discard $DISCARD_ARGS --gitignore --yes >/dev/null && ! test -e '$DISCARD_DIR/overlay/logs' && test -f '$DISCARD_DIR/overlay/src/main.rs'"
+
+echo "# Testing apply --plan"
+PLAN_DIR=$(mktemp -d /tmp/audit-box-plan-XXXXXX)
+mkdir -p "$PLAN_DIR/base" "$PLAN_DIR/overlay"
+echo "original" > "$PLAN_DIR/base/config"
+echo "reviewed" > "$PLAN_DIR/overlay/config"
+echo "junk" > "$PLAN_DIR/overlay/junk"
+echo "later" > "$PLAN_DIR/overlay/later"
+PLAN_ARGS="--overlay '$PLAN_DIR/overlay' --base '$PLAN_DIR/base'"
+REVIEWED_SHA=$(sha256sum "$PLAN_DIR/overlay/config" | cut -d' ' -f1)
+cat > "$PLAN_DIR/plan.json" <<EOF
+{"decisions": [
+  {"path": "config", "verdict": "apply", "sha256": "$REVIEWED_SHA"},
+  {"path": "junk", "verdict": "discard"},
+  {"path": "later", "verdict": "skip"}
+]}
+EOF
+echo '{"decisions": [{"path": "config", "verdict": "apply", "sha256": "0000"}]}' > "$PLAN_DIR/stale.json"
+run_test_output_contains "apply --plan --dry-run lists what it would do" "$AUDIT_BOX apply $PLAN_ARGS --plan '$PLAN_DIR/plan.json' --dry-run && test -f '$PLAN_DIR/overlay/junk'" "Would apply 1, discard 1 and skip 1"
+run_test_should_fail "apply --plan refuses a file changed since the plan was written" "$AUDIT_BOX apply $PLAN_ARGS --plan '$PLAN_DIR/stale.json'"
+run_test "apply --plan applies, discards and skips as the plan says" "$AUDIT_BOX apply $PLAN_ARGS --plan '$PLAN_DIR/plan.json' >/dev/null && grep -q reviewed '$PLAN_DIR/base/config' && ! test -e '$PLAN_DIR/overlay/junk' && ! test -e '$PLAN_DIR/base/junk' && test -f '$PLAN_DIR/overlay/later'"
+echo "yaml" > "$PLAN_DIR/overlay/yaml.txt"
+printf 'decisions:\n  - path: yaml.txt\n    verdict: apply\n    sha256: %s\n' "$(sha256sum "$PLAN_DIR/overlay/yaml.txt" | cut -d' ' -f1)" > "$PLAN_DIR/plan.yaml"
+run_test "apply --plan reads a YAML plan" "$AUDIT_BOX apply $PLAN_ARGS --plan '$PLAN_DIR/plan.yaml' >/dev/null && grep -q yaml '$PLAN_DIR/base/yaml.txt'"
+run_test_output_contains "apply --plan shows why a plan cannot be read" "$AUDIT_BOX apply $PLAN_ARGS --plan '$PLAN_DIR/missing.json'" "^Error: \"Failed to read plan"
+truncate -s 64M "$PLAN_DIR/overlay/disk.img"
+echo "superblock" | dd of="$PLAN_DIR/overlay/disk.img" conv=notrunc status=none
+echo "{\"decisions\": [{\"path\": \"disk.img\", \"verdict\": \"apply\", \"sha256\": \"$(sha256sum "$PLAN_DIR/overlay/disk.img" | cut -d' ' -f1)\"}]}" > "$PLAN_DIR/sparse.json"
+run_test "apply keeps the holes of a sparse file" "$AUDIT_BOX apply $PLAN_ARGS --plan '$PLAN_DIR/sparse.json' >/dev/null && test \$(stat -c %s '$PLAN_DIR/base/disk.img') -eq 67108864 && test \$(du -k '$PLAN_DIR/base/disk.img' | cut -f1) -lt 1024"
+
+WORKERS_DIR=$(mktemp -d /tmp/audit-box-workers-XXXXXX)
+mkdir -p "$WORKERS_DIR/base" "$WORKERS_DIR/overlay/many" "$WORKERS_DIR/home/.config/audit-box"
+for i in $(seq 1 200); do echo "file $i" > "$WORKERS_DIR/overlay/many/$i.txt"; done
+printf '[apply]\nworkers = 8\n' > "$WORKERS_DIR/home/.config/audit-box/config.toml"
+(cd "$WORKERS_DIR/overlay" && sha256sum many/*.txt) | awk 'BEGIN { printf "{\"decisions\": [" } { printf "%s{\"path\": \"%s\", \"verdict\": \"apply\", \"sha256\": \"%s\"}", (NR > 1 ? ", " : ""), $2, $1 } END { print "]}" }' > "$WORKERS_DIR/plan.json"
+run_test "apply copies files with several workers" "HOME='$WORKERS_DIR/home' $AUDIT_BOX apply --overlay '$WORKERS_DIR/overlay' --base '$WORKERS_DIR/base' --plan '$WORKERS_DIR/plan.json' >/dev/null && test \$(ls '$WORKERS_DIR/base/many' | wc -l) -eq 200 && grep -q 'file 137' '$WORKERS_DIR/base/many/137.txt' && ! test -e '$WORKERS_DIR/overlay/many/1.txt'"
+
+echo "# Testing apply.allowed_roots"
+ROOTS_DIR=$(mktemp -d /tmp/audit-box-roots-XXXXXX)
+mkdir -p "$ROOTS_DIR/base/project" "$ROOTS_DIR/base/.ssh" "$ROOTS_DIR/overlay/project" "$ROOTS_DIR/overlay/.ssh" "$ROOTS_DIR/home/.config/audit-box"
+echo "code" > "$ROOTS_DIR/overlay/project/main.c"
+echo "ssh-ed25519 AAAA intruder" > "$ROOTS_DIR/overlay/.ssh/authorized_keys"
+printf '[apply]\nallowed_roots = ["%s/base/project"]\n' "$ROOTS_DIR" > "$ROOTS_DIR/home/.config/audit-box/config.toml"
+MAIN_SHA=$(sha256sum "$ROOTS_DIR/overlay/project/main.c" | cut -d' ' -f1)
+KEYS_SHA=$(sha256sum "$ROOTS_DIR/overlay/.ssh/authorized_keys" | cut -d' ' -f1)
+echo "{\"decisions\": [{\"path\": \"project/main.c\", \"verdict\": \"apply\", \"sha256\": \"$MAIN_SHA\"}, {\"path\": \".ssh/authorized_keys\", \"verdict\": \"apply\", \"sha256\": \"$KEYS_SHA\"}]}" > "$ROOTS_DIR/plan.json"
+ROOTS_APPLY="HOME='$ROOTS_DIR/home' $AUDIT_BOX apply --overlay '$ROOTS_DIR/overlay' --base '$ROOTS_DIR/base' --plan '$ROOTS_DIR/plan.json'"
+run_test "apply refuses paths outside apply.allowed_roots" "$ROOTS_APPLY 2>&1 | grep -q 'outside apply.allowed_roots' && test ! -e '$ROOTS_DIR/base/project/main.c'"
+run_test "apply --allow-outside-roots applies them all the same" "$ROOTS_APPLY --allow-outside-roots >/dev/null && test -f '$ROOTS_DIR/base/.ssh/authorized_keys'"
+
+echo "# Testing apply validation on a scratch copy"
+VALIDATE_DIR=$(mktemp -d /tmp/audit-box-validate-XXXXXX)
+mkdir -p "$VALIDATE_DIR/base/conf" "$VALIDATE_DIR/overlay/conf" "$VALIDATE_DIR/home/.config/audit-box"
+echo "include extra.conf" > "$VALIDATE_DIR/base/conf/main.conf"
+echo "broken" > "$VALIDATE_DIR/overlay/conf/extra.conf"
+cat > "$VALIDATE_DIR/home/.config/audit-box/config.toml" <<'EOF'
+[[apply.validate]]
+name = "conf"
+root = "conf"
+command = ["sh", "-c", "test -f main.conf && grep -q valid extra.conf"]
+EOF
+validate_plan() {
+    echo "{\"decisions\": [{\"path\": \"conf/extra.conf\", \"verdict\": \"apply\", \"sha256\": \"$(sha256sum "$VALIDATE_DIR/overlay/conf/extra.conf" | cut -d' ' -f1)\"}]}" > "$VALIDATE_DIR/plan.json"
+}
+validate_plan
+VALIDATE_APPLY="HOME='$VALIDATE_DIR/home' $AUDIT_BOX apply --overlay '$VALIDATE_DIR/overlay' --base '$VALIDATE_DIR/base' --plan '$VALIDATE_DIR/plan.json'"
+run_test "apply leaves the base alone when validation fails" "$VALIDATE_APPLY 2>&1 | grep -q \"Validation 'conf' failed\" && test ! -e '$VALIDATE_DIR/base/conf/extra.conf'"
+echo valid > "$VALIDATE_DIR/overlay/conf/extra.conf"
+validate_plan
+run_test "apply applies once validation passes" "$VALIDATE_APPLY >/dev/null && grep -q valid '$VALIDATE_DIR/base/conf/extra.conf'"
+
+echo "# Testing review without a terminal"
+LINE_DIR=$(mktemp -d /tmp/audit-box-line-XXXXXX)
+mkdir -p "$LINE_DIR/base" "$LINE_DIR/overlay"
+echo "original" > "$LINE_DIR/base/config"
+echo "reviewed" > "$LINE_DIR/overlay/config"
+echo "junk" > "$LINE_DIR/overlay/junk"
+LINE_ARGS="--overlay '$LINE_DIR/overlay' --base '$LINE_DIR/base'"
+run_test_output_contains "review lists changes line by line when output is not a terminal" "printf 's\\ns\\n' | $AUDIT_BOX review $LINE_ARGS" "M  config  (+1 -1)"
+run_test "review quits line by line leaving the overlay as it was" "printf 'q\\n' | $AUDIT_BOX review $LINE_ARGS >/dev/null && grep -q original '$LINE_DIR/base/config' && test -f '$LINE_DIR/overlay/junk'"
+run_test "review applies and discards line by line" "printf 'a\\nd\\n' | $AUDIT_BOX review $LINE_ARGS >/dev/null && grep -q reviewed '$LINE_DIR/base/config' && ! test -e '$LINE_DIR/overlay/junk' && ! test -e '$LINE_DIR/base/junk'"
+mkdir -p "$LINE_DIR/pretty-base" "$LINE_DIR/pretty-overlay" "$LINE_DIR/home/.config/audit-box"
+echo '{"name":"app","deps":{"left-pad":"1.0.0","lodash":"4.17.20"}}' > "$LINE_DIR/pretty-base/package.json"
+echo '{"name":"app","deps":{"left-pad":"1.0.0","lodash":"4.17.21"}}' > "$LINE_DIR/pretty-overlay/package.json"
+printf '[diff]\npretty_print = true\n' > "$LINE_DIR/home/.config/audit-box/config.toml"
+run_test_output_contains "diff.pretty_print diffs minified JSON a member to a line" "printf 'v\\nq\\n' | HOME='$LINE_DIR/home' $AUDIT_BOX review --overlay '$LINE_DIR/pretty-overlay' --base '$LINE_DIR/pretty-base'" '^+    "lodash": "4.17.21"$'
+
+mkdir -p "$LINE_DIR/norm-base" "$LINE_DIR/norm-overlay" "$LINE_DIR/norm-home/.config/audit-box"
+printf 'b\na\n' > "$LINE_DIR/norm-base/list.txt"
+printf 'a\nb\nc\n' > "$LINE_DIR/norm-overlay/list.txt"
+printf '[[normalizers]]\nextensions = ["txt"]\ncommand = ["sort"]\n' > "$LINE_DIR/norm-home/.config/audit-box/config.toml"
+run_test_output_contains "normalizers diff both sides through the configured command" "printf 'v\\nq\\n' | HOME='$LINE_DIR/norm-home' $AUDIT_BOX review --overlay '$LINE_DIR/norm-overlay' --base '$LINE_DIR/norm-base'" '^# Normalized: both sides run through `sort`'
+
+mkdir -p "$LINE_DIR/svc-base/nginx" "$LINE_DIR/svc-overlay/nginx" "$LINE_DIR/svc-home/.config/audit-box"
+echo "worker_processes 1;" > "$LINE_DIR/svc-base/nginx/nginx.conf"
+echo "worker_processes 4;" > "$LINE_DIR/svc-overlay/nginx/nginx.conf"
+printf '[[apply.services]]\nname = "nginx"\nfiles = ["nginx/**"]\ncommand = ["touch", "%s"]\n' "$LINE_DIR/reloaded" > "$LINE_DIR/svc-home/.config/audit-box/config.toml"
+SVC_REVIEW="HOME='$LINE_DIR/svc-home' $AUDIT_BOX review --overlay '$LINE_DIR/svc-overlay' --base '$LINE_DIR/svc-base'"
+run_test_output_contains "applying a service's file offers to reload it, not running it when declined" "printf 'a\\nn\\n' | $SVC_REVIEW && ! test -e '$LINE_DIR/reloaded'" "Run them now? \\[y/n\\] Not run\$"
+mkdir -p "$LINE_DIR/svc-overlay/nginx"
+echo "worker_processes 8;" > "$LINE_DIR/svc-overlay/nginx/nginx.conf"
+run_test "applying a service's file runs its reload when accepted" "printf 'a\\ny\\n' | $SVC_REVIEW >/dev/null && test -e '$LINE_DIR/reloaded'"
+
+echo "# Testing session export and import"
+mkdir -p "$LIST_DIR/imported"
+run_test_output_contains "session export archives the current session's overlay" "$AUDIT_BOX session export '$LIST_DIR/session.tar.gz' >/dev/null && tar tzf '$LIST_DIR/session.tar.gz'" "^overlay/etc/app.conf\$"
+run_test_output_contains "session import makes the archived session current" "$AUDIT_BOX session import '$LIST_DIR/session.tar.gz' --workdir '$LIST_DIR/imported' >/dev/null && head -1 $TEST_SESSION_FILE | grep -q '^$LIST_DIR/imported/' && $AUDIT_BOX list" "M  etc/app.conf"
+run_test_should_fail "session import rejects a file that is not a session archive" "$AUDIT_BOX session import '$LIST_DIR/base/dir/changed.txt' --workdir '$LIST_DIR/imported'"
+
+echo "# Testing changesets"
+printf '{"changesets":{"app config":{"description":"Point the app at the new server","files":["etc/app.conf"]}}}' > "$(head -1 $TEST_SESSION_FILE)/review-state.json"
+run_test_output_contains "list --changeset lists the changeset's files under its description" "$AUDIT_BOX list --changeset 'app config'" "^# changeset: app config: Point the app at the new server\$"
+run_test_output_contains "export --changeset describes the patch by the changeset" "$AUDIT_BOX export --changeset 'app config'" "^Point the app at the new server\$"
+echo "welcome" > "$(head -1 $TEST_SESSION_FILE)/overlay/etc/motd"
+printf '{"changesets":{"app config":{"files":["etc/app.conf"],"order":1},"motd":{"files":["etc/motd"],"order":0}}}' > "$(head -1 $TEST_SESSION_FILE)/review-state.json"
+run_test_output_contains "export --group-by changeset writes patches in queue order" "$AUDIT_BOX export --group-by changeset -o '$LIST_DIR/changesets' && ls '$LIST_DIR/changesets'" "^0001-motd.patch\$"
+
+echo "# Testing dev fixture command"
+FIXTURE_DIR=$(mktemp -d /tmp/audit-box-fixture-XXXXXX)
+run_test_output_contains "dev fixture generates new and modified files" "$AUDIT_BOX dev fixture '$FIXTURE_DIR/fx' --files 40 --depth 2 --modified 50 --seed 7" "Generated 40 files"
+run_test "dev fixture overlay lists as many files as generated" "test \"\$($AUDIT_BOX list --overlay '$FIXTURE_DIR/fx/overlay' --base '$FIXTURE_DIR/fx/base' | grep -c '^[NM]  .*file')\" -eq 40"
+
+echo "# Testing init command"
+INIT_HOME=$(mktemp -d /tmp/audit-box-init-home-XXXXXX)
+run_test_output_contains "init reports bubblewrap check" "HOME='$INIT_HOME' $AUDIT_BOX init < /dev/null" "bubblewrap"
+run_test_output_contains "init writes default config without a terminal" "cat '$INIT_HOME/.config/audit-box/config.toml'" 'keymap = "default"'
+
 echo "# Testing run command"
 if command -v bwrap >/dev/null 2>&1; then
     # Create a fresh session for run tests
@@ -151,10 +376,16 @@ if command -v bwrap >/dev/null 2>&1; then
 
     run_test "run command executes echo" "$AUDIT_BOX run echo 'test' | grep -q 'test'"
     run_test "run command can use flags" "$AUDIT_BOX run ls -la / >/dev/null 2>&1"
+    run_test_output_contains "run records the command in the session" "cat \"\$(head -1 $TEST_SESSION_FILE)/session.json\"" '"exit_code": 0'
+    run_test_output_contains "run records overlay activity in the timeline" "$AUDIT_BOX run sh -c 'echo probe > \"\$HOME/timeline-probe\"' && cat \"\$(head -1 $TEST_SESSION_FILE)/timeline.jsonl\"" '"path":"timeline-probe"'
+    run_test_output_contains "run --attribute runs the command with or without CAP_SYS_ADMIN" "$AUDIT_BOX run --attribute echo attributed 2>&1" "attributed"
 else
     echo "ok $((TEST_NUM + 1)) - run command executes echo # SKIP bwrap not available"
     echo "ok $((TEST_NUM + 2)) - run command can use flags # SKIP bwrap not available"
-    TEST_NUM=$((TEST_NUM + 2))
+    echo "ok $((TEST_NUM + 3)) - run records the command in the session # SKIP bwrap not available"
+    echo "ok $((TEST_NUM + 4)) - run records overlay activity in the timeline # SKIP bwrap not available"
+    echo "ok $((TEST_NUM + 5)) - run --attribute runs the command with or without CAP_SYS_ADMIN # SKIP bwrap not available"
+    TEST_NUM=$((TEST_NUM + 5))
 fi
 
 # Cleanup